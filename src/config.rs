@@ -1,10 +1,13 @@
 use crate::explorer::Explorer;
-use crate::types::{CodeExplorer, Project};
+use crate::types::{CodeExplorer, Project, WebConfig};
 use anyhow::Result;
 use dirs;
 use serde_json;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tracing::warn;
 
 /// Get the path to the configuration file
 pub fn get_config_path() -> Result<PathBuf> {
@@ -67,7 +70,13 @@ impl ProjectManager for DefaultProjectManager {
         }
 
         // Add to temporary projects
-        self.temp_projects.insert(name.clone(), Project { path });
+        self.temp_projects.insert(
+            name.clone(),
+            Project {
+                path,
+                ..Default::default()
+            },
+        );
 
         Ok(name)
     }
@@ -103,3 +112,44 @@ pub fn load_projects() -> Result<HashMap<String, Project>> {
     let content = std::fs::read_to_string(config_path)?;
     Ok(serde_json::from_str(&content)?)
 }
+
+/// Load the settings of the web tools, the defaults if there are none. The
+/// file is only read again once it changed.
+pub fn load_web_config() -> Result<WebConfig> {
+    // Modification time and size of the file the config was read from
+    type Cached = (Option<(SystemTime, u64)>, WebConfig);
+    static CACHE: Mutex<Option<Cached>> = Mutex::new(None);
+
+    let config_path = get_config_path()?.with_file_name("web.json");
+    let stamp = std::fs::metadata(&config_path)
+        .ok()
+        .and_then(|metadata| Some((metadata.modified().ok()?, metadata.len())));
+    let mut cache = CACHE.lock().unwrap();
+    if let Some((cached_stamp, config)) = cache.as_ref() {
+        if *cached_stamp == stamp {
+            return Ok(config.clone());
+        }
+    }
+
+    let config = if config_path.exists() {
+        let content = std::fs::read_to_string(&config_path)?;
+        serde_json::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid {}: {}", config_path.display(), e))?
+    } else {
+        WebConfig::default()
+    };
+    *cache = Some((stamp, config.clone()));
+    Ok(config)
+}
+
+/// Whether web_search, web_fetch and crawl_docs are offered. They are not if
+/// web.json sets `offline` or is invalid, as the tools would fail anyway.
+pub fn web_tools_enabled() -> bool {
+    match load_web_config() {
+        Ok(config) => !config.offline,
+        Err(e) => {
+            warn!("Web tools are disabled: {}", e);
+            false
+        }
+    }
+}
@@ -0,0 +1,799 @@
+use crate::syntax::{extract_symbols, SourceLanguage, SourceSymbol, MAX_FILE_BYTES};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// Longer listings leave out modules, which can be listed one at a time
+const MAX_LISTING_CHARS: usize = 40_000;
+// Sources shown when an item name matches in several places
+const MAX_ITEM_MATCHES: usize = 5;
+const MAX_SIMILAR_ITEMS: usize = 10;
+// Doc comment summaries in listings are cut after this
+const MAX_SUMMARY_CHARS: usize = 300;
+
+/// Public API of a dependency, read from its source on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DependencyDocs {
+    pub name: String,
+    pub version: String,
+    /// Directory the items were read from
+    pub source_dir: PathBuf,
+    /// Item that was looked up, the whole dependency is listed without one
+    pub item: Option<String>,
+    /// Listing of public items with doc comments, or the source of the item
+    pub content: String,
+}
+
+impl std::fmt::Display for DependencyDocs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} (source in {})\n\n{}",
+            self.name,
+            self.version,
+            self.source_dir.display(),
+            self.content
+        )
+    }
+}
+
+/// Looks up a crate in the version locked by the project's Cargo.lock, or an
+/// npm package installed in node_modules. Without an item the public items are
+/// listed, an item can be a module to list or a name like `Client::get` to
+/// show its source.
+pub fn dependency_docs(
+    project_root: &Path,
+    name: &str,
+    version: Option<&str>,
+    item: Option<&str>,
+) -> Result<DependencyDocs> {
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home| home.join(".cargo")))
+        .ok_or_else(|| anyhow!("Could not determine the cargo home directory"))?;
+    lookup(project_root, &cargo_home, name, version, item)
+}
+
+fn lookup(
+    project_root: &Path,
+    cargo_home: &Path,
+    name: &str,
+    version: Option<&str>,
+    item: Option<&str>,
+) -> Result<DependencyDocs> {
+    let name = name.trim();
+    let version = version.map(str::trim).filter(|v| !v.is_empty());
+    let item = item.map(str::trim).filter(|i| !i.is_empty());
+
+    let lock_file = find_in_ancestors(project_root, "Cargo.lock");
+    let locked = match &lock_file {
+        Some(path) => locked_packages(&std::fs::read_to_string(path)?, name),
+        None => Vec::new(),
+    };
+    let (name, version, source_dir, modules) = if !locked.is_empty() {
+        let (package, dir) = locate_crate(cargo_home, &locked, version)?;
+        let modules = crate_modules(&dir, &package.name);
+        (package.name, package.version, dir, modules)
+    } else if let Some(package_dir) =
+        find_in_ancestors(project_root, &format!("node_modules/{}", name))
+    {
+        let (installed, types_dir) = locate_types(&package_dir, name)?;
+        if let Some(version) = version.filter(|v| !installed.starts_with(v)) {
+            return Err(anyhow!(
+                "node_modules has {} {} installed, not version {}",
+                name,
+                installed,
+                version
+            ));
+        }
+        let modules = type_modules(&types_dir);
+        (name.to_string(), installed, types_dir, modules)
+    } else if lock_file.is_some() {
+        return Err(anyhow!(
+            "No dependency '{}' in the Cargo.lock or node_modules of the project",
+            name
+        ));
+    } else {
+        return Err(anyhow!(
+            "No dependency '{}' in the node_modules of the project, and it has no Cargo.lock",
+            name
+        ));
+    };
+
+    let content = match item {
+        None => list_modules(&modules, None),
+        Some(item) => match find_module(&modules, item) {
+            Some(module) => list_modules(&modules, Some(module)),
+            None => item_sources(&modules, item, &name, &version)?,
+        },
+    };
+    Ok(DependencyDocs {
+        name,
+        version,
+        source_dir,
+        item: item.map(str::to_string),
+        content,
+    })
+}
+
+fn find_in_ancestors(dir: &Path, relative: &str) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|ancestor| ancestor.join(relative))
+        .find(|path| path.exists())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct LockedPackage {
+    name: String,
+    version: String,
+    /// None for crates of the workspace
+    source: Option<String>,
+}
+
+// Cargo treats `-` and `_` in crate names alike
+fn same_crate(a: &str, b: &str) -> bool {
+    a.replace('-', "_") == b.replace('-', "_")
+}
+
+/// Packages of the given name in a Cargo.lock, usually one
+fn locked_packages(lock: &str, name: &str) -> Vec<LockedPackage> {
+    lock.split("[[package]]")
+        .skip(1)
+        .filter_map(|block| {
+            let field = |key: &str| {
+                block.lines().find_map(|line| {
+                    let (line_key, value) = line.split_once('=')?;
+                    (line_key.trim() == key).then(|| value.trim().trim_matches('"').to_string())
+                })
+            };
+            Some(LockedPackage {
+                name: field("name")?,
+                version: field("version")?,
+                source: field("source"),
+            })
+        })
+        .filter(|package| same_crate(&package.name, name))
+        .collect()
+}
+
+/// The locked package matching the version and its source in the registry
+fn locate_crate(
+    cargo_home: &Path,
+    locked: &[LockedPackage],
+    version: Option<&str>,
+) -> Result<(LockedPackage, PathBuf)> {
+    let versions = || {
+        locked
+            .iter()
+            .map(|package| package.version.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let candidates: Vec<&LockedPackage> = locked
+        .iter()
+        .filter(|package| match version {
+            Some(version) => package.version.starts_with(version),
+            None => true,
+        })
+        .collect();
+    let package = match candidates.as_slice() {
+        [package] => (*package).clone(),
+        [] => {
+            return Err(anyhow!(
+                "Cargo.lock has {} {}, but no version {}",
+                locked[0].name,
+                versions(),
+                version.unwrap_or_default()
+            ))
+        }
+        _ => {
+            return Err(anyhow!(
+                "Cargo.lock has several versions of {}: {}. Choose one with the version parameter.",
+                locked[0].name,
+                versions()
+            ))
+        }
+    };
+
+    match package.source.as_deref() {
+        None => {
+            return Err(anyhow!(
+                "{} is a crate of the project, read its files instead",
+                package.name
+            ))
+        }
+        Some(source) if !source.starts_with("registry+") && !source.starts_with("sparse+") => {
+            return Err(anyhow!(
+                "{} {} comes from {}, only crates from a registry can be looked up",
+                package.name,
+                package.version,
+                source
+            ))
+        }
+        Some(_) => {}
+    }
+
+    // One directory per registry index, e.g. index.crates.io-6f17d22bba15001f
+    let registry = cargo_home.join("registry").join("src");
+    let dir_name = format!("{}-{}", package.name, package.version);
+    let dir = std::fs::read_dir(&registry)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path().join(&dir_name))
+        .find(|dir| dir.is_dir())
+        .ok_or_else(|| {
+            anyhow!(
+                "The source of {} {} is not in {}, run `cargo fetch` in the project to download it",
+                package.name,
+                package.version,
+                registry.display()
+            )
+        })?;
+    Ok((package, dir))
+}
+
+/// Installed version of an npm package and the directory of its type
+/// definitions, which come with the package or from @types
+fn locate_types(package_dir: &Path, name: &str) -> Result<(String, PathBuf)> {
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(package_dir.join("package.json"))?)?;
+    let version = manifest["version"]
+        .as_str()
+        .unwrap_or("unknown")
+        .to_string();
+
+    let has_types = manifest["types"].is_string()
+        || manifest["typings"].is_string()
+        || package_dir.join("index.d.ts").exists();
+    if has_types {
+        return Ok((version, package_dir.to_path_buf()));
+    }
+    // @types packages of scoped packages are named like @types/scope__name
+    let types_name = name.trim_start_matches('@').replace('/', "__");
+    let node_modules = package_dir
+        .ancestors()
+        .find(|dir| dir.ends_with("node_modules"))
+        .unwrap_or(package_dir);
+    let types_dir = node_modules.join("@types").join(types_name);
+    if types_dir.is_dir() {
+        Ok((version, types_dir))
+    } else {
+        Err(anyhow!(
+            "{} {} has no type definitions, and @types/{} is not installed",
+            name,
+            version,
+            name
+        ))
+    }
+}
+
+/// A source file of a dependency with its symbols
+struct Module {
+    /// Module path like `serde::de`, or the file path for type definitions
+    name: String,
+    path: PathBuf,
+    language: SourceLanguage,
+    content: String,
+    symbols: Vec<SourceSymbol>,
+}
+
+impl Module {
+    fn read(name: String, dir: &Path, path: &Path) -> Option<Self> {
+        let language = SourceLanguage::from_path(path)?;
+        if std::fs::metadata(path).ok()?.len() > MAX_FILE_BYTES {
+            return None;
+        }
+        let content = std::fs::read_to_string(path).ok()?;
+        let symbols = extract_symbols(language, &content).ok()?;
+        Some(Self {
+            name,
+            path: path.strip_prefix(dir).unwrap_or(path).to_path_buf(),
+            language,
+            content,
+            symbols,
+        })
+    }
+
+    fn lines(&self, start: usize, end: usize) -> Vec<&str> {
+        self.content
+            .lines()
+            .skip(start.saturating_sub(1))
+            .take(end.saturating_sub(start) + 1)
+            .collect()
+    }
+
+    /// Name including the module, e.g. `serde::de::Deserialize`
+    fn full_name(&self, symbol: &SourceSymbol) -> String {
+        match self.language {
+            SourceLanguage::Rust => format!("{}::{}", self.name, symbol.qualified_name()),
+            _ => symbol.qualified_name(),
+        }
+    }
+}
+
+/// Modules of the library in `src`, the crate root first
+fn crate_modules(dir: &Path, name: &str) -> Vec<Module> {
+    let src = dir.join("src");
+    let crate_name = name.replace('-', "_");
+    let mut modules: Vec<Module> = WalkDir::new(&src)
+        .into_iter()
+        .filter_entry(|entry| entry.path() != src.join("bin"))
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(&src).ok()?;
+            let mut segments = vec![crate_name.clone()];
+            for component in relative.with_extension("").components() {
+                let segment = component.as_os_str().to_string_lossy().to_string();
+                if !matches!(segment.as_str(), "lib" | "main" | "mod") {
+                    segments.push(segment);
+                }
+            }
+            Module::read(segments.join("::"), dir, entry.path())
+        })
+        .collect();
+    modules.sort_by(|a, b| a.name.cmp(&b.name));
+    modules
+}
+
+/// Type definition files of an npm package, without nested packages
+fn type_modules(dir: &Path) -> Vec<Module> {
+    let mut modules: Vec<Module> = WalkDir::new(dir)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != "node_modules")
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let file_name = entry.file_name().to_string_lossy();
+            [".d.ts", ".d.mts", ".d.cts"]
+                .iter()
+                .any(|extension| file_name.ends_with(extension))
+        })
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(dir).ok()?;
+            let name = relative.to_string_lossy().replace('\\', "/");
+            Module::read(name, dir, entry.path())
+        })
+        .collect();
+    // The entry point first, then by path
+    modules.sort_by_key(|module| (module.name != "index.d.ts", module.name.clone()));
+    modules
+}
+
+// A module by its full path, or without the crate name
+fn find_module<'a>(modules: &'a [Module], item: &str) -> Option<&'a Module> {
+    modules.iter().find(|module| {
+        module.name == item
+            || module
+                .name
+                .split_once("::")
+                .is_some_and(|(_, path)| path == item)
+    })
+}
+
+/// Public items of all modules, or of a single one
+fn list_modules(modules: &[Module], selected: Option<&Module>) -> String {
+    let mut output = String::new();
+    let mut left_out = Vec::new();
+    for module in modules {
+        if selected.is_some_and(|selected| !std::ptr::eq(selected, module)) {
+            continue;
+        }
+        let mut listing = String::new();
+        for symbol in &module.symbols {
+            write_item(&mut listing, module, symbol, None, 0);
+        }
+        let summary = module_summary(module);
+        if listing.is_empty() && summary.is_none() && selected.is_none() {
+            continue;
+        }
+        if selected.is_none() && output.len() + listing.len() > MAX_LISTING_CHARS {
+            left_out.push(module.name.as_str());
+            continue;
+        }
+        output.push_str(&format!("## {}", module.name));
+        // Type definitions are named by their path already
+        if module.language == SourceLanguage::Rust {
+            output.push_str(&format!(" ({})", module.path.display()));
+        }
+        output.push('\n');
+        if let Some(summary) = summary {
+            output.push_str(&format!("{}\n", summary));
+        }
+        output.push_str(&listing);
+        output.push('\n');
+    }
+    if output.is_empty() {
+        output.push_str("No public items found.\n");
+    }
+    if !left_out.is_empty() {
+        output.push_str(&format!(
+            "Not listed to keep the output short, use a module as the item to list it: {}\n",
+            left_out.join(", ")
+        ));
+    }
+    output.trim_end().to_string()
+}
+
+fn write_item(
+    output: &mut String,
+    module: &Module,
+    symbol: &SourceSymbol,
+    parent_kind: Option<&str>,
+    depth: usize,
+) {
+    if !is_public(module, symbol, parent_kind) {
+        return;
+    }
+    let mut children = String::new();
+    for child in &symbol.children {
+        write_item(&mut children, module, child, Some(symbol.kind), depth + 1);
+    }
+    // Impl blocks are only of interest for their public methods
+    if symbol.kind == "impl" && children.is_empty() {
+        return;
+    }
+
+    let indent = "  ".repeat(depth);
+    output.push_str(&format!("{}{}\n", indent, symbol.signature));
+    let docs = doc_comment(&module.lines(symbol.start_line, symbol.line.saturating_sub(1)));
+    if let Some(summary) = summarize(&docs) {
+        output.push_str(&format!("{}    {}\n", indent, summary));
+    }
+    output.push_str(&children);
+}
+
+fn is_public(module: &Module, symbol: &SourceSymbol, parent_kind: Option<&str>) -> bool {
+    let trivia = module.lines(symbol.start_line, symbol.line.saturating_sub(1));
+    if trivia.iter().any(|line| line.contains("#[doc(hidden)]")) {
+        return false;
+    }
+    match module.language {
+        SourceLanguage::Rust => match symbol.kind {
+            // Trait impls are left out, inherent impls show their public methods
+            "impl" => !symbol.signature.contains(" for "),
+            "macro_rules!" => trivia.iter().any(|line| line.contains("macro_export")),
+            _ if parent_kind == Some("trait") => true,
+            _ => symbol.signature.starts_with("pub ") && !symbol.name.starts_with("__"),
+        },
+        _ => !["private ", "protected ", "#"]
+            .iter()
+            .any(|prefix| symbol.signature.starts_with(prefix)),
+    }
+}
+
+/// Text of the doc comments among the lines above an item, without the
+/// comment markers
+fn doc_comment(lines: &[&str]) -> String {
+    let mut docs = Vec::new();
+    for line in lines {
+        let line = line.trim();
+        let text = if let Some(text) = line
+            .strip_prefix("///")
+            .or_else(|| line.strip_prefix("//!"))
+        {
+            text
+        } else if line.starts_with("/**") || line.starts_with('*') {
+            line.trim_start_matches("/**")
+                .trim_end_matches("*/")
+                .trim_start_matches('*')
+        } else {
+            continue;
+        };
+        docs.push(text.strip_prefix(' ').unwrap_or(text).trim_end());
+    }
+    docs.join("\n").trim().to_string()
+}
+
+// First paragraph of the docs on a single line
+fn summarize(docs: &str) -> Option<String> {
+    let paragraph = docs.split("\n\n").next()?;
+    let mut summary = paragraph.split_whitespace().collect::<Vec<_>>().join(" ");
+    if summary.is_empty() {
+        return None;
+    }
+    if let Some((cut, _)) = summary.char_indices().nth(MAX_SUMMARY_CHARS) {
+        summary.truncate(cut);
+        summary.push_str("...");
+    }
+    Some(summary)
+}
+
+// Summary of the `//!` docs at the top of a Rust file
+fn module_summary(module: &Module) -> Option<String> {
+    let lines: Vec<&str> = module
+        .content
+        .lines()
+        .take_while(|line| line.trim().is_empty() || line.trim_start().starts_with("//!"))
+        .collect();
+    summarize(&doc_comment(&lines))
+}
+
+/// Source of the items matching a name like `Client`, `Client::get` or
+/// `serde::de::Deserialize`, including their doc comments
+fn item_sources(modules: &[Module], item: &str, name: &str, version: &str) -> Result<String> {
+    let suffix = format!("::{}", item);
+    let mut matches: Vec<(&Module, &SourceSymbol)> = Vec::new();
+    let mut all: Vec<(&Module, &SourceSymbol)> = Vec::new();
+    for module in modules {
+        let mut stack: Vec<&SourceSymbol> = module.symbols.iter().collect();
+        while let Some(symbol) = stack.pop() {
+            let full_name = module.full_name(symbol);
+            if full_name == item || full_name.ends_with(&suffix) {
+                matches.push((module, symbol));
+            }
+            all.push((module, symbol));
+            stack.extend(&symbol.children);
+        }
+    }
+    // A type name refers to the type rather than its impl blocks
+    if matches.iter().any(|(_, symbol)| symbol.kind != "impl") {
+        matches.retain(|(_, symbol)| symbol.kind != "impl");
+    }
+    // Public items first
+    matches.sort_by_key(|(module, symbol)| {
+        (
+            !is_public(module, symbol, None),
+            module.name.clone(),
+            symbol.line,
+        )
+    });
+
+    if matches.is_empty() {
+        let wanted = item.rsplit("::").next().unwrap_or(item).to_lowercase();
+        let mut similar: Vec<String> = all
+            .iter()
+            .filter(|(_, symbol)| symbol.kind != "impl")
+            .filter(|(_, symbol)| symbol.name.to_lowercase().contains(&wanted))
+            .map(|(module, symbol)| module.full_name(symbol))
+            .collect();
+        similar.sort();
+        similar.truncate(MAX_SIMILAR_ITEMS);
+        return Err(if similar.is_empty() {
+            anyhow!("No item '{}' in {} {}", item, name, version)
+        } else {
+            anyhow!(
+                "No item '{}' in {} {}. Similar items: {}",
+                item,
+                name,
+                version,
+                similar.join(", ")
+            )
+        });
+    }
+
+    let fence = |module: &Module| match module.language {
+        SourceLanguage::Rust => "rust",
+        _ => "typescript",
+    };
+    let mut output = String::new();
+    for (module, symbol) in matches.iter().take(MAX_ITEM_MATCHES) {
+        output.push_str(&format!(
+            "## {} ({}:{})\n```{}\n{}\n```\n\n",
+            module.full_name(symbol),
+            module.path.display(),
+            symbol.start_line,
+            fence(module),
+            module.lines(symbol.start_line, symbol.end_line).join("\n")
+        ));
+    }
+    if matches.len() > MAX_ITEM_MATCHES {
+        output.push_str(&format!(
+            "{} more items match, use a qualified name like `{}`\n",
+            matches.len() - MAX_ITEM_MATCHES,
+            matches[MAX_ITEM_MATCHES]
+                .0
+                .full_name(matches[MAX_ITEM_MATCHES].1)
+        ));
+    }
+    Ok(output.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const CARGO_LOCK: &str = r#"version = 3
+
+[[package]]
+name = "app"
+version = "0.1.0"
+dependencies = [
+ "tiny-http",
+]
+
+[[package]]
+name = "tiny-http"
+version = "0.2.1"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "abc"
+"#;
+
+    const LIB_RS: &str = r#"//! A tiny HTTP client.
+//!
+//! More details.
+
+pub mod request;
+
+/// A client with connection reuse.
+///
+/// Create it once.
+pub struct Client {
+    pool: usize,
+}
+
+impl Client {
+    /// Sends a GET request.
+    pub fn get(&self, url: &str) -> String {
+        url.to_string()
+    }
+
+    fn connect(&self) {}
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client { pool: 1 }
+    }
+}
+
+#[doc(hidden)]
+pub fn internal() {}
+
+fn private() {}
+"#;
+
+    const REQUEST_RS: &str = r#"/// Builds requests.
+pub struct Builder;
+
+pub(crate) fn helper() {}
+"#;
+
+    fn setup() -> Result<(TempDir, PathBuf, PathBuf)> {
+        let temp = TempDir::new()?;
+        let project = temp.path().join("app");
+        fs::create_dir_all(&project)?;
+        fs::write(project.join("Cargo.lock"), CARGO_LOCK)?;
+
+        let cargo_home = temp.path().join("cargo");
+        let src =
+            cargo_home.join("registry/src/index.crates.io-6f17d22bba15001f/tiny-http-0.2.1/src");
+        fs::create_dir_all(&src)?;
+        fs::write(src.join("lib.rs"), LIB_RS)?;
+        fs::write(src.join("request.rs"), REQUEST_RS)?;
+        Ok((temp, project, cargo_home))
+    }
+
+    #[test]
+    fn test_list_crate_items() -> Result<()> {
+        let (_temp, project, cargo_home) = setup()?;
+        let docs = lookup(&project, &cargo_home, "tiny_http", None, None)?;
+        assert_eq!(docs.name, "tiny-http");
+        assert_eq!(docs.version, "0.2.1");
+        assert_eq!(
+            docs.content,
+            "## tiny_http (src/lib.rs)\n\
+             A tiny HTTP client.\n\
+             pub struct Client\n    A client with connection reuse.\n\
+             impl Client\n  pub fn get(&self, url: &str) -> String\n      Sends a GET request.\n\n\
+             ## tiny_http::request (src/request.rs)\n\
+             pub struct Builder;\n    Builds requests."
+        );
+
+        // A module lists only its items
+        let docs = lookup(&project, &cargo_home, "tiny-http", None, Some("request"))?;
+        assert!(docs.content.starts_with("## tiny_http::request"));
+        assert!(!docs.content.contains("Client"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_crate_item_source() -> Result<()> {
+        let (_temp, project, cargo_home) = setup()?;
+        let docs = lookup(
+            &project,
+            &cargo_home,
+            "tiny-http",
+            None,
+            Some("Client::get"),
+        )?;
+        assert_eq!(
+            docs.content,
+            "## tiny_http::Client::get (src/lib.rs:15)\n```rust\n    /// Sends a GET request.\n    pub fn get(&self, url: &str) -> String {\n        url.to_string()\n    }\n```"
+        );
+
+        let error = lookup(&project, &cargo_home, "tiny-http", None, Some("Clent")).unwrap_err();
+        assert_eq!(error.to_string(), "No item 'Clent' in tiny-http 0.2.1");
+        let error = lookup(&project, &cargo_home, "tiny-http", None, Some("build")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No item 'build' in tiny-http 0.2.1. Similar items: tiny_http::request::Builder"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_crate_versions() -> Result<()> {
+        let (_temp, project, cargo_home) = setup()?;
+        let lock = format!(
+            "{}\n[[package]]\nname = \"tiny-http\"\nversion = \"0.3.0\"\nsource = \"registry+https://github.com/rust-lang/crates.io-index\"\n",
+            CARGO_LOCK
+        );
+        fs::write(project.join("Cargo.lock"), lock)?;
+
+        let error = lookup(&project, &cargo_home, "tiny-http", None, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Cargo.lock has several versions of tiny-http: 0.2.1, 0.3.0. Choose one with the version parameter."
+        );
+        assert_eq!(
+            lookup(&project, &cargo_home, "tiny-http", Some("0.2"), None)?.version,
+            "0.2.1"
+        );
+        // Locked versions that were never downloaded
+        let error = lookup(&project, &cargo_home, "tiny-http", Some("0.3"), None).unwrap_err();
+        assert!(error.to_string().contains("run `cargo fetch`"));
+
+        let error = lookup(&project, &cargo_home, "app", None, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "app is a crate of the project, read its files instead"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_npm_type_definitions() -> Result<()> {
+        let temp = TempDir::new()?;
+        let package = temp.path().join("node_modules/fetcher");
+        fs::create_dir_all(package.join("lib"))?;
+        fs::write(
+            package.join("package.json"),
+            r#"{"name": "fetcher", "version": "2.4.0", "types": "index.d.ts"}"#,
+        )?;
+        fs::write(
+            package.join("index.d.ts"),
+            "/** Fetches a URL */\nexport declare function fetchUrl(url: string): Promise<string>;\n",
+        )?;
+        fs::write(
+            package.join("lib/client.d.ts"),
+            "export declare class Client {\n  private pool;\n  /** Sends the request */\n  send(): void;\n}\n",
+        )?;
+
+        let docs = lookup(temp.path(), temp.path(), "fetcher", None, None)?;
+        assert_eq!(docs.version, "2.4.0");
+        assert_eq!(
+            docs.content,
+            "## index.d.ts\n\
+             export declare function fetchUrl(url: string): Promise<string>;\n    Fetches a URL\n\n\
+             ## lib/client.d.ts\n\
+             export declare class Client\n  send(): void\n      Sends the request"
+        );
+
+        let docs = lookup(
+            temp.path(),
+            temp.path(),
+            "fetcher",
+            None,
+            Some("Client::send"),
+        )?;
+        assert!(docs
+            .content
+            .contains("/** Sends the request */\n  send(): void;"));
+
+        let error = lookup(temp.path(), temp.path(), "fetcher", Some("3"), None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "node_modules has fetcher 2.4.0 installed, not version 3"
+        );
+        let error = lookup(temp.path(), temp.path(), "missing", None, None).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No dependency 'missing' in the node_modules of the project, and it has no Cargo.lock"
+        );
+        Ok(())
+    }
+}
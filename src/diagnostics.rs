@@ -0,0 +1,362 @@
+use crate::types::{Diagnostic, DiagnosticSeverity};
+use crate::utils::CommandExecutor;
+use anyhow::Result;
+use regex::Regex;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A build system or linter that can check a project and report diagnostics
+pub trait BuildChecker: Send + Sync {
+    /// Name used to select the checker explicitly
+    fn name(&self) -> &'static str;
+    /// Whether the project at `root` uses this build system
+    fn detect(&self, root: &Path) -> bool;
+    fn command_line(&self) -> &'static str;
+    /// Extract diagnostics from the combined command output
+    fn parse_output(&self, output: &str, root: &Path) -> Vec<Diagnostic>;
+}
+
+/// All supported checkers, in detection order
+pub fn checkers() -> Vec<Box<dyn BuildChecker>> {
+    vec![
+        Box::new(CargoChecker),
+        Box::new(TscChecker),
+        Box::new(GoVetChecker),
+    ]
+}
+
+/// Get the checker with the given name, or the first one that detects the project
+pub fn find_checker(root: &Path, name: Option<&str>) -> Result<Box<dyn BuildChecker>> {
+    let available = checkers()
+        .iter()
+        .map(|c| c.name())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let found = match name {
+        Some(name) => checkers().into_iter().find(|c| c.name() == name.trim()),
+        None => checkers().into_iter().find(|c| c.detect(root)),
+    };
+    found.ok_or_else(|| match name {
+        Some(name) => anyhow::anyhow!("Unknown build system '{}', available: {}", name, available),
+        None => anyhow::anyhow!("Could not detect a supported build system ({})", available),
+    })
+}
+
+pub struct CheckOutput {
+    pub build_system: String,
+    pub success: bool,
+    /// Sorted by severity, then by location
+    pub diagnostics: Vec<Diagnostic>,
+    /// Raw command output, useful when no diagnostics could be extracted
+    pub output: String,
+}
+
+/// Run the checker of a project and collect its diagnostics
+pub async fn check_project(
+    command_executor: &dyn CommandExecutor,
+    root: &Path,
+    build_system: Option<&str>,
+    timeout: Option<Duration>,
+) -> Result<CheckOutput> {
+    let checker = find_checker(root, build_system)?;
+    let output = command_executor
+        .execute(
+            checker.command_line(),
+            Some(&root.to_path_buf()),
+            timeout,
+            None,
+        )
+        .await?;
+    if output.timed_out {
+        return Err(anyhow::anyhow!("`{}` timed out", checker.command_line()));
+    }
+
+    let mut diagnostics = Vec::new();
+    for diagnostic in checker.parse_output(&output.output, root) {
+        // Cargo reports the same message once per target
+        if !diagnostics.contains(&diagnostic) {
+            diagnostics.push(diagnostic);
+        }
+    }
+    diagnostics.sort_by(|a, b| {
+        (a.severity, &a.path, a.start_line).cmp(&(b.severity, &b.path, b.start_line))
+    });
+
+    Ok(CheckOutput {
+        build_system: checker.name().to_string(),
+        success: output.success,
+        diagnostics,
+        output: output.output,
+    })
+}
+
+// Make paths reported by the tool relative to the project root
+fn relative_path(path: &str, root: &Path) -> PathBuf {
+    let path = Path::new(path.trim_start_matches("./"));
+    path.strip_prefix(root).unwrap_or(path).to_path_buf()
+}
+
+pub struct CargoChecker;
+
+impl BuildChecker for CargoChecker {
+    fn name(&self) -> &'static str {
+        "cargo"
+    }
+
+    fn detect(&self, root: &Path) -> bool {
+        root.join("Cargo.toml").is_file()
+    }
+
+    fn command_line(&self) -> &'static str {
+        "cargo check --all-targets --message-format=json"
+    }
+
+    fn parse_output(&self, output: &str, root: &Path) -> Vec<Diagnostic> {
+        output
+            .lines()
+            .filter(|line| line.starts_with('{'))
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .filter(|msg| msg["reason"] == "compiler-message")
+            .filter_map(|msg| parse_rustc_message(&msg["message"], root))
+            .collect()
+    }
+}
+
+fn parse_rustc_message(message: &Value, root: &Path) -> Option<Diagnostic> {
+    let severity = match message["level"].as_str()? {
+        "error" | "error: internal compiler error" => DiagnosticSeverity::Error,
+        "warning" => DiagnosticSeverity::Warning,
+        "note" | "help" => DiagnosticSeverity::Note,
+        _ => return None,
+    };
+    // Summaries like "aborting due to 2 previous errors" have no location
+    let span = message["spans"]
+        .as_array()?
+        .iter()
+        .find(|span| span["is_primary"].as_bool() == Some(true))?;
+
+    // The first help with a replacement, otherwise the first help text
+    let helps = message["children"]
+        .as_array()
+        .map(|children| {
+            children
+                .iter()
+                .filter(|child| child["level"] == "help")
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let replacement = helps.iter().find_map(|help| {
+        let text = help["message"].as_str()?;
+        help["spans"].as_array()?.iter().find_map(|span| {
+            let replacement = span["suggested_replacement"].as_str()?;
+            Some(format!("{}: `{}`", text, replacement))
+        })
+    });
+    let suggestion = replacement.or_else(|| {
+        helps
+            .first()
+            .and_then(|help| help["message"].as_str())
+            .map(|s| s.to_string())
+    });
+
+    Some(Diagnostic {
+        path: relative_path(span["file_name"].as_str()?, root),
+        start_line: span["line_start"].as_u64()? as usize,
+        end_line: span["line_end"].as_u64()? as usize,
+        column: span["column_start"].as_u64().map(|c| c as usize),
+        severity,
+        code: message["code"]["code"].as_str().map(|s| s.to_string()),
+        message: message["message"].as_str()?.to_string(),
+        suggestion,
+    })
+}
+
+pub struct TscChecker;
+
+impl BuildChecker for TscChecker {
+    fn name(&self) -> &'static str {
+        "tsc"
+    }
+
+    fn detect(&self, root: &Path) -> bool {
+        root.join("tsconfig.json").is_file()
+    }
+
+    fn command_line(&self) -> &'static str {
+        "npx --no-install tsc --noEmit --pretty false"
+    }
+
+    fn parse_output(&self, output: &str, root: &Path) -> Vec<Diagnostic> {
+        // src/app.ts(12,5): error TS2322: Type 'string' is not assignable to type 'number'.
+        let pattern = Regex::new(r"^(.+?)\((\d+),(\d+)\): (error|warning) (TS\d+): (.*)$").unwrap();
+        output
+            .lines()
+            .filter_map(|line| pattern.captures(line.trim_end()))
+            .map(|caps| {
+                let line = caps[2].parse().unwrap_or(1);
+                Diagnostic {
+                    path: relative_path(&caps[1], root),
+                    start_line: line,
+                    end_line: line,
+                    column: caps[3].parse().ok(),
+                    severity: if &caps[4] == "error" {
+                        DiagnosticSeverity::Error
+                    } else {
+                        DiagnosticSeverity::Warning
+                    },
+                    code: Some(caps[5].to_string()),
+                    message: caps[6].to_string(),
+                    suggestion: None,
+                }
+            })
+            .collect()
+    }
+}
+
+pub struct GoVetChecker;
+
+impl BuildChecker for GoVetChecker {
+    fn name(&self) -> &'static str {
+        "go"
+    }
+
+    fn detect(&self, root: &Path) -> bool {
+        root.join("go.mod").is_file()
+    }
+
+    fn command_line(&self) -> &'static str {
+        "go vet ./..."
+    }
+
+    fn parse_output(&self, output: &str, root: &Path) -> Vec<Diagnostic> {
+        // ./main.go:7:2: fmt.Printf format %d has arg s of wrong type string
+        let pattern = Regex::new(r"^(?:vet: )?(.+?\.go):(\d+):(?:(\d+):)? (.*)$").unwrap();
+        output
+            .lines()
+            .filter_map(|line| pattern.captures(line.trim_end()))
+            .map(|caps| {
+                let line = caps[2].parse().unwrap_or(1);
+                Diagnostic {
+                    path: relative_path(&caps[1], root),
+                    start_line: line,
+                    end_line: line,
+                    column: caps.get(3).and_then(|c| c.as_str().parse().ok()),
+                    severity: DiagnosticSeverity::Error,
+                    code: None,
+                    message: caps[4].to_string(),
+                    suggestion: None,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::DefaultCommandExecutor;
+
+    #[test]
+    fn test_parse_cargo_messages() {
+        let root = Path::new("/work/crate");
+        let output = concat!(
+            "    Checking fixture v0.1.0 (/work/crate)\n",
+            r#"{"reason":"compiler-message","message":{"level":"warning","message":"unused variable: `x`","code":{"code":"unused_variables"},"spans":[{"file_name":"src/main.rs","line_start":2,"line_end":2,"column_start":9,"is_primary":true}],"children":[{"level":"help","message":"if this is intentional, prefix it with an underscore","spans":[{"suggested_replacement":"_x"}]}]}}"#,
+            "\n",
+            r#"{"reason":"compiler-message","message":{"level":"error","message":"aborting due to 1 previous error","code":null,"spans":[],"children":[]}}"#,
+            "\n",
+            r#"{"reason":"build-finished","success":false}"#,
+        );
+
+        let diagnostics = CargoChecker.parse_output(output, root);
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic {
+                path: PathBuf::from("src/main.rs"),
+                start_line: 2,
+                end_line: 2,
+                column: Some(9),
+                severity: DiagnosticSeverity::Warning,
+                code: Some("unused_variables".to_string()),
+                message: "unused variable: `x`".to_string(),
+                suggestion: Some(
+                    "if this is intentional, prefix it with an underscore: `_x`".to_string()
+                ),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_tsc_and_go_output() {
+        let root = Path::new("/work/app");
+
+        let tsc = TscChecker.parse_output(
+            "src/app.ts(12,5): error TS2322: Type 'string' is not assignable to type 'number'.\n",
+            root,
+        );
+        assert_eq!(tsc.len(), 1);
+        assert_eq!(tsc[0].path, PathBuf::from("src/app.ts"));
+        assert_eq!((tsc[0].start_line, tsc[0].column), (12, Some(5)));
+        assert_eq!(tsc[0].code.as_deref(), Some("TS2322"));
+
+        let go = GoVetChecker.parse_output(
+            "# example.com/app\n./main.go:7:2: fmt.Printf format %d has arg s of wrong type string\n",
+            root,
+        );
+        assert_eq!(go.len(), 1);
+        assert_eq!(go[0].path, PathBuf::from("main.go"));
+        assert_eq!(go[0].start_line, 7);
+        assert!(go[0].message.starts_with("fmt.Printf format"));
+    }
+
+    #[test]
+    fn test_find_checker() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        assert!(find_checker(dir.path(), None).is_err());
+        assert!(find_checker(dir.path(), Some("make")).is_err());
+        assert_eq!(find_checker(dir.path(), Some("tsc"))?.name(), "tsc");
+
+        std::fs::write(dir.path().join("go.mod"), "module example.com/app\n")?;
+        assert_eq!(find_checker(dir.path(), None)?.name(), "go");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_fixture_crate() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"fixture\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+        )?;
+        std::fs::create_dir(dir.path().join("src"))?;
+        std::fs::write(
+            dir.path().join("src/main.rs"),
+            "fn main() {\n    let unused = 1;\n}\n",
+        )?;
+        let executor = DefaultCommandExecutor::default();
+
+        let result = check_project(&executor, dir.path(), None, None).await?;
+        assert_eq!(result.build_system, "cargo");
+        assert!(result.success);
+        assert_eq!(result.diagnostics.len(), 1);
+        let warning = &result.diagnostics[0];
+        assert_eq!(warning.severity, DiagnosticSeverity::Warning);
+        assert_eq!(warning.path, PathBuf::from("src/main.rs"));
+        assert_eq!(warning.start_line, 2);
+        assert!(warning.suggestion.as_deref().unwrap().contains("`_unused`"));
+
+        // Type errors stop compilation before lints run
+        std::fs::write(
+            dir.path().join("src/main.rs"),
+            "fn main() {\n    let x: u32 = \"text\";\n}\n",
+        )?;
+        let result = check_project(&executor, dir.path(), None, None).await?;
+        assert!(!result.success);
+        let error = &result.diagnostics[0];
+        assert_eq!(error.severity, DiagnosticSeverity::Error);
+        assert_eq!(error.start_line, 2);
+        assert_eq!(error.code.as_deref(), Some("E0308"));
+        Ok(())
+    }
+}
@@ -1,10 +1,11 @@
+use crate::syntax::{self, SourceLanguage};
 use crate::types::{
-    CodeExplorer, FileEncoding, FileFormat, FileReplacement, FileSystemEntryType, FileTreeEntry,
-    SearchMode, SearchOptions, SearchResult,
+    CodeExplorer, FileEncoding, FileFormat, FileMatch, FileReplacement, FileSystemEntryType,
+    FileTreeEntry, FindOptions, RepoMap, SearchMode, SearchOptions, SearchResult,
 };
 use anyhow::Result;
 use ignore::WalkBuilder;
-use regex::RegexBuilder;
+use regex::{Regex, RegexBuilder};
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
@@ -26,6 +27,107 @@ const DEFAULT_IGNORE_PATTERNS: [&str; 12] = [
     "Thumbs.db",
 ];
 
+// Default number of results returned by find_files
+const DEFAULT_FIND_RESULTS: usize = 50;
+
+// Source files parsed for the repository map, large projects are cut off
+const MAX_REPO_MAP_FILES: usize = 2000;
+
+/// Creates a gitignore-aware walker that skips the default ignore patterns
+fn create_walker(path: &Path, max_depth: Option<usize>) -> ignore::Walk {
+    WalkBuilder::new(path)
+        .max_depth(max_depth)
+        .hidden(false)
+        .git_ignore(true)
+        .filter_entry(move |e| {
+            let file_name = e.file_name().to_string_lossy();
+            !DEFAULT_IGNORE_PATTERNS
+                .iter()
+                .any(|pattern| match glob::Pattern::new(pattern) {
+                    Ok(pat) => pat.matches(&file_name),
+                    Err(_) => file_name.contains(pattern),
+                })
+        })
+        .build()
+}
+
+/// Builds the regex matching a search query in the given mode
+pub fn search_regex(options: &SearchOptions) -> Result<Regex> {
+    let pattern = match options.mode {
+        SearchMode::Exact => regex::escape(&options.query),
+        SearchMode::Regex => options.query.clone(),
+    };
+    // The group keeps alternatives inside the word boundaries
+    let pattern = if options.whole_words {
+        format!(r"\b(?:{})\b", pattern)
+    } else {
+        pattern
+    };
+    Ok(RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()?)
+}
+
+/// Scores a relative path against a fuzzy query. Returns None if the query
+/// characters don't appear in order. Higher scores are better.
+fn fuzzy_score(query: &str, path: &str) -> Option<i64> {
+    let query: String = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(|c| c.to_lowercase())
+        .collect();
+    let path_lower = path.to_lowercase();
+    let file_name_start = path_lower.rfind('/').map(|i| i + 1).unwrap_or(0);
+    let file_name = &path_lower[file_name_start..];
+    let length_penalty = path.len() as i64;
+
+    // Exact and substring matches on the file name rank above everything else
+    if file_name == query {
+        return Some(4_000_000 - length_penalty);
+    }
+    if let Some(pos) = file_name.find(&query) {
+        return Some(3_000_000 - pos as i64 * 100 - length_penalty);
+    }
+    if path_lower.contains(&query) {
+        return Some(2_000_000 - length_penalty);
+    }
+
+    // Otherwise all query characters must appear in order
+    let mut query_chars = query.chars().peekable();
+    let mut score = 0i64;
+    let mut prev_char = '/';
+    let mut prev_matched = false;
+
+    for (pos, c) in path_lower.char_indices() {
+        let matched = match query_chars.peek() {
+            Some(&q) => q == c,
+            None => break,
+        };
+        if matched {
+            query_chars.next();
+            score += 10;
+            // Bonus for consecutive characters, word starts and file name matches
+            if prev_matched {
+                score += 50;
+            }
+            if matches!(prev_char, '/' | '_' | '-' | '.' | ' ') {
+                score += 30;
+            }
+            if pos >= file_name_start {
+                score += 20;
+            }
+        }
+        prev_matched = matched;
+        prev_char = c;
+    }
+
+    if query_chars.peek().is_some() {
+        return None;
+    }
+
+    Some(1_000_000 + score * 100 - length_penalty)
+}
+
 /// Helper struct for grouping search matches into sections
 struct SearchSection {
     start_line: usize,
@@ -143,20 +245,8 @@ impl Explorer {
             return Ok(());
         }
 
-        let walker = WalkBuilder::new(path)
-            .max_depth(Some(1)) // Only immediate children
-            .hidden(false)
-            .git_ignore(true)
-            .filter_entry(move |e| {
-                let file_name = e.file_name().to_string_lossy();
-                !DEFAULT_IGNORE_PATTERNS
-                    .iter()
-                    .any(|pattern| match glob::Pattern::new(pattern) {
-                        Ok(pat) => pat.matches(&file_name),
-                        Err(_) => file_name.contains(pattern),
-                    })
-            })
-            .build();
+        // Only immediate children
+        let walker = create_walker(path, Some(1));
 
         for result in walker {
             let dir_entry = result?;
@@ -277,6 +367,53 @@ impl Explorer {
 
         Ok(selected_content)
     }
+
+    // Source files of the project with relative paths, at most `max_files`
+    // besides the focus files, which are always included
+    fn repo_map_files(
+        &self,
+        focus: &[PathBuf],
+        max_files: usize,
+    ) -> Result<Vec<(PathBuf, String)>> {
+        // Unreadable, binary or large files are left out of the map
+        let read_source = |path: &Path| {
+            if SourceLanguage::from_path(path).is_none() || path.is_dir() {
+                return None;
+            }
+            if std::fs::metadata(path).is_ok_and(|m| m.len() > syntax::MAX_FILE_BYTES) {
+                return None;
+            }
+            std::fs::read_to_string(path).ok()
+        };
+
+        let mut files = Vec::new();
+        for entry in create_walker(&self.root_dir, None) {
+            if files.len() >= max_files {
+                debug!("Repository map limited to {} files", max_files);
+                break;
+            }
+            let entry = entry?;
+            let entry_path = entry.path();
+            let Some(content) = read_source(entry_path) else {
+                continue;
+            };
+            let rel_path = entry_path
+                .strip_prefix(&self.root_dir)
+                .unwrap_or(entry_path)
+                .to_path_buf();
+            files.push((rel_path, content));
+        }
+
+        for path in focus {
+            if files.iter().any(|(rel_path, _)| rel_path == path) {
+                continue;
+            }
+            if let Some(content) = read_source(&self.root_dir.join(path)) {
+                files.push((path.clone(), content));
+            }
+        }
+        Ok(files)
+    }
 }
 
 impl CodeExplorer for Explorer {
@@ -456,43 +593,9 @@ impl CodeExplorer for Explorer {
         let max_results = options.max_results.unwrap_or(usize::MAX);
         let context_lines = 2; // Lines of context before and after
 
-        // Prepare regex for different search modes
-        let regex = match options.mode {
-            SearchMode::Exact => {
-                let pattern = if options.whole_words {
-                    format!(r"\b{}\b", regex::escape(&options.query))
-                } else {
-                    regex::escape(&options.query)
-                };
-                RegexBuilder::new(&pattern)
-                    .case_insensitive(!options.case_sensitive)
-                    .build()?
-            }
-            SearchMode::Regex => {
-                let pattern = if options.whole_words {
-                    format!(r"\b{}\b", options.query)
-                } else {
-                    options.query.clone()
-                };
-                RegexBuilder::new(&pattern)
-                    .case_insensitive(!options.case_sensitive)
-                    .build()?
-            }
-        };
+        let regex = search_regex(&options)?;
 
-        let walker = WalkBuilder::new(path)
-            .hidden(false)
-            .git_ignore(true)
-            .filter_entry(move |e| {
-                let file_name = e.file_name().to_string_lossy();
-                !DEFAULT_IGNORE_PATTERNS
-                    .iter()
-                    .any(|pattern| match glob::Pattern::new(pattern) {
-                        Ok(pat) => pat.matches(&file_name),
-                        Err(_) => file_name.contains(pattern),
-                    })
-            })
-            .build();
+        let walker = create_walker(path, None);
 
         for entry in walker {
             let entry = entry?;
@@ -667,6 +770,83 @@ impl CodeExplorer for Explorer {
 
         Ok(results)
     }
+
+    fn find_files(&self, path: &Path, options: FindOptions) -> Result<Vec<FileMatch>> {
+        let query = options.pattern.trim();
+        if query.is_empty() {
+            return Err(anyhow::anyhow!("Pattern must not be empty"));
+        }
+        let max_results = options.max_results.unwrap_or(DEFAULT_FIND_RESULTS);
+
+        // Patterns with glob metacharacters are matched as globs, everything else fuzzy
+        let glob_pattern = if query.contains(['*', '?', '[']) {
+            Some(glob::Pattern::new(query)?)
+        } else {
+            None
+        };
+        let match_options = glob::MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+
+        let mut scored = Vec::new();
+        for entry in create_walker(path, None) {
+            let entry = entry?;
+            let entry_path = entry.path();
+            if entry_path.is_dir() {
+                continue;
+            }
+
+            let rel_path = entry_path
+                .strip_prefix(path)
+                .unwrap_or(entry_path)
+                .to_string_lossy()
+                .replace('\\', "/");
+
+            let score = match &glob_pattern {
+                Some(pattern) => {
+                    // Patterns without a separator only apply to the file name
+                    let matched = if query.contains('/') {
+                        pattern.matches_with(&rel_path, match_options)
+                    } else {
+                        let file_name = entry.file_name().to_string_lossy();
+                        pattern.matches_with(&file_name, match_options)
+                    };
+                    // Prefer shallow, short paths
+                    matched.then(|| -(rel_path.len() as i64))
+                }
+                None => fuzzy_score(query, &rel_path),
+            };
+
+            if let Some(score) = score {
+                scored.push((score, rel_path, entry_path.to_path_buf()));
+            }
+        }
+
+        // Highest score first, ties broken alphabetically
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        scored.truncate(max_results);
+
+        Ok(scored
+            .into_iter()
+            .map(|(_, _, path)| {
+                let metadata = std::fs::metadata(&path).ok();
+                FileMatch {
+                    size: metadata.as_ref().map(|m| m.len()).unwrap_or(0),
+                    modified: metadata
+                        .and_then(|m| m.modified().ok())
+                        .map(chrono::DateTime::<chrono::Utc>::from),
+                    path,
+                }
+            })
+            .collect())
+    }
+
+    fn repo_map(&self, focus: &[PathBuf], max_tokens: usize) -> Result<RepoMap> {
+        let files = self.repo_map_files(focus, MAX_REPO_MAP_FILES)?;
+        Ok(syntax::build_repo_map(&files, focus, max_tokens))
+    }
 }
 
 #[cfg(test)]
@@ -901,4 +1081,128 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_find_files() -> Result<()> {
+        let (temp_dir, explorer) = setup_test_directory()?;
+
+        fs::create_dir_all(temp_dir.path().join("src/agent"))?;
+        fs::create_dir(temp_dir.path().join("node_modules"))?;
+        create_test_file(temp_dir.path(), "Cargo.toml", "[package]")?;
+        create_test_file(&temp_dir.path().join("src"), "main.rs", "fn main() {}")?;
+        create_test_file(&temp_dir.path().join("src/agent"), "agent.rs", "")?;
+        create_test_file(&temp_dir.path().join("src/agent"), "tests.rs", "")?;
+        create_test_file(&temp_dir.path().join("node_modules"), "agent.rs", "")?;
+
+        let relative = |results: &[FileMatch]| -> Vec<String> {
+            results
+                .iter()
+                .map(|m| {
+                    m.path
+                        .strip_prefix(temp_dir.path())
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                })
+                .collect()
+        };
+
+        // Glob on file names
+        let results = explorer.find_files(
+            temp_dir.path(),
+            FindOptions {
+                pattern: "*.rs".to_string(),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(
+            relative(&results),
+            vec!["src/main.rs", "src/agent/agent.rs", "src/agent/tests.rs"]
+        );
+
+        // Glob on paths does not cross directory boundaries with a single '*'
+        let results = explorer.find_files(
+            temp_dir.path(),
+            FindOptions {
+                pattern: "src/*.rs".to_string(),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(relative(&results), vec!["src/main.rs"]);
+
+        // Fuzzy name match ranks exact file name hits first and skips ignored dirs
+        let results = explorer.find_files(
+            temp_dir.path(),
+            FindOptions {
+                pattern: "agent".to_string(),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(relative(&results)[0], "src/agent/agent.rs");
+        assert!(!relative(&results)
+            .iter()
+            .any(|p| p.contains("node_modules")));
+
+        // Subsequence match
+        let results = explorer.find_files(
+            temp_dir.path(),
+            FindOptions {
+                pattern: "agtests".to_string(),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(relative(&results), vec!["src/agent/tests.rs"]);
+        assert!(results[0].modified.is_some());
+
+        // Result cap
+        let results = explorer.find_files(
+            temp_dir.path(),
+            FindOptions {
+                pattern: "*".to_string(),
+                max_results: Some(2),
+            },
+        )?;
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().any(|m| m.size == "[package]".len() as u64));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repo_map() -> Result<()> {
+        let (temp_dir, explorer) = setup_test_directory()?;
+        fs::create_dir_all(temp_dir.path().join("src"))?;
+        fs::create_dir_all(temp_dir.path().join("target"))?;
+        create_test_file(
+            temp_dir.path(),
+            "src/main.rs",
+            "fn main() {\n    let _ = Store::open();\n}\n",
+        )?;
+        create_test_file(
+            temp_dir.path(),
+            "src/store.rs",
+            "pub struct Store;\n\nimpl Store {\n    pub fn open() -> Self {\n        Store\n    }\n}\n",
+        )?;
+        create_test_file(
+            temp_dir.path(),
+            "target/generated.rs",
+            "fn generated() {}\n",
+        )?;
+        create_test_file(temp_dir.path(), "notes.txt", "fn not_code() {}\n")?;
+
+        let map = explorer.repo_map(&[PathBuf::from("src/main.rs")], 1000)?;
+        assert_eq!(
+            map.content,
+            "src/store.rs:\n  1: pub struct Store;\n  3: impl Store\n    4: pub fn open() -> Self\n"
+        );
+        assert_eq!(map.files_shown, 1);
+        assert_eq!(map.files_total, 1);
+
+        // Focus files are included even past the file limit
+        let focus = [PathBuf::from("src/main.rs")];
+        let files = explorer.repo_map_files(&focus, 0)?;
+        let paths: Vec<_> = files.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(paths, focus);
+        Ok(())
+    }
 }
@@ -803,4 +803,8 @@ impl LLMProvider for AiCoreClient {
         self.send_with_retry(&converse_request, streaming_callback, 3)
             .await
     }
+
+    fn supports_images(&self) -> bool {
+        true
+    }
 }
@@ -737,4 +737,9 @@ impl LLMProvider for AiCoreClient {
         self.send_with_retry(&anthropic_request, streaming_callback, 3)
             .await
     }
+
+    fn supports_images(&self) -> bool {
+        // AI Core serves Claude models, which use the Anthropic image format
+        true
+    }
 }
@@ -765,4 +765,9 @@ impl LLMProvider for AnthropicClient {
         self.send_with_retry(&anthropic_request, streaming_callback, 3)
             .await
     }
+
+    fn supports_images(&self) -> bool {
+        // All Claude 3 and later models have vision support
+        true
+    }
 }
@@ -63,4 +63,9 @@ pub trait LLMProvider {
         request: LLMRequest,
         streaming_callback: Option<&StreamingCallback>,
     ) -> Result<LLMResponse>;
+
+    /// Whether the model accepts image content blocks
+    fn supports_images(&self) -> bool {
+        false
+    }
 }
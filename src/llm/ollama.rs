@@ -5,6 +5,18 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use tracing::debug;
 
+/// Model families with vision support, matched against the model name
+const VISION_MODELS: &[&str] = &[
+    "llava",
+    "vision",
+    "moondream",
+    "minicpm-v",
+    "gemma3",
+    "qwen2.5vl",
+    "llama4",
+    "mistral-small3.1",
+];
+
 #[derive(Debug, Serialize)]
 struct OllamaRequest {
     model: String,
@@ -25,6 +37,9 @@ struct OllamaMessage {
     #[serde(default)]
     role: String,
     content: String,
+    /// Base64 encoded images
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    images: Vec<String>,
     tool_calls: Option<Vec<OllamaToolCall>>,
 }
 
@@ -119,6 +134,16 @@ impl OllamaClient {
                 }
                 _ => None,
             },
+            images: match &message.content {
+                MessageContent::Structured(blocks) => blocks
+                    .iter()
+                    .filter_map(|block| match block {
+                        ContentBlock::Image { source } => Some(source.data.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            },
         }
     }
 
@@ -293,6 +318,7 @@ impl LLMProvider for OllamaClient {
         messages.push(OllamaMessage {
             role: "system".to_string(),
             content: request.system_prompt,
+            images: Vec::new(),
             tool_calls: None,
         });
 
@@ -333,4 +359,9 @@ impl LLMProvider for OllamaClient {
             self.try_send_request(&ollama_request).await
         }
     }
+
+    fn supports_images(&self) -> bool {
+        let model = self.model.to_lowercase();
+        VISION_MODELS.iter().any(|name| model.contains(name))
+    }
 }
@@ -8,6 +8,21 @@ use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use tracing::debug;
 
+/// Models with vision support, matched by name prefix
+const VISION_MODELS: &[&str] = &[
+    "gpt-4o",
+    "gpt-4.1",
+    "gpt-4.5",
+    "gpt-4-turbo",
+    "gpt-5",
+    "o1",
+    "o3",
+    "o4",
+    "claude",
+    "gemini",
+];
+const NON_VISION_MODELS: &[&str] = &["o1-mini", "o3-mini"];
+
 #[derive(Debug, Serialize, Clone)]
 struct OpenAIRequest {
     model: String,
@@ -48,11 +63,52 @@ impl OpenAIRequest {
 struct OpenAIChatMessage {
     role: String,
     #[serde(default)]
-    content: String,
+    content: OpenAIContent,
     #[serde(default)]
     tool_calls: Option<Vec<OpenAIToolCall>>,
 }
 
+/// Message content, parts are only used for messages with images
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+enum OpenAIContent {
+    Text(String),
+    Parts(Vec<OpenAIContentPart>),
+}
+
+impl Default for OpenAIContent {
+    fn default() -> Self {
+        OpenAIContent::Text(String::new())
+    }
+}
+
+impl OpenAIContent {
+    fn text(&self) -> String {
+        match self {
+            OpenAIContent::Text(text) => text.clone(),
+            OpenAIContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    OpenAIContentPart::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum OpenAIContentPart {
+    Text { text: String },
+    ImageUrl { image_url: OpenAIImageUrl },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct OpenAIImageUrl {
+    url: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct OpenAIToolCall {
     id: String,
@@ -264,18 +320,48 @@ impl OpenAIClient {
                 MessageRole::Assistant => "assistant".to_string(),
             },
             content: match &message.content {
-                MessageContent::Text(text) => text.clone(),
+                MessageContent::Text(text) => OpenAIContent::Text(text.clone()),
+                MessageContent::Structured(blocks)
+                    if blocks
+                        .iter()
+                        .any(|block| matches!(block, ContentBlock::Image { .. })) =>
+                {
+                    // Images are sent as data URLs in a list of content parts
+                    OpenAIContent::Parts(
+                        blocks
+                            .iter()
+                            .filter_map(|block| match block {
+                                ContentBlock::Text { text } => {
+                                    Some(OpenAIContentPart::Text { text: text.clone() })
+                                }
+                                ContentBlock::Image { source } => {
+                                    Some(OpenAIContentPart::ImageUrl {
+                                        image_url: OpenAIImageUrl {
+                                            url: format!(
+                                                "data:{};base64,{}",
+                                                source.media_type, source.data
+                                            ),
+                                        },
+                                    })
+                                }
+                                _ => None,
+                            })
+                            .collect(),
+                    )
+                }
                 MessageContent::Structured(blocks) => {
                     // Concatenate all text blocks into the content string
-                    blocks
-                        .iter()
-                        .filter_map(|block| match block {
-                            ContentBlock::Text { text } => Some(text),
-                            _ => None,
-                        })
-                        .cloned()
-                        .collect::<Vec<String>>()
-                        .join("")
+                    OpenAIContent::Text(
+                        blocks
+                            .iter()
+                            .filter_map(|block| match block {
+                                ContentBlock::Text { text } => Some(text),
+                                _ => None,
+                            })
+                            .cloned()
+                            .collect::<Vec<String>>()
+                            .join(""),
+                    )
                 }
             },
             tool_calls: match &message.content {
@@ -376,10 +462,9 @@ impl OpenAIClient {
                     let mut blocks = Vec::new();
 
                     // Add text content if present
-                    if !openai_response.choices[0].message.content.is_empty() {
-                        blocks.push(ContentBlock::Text {
-                            text: openai_response.choices[0].message.content.clone(),
-                        });
+                    let text = openai_response.choices[0].message.content.text();
+                    if !text.is_empty() {
+                        blocks.push(ContentBlock::Text { text });
                     }
 
                     // Add tool calls if present
@@ -627,7 +712,7 @@ impl LLMProvider for OpenAIClient {
         // Add system message
         messages.push(OpenAIChatMessage {
             role: "system".to_string(),
-            content: request.system_prompt,
+            content: OpenAIContent::Text(request.system_prompt),
             tool_calls: None,
         });
 
@@ -664,4 +749,11 @@ impl LLMProvider for OpenAIClient {
         self.send_with_retry(&openai_request, streaming_callback, 3)
             .await
     }
+
+    fn supports_images(&self) -> bool {
+        // OpenRouter model names start with the vendor, like `openai/gpt-4o`
+        let model = self.model.rsplit('/').next().unwrap_or_default();
+        VISION_MODELS.iter().any(|name| model.starts_with(name))
+            && !NON_VISION_MODELS.iter().any(|name| model.starts_with(name))
+    }
 }
@@ -32,4 +32,8 @@ impl LLMProvider for OpenRouterClient {
         // Delegate to inner OpenAI client since the APIs are compatible
         self.inner.send_message(request, streaming_callback).await
     }
+
+    fn supports_images(&self) -> bool {
+        self.inner.supports_images()
+    }
 }
@@ -741,3 +741,29 @@ async fn test_anthropic_rate_limit_retry() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_image_content_block() -> Result<()> {
+    // Image blocks use the Anthropic format, which is sent unchanged
+    let block = ContentBlock::image("image/png", "iVBORw0KGgo=");
+    assert_eq!(
+        serde_json::to_value(&block)?,
+        json!({
+            "type": "image",
+            "source": {
+                "type": "base64",
+                "media_type": "image/png",
+                "data": "iVBORw0KGgo="
+            }
+        })
+    );
+
+    let openai = |model: &str| OpenAIClient::new("key".into(), model.into(), "url".into());
+    assert!(openai("gpt-4o").supports_images());
+    assert!(!openai("gpt-3.5-turbo").supports_images());
+    assert!(!openai("o3-mini").supports_images());
+    let ollama = |model: &str| OllamaClient::new(model.into(), "url".into(), 4096);
+    assert!(ollama("llama3.2-vision:11b").supports_images());
+    assert!(!ollama("llama2").supports_images());
+    Ok(())
+}
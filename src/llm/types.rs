@@ -70,6 +70,30 @@ pub enum ContentBlock {
         tool_use_id: String,
         content: String,
     },
+
+    #[serde(rename = "image")]
+    Image { source: ImageSource },
+}
+
+/// Image content, only base64 encoded data is supported
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct ImageSource {
+    #[serde(rename = "type")]
+    pub source_type: String,
+    pub media_type: String,
+    pub data: String,
+}
+
+impl ContentBlock {
+    pub fn image(media_type: &str, data: &str) -> Self {
+        ContentBlock::Image {
+            source: ImageSource {
+                source_type: "base64".to_string(),
+                media_type: media_type.to_string(),
+                data: data.to_string(),
+            },
+        }
+    }
 }
 
 /// Generic response structure
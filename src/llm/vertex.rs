@@ -46,6 +46,15 @@ struct VertexPart {
     #[serde(rename = "functionResponse")]
     function_response: Option<VertexFunctionResponse>,
     text: Option<String>,
+    #[serde(rename = "inlineData")]
+    inline_data: Option<VertexInlineData>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VertexInlineData {
+    #[serde(rename = "mimeType")]
+    mime_type: String,
+    data: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -171,6 +180,7 @@ impl VertexClient {
                 text: Some(text.clone()),
                 function_call: None,
                 function_response: None,
+                inline_data: None,
             }],
             MessageContent::Structured(blocks) => blocks
                 .iter()
@@ -179,6 +189,7 @@ impl VertexClient {
                         text: Some(text.clone()),
                         function_call: None,
                         function_response: None,
+                        inline_data: None,
                     }),
                     ContentBlock::ToolUse { name, input, .. } => Some(VertexPart {
                         text: None,
@@ -187,6 +198,7 @@ impl VertexClient {
                             args: input.clone(),
                         }),
                         function_response: None,
+                        inline_data: None,
                     }),
                     ContentBlock::ToolResult {
                         tool_use_id,
@@ -198,6 +210,16 @@ impl VertexClient {
                             name: tool_use_id.clone(), // TODO: Should be function name
                             response: serde_json::Value::String(content.clone()),
                         }),
+                        inline_data: None,
+                    }),
+                    ContentBlock::Image { source } => Some(VertexPart {
+                        text: None,
+                        function_call: None,
+                        function_response: None,
+                        inline_data: Some(VertexInlineData {
+                            mime_type: source.media_type.clone(),
+                            data: source.data.clone(),
+                        }),
                     }),
                     _ => None,
                 })
@@ -481,6 +503,11 @@ impl LLMProvider for VertexClient {
         self.send_with_retry(&vertex_request, streaming_callback, 3)
             .await
     }
+
+    fn supports_images(&self) -> bool {
+        // Gemini models are multimodal
+        true
+    }
 }
 
 /*
@@ -0,0 +1,483 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{oneshot, Notify};
+use tracing::{debug, warn};
+use url::Url;
+
+// Requests like workspace/symbol can take a while on large projects
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+// How long a server gets to answer shutdown and exit before it is killed
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+type Writer = Arc<tokio::sync::Mutex<Box<dyn AsyncWrite + Send + Unpin>>>;
+type PendingRequests = Arc<Mutex<HashMap<i64, oneshot::Sender<Result<Value>>>>>;
+
+/// Diagnostics the server published for one document
+#[derive(Debug, Clone, Default)]
+pub struct PublishedDiagnostics {
+    /// Incremented with every publish, used to wait for fresh results
+    pub generation: u64,
+    pub version: Option<i64>,
+    pub items: Vec<Value>,
+}
+
+struct OpenDocument {
+    version: i64,
+    content: String,
+}
+
+/// JSON-RPC connection to a language server. Documents are synced with full
+/// content, which every server supports.
+pub struct LspClient {
+    writer: Writer,
+    pending: PendingRequests,
+    next_id: AtomicI64,
+    documents: tokio::sync::Mutex<HashMap<String, OpenDocument>>,
+    diagnostics: Arc<Mutex<HashMap<String, PublishedDiagnostics>>>,
+    diagnostics_changed: Arc<Notify>,
+    alive: Arc<AtomicBool>,
+    child: Mutex<Option<tokio::process::Child>>,
+    root: PathBuf,
+}
+
+impl LspClient {
+    /// Start the language server process and initialize it for `root`
+    pub async fn spawn(command: &str, args: &[String], root: &Path) -> Result<Self> {
+        let mut cmd = tokio::process::Command::new(command);
+        cmd.args(args)
+            .current_dir(root)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start language server '{}': {}", command, e))?;
+        if let Some(pid) = child.id() {
+            crate::utils::register_process_group(pid);
+        }
+        let (stdin, stdout) = match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => (stdin, stdout),
+            _ => {
+                return Err(anyhow!(
+                    "Failed to connect to language server '{}'",
+                    command
+                ))
+            }
+        };
+
+        let client = Self::connect(stdout, stdin, root);
+        *client.child.lock().unwrap() = Some(child);
+        client.initialize().await?;
+        Ok(client)
+    }
+
+    /// Use an existing connection, the caller has to call `initialize`
+    pub fn connect<R, W>(reader: R, writer: W, root: &Path) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let writer: Writer = Arc::new(tokio::sync::Mutex::new(Box::new(writer)));
+        let pending: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
+        let diagnostics = Arc::new(Mutex::new(HashMap::new()));
+        let diagnostics_changed = Arc::new(Notify::new());
+        let alive = Arc::new(AtomicBool::new(true));
+
+        tokio::spawn(read_loop(
+            BufReader::new(reader),
+            writer.clone(),
+            pending.clone(),
+            diagnostics.clone(),
+            diagnostics_changed.clone(),
+            alive.clone(),
+        ));
+
+        Self {
+            writer,
+            pending,
+            next_id: AtomicI64::new(1),
+            documents: tokio::sync::Mutex::new(HashMap::new()),
+            diagnostics,
+            diagnostics_changed,
+            alive,
+            child: Mutex::new(None),
+            root: root.to_path_buf(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether the server is still connected
+    pub fn is_alive(&self) -> bool {
+        self.alive.load(Ordering::SeqCst)
+    }
+
+    pub async fn initialize(&self) -> Result<Value> {
+        let root_uri = path_to_uri(&self.root)?;
+        let name = self
+            .root
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let result = self
+            .request(
+                "initialize",
+                json!({
+                    "processId": std::process::id(),
+                    "clientInfo": { "name": "code-assistant" },
+                    "rootUri": root_uri,
+                    "rootPath": self.root,
+                    "workspaceFolders": [{ "uri": root_uri, "name": name }],
+                    "capabilities": {
+                        "textDocument": {
+                            "synchronization": { "didSave": true },
+                            "publishDiagnostics": { "versionSupport": true },
+                            "hover": { "contentFormat": ["markdown", "plaintext"] },
+                            "definition": { "linkSupport": true },
+                            "references": {},
+                            "documentSymbol": { "hierarchicalDocumentSymbolSupport": true }
+                        },
+                        "workspace": {
+                            "symbol": {},
+                            "configuration": true,
+                            "workspaceFolders": true
+                        }
+                    }
+                }),
+            )
+            .await?;
+        self.notify("initialized", json!({})).await?;
+        Ok(result)
+    }
+
+    /// Send a request and wait for its result
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        if !self.is_alive() {
+            return Err(anyhow!("Language server is not running"));
+        }
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, sender);
+
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if let Err(e) = write_message(&mut **self.writer.lock().await, &message).await {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(e);
+        }
+
+        match tokio::time::timeout(REQUEST_TIMEOUT, receiver).await {
+            Ok(Ok(result)) => result.map_err(|e| anyhow!("{} failed: {}", method, e)),
+            Ok(Err(_)) => Err(anyhow!("Language server exited during {}", method)),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                Err(anyhow!(
+                    "Language server did not answer {} within {} seconds",
+                    method,
+                    REQUEST_TIMEOUT.as_secs()
+                ))
+            }
+        }
+    }
+
+    pub async fn notify(&self, method: &str, params: Value) -> Result<()> {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        write_message(&mut **self.writer.lock().await, &message).await
+    }
+
+    /// Make sure the server sees `content` for the document at `path`.
+    /// Opens the document on first use and sends a change otherwise,
+    /// returns the document version.
+    pub async fn sync_document(&self, path: &Path, content: &str) -> Result<i64> {
+        let uri = path_to_uri(path)?;
+        let mut documents = self.documents.lock().await;
+        match documents.get_mut(&uri) {
+            Some(document) if document.content == content => Ok(document.version),
+            Some(document) => {
+                document.version += 1;
+                document.content = content.to_string();
+                self.notify(
+                    "textDocument/didChange",
+                    json!({
+                        "textDocument": { "uri": uri, "version": document.version },
+                        "contentChanges": [{ "text": content }]
+                    }),
+                )
+                .await?;
+                Ok(document.version)
+            }
+            None => {
+                self.notify(
+                    "textDocument/didOpen",
+                    json!({
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": language_id(path),
+                            "version": 1,
+                            "text": content
+                        }
+                    }),
+                )
+                .await?;
+                documents.insert(
+                    uri,
+                    OpenDocument {
+                        version: 1,
+                        content: content.to_string(),
+                    },
+                );
+                Ok(1)
+            }
+        }
+    }
+
+    /// Tell the server the document was written to disk, some servers
+    /// (e.g. rust-analyzer) only run their checks on save
+    pub async fn did_save(&self, path: &Path) -> Result<()> {
+        let uri = path_to_uri(path)?;
+        self.notify(
+            "textDocument/didSave",
+            json!({ "textDocument": { "uri": uri } }),
+        )
+        .await
+    }
+
+    /// Latest diagnostics published for the document
+    pub fn diagnostics(&self, path: &Path) -> Option<PublishedDiagnostics> {
+        let uri = path_to_uri(path).ok()?;
+        self.diagnostics.lock().unwrap().get(&uri).cloned()
+    }
+
+    /// Wait until the server publishes diagnostics for `path` newer than
+    /// `since_generation`, returns None on timeout
+    pub async fn wait_for_diagnostics(
+        &self,
+        path: &Path,
+        since_generation: u64,
+        timeout: Duration,
+    ) -> Option<PublishedDiagnostics> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Register before checking so a publish in between isn't missed
+            let notified = self.diagnostics_changed.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if let Some(published) = self.diagnostics(path) {
+                if published.generation > since_generation {
+                    return Some(published);
+                }
+            }
+            if !self.is_alive() {
+                return None;
+            }
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return None;
+            }
+        }
+    }
+
+    /// Ask the server to shut down and exit, then kill its process group so
+    /// helper processes (e.g. rust-analyzer's proc-macro server) don't outlive it
+    pub async fn shutdown(&self) {
+        if self.is_alive() {
+            let graceful = async {
+                self.request("shutdown", Value::Null).await?;
+                self.notify("exit", Value::Null).await
+            };
+            match tokio::time::timeout(SHUTDOWN_TIMEOUT, graceful).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => debug!("Language server shutdown failed: {}", e),
+                Err(_) => debug!("Language server did not answer shutdown"),
+            }
+        }
+
+        let child = self.child.lock().unwrap().take();
+        if let Some(mut child) = child {
+            let pid = child.id();
+            let _ = tokio::time::timeout(SHUTDOWN_TIMEOUT, child.wait()).await;
+            if let Some(pid) = pid {
+                crate::utils::kill_process_group(pid);
+            }
+            let _ = child.start_kill();
+        }
+    }
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        if let Some(child) = self.child.get_mut().unwrap().as_mut() {
+            if let Some(pid) = child.id() {
+                crate::utils::kill_process_group(pid);
+            }
+            let _ = child.start_kill();
+        }
+    }
+}
+
+async fn read_loop<R: AsyncRead + Unpin>(
+    mut reader: BufReader<R>,
+    writer: Writer,
+    pending: PendingRequests,
+    diagnostics: Arc<Mutex<HashMap<String, PublishedDiagnostics>>>,
+    diagnostics_changed: Arc<Notify>,
+    alive: Arc<AtomicBool>,
+) {
+    loop {
+        let message = match read_message(&mut reader).await {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                warn!("Invalid message from language server: {}", e);
+                break;
+            }
+        };
+
+        let method = message.get("method").and_then(|m| m.as_str());
+        let id = message.get("id").filter(|id| !id.is_null());
+        match (method, id) {
+            // Response to one of our requests
+            (None, Some(id)) => {
+                let Some(id) = id.as_i64() else { continue };
+                if let Some(sender) = pending.lock().unwrap().remove(&id) {
+                    let result = match message.get("error") {
+                        Some(error) => Err(anyhow!(
+                            "{}",
+                            error
+                                .get("message")
+                                .and_then(|m| m.as_str())
+                                .unwrap_or("unknown error")
+                        )),
+                        None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                    };
+                    let _ = sender.send(result);
+                }
+            }
+            // Request from the server, answer with neutral defaults
+            (Some(method), Some(id)) => {
+                let result = match method {
+                    "workspace/configuration" => {
+                        let items = message["params"]["items"]
+                            .as_array()
+                            .map(|items| items.len())
+                            .unwrap_or(0);
+                        Value::Array(vec![Value::Null; items])
+                    }
+                    _ => Value::Null,
+                };
+                let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+                if let Err(e) = write_message(&mut **writer.lock().await, &response).await {
+                    debug!("Failed to answer {} request: {}", method, e);
+                }
+            }
+            (Some("textDocument/publishDiagnostics"), None) => {
+                let params = &message["params"];
+                // Normalized like our own URIs so lookups match
+                let Some(uri) = params["uri"].as_str().and_then(|u| Url::parse(u).ok()) else {
+                    continue;
+                };
+                let items = params["diagnostics"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+                {
+                    let mut diagnostics = diagnostics.lock().unwrap();
+                    let entry = diagnostics.entry(uri.to_string()).or_default();
+                    entry.generation += 1;
+                    entry.version = params["version"].as_i64();
+                    entry.items = items;
+                }
+                diagnostics_changed.notify_waiters();
+            }
+            // Progress, log messages and the like
+            _ => {}
+        }
+    }
+
+    alive.store(false, Ordering::SeqCst);
+    // Fail requests that will never be answered
+    pending.lock().unwrap().clear();
+    diagnostics_changed.notify_waiters();
+}
+
+/// Read one message with Content-Length framing, None at end of stream
+pub(crate) async fn read_message<R: AsyncRead + Unpin>(
+    reader: &mut BufReader<R>,
+) -> Result<Option<Value>> {
+    let mut content_length = None;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+
+    let length = content_length.ok_or_else(|| anyhow!("Missing Content-Length header"))?;
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+pub(crate) async fn write_message<W: AsyncWrite + Unpin + ?Sized>(
+    writer: &mut W,
+    message: &Value,
+) -> Result<()> {
+    let body = serde_json::to_vec(message)?;
+    writer
+        .write_all(format!("Content-Length: {}\r\n\r\n", body.len()).as_bytes())
+        .await?;
+    writer.write_all(&body).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+pub fn path_to_uri(path: &Path) -> Result<String> {
+    Url::from_file_path(path)
+        .map(|uri| uri.to_string())
+        .map_err(|_| anyhow!("Not an absolute path: {}", path.display()))
+}
+
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    Url::parse(uri).ok()?.to_file_path().ok()
+}
+
+/// LSP language identifier for a file
+fn language_id(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).unwrap_or("") {
+        "rs" => "rust",
+        "ts" => "typescript",
+        "tsx" => "typescriptreact",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "py" => "python",
+        "go" => "go",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" => "cpp",
+        "java" => "java",
+        "json" => "json",
+        "toml" => "toml",
+        "md" => "markdown",
+        _ => "plaintext",
+    }
+}
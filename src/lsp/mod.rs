@@ -0,0 +1,494 @@
+mod client;
+#[cfg(test)]
+mod tests;
+
+pub use client::LspClient;
+
+use crate::types::{
+    CodeLocation, CodeSymbol, Diagnostic, DiagnosticSeverity, LanguageServerConfig,
+};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tracing::debug;
+
+// How long an edit waits for the language server to report diagnostics
+const EDIT_DIAGNOSTICS_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Language servers per project, started on first use
+#[derive(Default)]
+pub struct LspManager {
+    clients: tokio::sync::Mutex<HashMap<String, Arc<LspClient>>>,
+}
+
+pub fn language_servers() -> &'static LspManager {
+    static MANAGER: OnceLock<LspManager> = OnceLock::new();
+    MANAGER.get_or_init(LspManager::default)
+}
+
+impl LspManager {
+    /// The running server of the project, (re)started if needed
+    pub async fn get_or_start(
+        &self,
+        project: &str,
+        root: &Path,
+        config: Option<&LanguageServerConfig>,
+    ) -> Result<Arc<LspClient>> {
+        let mut clients = self.clients.lock().await;
+        if let Some(client) = clients.get(project) {
+            if client.is_alive() {
+                return Ok(client.clone());
+            }
+            debug!("Language server of project {} exited, restarting", project);
+        }
+
+        let config = config.ok_or_else(|| {
+            anyhow!(
+                "No language server configured for project '{}'. Add e.g. \
+                 \"language_server\": {{ \"command\": \"rust-analyzer\" }} to the project in projects.json",
+                project
+            )
+        })?;
+        let client = Arc::new(LspClient::spawn(&config.command, &config.args, root).await?);
+        clients.insert(project.to_string(), client.clone());
+        Ok(client)
+    }
+
+    /// The server of the project if it is already running
+    pub async fn running(&self, project: &str) -> Option<Arc<LspClient>> {
+        let clients = self.clients.lock().await;
+        clients.get(project).filter(|c| c.is_alive()).cloned()
+    }
+
+    /// Shut down all running servers, called on exit since the clients
+    /// live in a static and are never dropped
+    pub async fn shutdown(&self) {
+        let clients: Vec<_> = self.clients.lock().await.drain().map(|(_, c)| c).collect();
+        futures::future::join_all(clients.iter().map(|client| client.shutdown())).await;
+    }
+}
+
+/// Locations where the symbol at the given position is defined
+pub async fn goto_definition(
+    client: &LspClient,
+    path: &Path,
+    line: usize,
+    symbol: Option<&str>,
+    column: Option<usize>,
+) -> Result<Vec<CodeLocation>> {
+    let params = position_params(client, path, line, symbol, column).await?;
+    let result = client.request("textDocument/definition", params).await?;
+    Ok(parse_locations(client.root(), &result))
+}
+
+/// All references to the symbol at the given position, including its declaration
+pub async fn find_references(
+    client: &LspClient,
+    path: &Path,
+    line: usize,
+    symbol: Option<&str>,
+    column: Option<usize>,
+) -> Result<Vec<CodeLocation>> {
+    let mut params = position_params(client, path, line, symbol, column).await?;
+    params["context"] = json!({ "includeDeclaration": true });
+    let result = client.request("textDocument/references", params).await?;
+    Ok(parse_locations(client.root(), &result))
+}
+
+/// Type information and documentation for the symbol at the given position
+pub async fn hover(
+    client: &LspClient,
+    path: &Path,
+    line: usize,
+    symbol: Option<&str>,
+    column: Option<usize>,
+) -> Result<String> {
+    let params = position_params(client, path, line, symbol, column).await?;
+    let result = client.request("textDocument/hover", params).await?;
+    Ok(hover_text(&result["contents"]))
+}
+
+pub async fn document_symbols(client: &LspClient, path: &Path) -> Result<Vec<CodeSymbol>> {
+    let full_path = client.root().join(path);
+    let content = read_source(&full_path)?;
+    client.sync_document(&full_path, &content).await?;
+
+    let uri = client::path_to_uri(&full_path)?;
+    let result = client
+        .request(
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": uri } }),
+        )
+        .await?;
+
+    let mut symbols = Vec::new();
+    for item in result.as_array().into_iter().flatten() {
+        collect_symbols(client.root(), item, None, 0, false, &mut symbols);
+    }
+    Ok(symbols)
+}
+
+pub async fn workspace_symbols(client: &LspClient, query: &str) -> Result<Vec<CodeSymbol>> {
+    let result = client
+        .request("workspace/symbol", json!({ "query": query }))
+        .await?;
+
+    let mut symbols = Vec::new();
+    for item in result.as_array().into_iter().flatten() {
+        collect_symbols(client.root(), item, None, 0, true, &mut symbols);
+    }
+    Ok(symbols)
+}
+
+/// Send an edited file to the project's language server, if one is running,
+/// and return the errors and warnings it reports for the file
+pub async fn sync_file(project: &str, path: &Path, content: &str) -> Vec<Diagnostic> {
+    let Some(client) = language_servers().running(project).await else {
+        return Vec::new();
+    };
+    match sync_and_wait(&client, path, content, EDIT_DIAGNOSTICS_TIMEOUT).await {
+        Ok(diagnostics) => diagnostics,
+        Err(e) => {
+            debug!(
+                "Failed to sync {} with language server: {}",
+                path.display(),
+                e
+            );
+            Vec::new()
+        }
+    }
+}
+
+/// Sync the file and wait for diagnostics of the new version.
+/// Returns nothing if the server doesn't publish any in time.
+pub async fn sync_and_wait(
+    client: &LspClient,
+    path: &Path,
+    content: &str,
+    timeout: Duration,
+) -> Result<Vec<Diagnostic>> {
+    let full_path = client.root().join(path);
+    let mut generation = client
+        .diagnostics(&full_path)
+        .map(|d| d.generation)
+        .unwrap_or(0);
+    let version = client.sync_document(&full_path, content).await?;
+    client.did_save(&full_path).await?;
+
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut current = client
+        .diagnostics(&full_path)
+        .filter(|d| d.version == Some(version));
+    while current.is_none() {
+        let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+        let Some(published) = client
+            .wait_for_diagnostics(&full_path, generation, remaining)
+            .await
+        else {
+            break;
+        };
+        generation = published.generation;
+        // Servers without version support publish without a version
+        if published.version.is_none_or(|v| v >= version) {
+            current = Some(published);
+        }
+    }
+
+    Ok(current
+        .map(|published| convert_diagnostics(path, content, &published.items))
+        .unwrap_or_default())
+}
+
+async fn position_params(
+    client: &LspClient,
+    path: &Path,
+    line: usize,
+    symbol: Option<&str>,
+    column: Option<usize>,
+) -> Result<Value> {
+    let full_path = client.root().join(path);
+    let content = read_source(&full_path)?;
+    // The file may have changed on disk since the server last saw it
+    client.sync_document(&full_path, &content).await?;
+
+    let (line, character) = resolve_position(&content, line, symbol, column)?;
+    Ok(json!({
+        "textDocument": { "uri": client::path_to_uri(&full_path)? },
+        "position": { "line": line, "character": character }
+    }))
+}
+
+fn read_source(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Convert a 1-based line and either a symbol on that line or a 1-based
+/// column into a zero-based LSP position with UTF-16 character offset.
+/// Without symbol and column the first non-blank character is used.
+pub fn resolve_position(
+    content: &str,
+    line: usize,
+    symbol: Option<&str>,
+    column: Option<usize>,
+) -> Result<(usize, usize)> {
+    let line_count = content.lines().count();
+    let text = line
+        .checked_sub(1)
+        .and_then(|index| content.lines().nth(index))
+        .ok_or_else(|| {
+            anyhow!(
+                "Line {} is out of range, the file has {} lines",
+                line,
+                line_count
+            )
+        })?;
+
+    let byte_offset = match (symbol, column) {
+        (Some(symbol), _) => find_symbol(text, symbol).ok_or_else(|| {
+            anyhow!(
+                "Symbol '{}' not found on line {}: {}",
+                symbol,
+                line,
+                text.trim()
+            )
+        })?,
+        (None, Some(column)) => text
+            .char_indices()
+            .nth(column.saturating_sub(1))
+            .map(|(i, _)| i)
+            .unwrap_or(text.len()),
+        (None, None) => text.len() - text.trim_start().len(),
+    };
+
+    Ok((line - 1, text[..byte_offset].encode_utf16().count()))
+}
+
+/// Byte offset of the first whole-word occurrence of the symbol
+fn find_symbol(text: &str, symbol: &str) -> Option<usize> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(symbol)
+        .find(|(start, _)| {
+            let before = text[..*start].chars().next_back();
+            let after = text[start + symbol.len()..].chars().next();
+            !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+        })
+        .map(|(start, _)| start)
+}
+
+/// 1-based character column for a UTF-16 offset within the line
+fn char_column(text: &str, utf16_offset: usize) -> usize {
+    let mut units = 0;
+    for (column, c) in text.chars().enumerate() {
+        if units >= utf16_offset {
+            return column + 1;
+        }
+        units += c.len_utf16();
+    }
+    text.chars().count() + 1
+}
+
+/// Paths inside the project are shown relative to its root
+fn display_path(root: &Path, path: PathBuf) -> PathBuf {
+    match path.strip_prefix(root) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => path,
+    }
+}
+
+/// Read Location, Location[] or LocationLink[] results
+fn parse_locations(root: &Path, result: &Value) -> Vec<CodeLocation> {
+    let items = match result {
+        Value::Array(items) => items.clone(),
+        Value::Null => Vec::new(),
+        item => vec![item.clone()],
+    };
+
+    let mut files: HashMap<PathBuf, Option<String>> = HashMap::new();
+    let mut locations: Vec<CodeLocation> = Vec::new();
+    for item in items {
+        let uri = item.get("targetUri").or_else(|| item.get("uri"));
+        let range = item
+            .get("targetSelectionRange")
+            .or_else(|| item.get("range"));
+        let (Some(path), Some(range)) = (
+            uri.and_then(|u| u.as_str()).and_then(client::uri_to_path),
+            range,
+        ) else {
+            continue;
+        };
+
+        let line = range["start"]["line"].as_u64().unwrap_or(0) as usize;
+        let character = range["start"]["character"].as_u64().unwrap_or(0) as usize;
+        let content = files
+            .entry(path.clone())
+            .or_insert_with(|| std::fs::read_to_string(&path).ok());
+        let text = content
+            .as_deref()
+            .and_then(|c| c.lines().nth(line))
+            .unwrap_or("");
+
+        let location = CodeLocation {
+            path: display_path(root, path),
+            line: line + 1,
+            column: char_column(text, character),
+            preview: text.trim().to_string(),
+        };
+        if !locations.contains(&location) {
+            locations.push(location);
+        }
+    }
+    locations
+}
+
+/// Flatten hover contents (MarkupContent, MarkedString or MarkedString[])
+fn hover_text(contents: &Value) -> String {
+    match contents {
+        Value::String(text) => text.trim().to_string(),
+        Value::Array(items) => items
+            .iter()
+            .map(hover_text)
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        Value::Object(object) => {
+            let value = object.get("value").and_then(|v| v.as_str()).unwrap_or("");
+            match object.get("language").and_then(|l| l.as_str()) {
+                Some(language) => format!("```{}\n{}\n```", language, value.trim()),
+                None => value.trim().to_string(),
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+/// Collect DocumentSymbol trees or flat SymbolInformation / WorkspaceSymbol lists
+fn collect_symbols(
+    root: &Path,
+    item: &Value,
+    container: Option<&str>,
+    depth: usize,
+    with_path: bool,
+    symbols: &mut Vec<CodeSymbol>,
+) {
+    let Some(name) = item["name"].as_str() else {
+        return;
+    };
+    // SymbolInformation carries its location, DocumentSymbol its range
+    let range = item
+        .get("location")
+        .map(|l| &l["range"])
+        .or_else(|| item.get("range"));
+    let path = item["location"]["uri"]
+        .as_str()
+        .and_then(client::uri_to_path)
+        .map(|p| display_path(root, p));
+    let line = |position: &str| {
+        range
+            .and_then(|r| r[position]["line"].as_u64())
+            .map(|l| l as usize + 1)
+            .unwrap_or(0)
+    };
+
+    symbols.push(CodeSymbol {
+        name: name.to_string(),
+        kind: symbol_kind(item["kind"].as_u64().unwrap_or(0)).to_string(),
+        detail: item["detail"]
+            .as_str()
+            .filter(|d| !d.is_empty())
+            .map(|d| d.to_string()),
+        path: if with_path { path } else { None },
+        start_line: line("start"),
+        end_line: line("end"),
+        container: item["containerName"]
+            .as_str()
+            .filter(|c| !c.is_empty())
+            .or(container)
+            .map(|c| c.to_string()),
+        depth,
+    });
+
+    for child in item["children"].as_array().into_iter().flatten() {
+        collect_symbols(root, child, Some(name), depth + 1, with_path, symbols);
+    }
+}
+
+fn symbol_kind(kind: u64) -> &'static str {
+    match kind {
+        1 => "file",
+        2 => "module",
+        3 => "namespace",
+        4 => "package",
+        5 => "class",
+        6 => "method",
+        7 => "property",
+        8 => "field",
+        9 => "constructor",
+        10 => "enum",
+        11 => "interface",
+        12 => "function",
+        13 => "variable",
+        14 => "constant",
+        15 => "string",
+        16 => "number",
+        17 => "boolean",
+        18 => "array",
+        19 => "object",
+        20 => "key",
+        21 => "null",
+        22 => "enum member",
+        23 => "struct",
+        24 => "event",
+        25 => "operator",
+        26 => "type parameter",
+        _ => "symbol",
+    }
+}
+
+/// Errors and warnings from a publishDiagnostics notification
+fn convert_diagnostics(path: &Path, content: &str, items: &[Value]) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut diagnostics: Vec<Diagnostic> = items
+        .iter()
+        .filter_map(|item| {
+            let severity = match item["severity"].as_u64() {
+                Some(1) | None => DiagnosticSeverity::Error,
+                Some(2) => DiagnosticSeverity::Warning,
+                // Information and hints are noise after an edit
+                _ => return None,
+            };
+            let start = &item["range"]["start"];
+            let start_line = start["line"].as_u64().unwrap_or(0) as usize;
+            let end_line = item["range"]["end"]["line"]
+                .as_u64()
+                .map(|l| l as usize)
+                .unwrap_or(start_line);
+            let column = start["character"].as_u64().map(|character| {
+                char_column(
+                    lines.get(start_line).copied().unwrap_or(""),
+                    character as usize,
+                )
+            });
+            let code = match &item["code"] {
+                Value::String(code) => Some(code.clone()),
+                Value::Number(code) => Some(code.to_string()),
+                _ => None,
+            };
+
+            Some(Diagnostic {
+                path: path.to_path_buf(),
+                start_line: start_line + 1,
+                end_line: end_line + 1,
+                column,
+                severity,
+                code,
+                message: item["message"].as_str().unwrap_or("").trim().to_string(),
+                suggestion: None,
+            })
+        })
+        .collect();
+    diagnostics.sort_by_key(|d| (d.severity, d.start_line, d.column));
+    diagnostics
+}
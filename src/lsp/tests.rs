@@ -0,0 +1,407 @@
+use super::client::{path_to_uri, read_message, write_message};
+use super::*;
+use std::sync::Mutex;
+use tokio::io::BufReader;
+
+const SOURCE: &str = "/// Greets\n\
+pub fn greet(name: &str) -> String {\n    format!(\"hello {}\", name)\n}\n\
+\n\
+pub fn main() { let s = \"😀\"; greet(s); }\n";
+
+/// Canned answers of the fake language server
+#[derive(Default, Clone)]
+struct Script {
+    responses: HashMap<String, Value>,
+    /// Published after every didOpen and didChange
+    diagnostics: Vec<Value>,
+}
+
+/// Connect a client to a scripted fake server speaking JSON-RPC, returns
+/// the client and every message the server received
+async fn start_fake_server(
+    root: &Path,
+    script: Script,
+) -> (
+    LspClient,
+    Arc<Mutex<Vec<Value>>>,
+    tokio::task::JoinHandle<()>,
+) {
+    let (client_io, server_io) = tokio::io::duplex(64 * 1024);
+    let received = Arc::new(Mutex::new(Vec::new()));
+
+    let log = received.clone();
+    let server = tokio::spawn(async move {
+        let (reader, mut writer) = tokio::io::split(server_io);
+        let mut reader = BufReader::new(reader);
+        while let Ok(Some(message)) = read_message(&mut reader).await {
+            log.lock().unwrap().push(message.clone());
+            let method = message["method"].as_str().unwrap_or("");
+
+            if let Some(id) = message.get("id").filter(|_| !method.is_empty()) {
+                let result = match method {
+                    "initialize" => json!({ "capabilities": {} }),
+                    _ => script.responses.get(method).cloned().unwrap_or(Value::Null),
+                };
+                let response = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+                write_message(&mut writer, &response).await.unwrap();
+                continue;
+            }
+
+            match method {
+                "initialized" => {
+                    // Servers ask the client for settings
+                    let request = json!({
+                        "jsonrpc": "2.0",
+                        "id": "config-1",
+                        "method": "workspace/configuration",
+                        "params": { "items": [{ "section": "a" }, { "section": "b" }] }
+                    });
+                    write_message(&mut writer, &request).await.unwrap();
+                }
+                "textDocument/didOpen" | "textDocument/didChange" => {
+                    let document = &message["params"]["textDocument"];
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": "textDocument/publishDiagnostics",
+                        "params": {
+                            "uri": document["uri"],
+                            "version": document["version"],
+                            "diagnostics": script.diagnostics
+                        }
+                    });
+                    write_message(&mut writer, &notification).await.unwrap();
+                }
+                _ => {}
+            }
+        }
+    });
+
+    let (reader, writer) = tokio::io::split(client_io);
+    let client = LspClient::connect(reader, writer, root);
+    client.initialize().await.unwrap();
+    (client, received, server)
+}
+
+fn received_with_method(received: &Arc<Mutex<Vec<Value>>>, method: &str) -> Vec<Value> {
+    received
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|m| m["method"] == method)
+        .cloned()
+        .collect()
+}
+
+fn range(start_line: u64, start_character: u64, end_line: u64) -> Value {
+    json!({
+        "start": { "line": start_line, "character": start_character },
+        "end": { "line": end_line, "character": 0 }
+    })
+}
+
+fn create_project() -> Result<(tempfile::TempDir, PathBuf)> {
+    let dir = tempfile::TempDir::new()?;
+    // Canonical root so server URIs map back to project paths
+    let root = dir.path().canonicalize()?;
+    std::fs::create_dir(root.join("src"))?;
+    std::fs::write(root.join("src/lib.rs"), SOURCE)?;
+    Ok((dir, root))
+}
+
+#[tokio::test]
+async fn test_navigation() -> Result<()> {
+    let (_dir, root) = create_project()?;
+    let uri = path_to_uri(&root.join("src/lib.rs"))?;
+    let outside = path_to_uri(Path::new("/usr/lib/rustlib/src/string.rs"))?;
+
+    let mut script = Script::default();
+    script.responses.insert(
+        "textDocument/definition".into(),
+        json!([{
+            "targetUri": uri,
+            "targetRange": range(0, 0, 3),
+            "targetSelectionRange": range(1, 7, 1)
+        }]),
+    );
+    script.responses.insert(
+        "textDocument/references".into(),
+        json!([
+            { "uri": uri, "range": range(1, 7, 1) },
+            { "uri": uri, "range": range(5, 30, 5) },
+            { "uri": outside, "range": range(10, 4, 10) }
+        ]),
+    );
+    script.responses.insert(
+        "textDocument/hover".into(),
+        json!({ "contents": {
+            "kind": "markdown",
+            "value": "```rust\npub fn greet(name: &str) -> String\n```\n\nGreets\n"
+        }}),
+    );
+    script.responses.insert(
+        "textDocument/documentSymbol".into(),
+        json!([
+            {
+                "name": "greet",
+                "kind": 12,
+                "detail": "fn(name: &str) -> String",
+                "range": range(0, 0, 3),
+                "selectionRange": range(1, 7, 1),
+                "children": [
+                    { "name": "name", "kind": 13, "range": range(1, 13, 1), "selectionRange": range(1, 13, 1) }
+                ]
+            },
+            { "name": "main", "kind": 12, "range": range(5, 0, 5), "selectionRange": range(5, 7, 5) }
+        ]),
+    );
+    script.responses.insert(
+        "workspace/symbol".into(),
+        json!([{
+            "name": "greet",
+            "kind": 12,
+            "location": { "uri": uri, "range": range(1, 0, 3) },
+            "containerName": "lib"
+        }]),
+    );
+    let (client, received, _server) = start_fake_server(&root, script).await;
+
+    let path = Path::new("src/lib.rs");
+    let definitions = goto_definition(&client, path, 6, Some("greet"), None).await?;
+    assert_eq!(
+        definitions,
+        vec![CodeLocation {
+            path: PathBuf::from("src/lib.rs"),
+            line: 2,
+            column: 8,
+            preview: "pub fn greet(name: &str) -> String {".to_string(),
+        }]
+    );
+
+    // The symbol position is sent as UTF-16 offset, the emoji takes two units
+    let request = &received_with_method(&received, "textDocument/definition")[0];
+    assert_eq!(request["params"]["textDocument"]["uri"], json!(uri));
+    assert_eq!(
+        request["params"]["position"],
+        json!({ "line": 5, "character": 30 })
+    );
+    // The file was opened before the first request
+    let opened = received_with_method(&received, "textDocument/didOpen");
+    assert_eq!(opened.len(), 1);
+    assert_eq!(opened[0]["params"]["textDocument"]["languageId"], "rust");
+    assert_eq!(opened[0]["params"]["textDocument"]["text"], SOURCE);
+
+    let references = find_references(&client, path, 2, Some("greet"), None).await?;
+    assert_eq!(references.len(), 3);
+    assert_eq!(references[1].line, 6);
+    assert_eq!(references[1].column, 30);
+    assert_eq!(
+        references[2].path,
+        PathBuf::from("/usr/lib/rustlib/src/string.rs")
+    );
+    let request = &received_with_method(&received, "textDocument/references")[0];
+    assert_eq!(request["params"]["context"]["includeDeclaration"], true);
+    assert_eq!(
+        request["params"]["position"],
+        json!({ "line": 1, "character": 7 })
+    );
+
+    let text = hover(&client, path, 2, None, Some(8)).await?;
+    assert!(text.starts_with("```rust\npub fn greet"));
+    assert!(text.ends_with("Greets"));
+
+    let symbols = document_symbols(&client, path).await?;
+    let summary: Vec<_> = symbols
+        .iter()
+        .map(|s| {
+            (
+                s.name.as_str(),
+                s.kind.as_str(),
+                s.start_line,
+                s.end_line,
+                s.depth,
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("greet", "function", 1, 4, 0),
+            ("name", "variable", 2, 2, 1),
+            ("main", "function", 6, 6, 0)
+        ]
+    );
+    assert_eq!(
+        symbols[0].detail.as_deref(),
+        Some("fn(name: &str) -> String")
+    );
+    assert_eq!(symbols[1].container.as_deref(), Some("greet"));
+    assert_eq!(symbols[0].path, None);
+
+    let symbols = workspace_symbols(&client, "gre").await?;
+    assert_eq!(symbols.len(), 1);
+    assert_eq!(symbols[0].path, Some(PathBuf::from("src/lib.rs")));
+    assert_eq!(symbols[0].container.as_deref(), Some("lib"));
+    assert_eq!(symbols[0].start_line, 2);
+    let request = &received_with_method(&received, "workspace/symbol")[0];
+    assert_eq!(request["params"]["query"], "gre");
+
+    // Unchanged content is not sent again
+    assert_eq!(
+        received_with_method(&received, "textDocument/didOpen").len(),
+        1
+    );
+    assert!(received_with_method(&received, "textDocument/didChange").is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sync_and_diagnostics() -> Result<()> {
+    let (_dir, root) = create_project()?;
+    let script = Script {
+        diagnostics: vec![
+            json!({
+                "range": range(5, 30, 5),
+                "severity": 1,
+                "code": "E0425",
+                "message": "cannot find function `greet`"
+            }),
+            json!({ "range": range(1, 7, 1), "severity": 4, "message": "unused" }),
+            json!({ "range": range(0, 0, 0), "severity": 2, "code": 42, "message": "doc comment" }),
+        ],
+        ..Default::default()
+    };
+    let (client, received, server) = start_fake_server(&root, script).await;
+
+    // The server request for configuration is answered
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    let answer = loop {
+        let answer = received
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|m| m["id"] == "config-1")
+            .cloned();
+        match answer {
+            Some(answer) => break answer,
+            None if tokio::time::Instant::now() < deadline => {
+                tokio::time::sleep(Duration::from_millis(10)).await
+            }
+            None => panic!("No answer to workspace/configuration"),
+        }
+    };
+    assert_eq!(answer["result"], json!([null, null]));
+
+    let path = Path::new("src/lib.rs");
+    let diagnostics = sync_and_wait(&client, path, SOURCE, Duration::from_secs(5)).await?;
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Error);
+    assert_eq!(diagnostics[0].start_line, 6);
+    assert_eq!(diagnostics[0].column, Some(30));
+    assert_eq!(diagnostics[0].code.as_deref(), Some("E0425"));
+    assert_eq!(diagnostics[0].path, PathBuf::from("src/lib.rs"));
+    assert_eq!(diagnostics[1].severity, DiagnosticSeverity::Warning);
+    assert_eq!(diagnostics[1].code.as_deref(), Some("42"));
+
+    // Edits are sent as full content with increasing versions
+    let changed = SOURCE.replace("hello", "hi");
+    sync_and_wait(&client, path, &changed, Duration::from_secs(5)).await?;
+    let changes = received_with_method(&received, "textDocument/didChange");
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0]["params"]["textDocument"]["version"], 2);
+    assert_eq!(changes[0]["params"]["contentChanges"][0]["text"], changed);
+    assert_eq!(
+        received_with_method(&received, "textDocument/didSave").len(),
+        2
+    );
+
+    // Diagnostics of an unchanged document are available without waiting
+    let start = std::time::Instant::now();
+    let diagnostics = sync_and_wait(&client, path, &changed, Duration::from_secs(5)).await?;
+    assert_eq!(diagnostics.len(), 2);
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    // A server that went away is reported
+    server.abort();
+    let _ = server.await;
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while client.is_alive() && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(!client.is_alive());
+    assert!(workspace_symbols(&client, "greet").await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_shutdown() -> Result<()> {
+    let (_dir, root) = create_project()?;
+    let (client, received, _server) = start_fake_server(&root, Script::default()).await;
+
+    client.shutdown().await;
+    assert_eq!(received_with_method(&received, "shutdown").len(), 1);
+    assert_eq!(received_with_method(&received, "exit").len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_without_language_server() -> Result<()> {
+    let (_dir, root) = create_project()?;
+
+    // Edits in projects without a running server don't wait
+    let diagnostics = sync_file("project-without-server", Path::new("src/lib.rs"), SOURCE).await;
+    assert!(diagnostics.is_empty());
+
+    let error = language_servers()
+        .get_or_start("project-without-server", &root, None)
+        .await
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("No language server configured"));
+
+    let config = LanguageServerConfig {
+        command: "code-assistant-no-such-language-server".to_string(),
+        args: Vec::new(),
+    };
+    let error = language_servers()
+        .get_or_start("project-without-server", &root, Some(&config))
+        .await
+        .err()
+        .unwrap();
+    assert!(error
+        .to_string()
+        .contains("Failed to start language server"));
+
+    Ok(())
+}
+
+#[test]
+fn test_resolve_position() {
+    // First non-blank character by default
+    assert_eq!(resolve_position(SOURCE, 3, None, None).unwrap(), (2, 4));
+    // Whole words only: `name` is found as parameter, not inside `names`
+    let content = "let names = 1; let name = names;";
+    assert_eq!(
+        resolve_position(content, 1, Some("name"), None).unwrap(),
+        (0, 19)
+    );
+    // Columns count characters, offsets UTF-16 units
+    assert_eq!(
+        resolve_position(SOURCE, 6, None, Some(26)).unwrap(),
+        (5, 25)
+    );
+    assert_eq!(
+        resolve_position(SOURCE, 6, None, Some(27)).unwrap(),
+        (5, 27)
+    );
+
+    let error = resolve_position(SOURCE, 6, Some("missing"), None).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("Symbol 'missing' not found on line 6"));
+    let error = resolve_position(SOURCE, 10, None, None).unwrap_err();
+    assert!(error.to_string().contains("the file has 6 lines"));
+    assert!(resolve_position(SOURCE, 0, None, None).is_err());
+}
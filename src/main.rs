@@ -1,9 +1,14 @@
 mod agent;
 mod config;
+mod dependencies;
+mod diagnostics;
 mod explorer;
 mod llm;
+mod lsp;
 mod mcp;
 mod persistence;
+mod syntax;
+mod test_runner;
 mod tests;
 mod tools;
 mod types;
@@ -274,7 +279,10 @@ async fn main() -> Result<()> {
 
             // Initialize server
             let mut server = MCPServer::new()?;
-            server.run().await?;
+            let result = server.run().await;
+            lsp::language_servers().shutdown().await;
+            web::web_client().shutdown().await;
+            result?;
         }
 
         // Agent mode (default)
@@ -322,7 +330,7 @@ async fn main() -> Result<()> {
                 let root_path = path.canonicalize()?;
                 let project_manager = Box::new(DefaultProjectManager::new());
                 let user_interface: Box<dyn UserInterface> = Box::new(gui.clone());
-                let command_executor = Box::new(DefaultCommandExecutor);
+                let command_executor = Box::new(DefaultCommandExecutor::default());
                 let state_persistence = Box::new(FileStatePersistence::new(root_path.clone()));
 
                 // Start the agent in a separate thread using a standard thread
@@ -374,6 +382,9 @@ async fn main() -> Result<()> {
 
                 // Run the GUI in the main thread - this will block until the application exits
                 gui.run_app();
+                lsp::language_servers().shutdown().await;
+                utils::kill_all_processes();
+                web::web_client().shutdown().await;
 
                 // We return here when the GUI is closed
                 return Ok(());
@@ -383,7 +394,7 @@ async fn main() -> Result<()> {
                 let root_path = path.canonicalize()?;
                 let project_manager = Box::new(DefaultProjectManager::new());
                 let user_interface = Box::new(TerminalUI::new());
-                let command_executor = Box::new(DefaultCommandExecutor);
+                let command_executor = Box::new(DefaultCommandExecutor::default());
                 let state_persistence = Box::new(FileStatePersistence::new(root_path.clone()));
 
                 // Setup LLM client with the specified provider
@@ -411,12 +422,25 @@ async fn main() -> Result<()> {
                     Some(root_path.clone()),
                 );
 
+                // Don't leave background processes behind when interrupted
+                tokio::spawn(async {
+                    if tokio::signal::ctrl_c().await.is_ok() {
+                        lsp::language_servers().shutdown().await;
+                        utils::kill_all_processes();
+                        web::web_client().shutdown().await;
+                        std::process::exit(130);
+                    }
+                });
+
                 // Get task either from state file or argument
-                if continue_task {
-                    agent.start_from_state().await?;
+                let result = if continue_task {
+                    agent.start_from_state().await
                 } else {
-                    agent.start_with_task(task.unwrap()).await?;
-                }
+                    agent.start_with_task(task.unwrap()).await
+                };
+                lsp::language_servers().shutdown().await;
+                web::web_client().shutdown().await;
+                result?;
             }
         }
     }
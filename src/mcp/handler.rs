@@ -4,6 +4,7 @@ use crate::config::{DefaultProjectManager, ProjectManager};
 use crate::tools::{parse_tool_json, MCPToolHandler, ToolExecutor};
 use crate::types::Tools;
 use crate::utils::{CommandExecutor, DefaultCommandExecutor};
+use crate::web::BrowserTabId;
 use anyhow::Result;
 use tokio::io::{AsyncWriteExt, Stdout};
 use tracing::{debug, error, trace};
@@ -11,6 +12,8 @@ use tracing::{debug, error, trace};
 pub struct MessageHandler {
     project_manager: Box<dyn ProjectManager>,
     command_executor: Box<dyn CommandExecutor>,
+    // Page of the browser tool, closed with the browser when the server stops
+    browser_tab: BrowserTabId,
     resources: ResourceManager,
     stdout: Stdout,
 }
@@ -19,7 +22,8 @@ impl MessageHandler {
     pub fn new(stdout: Stdout) -> Result<Self> {
         Ok(Self {
             project_manager: Box::new(DefaultProjectManager::new()),
-            command_executor: Box::new(DefaultCommandExecutor),
+            command_executor: Box::new(DefaultCommandExecutor::default()),
+            browser_tab: BrowserTabId::new(),
             resources: ResourceManager::new(),
             stdout,
         })
@@ -235,22 +239,33 @@ impl MessageHandler {
                 &mut handler,
                 &self.project_manager,
                 &self.command_executor,
+                self.browser_tab,
+                None,
                 None,
                 &tool,
             )
             .await?;
 
-            Ok::<_, anyhow::Error>((output, result.is_success()))
+            // Images are returned as image content after the text
+            let mut content = vec![ToolResultContent::Text { text: output }];
+            if let Some(image) = result.image() {
+                content.push(ToolResultContent::Image {
+                    data: image.data.clone(),
+                    mime_type: image.media_type.clone(),
+                });
+            }
+
+            Ok::<_, anyhow::Error>((content, result.is_success()))
         }
         .await;
 
         // Convert the result into a ToolCallResult response
         match result {
-            Ok((output, is_success)) => {
+            Ok((content, is_success)) => {
                 self.send_response(
                     id,
                     ToolCallResult {
-                        content: vec![ToolResultContent::Text { text: output }],
+                        content,
                         is_error: !is_success,
                     },
                 )
@@ -186,6 +186,12 @@ pub struct ToolCallResult {
 pub enum ToolResultContent {
     #[serde(rename = "text")]
     Text { text: String },
+    #[serde(rename = "image")]
+    Image {
+        data: String,
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+    },
 }
 
 // Prompt types
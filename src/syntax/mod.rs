@@ -0,0 +1,71 @@
+mod repo_map;
+mod symbols;
+mod validate;
+
+pub use repo_map::{build_repo_map, MAX_FILE_BYTES};
+pub use symbols::{extract_symbols, find_symbol, SourceSymbol};
+pub use validate::{new_syntax_errors, syntax_errors};
+
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+/// Languages with a tree-sitter grammar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceLanguage {
+    Rust,
+    Python,
+    TypeScript,
+    /// TypeScript with JSX, also used for JavaScript
+    Tsx,
+    Go,
+}
+
+impl SourceLanguage {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rs" => Some(Self::Rust),
+            "py" | "pyi" => Some(Self::Python),
+            "ts" | "mts" | "cts" => Some(Self::TypeScript),
+            "tsx" | "js" | "jsx" | "mjs" | "cjs" => Some(Self::Tsx),
+            "go" => Some(Self::Go),
+            _ => None,
+        }
+    }
+
+    fn grammar(self) -> tree_sitter::Language {
+        match self {
+            Self::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Self::Python => tree_sitter_python::LANGUAGE.into(),
+            Self::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Self::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Self::Go => tree_sitter_go::LANGUAGE.into(),
+        }
+    }
+}
+
+pub fn parse(language: SourceLanguage, source: &str) -> Result<tree_sitter::Tree> {
+    let mut parser = tree_sitter::Parser::new();
+    parser.set_language(&language.grammar())?;
+    parser
+        .parse(source, None)
+        .ok_or_else(|| anyhow!("Failed to parse {:?} source", language))
+}
+
+/// Source of a symbol like `Config::load` in a file, including its doc
+/// comments and attributes, and the number of its first line
+pub fn symbol_source(path: &Path, content: &str, query: &str) -> Result<(usize, String)> {
+    let language = SourceLanguage::from_path(path).ok_or_else(|| {
+        anyhow!(
+            "Symbols can't be resolved in {}, supported are Rust, Python, TypeScript, JavaScript and Go files",
+            path.display()
+        )
+    })?;
+    let symbol = find_symbol(language, content, query)
+        .map_err(|e| anyhow!("{} in {}", e, path.display()))?;
+    let lines: Vec<&str> = content
+        .lines()
+        .skip(symbol.start_line - 1)
+        .take(symbol.end_line + 1 - symbol.start_line)
+        .collect();
+    Ok((symbol.start_line, lines.join("\n")))
+}
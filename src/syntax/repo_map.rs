@@ -0,0 +1,227 @@
+use super::{extract_symbols, SourceLanguage, SourceSymbol};
+use crate::types::RepoMap;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Larger files are usually generated and are not parsed
+pub const MAX_FILE_BYTES: u64 = 512 * 1024;
+
+// Weights for ranking files by their relation to the focus files
+const DEFINES_FOCUS_REFERENCE: f64 = 3.0;
+const REFERENCES_FOCUS_DEFINITION: f64 = 1.0;
+const REFERENCED_ELSEWHERE: f64 = 0.5;
+const SAME_DIRECTORY: f64 = 1.0;
+
+fn identifier_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    // Short identifiers like `i` or `ok` would relate every file to every other
+    REGEX.get_or_init(|| Regex::new(r"[A-Za-z_][A-Za-z0-9_]{2,}").unwrap())
+}
+
+struct MappedFile<'a> {
+    path: &'a Path,
+    symbols: Vec<SourceSymbol>,
+    definitions: HashSet<String>,
+    identifiers: HashSet<&'a str>,
+}
+
+/// Builds a map of the symbols in `files` (relative path and content), ranked
+/// by relevance to the `focus` files and limited to about `max_tokens`
+pub fn build_repo_map(
+    files: &[(PathBuf, String)],
+    focus: &[PathBuf],
+    max_tokens: usize,
+) -> RepoMap {
+    let mapped: Vec<MappedFile> = files
+        .iter()
+        .filter_map(|(path, content)| {
+            let language = SourceLanguage::from_path(path)?;
+            let symbols = extract_symbols(language, content).ok()?;
+            let mut definitions = HashSet::new();
+            collect_names(&symbols, &mut definitions);
+            let identifiers = identifier_regex()
+                .find_iter(content)
+                .map(|m| m.as_str())
+                .collect();
+            Some(MappedFile {
+                path,
+                symbols,
+                definitions,
+                identifiers,
+            })
+        })
+        .collect();
+
+    // Names defined in many files say little about a relation between files
+    let mut definition_counts: HashMap<&str, usize> = HashMap::new();
+    for file in &mapped {
+        for name in &file.definitions {
+            *definition_counts.entry(name.as_str()).or_default() += 1;
+        }
+    }
+    let weight = |name: &str| 1.0 / definition_counts.get(name).copied().unwrap_or(1) as f64;
+
+    // Files mentioning each defined name, counted once instead of per definition
+    let mut reference_counts: HashMap<&str, usize> = HashMap::new();
+    for file in &mapped {
+        for name in &file.identifiers {
+            if definition_counts.contains_key(name) {
+                *reference_counts.entry(name).or_default() += 1;
+            }
+        }
+    }
+
+    let is_focus = |path: &Path| focus.iter().any(|f| f == path);
+    let focus_files: Vec<&MappedFile> = mapped.iter().filter(|f| is_focus(f.path)).collect();
+    let focus_identifiers: HashSet<&str> = focus_files
+        .iter()
+        .flat_map(|f| f.identifiers.iter().copied())
+        .collect();
+    let focus_definitions: HashSet<&str> = focus_files
+        .iter()
+        .flat_map(|f| f.definitions.iter().map(|d| d.as_str()))
+        .collect();
+    let focus_directories: HashSet<&Path> = focus.iter().filter_map(|f| f.parent()).collect();
+
+    let mut ranked: Vec<(f64, &MappedFile)> = mapped
+        .iter()
+        .filter(|f| !is_focus(f.path) && !f.symbols.is_empty())
+        .map(|file| {
+            let mut score = 0.0;
+            for name in &file.definitions {
+                if focus_identifiers.contains(name.as_str()) {
+                    score += DEFINES_FOCUS_REFERENCE * weight(name);
+                }
+                // Only other files, the defining one usually mentions the name too
+                let references = reference_counts.get(name.as_str()).copied().unwrap_or(0)
+                    - usize::from(file.identifiers.contains(name.as_str()));
+                score += REFERENCED_ELSEWHERE * weight(name) * (references as f64).ln_1p();
+            }
+            for name in &focus_definitions {
+                if file.identifiers.contains(name) && !file.definitions.contains(*name) {
+                    score += REFERENCES_FOCUS_DEFINITION * weight(name);
+                }
+            }
+            if file
+                .path
+                .parent()
+                .is_some_and(|dir| focus_directories.contains(dir))
+            {
+                score += SAME_DIRECTORY;
+            }
+            (score, file)
+        })
+        .collect();
+
+    // Without a focus, files closer to the root come first
+    ranked.sort_by(|(a_score, a), (b_score, b)| {
+        b_score
+            .total_cmp(a_score)
+            .then_with(|| {
+                a.path
+                    .components()
+                    .count()
+                    .cmp(&b.path.components().count())
+            })
+            .then_with(|| a.path.cmp(b.path))
+    });
+
+    let files_total = ranked.len();
+    let max_chars = max_tokens * 4;
+    let mut content = String::new();
+    let mut files_shown = 0;
+    for (_, file) in ranked {
+        let block = format_file(file.path, &file.symbols);
+        // Skip files that don't fit, a smaller one further down might
+        if content.len() + block.len() > max_chars {
+            continue;
+        }
+        content.push_str(&block);
+        files_shown += 1;
+    }
+
+    RepoMap {
+        content,
+        files_shown,
+        files_total,
+    }
+}
+
+fn collect_names(symbols: &[SourceSymbol], names: &mut HashSet<String>) {
+    for symbol in symbols {
+        // Impls don't define their type name
+        if symbol.kind != "impl" {
+            names.insert(symbol.name.clone());
+        }
+        collect_names(&symbol.children, names);
+    }
+}
+
+fn format_file(path: &Path, symbols: &[SourceSymbol]) -> String {
+    let mut block = format!("{}:\n", path.display());
+    format_symbols(symbols, 1, &mut block);
+    block
+}
+
+fn format_symbols(symbols: &[SourceSymbol], depth: usize, output: &mut String) {
+    for symbol in symbols {
+        output.push_str(&format!(
+            "{}{}: {}\n",
+            "  ".repeat(depth),
+            symbol.line,
+            symbol.signature
+        ));
+        format_symbols(&symbol.children, depth + 1, output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> Vec<(PathBuf, String)> {
+        vec![
+            (
+                PathBuf::from("src/main.rs"),
+                "mod config;\n\nfn main() {\n    let config = Config::load();\n}\n".to_string(),
+            ),
+            (
+                PathBuf::from("src/config.rs"),
+                "pub struct Config {}\n\nimpl Config {\n    pub fn load() -> Self {\n        Config {}\n    }\n}\n"
+                    .to_string(),
+            ),
+            (
+                PathBuf::from("src/net/client.rs"),
+                "pub fn connect(address: &str) {}\n".to_string(),
+            ),
+            (PathBuf::from("README.md"), "# Readme\n".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_repo_map_ranks_by_focus() {
+        let map = build_repo_map(&files(), &[PathBuf::from("src/main.rs")], 1000);
+        assert_eq!(
+            map.content,
+            "src/config.rs:\n  1: pub struct Config\n  3: impl Config\n    4: pub fn load() -> Self\nsrc/net/client.rs:\n  1: pub fn connect(address: &str)\n"
+        );
+        assert_eq!(map.files_shown, 2);
+        assert_eq!(map.files_total, 2);
+    }
+
+    #[test]
+    fn test_repo_map_token_budget() {
+        // Without a focus, files referenced by others come first
+        let map = build_repo_map(&files(), &[], 1000);
+        assert!(map.content.starts_with("src/config.rs:\n"));
+        assert_eq!(map.files_total, 3);
+
+        // The config file doesn't fit into 15 tokens, the smaller main file does
+        let map = build_repo_map(&files(), &[], 15);
+        assert_eq!(map.content, "src/main.rs:\n  3: fn main()\n");
+        assert_eq!(map.files_shown, 1);
+        assert_eq!(map.files_total, 3);
+    }
+}
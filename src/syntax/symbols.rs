@@ -0,0 +1,676 @@
+use super::{parse, SourceLanguage};
+use anyhow::{anyhow, Result};
+use tree_sitter::Node;
+
+// Longer signatures are cut, e.g. functions with many parameters
+const MAX_SIGNATURE_CHARS: usize = 200;
+
+/// A definition in a source file
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceSymbol {
+    pub name: String,
+    /// Enclosing type, impl, class or module
+    pub parent: Option<String>,
+    /// Keyword like fn, struct, class or method
+    pub kind: &'static str,
+    /// Declaration without its body on a single line
+    pub signature: String,
+    /// 1-based line of the declaration
+    pub line: usize,
+    /// 1-based line range including doc comments and attributes
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Methods of types, impls and classes
+    pub children: Vec<SourceSymbol>,
+}
+
+impl SourceSymbol {
+    /// Name including the parent, e.g. `Config::load`
+    pub fn qualified_name(&self) -> String {
+        match &self.parent {
+            Some(parent) => format!("{}::{}", parent, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+// Number of suggestions listed for a missing or ambiguous symbol
+const MAX_CLOSE_MATCHES: usize = 10;
+
+/// Top-level definitions of a source file, with their methods as children
+pub fn extract_symbols(language: SourceLanguage, source: &str) -> Result<Vec<SourceSymbol>> {
+    let tree = parse(language, source)?;
+    let mut symbols = Vec::new();
+    let extractor = Extractor { source, language };
+    extractor.collect(tree.root_node(), None, &mut symbols);
+    Ok(symbols)
+}
+
+struct Extractor<'a> {
+    source: &'a str,
+    language: SourceLanguage,
+}
+
+impl Extractor<'_> {
+    fn collect(&self, container: Node, parent: Option<&str>, symbols: &mut Vec<SourceSymbol>) {
+        let mut cursor = container.walk();
+        for node in container.named_children(&mut cursor) {
+            match self.language {
+                SourceLanguage::Rust => self.rust_item(node, parent, symbols),
+                SourceLanguage::Python => self.python_item(node, node, parent, symbols),
+                SourceLanguage::TypeScript | SourceLanguage::Tsx => {
+                    self.typescript_item(node, node, parent, symbols)
+                }
+                SourceLanguage::Go => self.go_item(node, symbols),
+            }
+        }
+    }
+
+    fn rust_item(&self, node: Node, parent: Option<&str>, symbols: &mut Vec<SourceSymbol>) {
+        let kind = match node.kind() {
+            "function_item" | "function_signature_item" => "fn",
+            "struct_item" => "struct",
+            "enum_item" => "enum",
+            "union_item" => "union",
+            "trait_item" => "trait",
+            "impl_item" => "impl",
+            "type_item" => "type",
+            "const_item" => "const",
+            "static_item" => "static",
+            "mod_item" => "mod",
+            "macro_definition" => "macro_rules!",
+            _ => return,
+        };
+
+        let name = match kind {
+            // Methods of an impl belong to the implementing type
+            "impl" => node
+                .child_by_field_name("type")
+                .map(|t| strip_generics(self.text(t)).to_string()),
+            _ => node
+                .child_by_field_name("name")
+                .map(|n| self.text(n).to_string()),
+        };
+        let Some(name) = name else { return };
+
+        let body = node.child_by_field_name("body");
+        // `mod name;` only points to another file
+        if kind == "mod" && body.is_none() {
+            return;
+        }
+        let mut symbol = self.symbol(node, node, body, &name, kind, parent);
+        if let (Some(body), "impl" | "trait" | "mod") = (body, kind) {
+            let qualified = symbol.qualified_name();
+            // Impl methods are qualified by the type, not by the module
+            let child_parent = if kind == "impl" { &name } else { &qualified };
+            self.collect(body, Some(child_parent), &mut symbol.children);
+        }
+        symbols.push(symbol);
+    }
+
+    /// `outer` includes decorators, `node` is the definition itself
+    fn python_item(
+        &self,
+        outer: Node,
+        node: Node,
+        parent: Option<&str>,
+        symbols: &mut Vec<SourceSymbol>,
+    ) {
+        let kind = match node.kind() {
+            "decorated_definition" => {
+                if let Some(definition) = node.child_by_field_name("definition") {
+                    self.python_item(outer, definition, parent, symbols);
+                }
+                return;
+            }
+            "function_definition" if parent.is_some() => "method",
+            "function_definition" => "def",
+            "class_definition" => "class",
+            _ => return,
+        };
+        let Some(name) = node.child_by_field_name("name") else {
+            return;
+        };
+        let name = self.text(name).to_string();
+        let body = node.child_by_field_name("body");
+
+        let mut symbol = self.symbol(outer, node, body, &name, kind, parent);
+        if let (Some(body), "class") = (body, kind) {
+            self.collect(body, Some(&name), &mut symbol.children);
+        }
+        symbols.push(symbol);
+    }
+
+    /// `outer` includes an export statement, `node` is the declaration
+    fn typescript_item(
+        &self,
+        outer: Node,
+        node: Node,
+        parent: Option<&str>,
+        symbols: &mut Vec<SourceSymbol>,
+    ) {
+        let kind = match node.kind() {
+            "export_statement" => {
+                if let Some(declaration) = node.child_by_field_name("declaration") {
+                    self.typescript_item(outer, declaration, parent, symbols);
+                }
+                return;
+            }
+            // `declare function`, `declare class` and the like in type definitions
+            "ambient_declaration" => {
+                let mut cursor = node.walk();
+                for declaration in node.named_children(&mut cursor) {
+                    self.typescript_item(outer, declaration, parent, symbols);
+                }
+                return;
+            }
+            "lexical_declaration" | "variable_declaration" if parent.is_none() => {
+                let mut cursor = node.walk();
+                for declarator in node.named_children(&mut cursor) {
+                    if declarator.kind() != "variable_declarator" {
+                        continue;
+                    }
+                    let Some(name) = declarator.child_by_field_name("name") else {
+                        continue;
+                    };
+                    // Functions assigned to constants show their parameters
+                    let value = declarator.child_by_field_name("value");
+                    let (kind, body) = match value.map(|v| v.kind()) {
+                        Some("arrow_function" | "function_expression" | "function") => (
+                            "function",
+                            value.and_then(|v| v.child_by_field_name("body")),
+                        ),
+                        _ => ("const", None),
+                    };
+                    let name = self.text(name).to_string();
+                    symbols.push(self.symbol(outer, outer, body, &name, kind, parent));
+                }
+                return;
+            }
+            "function_declaration" | "generator_function_declaration" | "function_signature" => {
+                "function"
+            }
+            "class_declaration" | "abstract_class_declaration" => "class",
+            "internal_module" | "module" => "namespace",
+            "interface_declaration" => "interface",
+            "type_alias_declaration" => "type",
+            "enum_declaration" => "enum",
+            "method_definition" | "abstract_method_signature" | "method_signature"
+                if parent.is_some() =>
+            {
+                "method"
+            }
+            _ => return,
+        };
+        let Some(name) = node.child_by_field_name("name") else {
+            return;
+        };
+        // Modules declared for a package are named by a string
+        let name = self.text(name).trim_matches(['"', '\'']).to_string();
+        let body = node.child_by_field_name("body");
+
+        let mut symbol = self.symbol(outer, node, body, &name, kind, parent);
+        if let (Some(body), "class" | "namespace") = (body, kind) {
+            self.collect(body, Some(&name), &mut symbol.children);
+        }
+        symbols.push(symbol);
+    }
+
+    fn go_item(&self, node: Node, symbols: &mut Vec<SourceSymbol>) {
+        match node.kind() {
+            "function_declaration" | "method_declaration" => {
+                let Some(name) = node.child_by_field_name("name") else {
+                    return;
+                };
+                // Methods belong to the receiver type
+                let receiver = node
+                    .child_by_field_name("receiver")
+                    .and_then(|r| self.find_type_name(r));
+                let kind = if receiver.is_some() { "method" } else { "func" };
+                let body = node.child_by_field_name("body");
+                let name = self.text(name).to_string();
+                symbols.push(self.symbol(node, node, body, &name, kind, receiver.as_deref()));
+            }
+            "type_declaration" => {
+                let mut cursor = node.walk();
+                let specs: Vec<Node> = node
+                    .named_children(&mut cursor)
+                    .filter(|s| matches!(s.kind(), "type_spec" | "type_alias"))
+                    .collect();
+                for spec in &specs {
+                    let Some(name) = spec.child_by_field_name("name") else {
+                        continue;
+                    };
+                    // A single spec owns the doc comment of the declaration
+                    let outer = if specs.len() == 1 { node } else { *spec };
+                    let body = spec
+                        .child_by_field_name("type")
+                        .filter(|t| matches!(t.kind(), "struct_type" | "interface_type"));
+                    let name = self.text(name).to_string();
+                    let mut symbol = self.symbol(outer, outer, body, &name, "type", None);
+                    if let Some(body) = body {
+                        // Keep the struct or interface keyword in the signature
+                        let keyword = body.kind().trim_end_matches("_type");
+                        symbol.signature = format!("{} {}", symbol.signature, keyword);
+                    }
+                    symbols.push(symbol);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Name of the type in a Go receiver like `(r *Server[T])`
+    fn find_type_name(&self, node: Node) -> Option<String> {
+        if node.kind() == "type_identifier" {
+            return Some(self.text(node).to_string());
+        }
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.named_children(&mut cursor).collect();
+        children
+            .into_iter()
+            .filter(|c| c.kind() != "identifier")
+            .find_map(|c| self.find_type_name(c))
+    }
+
+    /// `outer` is the item with visibility, decorators or export, `node` the
+    /// declaration and `body` the part left out of the signature
+    fn symbol(
+        &self,
+        outer: Node,
+        node: Node,
+        body: Option<Node>,
+        name: &str,
+        kind: &'static str,
+        parent: Option<&str>,
+    ) -> SourceSymbol {
+        let signature_start = if outer.kind() == "decorated_definition" {
+            node.start_byte()
+        } else {
+            outer.start_byte()
+        };
+        let signature = match body {
+            Some(body) => &self.source[signature_start..body.start_byte()],
+            // Without a body only the first line is shown, e.g. of a constant
+            None => self.source[signature_start..outer.end_byte()]
+                .lines()
+                .next()
+                .unwrap_or(""),
+        };
+
+        SourceSymbol {
+            name: name.to_string(),
+            parent: parent.map(|p| p.to_string()),
+            kind,
+            signature: clean_signature(signature),
+            line: node.start_position().row + 1,
+            start_line: self.leading_trivia_start(outer) + 1,
+            end_line: outer.end_position().row + 1,
+            children: Vec::new(),
+        }
+    }
+
+    /// First row of the doc comments and attributes directly above the node
+    fn leading_trivia_start(&self, node: Node) -> usize {
+        let mut start = node.start_position().row;
+        let mut previous = node.prev_named_sibling();
+        while let Some(sibling) = previous {
+            let is_trivia = matches!(
+                sibling.kind(),
+                "line_comment" | "block_comment" | "comment" | "attribute_item"
+            );
+            // Blank lines separate unrelated comments
+            if !is_trivia || sibling.end_position().row + 1 < start {
+                break;
+            }
+            // A trailing comment belongs to the item before it
+            let line_start = self.source[..sibling.start_byte()]
+                .rfind('\n')
+                .map_or(0, |i| i + 1);
+            if !self.source[line_start..sibling.start_byte()]
+                .trim()
+                .is_empty()
+            {
+                break;
+            }
+            start = sibling.start_position().row;
+            previous = sibling.prev_named_sibling();
+        }
+        start
+    }
+
+    fn text(&self, node: Node) -> &str {
+        &self.source[node.byte_range()]
+    }
+}
+
+/// `Config<T>` -> `Config`, `&mut Config` stays as is
+fn strip_generics(name: &str) -> &str {
+    name.split('<').next().unwrap_or(name).trim()
+}
+
+fn clean_signature(signature: &str) -> String {
+    let mut signature = signature.split_whitespace().collect::<Vec<_>>().join(" ");
+    // Python puts a colon before the body
+    if signature.ends_with(':') {
+        signature.pop();
+    }
+    if let Some((cut, _)) = signature.char_indices().nth(MAX_SIGNATURE_CHARS) {
+        signature.truncate(cut);
+        signature.push_str("...");
+    }
+    signature.trim_end().to_string()
+}
+
+/// Finds a symbol by name like `load`, `Config` or `Config::load`. Errors for
+/// missing or ambiguous symbols list the closest matches.
+pub fn find_symbol(language: SourceLanguage, source: &str, query: &str) -> Result<SourceSymbol> {
+    let symbols = extract_symbols(language, source)?;
+    let mut all = Vec::new();
+    flatten(&symbols, &mut all);
+
+    let query = query.trim();
+    let suffix = format!("::{}", query);
+    let mut matches: Vec<&SourceSymbol> = all
+        .iter()
+        .copied()
+        .filter(|s| {
+            let qualified = s.qualified_name();
+            qualified == query || qualified.ends_with(&suffix)
+        })
+        .collect();
+    // A type name refers to the type rather than its impl blocks
+    if matches.iter().any(|s| s.kind != "impl") {
+        matches.retain(|s| s.kind != "impl");
+    }
+
+    match matches.as_slice() {
+        [symbol] => Ok((*symbol).clone()),
+        [] => {
+            let close = close_matches(&all, query);
+            if close.is_empty() {
+                Err(anyhow!("Symbol '{}' not found", query))
+            } else {
+                Err(anyhow!(
+                    "Symbol '{}' not found. Close matches: {}",
+                    query,
+                    close.join(", ")
+                ))
+            }
+        }
+        _ => Err(anyhow!(
+            "Symbol '{}' is ambiguous, it matches: {}. Use the qualified name or a line range.",
+            query,
+            matches
+                .iter()
+                .take(MAX_CLOSE_MATCHES)
+                .map(|s| describe(s))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+fn flatten<'a>(symbols: &'a [SourceSymbol], all: &mut Vec<&'a SourceSymbol>) {
+    for symbol in symbols {
+        all.push(symbol);
+        flatten(&symbol.children, all);
+    }
+}
+
+fn describe(symbol: &SourceSymbol) -> String {
+    format!(
+        "{} {} (line {})",
+        symbol.kind,
+        symbol.qualified_name(),
+        symbol.line
+    )
+}
+
+/// Symbols whose name contains the queried name or differs only by a few edits
+fn close_matches(all: &[&SourceSymbol], query: &str) -> Vec<String> {
+    let name = query.rsplit("::").next().unwrap_or(query).to_lowercase();
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut scored: Vec<(usize, &SourceSymbol)> = all
+        .iter()
+        .filter(|s| s.kind != "impl")
+        .filter_map(|s| {
+            let candidate = s.name.to_lowercase();
+            if candidate.contains(&name) || name.contains(&candidate) {
+                Some((0, *s))
+            } else {
+                let distance = edit_distance(&candidate, &name);
+                (distance <= max_distance).then_some((distance, *s))
+            }
+        })
+        .collect();
+    scored.sort_by_key(|(distance, s)| (*distance, s.line));
+    scored
+        .into_iter()
+        .take(MAX_CLOSE_MATCHES)
+        .map(|(_, s)| describe(s))
+        .collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(symbols: &[SourceSymbol]) -> Vec<(String, &'static str, usize, usize)> {
+        let mut result = Vec::new();
+        for symbol in symbols {
+            result.push((
+                symbol.qualified_name(),
+                symbol.kind,
+                symbol.start_line,
+                symbol.end_line,
+            ));
+            result.extend(summary(&symbol.children));
+        }
+        result
+    }
+
+    #[test]
+    fn test_rust_symbols() -> Result<()> {
+        let source = r#"use std::fmt;
+
+/// A configuration
+#[derive(Debug)]
+pub struct Config<T> {
+    value: T,
+}
+
+// Unrelated comment
+
+impl<T: fmt::Debug> Config<T> {
+    /// Creates a config
+    pub fn new(value: T) -> Self {
+        Self { value }
+    }
+}
+
+pub trait Named {
+    fn name(&self) -> String;
+}
+
+mod util {
+    pub fn helper() {}
+}
+
+pub const LIMIT: usize = 10;
+"#;
+        let symbols = extract_symbols(SourceLanguage::Rust, source)?;
+        assert_eq!(
+            summary(&symbols),
+            vec![
+                ("Config".to_string(), "struct", 3, 7),
+                ("Config".to_string(), "impl", 11, 16),
+                ("Config::new".to_string(), "fn", 12, 15),
+                ("Named".to_string(), "trait", 18, 20),
+                ("Named::name".to_string(), "fn", 19, 19),
+                ("util".to_string(), "mod", 22, 24),
+                ("util::helper".to_string(), "fn", 23, 23),
+                ("LIMIT".to_string(), "const", 26, 26),
+            ]
+        );
+        assert_eq!(symbols[0].signature, "pub struct Config<T>");
+        assert_eq!(symbols[0].line, 5);
+        assert_eq!(symbols[1].signature, "impl<T: fmt::Debug> Config<T>");
+        assert_eq!(
+            symbols[1].children[0].signature,
+            "pub fn new(value: T) -> Self"
+        );
+        assert_eq!(
+            symbols[2].children[0].signature,
+            "fn name(&self) -> String;"
+        );
+        assert_eq!(symbols[4].signature, "pub const LIMIT: usize = 10;");
+        Ok(())
+    }
+
+    #[test]
+    fn test_python_typescript_go_symbols() -> Result<()> {
+        let python = "import os\n\n# Loads things\n@cache\ndef load(path: str) -> dict:\n    return {}\n\nclass Store(Base):\n    def get(self, key):\n        pass\n";
+        let symbols = extract_symbols(SourceLanguage::Python, python)?;
+        assert_eq!(
+            summary(&symbols),
+            vec![
+                ("load".to_string(), "def", 3, 6),
+                ("Store".to_string(), "class", 8, 10),
+                ("Store::get".to_string(), "method", 9, 10),
+            ]
+        );
+        assert_eq!(symbols[0].signature, "def load(path: str) -> dict");
+        assert_eq!(symbols[1].signature, "class Store(Base)");
+
+        let typescript = "/** Greets */\nexport function greet(name: string): string {\n  return name;\n}\n\nexport class Greeter extends Base {\n  greet(): void {}\n  count = 1;\n}\n\ninterface Options { loud: boolean }\ntype Name = string;\nexport const shout = (text: string) => text.toUpperCase();\n";
+        let symbols = extract_symbols(SourceLanguage::TypeScript, typescript)?;
+        assert_eq!(
+            summary(&symbols),
+            vec![
+                ("greet".to_string(), "function", 1, 4),
+                ("Greeter".to_string(), "class", 6, 9),
+                ("Greeter::greet".to_string(), "method", 7, 7),
+                ("Options".to_string(), "interface", 11, 11),
+                ("Name".to_string(), "type", 12, 12),
+                ("shout".to_string(), "function", 13, 13),
+            ]
+        );
+        assert_eq!(
+            symbols[0].signature,
+            "export function greet(name: string): string"
+        );
+        assert_eq!(symbols[1].signature, "export class Greeter extends Base");
+        assert_eq!(
+            symbols[4].signature,
+            "export const shout = (text: string) =>"
+        );
+
+        let go = "package store\n\n// Store keeps values\ntype Store struct {\n\tvalues map[string]string\n}\n\nfunc New() *Store { return &Store{} }\n\nfunc (s *Store) Get(key string) string {\n\treturn s.values[key]\n}\n";
+        let symbols = extract_symbols(SourceLanguage::Go, go)?;
+        assert_eq!(
+            summary(&symbols),
+            vec![
+                ("Store".to_string(), "type", 3, 6),
+                ("New".to_string(), "func", 8, 8),
+                ("Store::Get".to_string(), "method", 10, 12),
+            ]
+        );
+        assert_eq!(symbols[0].signature, "type Store struct");
+        assert_eq!(
+            symbols[2].signature,
+            "func (s *Store) Get(key string) string"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_definition_symbols() -> Result<()> {
+        let definitions = "/** Adds numbers */\nexport declare function add(a: number): number;\nexport declare class Client {\n  get(url: string): Promise<string>;\n}\ndeclare namespace util {\n  function format(value: unknown): string;\n}\n";
+        let symbols = extract_symbols(SourceLanguage::TypeScript, definitions)?;
+        assert_eq!(
+            summary(&symbols),
+            vec![
+                ("add".to_string(), "function", 1, 2),
+                ("Client".to_string(), "class", 3, 5),
+                ("Client::get".to_string(), "method", 4, 4),
+                ("util".to_string(), "namespace", 6, 8),
+                ("util::format".to_string(), "function", 7, 7),
+            ]
+        );
+        assert_eq!(
+            symbols[0].signature,
+            "export declare function add(a: number): number;"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_symbol() -> Result<()> {
+        let source = r#"/// A configuration
+#[derive(Debug)]
+pub struct Config {}
+
+impl Config {
+    /// Loads the config
+    pub fn load() -> Self {
+        Config {}
+    }
+}
+
+pub struct Store {}
+
+impl Store {
+    pub fn load() -> Self {
+        Store {}
+    }
+}
+
+fn helper() {} // trailing comment
+fn other() {}
+"#;
+        let symbol = find_symbol(SourceLanguage::Rust, source, "Config::load")?;
+        assert_eq!((symbol.start_line, symbol.end_line), (6, 9));
+
+        // Type names prefer the type over its impl blocks
+        let symbol = find_symbol(SourceLanguage::Rust, source, "Config")?;
+        assert_eq!(
+            (symbol.kind, symbol.start_line, symbol.end_line),
+            ("struct", 1, 3)
+        );
+
+        // The trailing comment of the previous item is not included
+        let symbol = find_symbol(SourceLanguage::Rust, source, "other")?;
+        assert_eq!((symbol.start_line, symbol.end_line), (21, 21));
+
+        let error = find_symbol(SourceLanguage::Rust, source, "load").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Symbol 'load' is ambiguous, it matches: fn Config::load (line 7), fn Store::load (line 15). Use the qualified name or a line range."
+        );
+
+        let error = find_symbol(SourceLanguage::Rust, source, "Config::lod").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Symbol 'Config::lod' not found. Close matches: fn Config::load (line 7), fn Store::load (line 15)"
+        );
+
+        let error = find_symbol(SourceLanguage::Rust, source, "missing").unwrap_err();
+        assert_eq!(error.to_string(), "Symbol 'missing' not found");
+        Ok(())
+    }
+}
@@ -0,0 +1,196 @@
+use super::{parse, SourceLanguage};
+use crate::types::{Diagnostic, DiagnosticSeverity};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+use tree_sitter::Node;
+
+// Unexpected text quoted in error messages is cut after this many characters
+const MAX_QUOTED_CHARS: usize = 40;
+
+/// Parse errors of a source file as error diagnostics
+pub fn syntax_errors(
+    language: SourceLanguage,
+    path: &Path,
+    source: &str,
+) -> Result<Vec<Diagnostic>> {
+    let tree = parse(language, source)?;
+    let mut errors = Vec::new();
+    collect_errors(tree.root_node(), path, source, &mut errors);
+    Ok(errors)
+}
+
+/// Syntax errors in `edited` that `original` doesn't have yet. Files without
+/// a grammar never have errors.
+pub fn new_syntax_errors(path: &Path, original: Option<&str>, edited: &str) -> Vec<Diagnostic> {
+    let Some(language) = SourceLanguage::from_path(path) else {
+        return Vec::new();
+    };
+    let Ok(errors) = syntax_errors(language, path, edited) else {
+        return Vec::new();
+    };
+
+    // Errors move with the edit, so single-line errors are compared by message
+    // and line text. Errors spanning lines grow or shrink with edits inside
+    // them and name their lines in the message, so they match an existing one
+    // with the same first or last line.
+    let line_text = |source: &str, line: usize| {
+        let text = source.lines().nth(line - 1).unwrap_or("");
+        text.trim().to_string()
+    };
+    let mut existing: HashMap<(String, String), usize> = HashMap::new();
+    let mut existing_spans: Vec<(String, String)> = Vec::new();
+    if let Some(original) = original {
+        for error in syntax_errors(language, path, original).unwrap_or_default() {
+            let first = line_text(original, error.start_line);
+            if error.end_line > error.start_line {
+                existing_spans.push((first, line_text(original, error.end_line)));
+            } else {
+                *existing.entry((error.message, first)).or_default() += 1;
+            }
+        }
+    }
+
+    errors
+        .into_iter()
+        .filter(|error| {
+            let first = line_text(edited, error.start_line);
+            if error.end_line > error.start_line {
+                let last = line_text(edited, error.end_line);
+                match existing_spans
+                    .iter()
+                    .position(|(f, l)| *f == first || *l == last)
+                {
+                    Some(index) => {
+                        existing_spans.swap_remove(index);
+                        false
+                    }
+                    None => true,
+                }
+            } else {
+                match existing.get_mut(&(error.message.clone(), first)) {
+                    Some(count) if *count > 0 => {
+                        *count -= 1;
+                        false
+                    }
+                    _ => true,
+                }
+            }
+        })
+        .collect()
+}
+
+fn collect_errors(node: Node, path: &Path, source: &str, errors: &mut Vec<Diagnostic>) {
+    let message = if node.is_missing() {
+        Some(format!("syntax error, missing `{}`", node.kind()))
+    } else if node.is_error() {
+        let text = source[node.byte_range()].trim();
+        let first_line = text.lines().next().unwrap_or("");
+        let quoted = match first_line.char_indices().nth(MAX_QUOTED_CHARS) {
+            Some((cut, _)) => format!("`{}...`", &first_line[..cut]),
+            None => format!("`{}`", first_line),
+        };
+        let (start_row, end_row) = (node.start_position().row, last_row(node));
+        // Unbalanced braces often make the rest of an item unparsable
+        Some(if first_line.is_empty() {
+            "syntax error".to_string()
+        } else if end_row > start_row {
+            format!(
+                "syntax error, lines {}-{} could not be parsed, starting with {}",
+                start_row + 1,
+                end_row + 1,
+                quoted
+            )
+        } else {
+            format!("syntax error, unexpected {}", quoted)
+        })
+    } else {
+        None
+    };
+
+    if let Some(message) = message {
+        let start = node.start_position();
+        let line_start = node.start_byte() - start.column;
+        errors.push(Diagnostic {
+            path: path.to_path_buf(),
+            start_line: start.row + 1,
+            end_line: last_row(node) + 1,
+            column: Some(source[line_start..node.start_byte()].chars().count() + 1),
+            severity: DiagnosticSeverity::Error,
+            code: None,
+            message,
+            suggestion: None,
+        });
+        return;
+    }
+
+    // Only subtrees with errors need to be visited
+    if node.has_error() {
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            collect_errors(child, path, source, errors);
+        }
+    }
+}
+
+/// Row of the last character of a node, which may end after a line break
+fn last_row(node: Node) -> usize {
+    let end = node.end_position();
+    if end.column == 0 && end.row > node.start_position().row {
+        end.row - 1
+    } else {
+        end.row
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syntax_errors() -> Result<()> {
+        let path = Path::new("src/main.rs");
+        let source = "fn main() {\n    let x = 1;\n";
+        let errors = syntax_errors(SourceLanguage::Rust, path, source)?;
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].to_string(),
+            "src/main.rs:1:1 error: syntax error, lines 1-2 could not be parsed, starting with `fn main() {`"
+        );
+
+        let source = "def load(:\n    pass\n";
+        let errors = syntax_errors(SourceLanguage::Python, Path::new("load.py"), source)?;
+        assert!(!errors.is_empty());
+        assert_eq!(errors[0].start_line, 1);
+
+        let valid = "fn main() {\n    let x = 1;\n}\n";
+        assert!(syntax_errors(SourceLanguage::Rust, path, valid)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_new_syntax_errors() {
+        let path = Path::new("src/lib.rs");
+        let broken = "fn a() {\n    let x = ;\n}\n";
+
+        // Only errors the edit introduces are reported
+        let errors = new_syntax_errors(path, Some("fn a() {}\n"), broken);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].start_line, 2);
+
+        let edited = format!("fn b() {{}}\n\n{}", broken);
+        assert!(new_syntax_errors(path, Some(broken), &edited).is_empty());
+
+        // An unbalanced brace isn't new when an edit above moves or extends it
+        let unbalanced = "fn a() {}\n\nfn b() {\n    let x = 1;\n";
+        let edited = format!("use std::fmt;\n\n{}", unbalanced);
+        assert!(new_syntax_errors(path, None, &edited)[0]
+            .message
+            .contains("could not be parsed"));
+        assert!(new_syntax_errors(path, Some(unbalanced), &edited).is_empty());
+
+        // New files are checked as a whole, unknown languages not at all
+        assert_eq!(new_syntax_errors(path, None, broken).len(), 1);
+        assert!(new_syntax_errors(Path::new("notes.txt"), None, "fn {").is_empty());
+    }
+}
@@ -0,0 +1,699 @@
+use crate::types::{TestFailure, TestReport};
+use crate::utils::{shell_quote, CommandExecutor, OutputSender};
+use anyhow::Result;
+use regex::Regex;
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+
+/// A test framework whose output can be parsed into a report
+pub trait TestRunner: Send + Sync {
+    /// Name used to select the runner explicitly
+    fn name(&self) -> &'static str;
+    /// Whether the project at `root` uses this test framework
+    fn detect(&self, root: &Path) -> bool;
+    /// Command to run all tests, or only those matching the filter
+    fn command_line(&self, filter: Option<&str>) -> String;
+    fn parse_output(&self, output: &str, root: &Path) -> TestReport;
+}
+
+/// All supported runners, in detection order
+pub fn runners() -> Vec<Box<dyn TestRunner>> {
+    vec![
+        Box::new(CargoTestRunner),
+        Box::new(JestRunner),
+        Box::new(PytestRunner),
+    ]
+}
+
+/// Get the runner with the given name, or the first one that detects the project
+pub fn find_runner(root: &Path, name: Option<&str>) -> Result<Box<dyn TestRunner>> {
+    let available = runners()
+        .iter()
+        .map(|r| r.name())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let found = match name {
+        Some(name) => runners().into_iter().find(|r| r.name() == name.trim()),
+        None => runners().into_iter().find(|r| r.detect(root)),
+    };
+    found.ok_or_else(|| match name {
+        Some(name) => anyhow::anyhow!("Unknown test runner '{}', available: {}", name, available),
+        None => anyhow::anyhow!("Could not detect a supported test runner ({})", available),
+    })
+}
+
+pub struct TestRunOutput {
+    pub runner: String,
+    pub success: bool,
+    pub report: TestReport,
+    /// Raw command output, useful when the tests did not run at all
+    pub output: String,
+}
+
+/// Run the tests of a project and parse the results
+pub async fn run_tests(
+    command_executor: &dyn CommandExecutor,
+    root: &Path,
+    runner: Option<&str>,
+    filter: Option<&str>,
+    timeout: Option<Duration>,
+    output_sender: Option<OutputSender>,
+) -> Result<TestRunOutput> {
+    let runner = find_runner(root, runner)?;
+    let command_line = runner.command_line(filter);
+    let output = command_executor
+        .execute(
+            &command_line,
+            Some(&root.to_path_buf()),
+            timeout,
+            output_sender,
+        )
+        .await?;
+    if output.timed_out {
+        return Err(anyhow::anyhow!("`{}` timed out", command_line));
+    }
+
+    Ok(TestRunOutput {
+        runner: runner.name().to_string(),
+        success: output.success,
+        report: runner.parse_output(&output.output, root),
+        output: output.output,
+    })
+}
+
+fn non_empty(text: String) -> Option<String> {
+    let text = text.trim_end().to_string();
+    (!text.trim().is_empty()).then_some(text)
+}
+
+fn strip_ansi(text: &str) -> String {
+    Regex::new(r"\x1b\[[0-9;]*m")
+        .unwrap()
+        .replace_all(text, "")
+        .into_owned()
+}
+
+pub struct CargoTestRunner;
+
+impl TestRunner for CargoTestRunner {
+    fn name(&self) -> &'static str {
+        "cargo"
+    }
+
+    fn detect(&self, root: &Path) -> bool {
+        root.join("Cargo.toml").is_file()
+    }
+
+    fn command_line(&self, filter: Option<&str>) -> String {
+        // Keep going after the first failing test binary
+        let mut command = "cargo test --no-fail-fast --color never".to_string();
+        if let Some(filter) = filter {
+            command.push(' ');
+            command.push_str(&shell_quote(filter));
+        }
+        command
+    }
+
+    fn parse_output(&self, output: &str, _root: &Path) -> TestReport {
+        // Test binaries run with `-Z unstable-options --format json` (nightly,
+        // e.g. through a cargo alias) report events, otherwise libtest prints text
+        let json = parse_libtest_json(output);
+        if json.passed + json.failed + json.ignored > 0 {
+            json
+        } else {
+            parse_libtest_text(output)
+        }
+    }
+}
+
+fn parse_libtest_json(output: &str) -> TestReport {
+    let mut report = TestReport::default();
+    let events = output
+        .lines()
+        .filter(|line| line.starts_with('{'))
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|event| event["type"] == "test");
+    for event in events {
+        match event["event"].as_str() {
+            Some("ok") => report.passed += 1,
+            Some("ignored") => report.ignored += 1,
+            Some("failed") => {
+                report.failed += 1;
+                let stdout = event["stdout"].as_str().unwrap_or_default();
+                let lines: Vec<&str> = stdout.lines().collect();
+                report.failures.push(parse_libtest_failure(
+                    event["name"].as_str().unwrap_or_default(),
+                    &lines,
+                ));
+            }
+            _ => {}
+        }
+    }
+    report
+}
+
+fn parse_libtest_text(output: &str) -> TestReport {
+    let result_line = Regex::new(r"^test (.+?) \.\.\. (ok|FAILED|ignored)").unwrap();
+    let block_header = Regex::new(r"^---- (.+?) std(out|err) ----$").unwrap();
+
+    let mut report = TestReport::default();
+    let mut failed_names = Vec::new();
+    for caps in output.lines().filter_map(|line| result_line.captures(line)) {
+        match &caps[2] {
+            "ok" => report.passed += 1,
+            "ignored" => report.ignored += 1,
+            _ => {
+                report.failed += 1;
+                failed_names.push(caps[1].to_string());
+            }
+        }
+    }
+
+    // Details follow in "---- name stdout ----" blocks
+    let lines: Vec<&str> = output.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let Some(caps) = block_header.captures(lines[i]) else {
+            i += 1;
+            continue;
+        };
+        let name = caps[1].to_string();
+        let start = i + 1;
+        i = start;
+        while i < lines.len()
+            && !lines[i].starts_with("---- ")
+            && lines[i] != "failures:"
+            && !lines[i].starts_with("test result:")
+        {
+            i += 1;
+        }
+        if !report.failures.iter().any(|f| f.name == name) {
+            report
+                .failures
+                .push(parse_libtest_failure(&name, &lines[start..i]));
+        }
+    }
+
+    // Failures without details, e.g. when the output was cut off
+    for name in failed_names {
+        if !report.failures.iter().any(|f| f.name == name) {
+            report.failures.push(TestFailure {
+                name,
+                message: String::new(),
+                location: None,
+                output: None,
+            });
+        }
+    }
+    report
+}
+
+// Split the captured output of a failed test into output, panic message and location
+fn parse_libtest_failure(name: &str, lines: &[&str]) -> TestFailure {
+    // Since Rust 1.73: "thread 'x' panicked at src/lib.rs:10:5:" followed by the message,
+    // newer versions also print the thread id: "thread 'x' (123) panicked at ..."
+    let panic_new = Regex::new(r"^thread '.*'(?: \(\d+\))? panicked at (.+:\d+:\d+):$").unwrap();
+    // Before: "thread 'x' panicked at 'message', src/lib.rs:10:5"
+    let panic_old =
+        Regex::new(r"^thread '.*'(?: \(\d+\))? panicked at '(.*)', (.+:\d+:\d+)$").unwrap();
+
+    let is_trailer = |line: &str| {
+        line.starts_with("note: run with `RUST_BACKTRACE")
+            || line.starts_with("stack backtrace:")
+            || line.starts_with("Error: ")
+    };
+
+    for (index, line) in lines.iter().enumerate() {
+        let (message, location, rest) = if let Some(caps) = panic_new.captures(line) {
+            let end = lines[index + 1..]
+                .iter()
+                .position(|l| is_trailer(l))
+                .map_or(lines.len(), |p| index + 1 + p);
+            (
+                lines[index + 1..end].join("\n"),
+                caps[1].to_string(),
+                &lines[end..],
+            )
+        } else if let Some(caps) = panic_old.captures(line) {
+            (
+                caps[1].to_string(),
+                caps[2].to_string(),
+                &lines[index + 1..],
+            )
+        } else {
+            continue;
+        };
+
+        // Output printed before and after the panic, without the backtrace
+        let mut output: Vec<&str> = lines[..index].to_vec();
+        let mut in_backtrace = false;
+        for line in rest {
+            if line.starts_with("stack backtrace:") {
+                in_backtrace = true;
+            } else if in_backtrace && line.starts_with("note: ") {
+                in_backtrace = false;
+            } else if !in_backtrace && !is_trailer(line) {
+                output.push(line);
+            }
+        }
+        return TestFailure {
+            name: name.to_string(),
+            message: message.trim().to_string(),
+            location: Some(location),
+            output: non_empty(output.join("\n")),
+        };
+    }
+
+    // No panic, e.g. a test returning Err(...)
+    let message = lines
+        .iter()
+        .find(|l| l.starts_with("Error: "))
+        .map(|l| l.to_string())
+        .unwrap_or_default();
+    TestFailure {
+        name: name.to_string(),
+        message,
+        location: None,
+        output: non_empty(lines.join("\n")),
+    }
+}
+
+pub struct PytestRunner;
+
+impl TestRunner for PytestRunner {
+    fn name(&self) -> &'static str {
+        "pytest"
+    }
+
+    fn detect(&self, root: &Path) -> bool {
+        let mentions_pytest = |file: &str| {
+            std::fs::read_to_string(root.join(file))
+                .map(|content| content.contains("pytest"))
+                .unwrap_or(false)
+        };
+        root.join("pytest.ini").is_file()
+            || root.join("conftest.py").is_file()
+            || mentions_pytest("pyproject.toml")
+            || mentions_pytest("setup.cfg")
+            || mentions_pytest("tox.ini")
+    }
+
+    fn command_line(&self, filter: Option<&str>) -> String {
+        let mut command = "python -m pytest --tb=short -rfE --color=no".to_string();
+        if let Some(filter) = filter {
+            command.push_str(" -k ");
+            command.push_str(&shell_quote(filter));
+        }
+        command
+    }
+
+    fn parse_output(&self, output: &str, _root: &Path) -> TestReport {
+        let section_header = Regex::new(r"^_{3,} (.+?) _{3,}$").unwrap();
+        let captured_header = Regex::new(r"^-{3,} Captured .+ -{3,}$").unwrap();
+        let location_line = Regex::new(r"^(\S+?\.py:\d+): ").unwrap();
+        let summary_failed = Regex::new(r"^(FAILED|ERROR) (\S+)(?: - (.*))?$").unwrap();
+        let counts = Regex::new(
+            r"(\d+) (passed|failed|skipped|errors?|xfailed|xpassed|deselected|warnings?)",
+        )
+        .unwrap();
+
+        let mut report = TestReport::default();
+        let lines: Vec<&str> = output.lines().collect();
+
+        // Full test ids from the short summary, e.g. tests/test_math.py::test_divide
+        let summary: Vec<(String, Option<String>)> = lines
+            .iter()
+            .filter_map(|line| summary_failed.captures(line))
+            .map(|caps| {
+                (
+                    caps[2].to_string(),
+                    caps.get(3).map(|m| m.as_str().to_string()),
+                )
+            })
+            .collect();
+
+        // Failure sections between the FAILURES banner and the next banner
+        let mut in_failures = false;
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            if line.starts_with('=') {
+                in_failures = line.contains(" FAILURES ") || line.contains(" ERRORS ");
+                i += 1;
+                continue;
+            }
+            let Some(caps) = section_header.captures(line).filter(|_| in_failures) else {
+                i += 1;
+                continue;
+            };
+            let title = caps[1].to_string();
+            i += 1;
+
+            let (mut message, mut location, mut captured) = (Vec::new(), None, Vec::new());
+            let mut in_captured = false;
+            while i < lines.len()
+                && !lines[i].starts_with('=')
+                && !section_header.is_match(lines[i])
+            {
+                let line = lines[i];
+                if captured_header.is_match(line) {
+                    in_captured = true;
+                } else if in_captured {
+                    captured.push(line);
+                } else if let Some(rest) = line.strip_prefix('E') {
+                    message.push(rest.trim());
+                } else if let Some(caps) = location_line.captures(line) {
+                    // The last frame is where the assertion failed
+                    location = Some(caps[1].to_string());
+                }
+                i += 1;
+            }
+
+            // "TestClass.test_method" is "file.py::TestClass::test_method" in the summary
+            let suffix = format!("::{}", title.replace('.', "::"));
+            let name = summary
+                .iter()
+                .find(|(id, _)| id.ends_with(&suffix))
+                .map_or(title, |(id, _)| id.clone());
+            report.failures.push(TestFailure {
+                name,
+                message: message.join("\n"),
+                location,
+                output: non_empty(captured.join("\n")),
+            });
+        }
+
+        for (id, message) in summary {
+            if !report.failures.iter().any(|f| f.name == id) {
+                report.failures.push(TestFailure {
+                    name: id,
+                    message: message.unwrap_or_default(),
+                    location: None,
+                    output: None,
+                });
+            }
+        }
+
+        // Final line like "==== 1 failed, 2 passed, 1 skipped in 0.03s ===="
+        if let Some(line) = lines
+            .iter()
+            .rev()
+            .find(|l| l.contains(" in ") && counts.is_match(l))
+        {
+            for caps in counts.captures_iter(line) {
+                let count: usize = caps[1].parse().unwrap_or(0);
+                match &caps[2] {
+                    "passed" | "xpassed" => report.passed += count,
+                    "failed" | "error" | "errors" => report.failed += count,
+                    "skipped" | "xfailed" => report.ignored += count,
+                    _ => {}
+                }
+            }
+        }
+        report
+    }
+}
+
+pub struct JestRunner;
+
+impl TestRunner for JestRunner {
+    fn name(&self) -> &'static str {
+        "jest"
+    }
+
+    fn detect(&self, root: &Path) -> bool {
+        root.join("jest.config.js").is_file()
+            || root.join("jest.config.ts").is_file()
+            || std::fs::read_to_string(root.join("package.json"))
+                .map(|content| content.contains("jest"))
+                .unwrap_or(false)
+    }
+
+    fn command_line(&self, filter: Option<&str>) -> String {
+        let mut command = "npx --no-install jest --json --testLocationInResults".to_string();
+        if let Some(filter) = filter {
+            command.push_str(" -t ");
+            command.push_str(&shell_quote(filter));
+        }
+        command
+    }
+
+    fn parse_output(&self, output: &str, root: &Path) -> TestReport {
+        let mut report = TestReport::default();
+        // The JSON report is a single line between the console output
+        let Some(json) = output
+            .lines()
+            .filter(|line| line.starts_with('{'))
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find(|value| value.get("numTotalTests").is_some())
+        else {
+            return report;
+        };
+
+        let count = |key: &str| json[key].as_u64().unwrap_or(0) as usize;
+        report.passed = count("numPassedTests");
+        report.failed = count("numFailedTests");
+        report.ignored = count("numPendingTests") + count("numTodoTests");
+
+        for suite in json["testResults"].as_array().into_iter().flatten() {
+            let file = suite["name"].as_str().unwrap_or_default();
+            let file = Path::new(file)
+                .strip_prefix(root)
+                .unwrap_or(Path::new(file))
+                .display()
+                .to_string();
+            let assertions = suite["assertionResults"].as_array();
+
+            // A suite that fails to load has no assertions, only a message
+            if suite["status"] == "failed" && assertions.is_none_or(|a| a.is_empty()) {
+                report.failed += 1;
+                report.failures.push(TestFailure {
+                    name: file.clone(),
+                    message: strip_ansi(suite["message"].as_str().unwrap_or_default())
+                        .trim()
+                        .to_string(),
+                    location: None,
+                    output: None,
+                });
+                continue;
+            }
+
+            for assertion in assertions.into_iter().flatten() {
+                if assertion["status"] != "failed" {
+                    continue;
+                }
+                let failure = assertion["failureMessages"][0].as_str().unwrap_or_default();
+                // Keep the assertion message, drop the stack trace
+                let message = strip_ansi(failure)
+                    .lines()
+                    .take_while(|line| !line.trim_start().starts_with("at "))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                let location = assertion["location"]["line"].as_u64().map(|line| {
+                    match assertion["location"]["column"].as_u64() {
+                        Some(column) => format!("{}:{}:{}", file, line, column),
+                        None => format!("{}:{}", file, line),
+                    }
+                });
+                report.failures.push(TestFailure {
+                    name: assertion["fullName"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    message: message.trim().to_string(),
+                    location,
+                    output: None,
+                });
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::DefaultCommandExecutor;
+
+    #[test]
+    fn test_parse_libtest_text() {
+        let output = r#"
+running 4 tests
+test tests::adds ... ok
+test tests::compares ... FAILED
+test tests::legacy ... FAILED
+test tests::slow ... ignored
+
+failures:
+
+---- tests::compares stdout ----
+computing
+thread 'tests::compares' (4242) panicked at src/lib.rs:12:9:
+assertion `left == right` failed
+  left: 1
+ right: 2
+note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace
+
+---- tests::legacy stdout ----
+thread 'tests::legacy' panicked at 'boom', src/lib.rs:20:5
+
+
+failures:
+    tests::compares
+    tests::legacy
+
+test result: FAILED. 1 passed; 2 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.00s
+"#;
+        let report = CargoTestRunner.parse_output(output, Path::new("."));
+        assert_eq!((report.passed, report.failed, report.ignored), (1, 2, 1));
+        assert_eq!(
+            report.failures,
+            vec![
+                TestFailure {
+                    name: "tests::compares".to_string(),
+                    message: "assertion `left == right` failed\n  left: 1\n right: 2".to_string(),
+                    location: Some("src/lib.rs:12:9".to_string()),
+                    output: Some("computing".to_string()),
+                },
+                TestFailure {
+                    name: "tests::legacy".to_string(),
+                    message: "boom".to_string(),
+                    location: Some("src/lib.rs:20:5".to_string()),
+                    output: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_libtest_json() {
+        let output = concat!(
+            r#"{ "type": "suite", "event": "started", "test_count": 2 }"#,
+            "\n",
+            r#"{ "type": "test", "event": "ok", "name": "tests::adds" }"#,
+            "\n",
+            r#"{ "type": "test", "name": "tests::compares", "event": "failed", "stdout": "thread 'tests::compares' panicked at src/lib.rs:12:9:\nexpected true\nnote: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n" }"#,
+            "\n",
+        );
+        let report = CargoTestRunner.parse_output(output, Path::new("."));
+        assert_eq!((report.passed, report.failed, report.ignored), (1, 1, 0));
+        assert_eq!(report.failures[0].message, "expected true");
+        assert_eq!(
+            report.failures[0].location.as_deref(),
+            Some("src/lib.rs:12:9")
+        );
+    }
+
+    #[test]
+    fn test_parse_pytest() {
+        let output = r#"============================= test session starts ==============================
+collected 3 items
+
+tests/test_math.py .Fs                                                   [100%]
+
+=================================== FAILURES ===================================
+________________________________ test_divide _________________________________
+tests/test_math.py:9: in test_divide
+    assert divide(4, 2) == 3
+E   assert 2.0 == 3
+E    +  where 2.0 = divide(4, 2)
+----------------------------- Captured stdout call -----------------------------
+dividing 4 by 2
+=========================== short test summary info ============================
+FAILED tests/test_math.py::test_divide - assert 2.0 == 3
+=================== 1 failed, 1 passed, 1 skipped in 0.03s ====================
+"#;
+        let report = PytestRunner.parse_output(output, Path::new("."));
+        assert_eq!((report.passed, report.failed, report.ignored), (1, 1, 1));
+        assert_eq!(
+            report.failures,
+            vec![TestFailure {
+                name: "tests/test_math.py::test_divide".to_string(),
+                message: "assert 2.0 == 3\n+  where 2.0 = divide(4, 2)".to_string(),
+                location: Some("tests/test_math.py:9".to_string()),
+                output: Some("dividing 4 by 2".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_jest() {
+        let output = concat!(
+            "PASS src/other.test.js\n",
+            r#"{"numFailedTests":1,"numPassedTests":3,"numPendingTests":1,"numTodoTests":0,"numTotalTests":5,"testResults":[{"name":"/work/app/src/sum.test.js","status":"failed","message":"","assertionResults":[{"fullName":"sum adds numbers","status":"failed","location":{"line":4,"column":3},"failureMessages":["Error: \u001b[2mexpect(\u001b[22mreceived\u001b[2m).toBe(\u001b[22mexpected\u001b[2m)\u001b[22m\n\nExpected: 4\nReceived: 5\n    at Object.<anonymous> (/work/app/src/sum.test.js:5:20)"]},{"fullName":"sum works","status":"passed","failureMessages":[]}]}]}"#,
+            "\n",
+        );
+        let report = JestRunner.parse_output(output, Path::new("/work/app"));
+        assert_eq!((report.passed, report.failed, report.ignored), (3, 1, 1));
+        assert_eq!(
+            report.failures,
+            vec![TestFailure {
+                name: "sum adds numbers".to_string(),
+                message: "Error: expect(received).toBe(expected)\n\nExpected: 4\nReceived: 5"
+                    .to_string(),
+                location: Some("src/sum.test.js:4:3".to_string()),
+                output: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_command_lines_and_detection() -> Result<()> {
+        assert_eq!(
+            CargoTestRunner.command_line(Some("parser::it's")),
+            "cargo test --no-fail-fast --color never 'parser::it'\\''s'"
+        );
+        assert_eq!(
+            PytestRunner.command_line(Some("divide")),
+            "python -m pytest --tb=short -rfE --color=no -k 'divide'"
+        );
+
+        let dir = tempfile::TempDir::new()?;
+        assert!(find_runner(dir.path(), None).is_err());
+        std::fs::write(
+            dir.path().join("pyproject.toml"),
+            "[tool.pytest.ini_options]\n",
+        )?;
+        assert_eq!(find_runner(dir.path(), None)?.name(), "pytest");
+        assert_eq!(find_runner(dir.path(), Some("jest"))?.name(), "jest");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_fixture_crate_tests() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"fixture\"\nversion = \"0.1.0\"\nedition = \"2021\"\n\n[workspace]\n",
+        )?;
+        std::fs::create_dir(dir.path().join("src"))?;
+        std::fs::write(
+            dir.path().join("src/lib.rs"),
+            concat!(
+                "#[test]\nfn passes() {}\n\n",
+                "#[test]\nfn fails() {\n    println!(\"captured\");\n    assert_eq!(1 + 1, 3);\n}\n\n",
+                "#[test]\n#[ignore]\nfn skipped() {}\n",
+            ),
+        )?;
+        let executor = DefaultCommandExecutor::default();
+
+        let result = run_tests(&executor, dir.path(), None, None, None, None).await?;
+        assert_eq!(result.runner, "cargo");
+        assert!(!result.success);
+        let report = result.report;
+        assert_eq!((report.passed, report.failed, report.ignored), (1, 1, 1));
+        let failure = &report.failures[0];
+        assert_eq!(failure.name, "fails");
+        assert!(failure.message.contains("left: 2"));
+        assert_eq!(failure.location.as_deref(), Some("src/lib.rs:7:5"));
+        assert_eq!(failure.output.as_deref(), Some("captured"));
+
+        // The filter selects tests by name
+        let result = run_tests(&executor, dir.path(), None, Some("passes"), None, None).await?;
+        assert!(result.success);
+        assert_eq!(result.report.passed, 1);
+        assert!(result.report.failures.is_empty());
+        Ok(())
+    }
+}
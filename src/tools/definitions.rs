@@ -1,37 +1,96 @@
+use crate::config;
 use crate::types::{ToolDefinition, Tools};
 use serde_json::json;
 
+/// Tools that reach the internet, left out if web.json sets `offline` or is invalid
+pub const WEB_TOOLS: [&str; 4] = ["web_search", "web_fetch", "crawl_docs", "browser"];
+
+fn without_disabled_tools(tools: Vec<ToolDefinition>) -> Vec<ToolDefinition> {
+    if config::web_tools_enabled() {
+        return tools;
+    }
+    tools
+        .into_iter()
+        .filter(|tool| !WEB_TOOLS.contains(&tool.name.as_str()))
+        .collect()
+}
+
 impl Tools {
     /// Returns all available tool definitions
     pub fn all() -> Vec<ToolDefinition> {
-        vec![
+        without_disabled_tools(vec![
             Self::update_plan(),
+            Self::ask_user(),
             Self::execute_command(),
+            Self::reset_shell_session(),
+            Self::start_process(),
+            Self::read_process_output(),
+            Self::send_process_input(),
+            Self::stop_process(),
+            Self::check_project(),
+            Self::run_tests(),
             Self::search_files(),
+            Self::find_files(),
+            Self::goto_definition(),
+            Self::find_references(),
+            Self::hover(),
+            Self::document_symbols(),
+            Self::workspace_symbols(),
+            Self::repo_map(),
+            Self::dependency_docs(),
             Self::list_files(),
             Self::read_files(),
+            Self::view_image(),
             Self::summarize(),
             Self::replace_in_file(),
+            Self::edit_lines(),
+            Self::edit_files(),
+            Self::replace_across_files(),
             Self::write_file(),
             Self::delete_files(),
             Self::web_search(),
             Self::web_fetch(),
-        ]
+            Self::crawl_docs(),
+            Self::http_request(),
+            Self::browser(),
+        ])
     }
 
     pub fn mcp() -> Vec<ToolDefinition> {
-        vec![
+        without_disabled_tools(vec![
             Self::list_projects(),
             Self::execute_command(),
+            Self::reset_shell_session(),
+            Self::start_process(),
+            Self::read_process_output(),
+            Self::send_process_input(),
+            Self::stop_process(),
+            Self::check_project(),
+            Self::run_tests(),
             Self::search_files(),
+            Self::find_files(),
+            Self::goto_definition(),
+            Self::find_references(),
+            Self::hover(),
+            Self::document_symbols(),
+            Self::workspace_symbols(),
+            Self::repo_map(),
+            Self::dependency_docs(),
             Self::list_files(),
             Self::read_files(),
+            Self::view_image(),
             Self::replace_in_file(),
+            Self::edit_lines(),
+            Self::edit_files(),
+            Self::replace_across_files(),
             Self::write_file(),
             Self::delete_files(),
             Self::web_search(),
             Self::web_fetch(),
-        ]
+            Self::crawl_docs(),
+            Self::http_request(),
+            Self::browser(),
+        ])
     }
 
     pub fn list_projects() -> ToolDefinition {
@@ -63,6 +122,35 @@ impl Tools {
         }
     }
 
+    pub fn ask_user() -> ToolDefinition {
+        ToolDefinition {
+            name: "ask_user".to_string(),
+            description: "Ask the user a question and continue working with the answer".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "question": {
+                        "type": "string",
+                        "description": "The question to ask"
+                    },
+                    "answer_type": {
+                        "type": "string",
+                        "enum": ["text", "yes_no", "pick_one", "pick_many"],
+                        "description": "Kind of answer expected, text unless choices are given"
+                    },
+                    "choices": {
+                        "type": "array",
+                        "description": "Choices the user can pick, required for pick_one and pick_many",
+                        "items": {
+                            "type": "string"
+                        }
+                    }
+                },
+                "required": ["question"]
+            }),
+        }
+    }
+
     pub fn execute_command() -> ToolDefinition {
         ToolDefinition {
             name: "execute_command".to_string(),
@@ -81,6 +169,14 @@ impl Tools {
                     "working_dir": {
                         "type": "string",
                         "description": "Optional: working directory for the command (relative to project root)"
+                    },
+                    "timeout_secs": {
+                        "type": "integer",
+                        "description": "Optional: Timeout in seconds after which the command and its child processes are killed (0 disables the timeout). Defaults to the project setting or 600."
+                    },
+                    "persistent_session": {
+                        "type": "boolean",
+                        "description": "Optional: Run in the project's persistent shell session, so the working directory and environment variables carry over to later commands. Defaults to the project setting."
                     }
                 },
                 "required": ["project", "command_line"]
@@ -88,6 +184,294 @@ impl Tools {
         }
     }
 
+    pub fn reset_shell_session() -> ToolDefinition {
+        ToolDefinition {
+            name: "reset_shell_session".to_string(),
+            description: "Terminate the persistent shell session of a project. The next command with a persistent session starts a fresh shell in the project root.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project whose session to reset"
+                    }
+                },
+                "required": ["project"]
+            }),
+        }
+    }
+
+    pub fn start_process() -> ToolDefinition {
+        ToolDefinition {
+            name: "start_process".to_string(),
+            description: "Start a long-running process (e.g. a dev server or watcher) in the background within a specified project. Returns a process ID to use with read_process_output, send_process_input and stop_process. Processes are stopped when the session ends.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project context"
+                    },
+                    "command_line": {
+                        "type": "string",
+                        "description": "The complete command to execute"
+                    },
+                    "working_dir": {
+                        "type": "string",
+                        "description": "Optional: working directory relative to project root"
+                    }
+                },
+                "required": ["project", "command_line"]
+            }),
+        }
+    }
+
+    pub fn read_process_output() -> ToolDefinition {
+        ToolDefinition {
+            name: "read_process_output".to_string(),
+            description: "Read the combined stdout/stderr of a background process. By default returns only output produced since the last read.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project the process was started in"
+                    },
+                    "process_id": {
+                        "type": "string",
+                        "description": "ID returned by start_process"
+                    },
+                    "since_offset": {
+                        "type": "integer",
+                        "description": "Optional: read from this offset instead (use 0 for the full output)"
+                    },
+                    "tail_lines": {
+                        "type": "integer",
+                        "description": "Optional: only return the last N lines"
+                    }
+                },
+                "required": ["project", "process_id"]
+            }),
+        }
+    }
+
+    pub fn send_process_input() -> ToolDefinition {
+        ToolDefinition {
+            name: "send_process_input".to_string(),
+            description: "Send a line of input to the stdin of a background process. A trailing newline is added if missing.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project the process was started in"
+                    },
+                    "process_id": {
+                        "type": "string",
+                        "description": "ID returned by start_process"
+                    },
+                    "input": {
+                        "type": "string",
+                        "description": "The text to send"
+                    }
+                },
+                "required": ["project", "process_id", "input"]
+            }),
+        }
+    }
+
+    pub fn stop_process() -> ToolDefinition {
+        ToolDefinition {
+            name: "stop_process".to_string(),
+            description: "Stop a background process and all processes it spawned".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project the process was started in"
+                    },
+                    "process_id": {
+                        "type": "string",
+                        "description": "ID returned by start_process"
+                    }
+                },
+                "required": ["project", "process_id"]
+            }),
+        }
+    }
+
+    pub fn check_project() -> ToolDefinition {
+        ToolDefinition {
+            name: "check_project".to_string(),
+            description: "Build or type-check a project and return structured diagnostics (file, line, severity, message, suggested fix). Detects the build system (cargo, tsc, go). Prefer this over running the compiler via execute_command.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project to check"
+                    },
+                    "build_system": {
+                        "type": "string",
+                        "enum": ["cargo", "tsc", "go"],
+                        "description": "Optional: build system to use instead of detecting it"
+                    }
+                },
+                "required": ["project"]
+            }),
+        }
+    }
+
+    pub fn run_tests() -> ToolDefinition {
+        ToolDefinition {
+            name: "run_tests".to_string(),
+            description: "Run the tests of a project and return passed, failed and ignored counts. Each failure includes its name, assertion message, location and captured output. Detects the test runner (cargo, jest, pytest). Prefer this over running tests via execute_command.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project to test"
+                    },
+                    "filter": {
+                        "type": "string",
+                        "description": "Optional: only run tests whose name matches this filter"
+                    },
+                    "runner": {
+                        "type": "string",
+                        "enum": ["cargo", "jest", "pytest"],
+                        "description": "Optional: test runner to use instead of detecting it"
+                    }
+                },
+                "required": ["project"]
+            }),
+        }
+    }
+
+    pub fn goto_definition() -> ToolDefinition {
+        ToolDefinition {
+            name: "goto_definition".to_string(),
+            description: "Find where a symbol is defined using the project's language server. Identify the symbol by its line and name (or column). Returns file locations with the source line.".to_string(),
+            parameters: symbol_position_parameters("Symbol to look up"),
+        }
+    }
+
+    pub fn find_references() -> ToolDefinition {
+        ToolDefinition {
+            name: "find_references".to_string(),
+            description: "Find all references to a symbol, including its declaration, using the project's language server. More precise than a text search for renames and call sites.".to_string(),
+            parameters: symbol_position_parameters("Symbol to find references of"),
+        }
+    }
+
+    pub fn hover() -> ToolDefinition {
+        ToolDefinition {
+            name: "hover".to_string(),
+            description: "Show the type signature and documentation of a symbol using the project's language server.".to_string(),
+            parameters: symbol_position_parameters("Symbol to describe"),
+        }
+    }
+
+    pub fn document_symbols() -> ToolDefinition {
+        ToolDefinition {
+            name: "document_symbols".to_string(),
+            description: "List the symbols (types, functions, methods, fields) defined in a file with their line ranges, using the project's language server.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project containing the file"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Path to the file relative to the project root"
+                    }
+                },
+                "required": ["project", "path"]
+            }),
+        }
+    }
+
+    pub fn workspace_symbols() -> ToolDefinition {
+        ToolDefinition {
+            name: "workspace_symbols".to_string(),
+            description: "Search symbols by name across the whole project using the project's language server. Returns kind, file and line of each match.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project to search"
+                    },
+                    "query": {
+                        "type": "string",
+                        "description": "Symbol name or part of it"
+                    }
+                },
+                "required": ["project", "query"]
+            }),
+        }
+    }
+
+    pub fn repo_map() -> ToolDefinition {
+        ToolDefinition {
+            name: "repo_map".to_string(),
+            description: "Show a compact map of the project's source files (Rust, Python, TypeScript/JavaScript, Go) with their top-level types, functions, traits and impls, including signatures and line numbers. Files related to the loaded files and the given paths come first, the map is capped to a token budget.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project to map"
+                    },
+                    "paths": {
+                        "type": "array",
+                        "items": {
+                            "type": "string"
+                        },
+                        "description": "Files to focus the map on, relative to the project root (optional, loaded files are always used)"
+                    },
+                    "max_tokens": {
+                        "type": "integer",
+                        "description": "Approximate size limit of the map in tokens (default: 2048)"
+                    }
+                },
+                "required": ["project"]
+            }),
+        }
+    }
+
+    pub fn dependency_docs() -> ToolDefinition {
+        ToolDefinition {
+            name: "dependency_docs".to_string(),
+            description: "Read the documentation of a dependency from its source on disk, in the version the project uses: the version locked in Cargo.lock for crates, the installed type definitions in node_modules for npm packages. Lists the public items with their doc comments, or shows the source of one item. Works offline and prefer it over web searches for API details of dependencies.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project using the dependency"
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "Name of the crate or npm package"
+                    },
+                    "version": {
+                        "type": "string",
+                        "description": "Version to choose when Cargo.lock has several versions of the crate (optional)"
+                    },
+                    "item": {
+                        "type": "string",
+                        "description": "Module to list like `serde::de`, or item to show the source of like `Deserializer` or `Client::get` (optional, lists all public items without it)"
+                    }
+                },
+                "required": ["project", "name"]
+            }),
+        }
+    }
+
     pub fn search_files() -> ToolDefinition {
         ToolDefinition {
             name: "search_files".to_string(),
@@ -109,6 +493,31 @@ impl Tools {
         }
     }
 
+    pub fn find_files() -> ToolDefinition {
+        ToolDefinition {
+            name: "find_files".to_string(),
+            description: "Find files by name within a specified project. Accepts a glob pattern (e.g. 'src/**/*.rs', '*.toml') or a partial/fuzzy file name (e.g. 'agent tests'). Respects .gitignore. Results are ranked by relevance and include file size and modification time.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project to search within"
+                    },
+                    "pattern": {
+                        "type": "string",
+                        "description": "Glob pattern or fuzzy file name. Globs without '/' are matched against file names only."
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": "Optional: Maximum number of results (default 50)"
+                    }
+                },
+                "required": ["project", "pattern"]
+            }),
+        }
+    }
+
     pub fn list_files() -> ToolDefinition {
         ToolDefinition {
             name: "list_files".to_string(),
@@ -139,13 +548,14 @@ impl Tools {
 
     pub fn read_files() -> ToolDefinition {
         let description = concat!(
-            "Load files into working memory. You can specify line ranges by appending them to the file path using a colon.\n\n",
+            "Load files into working memory. You can specify line ranges by appending them to the file path using a colon, or a symbol using '#'.\n\n",
             "Examples:\n",
             "- file.txt - Read the entire file. Prefer this form unless you are absolutely sure you need only a section of the file.\n",
             "- file.txt:10-20 - Read only lines 10 to 20\n",
             "- file.txt:10- - Read from line 10 to the end\n",
             "- file.txt:-20 - Read from the beginning to line 20\n",
-            "- file.txt:15 - Read only line 15");
+            "- file.txt:15 - Read only line 15\n",
+            "- file.rs#Type::method - Read only the definition of a symbol including its doc comments and attributes (Rust, Python, TypeScript/JavaScript and Go)");
 
         ToolDefinition {
             name: "read_files".to_string(),
@@ -159,10 +569,14 @@ impl Tools {
                     },
                     "paths": {
                         "type": "array",
-                        "description": "Paths to the files relative to the project root directory. Can include line ranges using 'file.txt:10-20' syntax.",
+                        "description": "Paths to the files relative to the project root directory. Can include line ranges using 'file.txt:10-20' or symbols using 'file.rs#Type::method' syntax.",
                         "items": {
                             "type": "string"
                         }
+                    },
+                    "line_numbers": {
+                        "type": "boolean",
+                        "description": "Show the files with line numbers, e.g. before using edit_lines. The numbers are not part of the file content. Default is false."
                     }
                 },
                 "required": ["project", "paths"]
@@ -170,6 +584,27 @@ impl Tools {
         }
     }
 
+    pub fn view_image() -> ToolDefinition {
+        ToolDefinition {
+            name: "view_image".to_string(),
+            description: "Look at an image file like a screenshot, diagram or chart. Supports PNG, JPEG, GIF and WebP, large images are downscaled. Only works with models that support images.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project containing the image"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Path to the image file relative to the project root directory"
+                    }
+                },
+                "required": ["project", "path"]
+            }),
+        }
+    }
+
     pub fn summarize() -> ToolDefinition {
         ToolDefinition {
             name: "summarize".to_string(),
@@ -200,7 +635,7 @@ impl Tools {
     pub fn replace_in_file() -> ToolDefinition {
         ToolDefinition {
             name: "replace_in_file".to_string(),
-            description: "Replace sections in a file within a specified project using search/replace blocks. By default, each search text must match exactly once in the file, but you can use SEARCH_ALL/REPLACE_ALL blocks to replace all occurrences of a pattern.".to_string(),
+            description: "Replace sections in a file within a specified project using search/replace blocks. By default, each search text must match exactly once in the file, but you can use SEARCH_ALL/REPLACE_ALL blocks to replace all occurrences of a pattern. Edits that introduce syntax errors in Rust, Python, TypeScript/JavaScript or Go files are rejected with the locations of the errors.".to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
@@ -222,6 +657,134 @@ impl Tools {
         }
     }
 
+    pub fn edit_lines() -> ToolDefinition {
+        ToolDefinition {
+            name: "edit_lines".to_string(),
+            description: "Insert, delete or replace lines of a file by their 1-based line numbers, e.g. to add a function after line 120 or to delete a block of lines. Read the file with line_numbers first to get reliable numbers. Pass the current content of the addressed lines as expected to guard against stale line numbers, the edit is rejected if they changed. For inserts, the addressed line is start_line.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project containing the file"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Path to the file to modify (relative to project root)"
+                    },
+                    "operation": {
+                        "type": "string",
+                        "enum": ["insert_after", "insert_before", "delete_range", "replace_range"],
+                        "description": "The kind of edit"
+                    },
+                    "start_line": {
+                        "type": "integer",
+                        "description": "First line of the range, or the line to insert after or before"
+                    },
+                    "end_line": {
+                        "type": "integer",
+                        "description": "Last line of the range for delete_range and replace_range, defaults to start_line"
+                    },
+                    "content": {
+                        "type": "string",
+                        "description": "Lines to insert or to replace the range with, required except for delete_range"
+                    },
+                    "expected": {
+                        "type": "string",
+                        "description": "Current content of the lines start_line to end_line, without line numbers"
+                    }
+                },
+                "required": ["project", "path", "operation", "start_line"]
+            }),
+        }
+    }
+
+    pub fn edit_files() -> ToolDefinition {
+        ToolDefinition {
+            name: "edit_files".to_string(),
+            description: "Edit several files at once with search/replace blocks, e.g. to rename a function together with its callers. All edits are checked against the current file contents before anything is written. If any edit fails, no file is changed. The result contains a diff of all changes.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project containing the files"
+                    },
+                    "edits": {
+                        "type": "array",
+                        "description": "Edits to apply, at most one per file",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "path": {
+                                    "type": "string",
+                                    "description": "Path to the file to modify (relative to project root)"
+                                },
+                                "diff": {
+                                    "type": "string",
+                                    "description": "SEARCH/REPLACE or SEARCH_ALL/REPLACE_ALL blocks in the same format as for replace_in_file"
+                                }
+                            },
+                            "required": ["path", "diff"]
+                        }
+                    }
+                },
+                "required": ["project", "edits"]
+            }),
+        }
+    }
+
+    pub fn replace_across_files() -> ToolDefinition {
+        ToolDefinition {
+            name: "replace_across_files".to_string(),
+            description: "Replace a literal text or regex in all files of a project, e.g. to rename an identifier. Files can be filtered with include and exclude globs, .gitignore is respected. By default only a preview of every change is returned. Set apply to write the changes, all files are changed or none.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project to edit"
+                    },
+                    "pattern": {
+                        "type": "string",
+                        "description": "Text to replace, or a regex in Rust syntax if regex is set"
+                    },
+                    "replacement": {
+                        "type": "string",
+                        "description": "Replacement text. With regex set, capture groups can be referenced as $1 or ${name}"
+                    },
+                    "regex": {
+                        "type": "boolean",
+                        "description": "Treat the pattern as a regex (default false)"
+                    },
+                    "whole_word": {
+                        "type": "boolean",
+                        "description": "Only match whole words (default false)"
+                    },
+                    "ignore_case": {
+                        "type": "boolean",
+                        "description": "Match case-insensitively (default false)"
+                    },
+                    "include": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Globs of files to edit, e.g. 'src/**/*.rs'. Globs without '/' match file names only. All files if omitted."
+                    },
+                    "exclude": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Globs of files to skip"
+                    },
+                    "apply": {
+                        "type": "boolean",
+                        "description": "Write the changes instead of previewing them (default false)"
+                    }
+                },
+                "required": ["project", "pattern", "replacement"]
+            }),
+        }
+    }
+
     pub fn write_file() -> ToolDefinition {
         ToolDefinition {
             name: "write_file".to_string(),
@@ -231,7 +794,9 @@ impl Tools {
                          of the COMPLETE file, especially when overwriting existing files!! \
                          If the file to write is large, write it in chunks making use of the 'append' parameter. \
                          Always end your turn after using this tool, especially when using 'append'. \
-                         This avoids hitting an output token limit when replying."
+                         This avoids hitting an output token limit when replying. \
+                         Edits that introduce syntax errors in Rust, Python, TypeScript/JavaScript or Go files \
+                         are rejected with the locations of the errors."
                     .to_string(),
             parameters: json!({
                 "type": "object",
@@ -286,10 +851,14 @@ impl Tools {
     pub fn web_search() -> ToolDefinition {
         ToolDefinition {
             name: "web_search".to_string(),
-            description: "Search the web using DuckDuckGo".to_string(),
+            description: "Search the web".to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project context, results from domains it may not fetch are left out"
+                    },
                     "query": {
                         "type": "string",
                         "description": "Search query"
@@ -300,7 +869,7 @@ impl Tools {
                         "minimum": 1
                     }
                 },
-                "required": ["query", "hits_page_number"]
+                "required": ["project", "query", "hits_page_number"]
             }),
         }
     }
@@ -312,6 +881,10 @@ impl Tools {
             parameters: json!({
                 "type": "object",
                 "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project context, its allowed and blocked domains apply"
+                    },
                     "url": {
                         "type": "string",
                         "description": "URL of the web page to fetch"
@@ -322,10 +895,161 @@ impl Tools {
                         "items": {
                             "type": "string"
                         }
+                    },
+                    "page": {
+                        "type": "integer",
+                        "description": "Page of the content to read, for pages too long to return at once (default: 1)"
+                    },
+                    "section": {
+                        "type": "string",
+                        "description": "Optional number or title of a section from the page outline, to read only that section"
+                    }
+                },
+                "required": ["project", "url"]
+            }),
+        }
+    }
+
+    pub fn crawl_docs() -> ToolDefinition {
+        ToolDefinition {
+            name: "crawl_docs".to_string(),
+            description: "Fetch a documentation page and the pages it links on the same site, returning an index and the condensed content of all pages".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project context, its allowed and blocked domains apply"
+                    },
+                    "url": {
+                        "type": "string",
+                        "description": "URL of the page to start from"
+                    },
+                    "max_depth": {
+                        "type": "integer",
+                        "description": "How many links to follow from the start page (default: 2, at most 5)"
+                    },
+                    "max_pages": {
+                        "type": "integer",
+                        "description": "Maximum number of pages to fetch (default: 10, at most 30)"
+                    },
+                    "prefix": {
+                        "type": "string",
+                        "description": "Only crawl URLs starting with this, a full URL or a path like /docs/"
+                    }
+                },
+                "required": ["project", "url"]
+            }),
+        }
+    }
+
+    pub fn http_request() -> ToolDefinition {
+        ToolDefinition {
+            name: "http_request".to_string(),
+            description: "Send an HTTP request and return the status, headers and body of the response. Requests can go to localhost and the hosts allowed in the configuration, redirects are not followed.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "method": {
+                        "type": "string",
+                        "description": "HTTP method like GET, POST, PUT, PATCH or DELETE (default: GET)"
+                    },
+                    "url": {
+                        "type": "string",
+                        "description": "URL to send the request to, e.g. http://localhost:8080/api/items"
+                    },
+                    "headers": {
+                        "type": "object",
+                        "description": "Request headers by name",
+                        "additionalProperties": { "type": "string" }
+                    },
+                    "query": {
+                        "type": "object",
+                        "description": "Query parameters added to the URL",
+                        "additionalProperties": { "type": "string" }
+                    },
+                    "json": {
+                        "description": "JSON body, sent with the content type application/json"
+                    },
+                    "body": {
+                        "type": "string",
+                        "description": "Text body, instead of json"
+                    },
+                    "timeout_secs": {
+                        "type": "integer",
+                        "description": "Timeout of the request in seconds (default: 30)"
                     }
                 },
                 "required": ["url"]
             }),
         }
     }
+
+    pub fn browser() -> ToolDefinition {
+        ToolDefinition {
+            name: "browser".to_string(),
+            description: "Interact with a page in a headless browser, e.g. to debug a frontend on localhost. The page stays open between calls. Every action returns the page's URL, title and new console errors, screenshot also returns an image.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "project": {
+                        "type": "string",
+                        "description": "Name of the project context, its allowed and blocked domains apply to hosts other than localhost, else the global ones"
+                    },
+                    "action": {
+                        "type": "string",
+                        "enum": ["open", "click", "type", "wait_for", "console", "screenshot"],
+                        "description": "open a URL, click an element, type text into an input, wait for an element, read all console errors of the page, or take a screenshot"
+                    },
+                    "url": {
+                        "type": "string",
+                        "description": "URL to open, for the open action"
+                    },
+                    "selector": {
+                        "type": "string",
+                        "description": "CSS selector of the element, for the click, type and wait_for actions"
+                    },
+                    "text": {
+                        "type": "string",
+                        "description": "Text to type, for the type action"
+                    },
+                    "timeout_secs": {
+                        "type": "integer",
+                        "description": "How long wait_for waits for the element (default: 10, at most 60)"
+                    }
+                },
+                "required": ["action"]
+            }),
+        }
+    }
+}
+
+/// Parameters of tools operating on a symbol at a position in a file
+fn symbol_position_parameters(symbol_description: &str) -> serde_json::Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "project": {
+                "type": "string",
+                "description": "Name of the project containing the file"
+            },
+            "path": {
+                "type": "string",
+                "description": "Path to the file relative to the project root"
+            },
+            "line": {
+                "type": "integer",
+                "description": "1-based line number of the symbol"
+            },
+            "symbol": {
+                "type": "string",
+                "description": format!("{} as it appears on the line, preferred over column", symbol_description)
+            },
+            "column": {
+                "type": "integer",
+                "description": "Optional: 1-based column of the symbol, used if symbol is not given"
+            }
+        },
+        "required": ["project", "path", "line"]
+    })
 }
@@ -1,15 +1,92 @@
 use super::ToolResultHandler;
 use crate::config::{self, ProjectManager};
-use crate::types::{SearchMode, SearchOptions, Tool, ToolResult};
-use crate::ui::{UIMessage, UserInterface};
-use crate::utils::CommandExecutor;
-use crate::web::{WebClient, WebPage};
+use crate::dependencies;
+use crate::diagnostics;
+use crate::explorer;
+use crate::lsp::{self, LspClient};
+use crate::syntax;
+use crate::test_runner;
+use crate::types::{
+    CodeExplorer, Diagnostic, FetchConfig, FileEdit, FindOptions, Project, ReplacementSite,
+    SearchMode, SearchOptions, SyntaxCheck, Tool, ToolResult,
+};
+use crate::ui::{UIMessage, UserInterface, UserQuestion};
+use crate::utils::image::{image_media_type, load_image_file};
+use crate::utils::{
+    apply_line_edit, apply_replacements_normalized, truncate_output, unified_diff, CommandExecutor,
+    FileUpdaterError,
+};
+use crate::web::{
+    check_fetch_domain, create_search_backend, web_client, BrowserTabId, CrawlOptions, HttpRequest,
+    WebPage, DEFAULT_HTTP_REQUEST_TIMEOUT,
+};
 use anyhow::Result;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::debug;
+
+// Timeout for execute_command unless set per call or per project
+const DEFAULT_COMMAND_TIMEOUT_SECS: u64 = 600;
+// Limits for command output returned to the LLM
+const OUTPUT_HEAD_LINES: usize = 50;
+const OUTPUT_TAIL_LINES: usize = 150;
+const MAX_OUTPUT_BYTES: usize = 30 * 1024;
+// Project-relative directory for complete command outputs
+const COMMAND_OUTPUT_DIR: &str = ".code-assistant/output";
+// Default size of the repository map in tokens
+const DEFAULT_REPO_MAP_TOKENS: usize = 2048;
 
 pub struct ToolExecutor {}
 
+/// Write complete command output to a log file, returns the project-relative path
+fn save_command_output(root_dir: &Path, output: &str) -> Result<PathBuf> {
+    let dir = root_dir.join(COMMAND_OUTPUT_DIR);
+    std::fs::create_dir_all(&dir)?;
+    // Keep the logs out of version control
+    let gitignore = dir.parent().unwrap_or(&dir).join(".gitignore");
+    if !gitignore.exists() {
+        std::fs::write(gitignore, "*\n")?;
+    }
+
+    let file_name = format!(
+        "command-{}.log",
+        chrono::Local::now().format("%Y%m%d-%H%M%S-%3f")
+    );
+    std::fs::write(dir.join(&file_name), output)?;
+    Ok(PathBuf::from(COMMAND_OUTPUT_DIR).join(file_name))
+}
+
+/// Timeout for commands: the explicit value, then the project default, then
+/// the global default. A value of 0 disables the timeout.
+fn command_timeout(project: Option<&Project>, timeout_secs: Option<u64>) -> Option<Duration> {
+    let timeout_secs = timeout_secs
+        .or_else(|| project.and_then(|p| p.command_timeout_secs))
+        .unwrap_or(DEFAULT_COMMAND_TIMEOUT_SECS);
+    (timeout_secs > 0).then(|| Duration::from_secs(timeout_secs))
+}
+
+/// Channel for the output of a running command, and a future forwarding it to
+/// the tool block. The future ends once the sender is dropped.
+fn tool_output_stream<'a>(
+    ui: Option<&'a Box<dyn UserInterface>>,
+    tool_id: Option<&'a str>,
+) -> (
+    tokio::sync::mpsc::UnboundedSender<String>,
+    impl std::future::Future<Output = ()> + 'a,
+) {
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let forward_output = async move {
+        while let Some(chunk) = receiver.recv().await {
+            if let (Some(ui), Some(tool_id)) = (ui, tool_id) {
+                let _ = ui.append_tool_output(tool_id, &chunk).await;
+            }
+        }
+    };
+    (sender, forward_output)
+}
+
 fn check_absolute_path(path: &Path) -> Option<ToolResult> {
     if path.is_absolute() {
         Some(ToolResult::AbsolutePathError {
@@ -20,12 +97,334 @@ fn check_absolute_path(path: &Path) -> Option<ToolResult> {
     }
 }
 
+/// Domains the web tools can reach, by the fetch settings of the project or
+/// else those of web.json. Fails if the web tools are disabled.
+fn web_fetch_config(
+    project_manager: &dyn ProjectManager,
+    project: Option<&str>,
+) -> Result<FetchConfig> {
+    let config = config::load_web_config()?;
+    if config.offline {
+        return Err(anyhow::anyhow!(
+            "The web tools are disabled, web.json in the config directory sets `offline`."
+        ));
+    }
+    match project {
+        Some(project) => Ok(project_manager
+            .get_project(project)?
+            .ok_or_else(|| anyhow::anyhow!("Project not found: {}", project))?
+            .fetch
+            .unwrap_or(config.fetch)),
+        None => Ok(config.fetch),
+    }
+}
+
+/// The language server of a project, started on first use
+async fn project_language_server(
+    project_manager: &dyn ProjectManager,
+    project: &str,
+) -> Result<Arc<LspClient>> {
+    let explorer = project_manager.get_explorer_for_project(project)?;
+    let config = project_manager
+        .get_project(project)?
+        .and_then(|p| p.language_server);
+    lsp::language_servers()
+        .get_or_start(project, &explorer.root_dir(), config.as_ref())
+        .await
+}
+
+/// Syntax errors an edit introduces. If the project rejects such edits, the
+/// error is a message for the model listing their locations. Files that had
+/// syntax errors before, e.g. halfway through a refactoring, only get a warning.
+fn check_edit_syntax(
+    project_manager: &dyn ProjectManager,
+    project: &str,
+    path: &Path,
+    original: Option<&str>,
+    edited: &str,
+) -> Result<Vec<Diagnostic>, String> {
+    let mode = project_manager
+        .get_project(project)
+        .ok()
+        .flatten()
+        .map(|p| p.syntax_check)
+        .unwrap_or_default();
+    if mode == SyntaxCheck::Off {
+        return Ok(Vec::new());
+    }
+
+    let errors = syntax::new_syntax_errors(path, original, edited);
+    let already_broken = || {
+        original.is_some_and(|original| !syntax::new_syntax_errors(path, None, original).is_empty())
+    };
+    if mode == SyntaxCheck::Reject && !errors.is_empty() && !already_broken() {
+        let mut message =
+            String::from("The edit was not applied because it introduces syntax errors:\n");
+        for error in &errors {
+            message.push_str(&format!("{}\n", error));
+        }
+        message.push_str("Please fix the edit and try again.");
+        return Err(message);
+    }
+    Ok(errors)
+}
+
+/// A validated edit of an edit_files batch
+struct PlannedEdit {
+    path: PathBuf,
+    full_path: PathBuf,
+    /// File content as the model saw it
+    original: String,
+    /// Exact bytes on disk to restore on rollback, read_file normalizes whitespace
+    snapshot: Option<Vec<u8>>,
+    updated: String,
+}
+
+/// Writes all planned edits. If a write fails, the files written so far are
+/// restored.
+fn write_planned_edits(explorer: &dyn CodeExplorer, planned: &[PlannedEdit]) -> Result<(), String> {
+    for (index, edit) in planned.iter().enumerate() {
+        if let Err(e) = explorer.write_file(&edit.full_path, &edit.updated, false) {
+            // Roll back this and all previous files
+            let mut message = format!(
+                "Failed to write {}: {}. No files were changed.",
+                edit.path.display(),
+                e
+            );
+            for written in planned[..=index].iter().rev() {
+                let restored = match &written.snapshot {
+                    Some(bytes) => std::fs::write(&written.full_path, bytes).map_err(Into::into),
+                    None => explorer.write_file(&written.full_path, &written.original, false),
+                };
+                if let Err(e) = restored {
+                    message.push_str(&format!(
+                        "\nFailed to restore {}: {}",
+                        written.path.display(),
+                        e
+                    ));
+                }
+            }
+            return Err(message);
+        }
+    }
+    Ok(())
+}
+
+/// Builds a glob for a path filter, patterns without a separator apply to
+/// file names only
+fn path_filter(pattern: &str) -> Result<impl Fn(&Path) -> bool, String> {
+    let glob = glob::Pattern::new(pattern)
+        .map_err(|e| format!("Invalid glob pattern '{}': {}", pattern, e))?;
+    let match_file_name = !pattern.contains('/');
+    Ok(move |path: &Path| {
+        if match_file_name {
+            path.file_name()
+                .is_some_and(|name| glob.matches(&name.to_string_lossy()))
+        } else {
+            glob.matches(&path.to_string_lossy().replace('\\', "/"))
+        }
+    })
+}
+
+/// Replaces a pattern in all project files matching the include and exclude
+/// globs. Only writes the files if `apply` is set, returns the change sites
+/// and the new contents of the changed files.
+fn replace_across_files(
+    project_manager: &dyn ProjectManager,
+    project: &str,
+    options: SearchOptions,
+    replacement: &str,
+    include: &[String],
+    exclude: &[String],
+    apply: bool,
+) -> Result<
+    (
+        Vec<ReplacementSite>,
+        Vec<(PathBuf, String)>,
+        Vec<Diagnostic>,
+    ),
+    String,
+> {
+    if options.query.is_empty() {
+        return Err("Pattern must not be empty".to_string());
+    }
+    let explorer = project_manager
+        .get_explorer_for_project(project)
+        .map_err(|e| format!("Failed to get explorer for project {}: {}", project, e))?;
+    let root_dir = explorer.root_dir();
+    let regex = explorer::search_regex(&options).map_err(|e| e.to_string())?;
+    let literal = matches!(options.mode, SearchMode::Exact);
+    let include = include
+        .iter()
+        .map(|p| path_filter(p))
+        .collect::<Result<Vec<_>, _>>()?;
+    let exclude = exclude
+        .iter()
+        .map(|p| path_filter(p))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // The search finds the candidate files, respecting .gitignore
+    let mut paths: Vec<PathBuf> = explorer
+        .search(&root_dir, options)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter_map(|result| {
+            let path = result.file.strip_prefix(&root_dir).ok()?.to_path_buf();
+            let included = include.is_empty() || include.iter().any(|f| f(&path));
+            (included && !exclude.iter().any(|f| f(&path))).then_some(path)
+        })
+        .collect();
+    paths.sort();
+    paths.dedup();
+
+    let mut sites = Vec::new();
+    let mut planned = Vec::new();
+    let mut syntax_errors = Vec::new();
+    let mut failures = Vec::new();
+    for path in paths {
+        let full_path = root_dir.join(&path);
+        // Reading records the encoding and line endings the write keeps
+        let original = match explorer.read_file(&full_path) {
+            Ok(content) => content,
+            Err(e) => {
+                failures.push(format!("{}: {}", path.display(), e));
+                continue;
+            }
+        };
+        let (updated, file_sites) =
+            crate::utils::replace_pattern(&path, &original, &regex, replacement, literal);
+        if file_sites.is_empty() {
+            continue;
+        }
+        sites.extend(file_sites);
+
+        if apply {
+            match check_edit_syntax(project_manager, project, &path, Some(&original), &updated) {
+                Ok(errors) => syntax_errors.extend(errors),
+                Err(e) => failures.push(e),
+            }
+        }
+        planned.push(PlannedEdit {
+            path,
+            snapshot: std::fs::read(&full_path).ok(),
+            full_path,
+            original,
+            updated,
+        });
+    }
+
+    if apply {
+        if !failures.is_empty() {
+            return Err(format!(
+                "No files were changed because {} files could not be edited:\n{}",
+                failures.len(),
+                failures.join("\n")
+            ));
+        }
+        write_planned_edits(explorer.as_ref(), &planned)?;
+    }
+
+    let files = if apply {
+        planned
+            .into_iter()
+            .map(|edit| (edit.path, edit.updated))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    Ok((sites, files, syntax_errors))
+}
+
+/// Validates all edits against the current file contents before writing any
+/// of them. If a write fails, the files written so far are restored.
+/// Returns the new file contents, their combined diff and the syntax errors
+/// of the edits.
+fn apply_edit_transaction(
+    project_manager: &dyn ProjectManager,
+    project: &str,
+    edits: &[FileEdit],
+) -> Result<(Vec<(PathBuf, String)>, String, Vec<Diagnostic>), String> {
+    if edits.is_empty() {
+        return Err("No edits given".to_string());
+    }
+    let explorer = project_manager
+        .get_explorer_for_project(project)
+        .map_err(|e| format!("Failed to get explorer for project {}: {}", project, e))?;
+    let root_dir = explorer.root_dir();
+
+    let mut planned = Vec::new();
+    let mut syntax_errors = Vec::new();
+    let mut failures = Vec::new();
+    for (index, edit) in edits.iter().enumerate() {
+        if edits[..index].iter().any(|e| e.path == edit.path) {
+            failures.push(format!(
+                "{}: the file is edited more than once, combine its replacements into one edit",
+                edit.path.display()
+            ));
+            continue;
+        }
+
+        let full_path = root_dir.join(&edit.path);
+        let result = explorer
+            .read_file(&full_path)
+            .map_err(|e| e.to_string())
+            .and_then(|original| {
+                let updated = apply_replacements_normalized(&original, &edit.replacements)
+                    .map_err(|e| e.to_string())?;
+                let errors = check_edit_syntax(
+                    project_manager,
+                    project,
+                    &edit.path,
+                    Some(&original),
+                    &updated,
+                )?;
+                Ok((original, updated, errors))
+            });
+        match result {
+            Ok((original, updated, errors)) => {
+                syntax_errors.extend(errors);
+                planned.push(PlannedEdit {
+                    path: edit.path.clone(),
+                    snapshot: std::fs::read(&full_path).ok(),
+                    full_path,
+                    original,
+                    updated,
+                });
+            }
+            Err(e) => failures.push(format!("{}: {}", edit.path.display(), e)),
+        }
+    }
+    if !failures.is_empty() {
+        return Err(format!(
+            "No files were changed because {} of {} edits failed:\n{}",
+            failures.len(),
+            edits.len(),
+            failures.join("\n")
+        ));
+    }
+
+    write_planned_edits(explorer.as_ref(), &planned)?;
+
+    let diff = planned
+        .iter()
+        .map(|edit| unified_diff(&edit.path, &edit.original, &edit.updated))
+        .collect::<Vec<_>>()
+        .join("");
+    let files = planned
+        .into_iter()
+        .map(|edit| (edit.path, edit.updated))
+        .collect();
+    Ok((files, diff, syntax_errors))
+}
+
 impl ToolExecutor {
     pub async fn execute<H: ToolResultHandler>(
         handler: &mut H,
         project_manager: &Box<dyn ProjectManager>,
         command_executor: &Box<dyn CommandExecutor>,
+        browser_tab: BrowserTabId,
         ui: Option<&Box<dyn UserInterface>>,
+        tool_id: Option<&str>,
         tool: &Tool,
     ) -> Result<(String, ToolResult)> {
         let result = match tool {
@@ -58,13 +457,57 @@ impl ToolExecutor {
                 },
             },
 
+            Tool::AskUser {
+                question,
+                answer_type,
+                choices,
+            } => {
+                let result = match &ui {
+                    Some(ui) => ui
+                        .ask_user(&UserQuestion {
+                            question: question.clone(),
+                            answer_type: *answer_type,
+                            choices: choices.clone(),
+                        })
+                        .await
+                        .map_err(|e| e.to_string()),
+                    None => Err("ask_user needs an interactive user interface".to_string()),
+                };
+                match result {
+                    Ok(answers) => ToolResult::AskUser {
+                        question: question.clone(),
+                        answers,
+                        error: None,
+                    },
+                    Err(e) => ToolResult::AskUser {
+                        question: question.clone(),
+                        answers: Vec::new(),
+                        error: Some(e),
+                    },
+                }
+            }
+
             Tool::WebSearch {
+                project,
                 query,
                 hits_page_number,
             } => {
-                // Create new client for each request
-                let client = WebClient::new().await?;
-                match client.search(query, *hits_page_number).await {
+                let result: Result<_> = async {
+                    let fetch = web_fetch_config(project_manager.as_ref(), Some(project.as_str()))?;
+                    // Read on every search, so changes of the backend apply right away
+                    let backend = create_search_backend(&config::load_web_config()?.search);
+                    let mut results = web_client()
+                        .search(backend.as_ref(), query, *hits_page_number)
+                        .await?;
+                    // Pages the project may not fetch are not worth finding
+                    results.retain(|result| {
+                        url::Url::parse(&result.url)
+                            .is_ok_and(|url| check_fetch_domain(&url, &fetch).is_ok())
+                    });
+                    Ok(results)
+                }
+                .await;
+                match result {
                     Ok(results) => ToolResult::WebSearch {
                         query: query.to_string(),
                         results,
@@ -78,10 +521,27 @@ impl ToolExecutor {
                 }
             }
 
-            Tool::WebFetch { url, selectors: _ } => {
-                // Create new client for each request
-                let client = WebClient::new().await?;
-                match client.fetch(url).await {
+            Tool::WebFetch {
+                project,
+                url,
+                selectors,
+                page,
+                section,
+            } => {
+                let result: Result<_> = async {
+                    let fetch = web_fetch_config(project_manager.as_ref(), Some(project.as_str()))?;
+                    web_client()
+                        .fetch_page(
+                            url,
+                            selectors.as_deref(),
+                            section.as_deref(),
+                            page.unwrap_or(1),
+                            &fetch,
+                        )
+                        .await
+                }
+                .await;
+                match result {
                     Ok(page) => ToolResult::WebFetch { page, error: None },
                     Err(e) => ToolResult::WebFetch {
                         page: WebPage::default(),
@@ -90,7 +550,141 @@ impl ToolExecutor {
                 }
             }
 
-            Tool::ReadFiles { project, paths } => {
+            Tool::HttpRequest {
+                method,
+                url,
+                headers,
+                query,
+                body,
+                timeout_secs,
+            } => {
+                let request = HttpRequest {
+                    method: method.clone(),
+                    url: url.clone(),
+                    headers: headers.clone(),
+                    query: query.clone(),
+                    body: body.clone(),
+                    timeout: timeout_secs
+                        .map(Duration::from_secs)
+                        .unwrap_or(DEFAULT_HTTP_REQUEST_TIMEOUT),
+                };
+                let result: Result<_> = async {
+                    let config = config::load_web_config()?;
+                    web_client()
+                        .http_request(&request, &config.http_request.allowed_hosts)
+                        .await
+                }
+                .await;
+                match result {
+                    Ok(response) => ToolResult::HttpRequest {
+                        method: method.clone(),
+                        url: url.clone(),
+                        response: Some(response),
+                        error: None,
+                    },
+                    Err(e) => ToolResult::HttpRequest {
+                        method: method.clone(),
+                        url: url.clone(),
+                        response: None,
+                        error: Some(e.to_string()),
+                    },
+                }
+            }
+
+            Tool::Browser { project, action } => {
+                let result: Result<_> = async {
+                    let fetch = web_fetch_config(project_manager.as_ref(), project.as_deref())?;
+                    web_client().browser(browser_tab, action, &fetch).await
+                }
+                .await;
+                match result {
+                    Ok(state) => ToolResult::Browser {
+                        action: action.clone(),
+                        state: Some(state),
+                        error: None,
+                    },
+                    Err(e) => ToolResult::Browser {
+                        action: action.clone(),
+                        state: None,
+                        error: Some(e.to_string()),
+                    },
+                }
+            }
+
+            Tool::CrawlDocs {
+                project,
+                url,
+                max_depth,
+                max_pages,
+                prefix,
+            } => {
+                let defaults = CrawlOptions::default();
+                let options = CrawlOptions {
+                    max_depth: max_depth.unwrap_or(defaults.max_depth),
+                    max_pages: max_pages.unwrap_or(defaults.max_pages),
+                    prefix: prefix.clone(),
+                };
+                let result: Result<_> = async {
+                    let fetch = web_fetch_config(project_manager.as_ref(), Some(project.as_str()))?;
+                    web_client().crawl_docs(url, &options, &fetch).await
+                }
+                .await;
+                match result {
+                    Ok(crawl) => ToolResult::CrawlDocs {
+                        url: url.clone(),
+                        crawl: Some(crawl),
+                        error: None,
+                    },
+                    Err(e) => ToolResult::CrawlDocs {
+                        url: url.clone(),
+                        crawl: None,
+                        error: Some(e.to_string()),
+                    },
+                }
+            }
+
+            Tool::DependencyDocs {
+                project,
+                name,
+                version,
+                item,
+            } => {
+                let result: Result<_> = async {
+                    let root_dir = project_manager
+                        .get_explorer_for_project(project)?
+                        .root_dir();
+                    let name = name.clone();
+                    let version = version.clone();
+                    let item = item.clone();
+                    // Parsing the sources of a large crate takes a while
+                    tokio::task::spawn_blocking(move || {
+                        dependencies::dependency_docs(
+                            &root_dir,
+                            &name,
+                            version.as_deref(),
+                            item.as_deref(),
+                        )
+                    })
+                    .await?
+                }
+                .await;
+                let (docs, error) = match result {
+                    Ok(docs) => (Some(docs), None),
+                    Err(e) => (None, Some(e.to_string())),
+                };
+                ToolResult::DependencyDocs {
+                    project: project.clone(),
+                    name: name.clone(),
+                    docs,
+                    error,
+                }
+            }
+
+            Tool::ReadFiles {
+                project,
+                paths,
+                line_numbers,
+            } => {
                 // Get explorer for the specified project
                 let explorer = match project_manager.get_explorer_for_project(project) {
                     Ok(explorer) => explorer,
@@ -100,6 +694,7 @@ impl ToolExecutor {
                             ToolResult::ReadFiles {
                                 project: project.clone(),
                                 loaded_files: HashMap::new(),
+                                first_lines: HashMap::new(),
                                 failed_files: vec![(
                                     PathBuf::from("."),
                                     format!(
@@ -120,6 +715,7 @@ impl ToolExecutor {
                 }
                 let mut loaded_files = HashMap::new();
                 let mut failed_files = Vec::new();
+                let mut first_lines = HashMap::new();
 
                 // Parse the path string to extract line range information
                 use super::parse;
@@ -139,21 +735,30 @@ impl ToolExecutor {
                     let full_path = explorer.root_dir().join(&parsed_path.path);
 
                     // Use either read_file_range or read_file based on whether we have line range info
-                    let read_result =
-                        if parsed_path.start_line.is_some() || parsed_path.end_line.is_some() {
-                            // We have line range information, use read_file_range
-                            explorer.read_file_range(
+                    let read_result = if let Some(symbol) = &parsed_path.symbol {
+                        // Only the definition of the symbol is loaded
+                        explorer.read_file(&full_path).and_then(|content| {
+                            syntax::symbol_source(&parsed_path.path, &content, symbol)
+                        })
+                    } else if parsed_path.start_line.is_some() || parsed_path.end_line.is_some() {
+                        // We have line range information, use read_file_range
+                        explorer
+                            .read_file_range(
                                 &full_path,
                                 parsed_path.start_line,
                                 parsed_path.end_line,
                             )
-                        } else {
-                            // No line range specified, read the whole file
-                            explorer.read_file(&full_path)
-                        };
+                            .map(|content| (parsed_path.start_line.unwrap_or(1), content))
+                    } else {
+                        // No line range specified, read the whole file
+                        explorer.read_file(&full_path).map(|content| (1, content))
+                    };
 
                     match read_result {
-                        Ok(content) => {
+                        Ok((first_line, content)) => {
+                            if *line_numbers {
+                                first_lines.insert(path.clone(), first_line);
+                            }
                             loaded_files.insert(path.clone(), content);
                         }
                         Err(e) => {
@@ -166,6 +771,7 @@ impl ToolExecutor {
                     project: project.clone(),
                     loaded_files,
                     failed_files,
+                    first_lines,
                 }
             }
 
@@ -277,10 +883,10 @@ impl ToolExecutor {
                 }
             }
 
-            Tool::ExecuteCommand {
+            Tool::FindFiles {
                 project,
-                command_line,
-                working_dir,
+                pattern,
+                max_results,
             } => {
                 // Get explorer for the specified project
                 let explorer = match project_manager.get_explorer_for_project(project) {
@@ -288,28 +894,188 @@ impl ToolExecutor {
                     Err(e) => {
                         return Ok((
                             String::new(),
-                            ToolResult::ExecuteCommand {
+                            ToolResult::FindFiles {
                                 project: project.clone(),
-                                output: format!(
+                                pattern: pattern.clone(),
+                                results: Vec::new(),
+                                error: Some(format!(
                                     "Failed to get explorer for project {}: {}",
                                     project, e
-                                ),
-                                success: false,
+                                )),
                             },
                         ));
                     }
                 };
 
+                let options = FindOptions {
+                    pattern: pattern.clone(),
+                    max_results: *max_results,
+                };
+
+                let root_dir = explorer.root_dir();
+                match explorer.find_files(&root_dir, options) {
+                    Ok(mut results) => {
+                        // Convert absolute paths to relative paths
+                        for result in &mut results {
+                            if let Ok(rel_path) = result.path.strip_prefix(&root_dir) {
+                                result.path = rel_path.to_path_buf();
+                            }
+                        }
+
+                        ToolResult::FindFiles {
+                            project: project.clone(),
+                            pattern: pattern.clone(),
+                            results,
+                            error: None,
+                        }
+                    }
+                    Err(e) => ToolResult::FindFiles {
+                        project: project.clone(),
+                        pattern: pattern.clone(),
+                        results: Vec::new(),
+                        error: Some(format!("Find failed: {}", e)),
+                    },
+                }
+            }
+
+            Tool::ExecuteCommand {
+                project,
+                command_line,
+                working_dir,
+                timeout_secs,
+                persistent_session,
+            } => {
+                let failed = |output: String| ToolResult::ExecuteCommand {
+                    project: project.clone(),
+                    output,
+                    success: false,
+                    timed_out: false,
+                    output_file: None,
+                };
+
+                // Get explorer for the specified project
+                let explorer = match project_manager.get_explorer_for_project(project) {
+                    Ok(explorer) => explorer,
+                    Err(e) => {
+                        return Ok((
+                            String::new(),
+                            failed(format!(
+                                "Failed to get explorer for project {}: {}",
+                                project, e
+                            )),
+                        ));
+                    }
+                };
+
                 let effective_working_dir = match working_dir {
                     Some(dir) if dir.is_absolute() => {
                         return Ok((
                             String::new(),
-                            ToolResult::ExecuteCommand {
-                                project: project.clone(),
-                                output: "Working directory must be relative to project root"
-                                    .to_string(),
-                                success: false,
-                            },
+                            failed(
+                                "Working directory must be relative to project root".to_string(),
+                            ),
+                        ));
+                    }
+                    Some(dir) => explorer.root_dir().join(dir),
+                    None => explorer.root_dir(),
+                };
+
+                let project_config = project_manager.get_project(project).ok().flatten();
+
+                let timeout = command_timeout(project_config.as_ref(), *timeout_secs);
+
+                // Stream output to the tool block while the command runs
+                let (sender, forward_output) = tool_output_stream(ui, tool_id);
+                let use_session = persistent_session
+                    .unwrap_or_else(|| project_config.is_some_and(|p| p.persistent_shell));
+                let run = async {
+                    if use_session {
+                        // Only change directory when asked, the session keeps its own
+                        command_executor
+                            .execute_in_session(
+                                project,
+                                &explorer.root_dir(),
+                                command_line,
+                                working_dir.as_ref().map(|_| &effective_working_dir),
+                                timeout,
+                                Some(sender),
+                            )
+                            .await
+                    } else {
+                        command_executor
+                            .execute(
+                                command_line,
+                                Some(&effective_working_dir),
+                                timeout,
+                                Some(sender),
+                            )
+                            .await
+                    }
+                };
+                let (result, _) = tokio::join!(run, forward_output);
+
+                match result {
+                    Ok(output) => {
+                        let mut text = output.output;
+                        let mut output_file = None;
+                        if let Some(truncated) = truncate_output(
+                            &text,
+                            OUTPUT_HEAD_LINES,
+                            OUTPUT_TAIL_LINES,
+                            MAX_OUTPUT_BYTES,
+                        ) {
+                            // Keep the complete output around for read_files
+                            match save_command_output(&explorer.root_dir(), &text) {
+                                Ok(path) => output_file = Some(path),
+                                Err(e) => debug!("Failed to save command output: {}", e),
+                            }
+                            text = truncated;
+                        }
+                        ToolResult::ExecuteCommand {
+                            project: project.clone(),
+                            output: text,
+                            success: output.success,
+                            timed_out: output.timed_out,
+                            output_file,
+                        }
+                    }
+                    Err(e) => failed(e.to_string()),
+                }
+            }
+
+            Tool::StartProcess {
+                project,
+                command_line,
+                working_dir,
+            } => {
+                let failed = |error: String| ToolResult::StartProcess {
+                    project: project.clone(),
+                    command_line: command_line.clone(),
+                    process: None,
+                    error: Some(error),
+                };
+
+                // Get explorer for the specified project
+                let explorer = match project_manager.get_explorer_for_project(project) {
+                    Ok(explorer) => explorer,
+                    Err(e) => {
+                        return Ok((
+                            String::new(),
+                            failed(format!(
+                                "Failed to get explorer for project {}: {}",
+                                project, e
+                            )),
+                        ));
+                    }
+                };
+
+                let effective_working_dir = match working_dir {
+                    Some(dir) if dir.is_absolute() => {
+                        return Ok((
+                            String::new(),
+                            failed(
+                                "Working directory must be relative to project root".to_string(),
+                            ),
                         ));
                     }
                     Some(dir) => explorer.root_dir().join(dir),
@@ -317,18 +1083,393 @@ impl ToolExecutor {
                 };
 
                 match command_executor
-                    .execute(command_line, Some(&effective_working_dir))
+                    .start_process(project, command_line, &effective_working_dir)
                     .await
                 {
-                    Ok(output) => ToolResult::ExecuteCommand {
+                    Ok(process) => ToolResult::StartProcess {
                         project: project.clone(),
-                        output: output.output,
-                        success: output.success,
+                        command_line: command_line.clone(),
+                        process: Some(process),
+                        error: None,
                     },
-                    Err(e) => ToolResult::ExecuteCommand {
+                    Err(e) => failed(e.to_string()),
+                }
+            }
+
+            Tool::ReadProcessOutput {
+                project,
+                process_id,
+                since_offset,
+                tail_lines,
+            } => match command_executor
+                .read_process_output(project, process_id, *since_offset, *tail_lines)
+                .await
+            {
+                Ok(output) => ToolResult::ReadProcessOutput {
+                    project: project.clone(),
+                    process_id: process_id.clone(),
+                    output: output.output,
+                    next_offset: output.next_offset,
+                    truncated: output.truncated,
+                    process: Some(output.process),
+                    error: None,
+                },
+                Err(e) => ToolResult::ReadProcessOutput {
+                    project: project.clone(),
+                    process_id: process_id.clone(),
+                    output: String::new(),
+                    next_offset: since_offset.unwrap_or(0),
+                    truncated: false,
+                    process: None,
+                    error: Some(e.to_string()),
+                },
+            },
+
+            Tool::SendProcessInput {
+                project,
+                process_id,
+                input,
+            } => ToolResult::SendProcessInput {
+                project: project.clone(),
+                process_id: process_id.clone(),
+                error: command_executor
+                    .send_process_input(project, process_id, input)
+                    .await
+                    .err()
+                    .map(|e| e.to_string()),
+            },
+
+            Tool::ResetShellSession { project } => {
+                match command_executor.reset_session(project).await {
+                    Ok(was_running) => ToolResult::ResetShellSession {
+                        project: project.clone(),
+                        was_running,
+                        error: None,
+                    },
+                    Err(e) => ToolResult::ResetShellSession {
                         project: project.clone(),
-                        output: e.to_string(),
-                        success: false,
+                        was_running: false,
+                        error: Some(e.to_string()),
+                    },
+                }
+            }
+
+            Tool::CheckProject {
+                project,
+                build_system,
+            } => {
+                let failed = |error: String| ToolResult::CheckProject {
+                    project: project.clone(),
+                    build_system: build_system.clone().unwrap_or_default(),
+                    success: false,
+                    diagnostics: Vec::new(),
+                    output: None,
+                    error: Some(error),
+                };
+
+                let explorer = match project_manager.get_explorer_for_project(project) {
+                    Ok(explorer) => explorer,
+                    Err(e) => {
+                        return Ok((
+                            String::new(),
+                            failed(format!(
+                                "Failed to get explorer for project {}: {}",
+                                project, e
+                            )),
+                        ));
+                    }
+                };
+
+                let project_config = project_manager.get_project(project).ok().flatten();
+                let timeout = command_timeout(project_config.as_ref(), None);
+
+                match diagnostics::check_project(
+                    command_executor.as_ref(),
+                    &explorer.root_dir(),
+                    build_system.as_deref(),
+                    timeout,
+                )
+                .await
+                {
+                    Ok(check) => {
+                        // Without diagnostics the raw output is the only hint what failed
+                        let output = (!check.success && check.diagnostics.is_empty()).then(|| {
+                            truncate_output(&check.output, 0, 40, MAX_OUTPUT_BYTES / 4)
+                                .unwrap_or(check.output)
+                        });
+                        ToolResult::CheckProject {
+                            project: project.clone(),
+                            build_system: check.build_system,
+                            success: check.success,
+                            diagnostics: check.diagnostics,
+                            output,
+                            error: None,
+                        }
+                    }
+                    Err(e) => failed(e.to_string()),
+                }
+            }
+
+            Tool::RunTests {
+                project,
+                filter,
+                runner,
+            } => {
+                let failed = |error: String| ToolResult::RunTests {
+                    project: project.clone(),
+                    runner: runner.clone().unwrap_or_default(),
+                    success: false,
+                    report: Default::default(),
+                    output: None,
+                    error: Some(error),
+                };
+
+                let explorer = match project_manager.get_explorer_for_project(project) {
+                    Ok(explorer) => explorer,
+                    Err(e) => {
+                        return Ok((
+                            String::new(),
+                            failed(format!(
+                                "Failed to get explorer for project {}: {}",
+                                project, e
+                            )),
+                        ));
+                    }
+                };
+
+                let project_config = project_manager.get_project(project).ok().flatten();
+                let timeout = command_timeout(project_config.as_ref(), None);
+
+                // Stream output to the tool block while the tests run
+                let (sender, forward_output) = tool_output_stream(ui, tool_id);
+                let root_dir = explorer.root_dir();
+                let (result, _) = tokio::join!(
+                    test_runner::run_tests(
+                        command_executor.as_ref(),
+                        &root_dir,
+                        runner.as_deref(),
+                        filter.as_deref(),
+                        timeout,
+                        Some(sender),
+                    ),
+                    forward_output
+                );
+
+                match result {
+                    Ok(run) => {
+                        // Without failures the raw output is the only hint, e.g. a build error
+                        let output = (!run.success && run.report.failures.is_empty()).then(|| {
+                            truncate_output(&run.output, 0, 40, MAX_OUTPUT_BYTES / 4)
+                                .unwrap_or(run.output)
+                        });
+                        ToolResult::RunTests {
+                            project: project.clone(),
+                            runner: run.runner,
+                            success: run.success,
+                            report: run.report,
+                            output,
+                            error: None,
+                        }
+                    }
+                    Err(e) => failed(e.to_string()),
+                }
+            }
+
+            Tool::GotoDefinition {
+                project,
+                path,
+                line,
+                symbol,
+                column,
+            } => {
+                if let Some(error) = check_absolute_path(path) {
+                    return Ok((String::new(), error));
+                }
+                let result: Result<_> = async {
+                    let client = project_language_server(project_manager.as_ref(), project).await?;
+                    lsp::goto_definition(&client, path, *line, symbol.as_deref(), *column).await
+                }
+                .await;
+                let (locations, error) = match result {
+                    Ok(locations) => (locations, None),
+                    Err(e) => (Vec::new(), Some(e.to_string())),
+                };
+                ToolResult::GotoDefinition {
+                    project: project.clone(),
+                    path: path.clone(),
+                    line: *line,
+                    locations,
+                    error,
+                }
+            }
+
+            Tool::FindReferences {
+                project,
+                path,
+                line,
+                symbol,
+                column,
+            } => {
+                if let Some(error) = check_absolute_path(path) {
+                    return Ok((String::new(), error));
+                }
+                let result: Result<_> = async {
+                    let client = project_language_server(project_manager.as_ref(), project).await?;
+                    lsp::find_references(&client, path, *line, symbol.as_deref(), *column).await
+                }
+                .await;
+                let (locations, error) = match result {
+                    Ok(locations) => (locations, None),
+                    Err(e) => (Vec::new(), Some(e.to_string())),
+                };
+                ToolResult::FindReferences {
+                    project: project.clone(),
+                    path: path.clone(),
+                    line: *line,
+                    locations,
+                    error,
+                }
+            }
+
+            Tool::Hover {
+                project,
+                path,
+                line,
+                symbol,
+                column,
+            } => {
+                if let Some(error) = check_absolute_path(path) {
+                    return Ok((String::new(), error));
+                }
+                let result: Result<_> = async {
+                    let client = project_language_server(project_manager.as_ref(), project).await?;
+                    lsp::hover(&client, path, *line, symbol.as_deref(), *column).await
+                }
+                .await;
+                let (contents, error) = match result {
+                    Ok(contents) => (contents, None),
+                    Err(e) => (String::new(), Some(e.to_string())),
+                };
+                ToolResult::Hover {
+                    project: project.clone(),
+                    path: path.clone(),
+                    line: *line,
+                    contents,
+                    error,
+                }
+            }
+
+            Tool::DocumentSymbols { project, path } => {
+                if let Some(error) = check_absolute_path(path) {
+                    return Ok((String::new(), error));
+                }
+                let result: Result<_> = async {
+                    let client = project_language_server(project_manager.as_ref(), project).await?;
+                    lsp::document_symbols(&client, path).await
+                }
+                .await;
+                let (symbols, error) = match result {
+                    Ok(symbols) => (symbols, None),
+                    Err(e) => (Vec::new(), Some(e.to_string())),
+                };
+                ToolResult::DocumentSymbols {
+                    project: project.clone(),
+                    path: path.clone(),
+                    symbols,
+                    error,
+                }
+            }
+
+            Tool::WorkspaceSymbols { project, query } => {
+                let result: Result<_> = async {
+                    let client = project_language_server(project_manager.as_ref(), project).await?;
+                    lsp::workspace_symbols(&client, query).await
+                }
+                .await;
+                let (symbols, error) = match result {
+                    Ok(symbols) => (symbols, None),
+                    Err(e) => (Vec::new(), Some(e.to_string())),
+                };
+                ToolResult::WorkspaceSymbols {
+                    project: project.clone(),
+                    query: query.clone(),
+                    symbols,
+                    error,
+                }
+            }
+
+            Tool::RepoMap {
+                project,
+                paths,
+                max_tokens,
+            } => {
+                if let Some(error) = paths.iter().find_map(|p| check_absolute_path(p)) {
+                    return Ok((String::new(), error));
+                }
+                let result: Result<_> = async {
+                    let explorer = project_manager.get_explorer_for_project(project)?;
+                    let paths = paths.clone();
+                    let max_tokens = max_tokens.unwrap_or(DEFAULT_REPO_MAP_TOKENS);
+                    // Parsing a large project takes a while
+                    tokio::task::spawn_blocking(move || explorer.repo_map(&paths, max_tokens))
+                        .await?
+                }
+                .await;
+                let (map, error) = match result {
+                    Ok(map) => (Some(map), None),
+                    Err(e) => (None, Some(e.to_string())),
+                };
+                ToolResult::RepoMap {
+                    project: project.clone(),
+                    map,
+                    error,
+                }
+            }
+
+            Tool::StopProcess {
+                project,
+                process_id,
+            } => match command_executor.stop_process(project, process_id).await {
+                Ok(process) => ToolResult::StopProcess {
+                    project: project.clone(),
+                    process_id: process_id.clone(),
+                    process: Some(process),
+                    error: None,
+                },
+                Err(e) => ToolResult::StopProcess {
+                    project: project.clone(),
+                    process_id: process_id.clone(),
+                    process: None,
+                    error: Some(e.to_string()),
+                },
+            },
+
+            Tool::ViewImage { project, path } => {
+                if let Some(error) = check_absolute_path(path) {
+                    return Ok((String::new(), error));
+                }
+                let result: Result<_> = async {
+                    if image_media_type(path).is_none() {
+                        return Err(anyhow::anyhow!(
+                            "Not an image file, supported are PNG, JPEG, GIF and WebP"
+                        ));
+                    }
+                    let explorer = project_manager.get_explorer_for_project(project)?;
+                    load_image_file(&explorer.root_dir().join(path))
+                }
+                .await;
+                match result {
+                    Ok(image) => ToolResult::ViewImage {
+                        project: project.clone(),
+                        path: path.clone(),
+                        image: Some(image),
+                        error: None,
+                    },
+                    Err(e) => ToolResult::ViewImage {
+                        project: project.clone(),
+                        path: path.clone(),
+                        image: None,
+                        error: Some(e.to_string()),
                     },
                 }
             }
@@ -349,6 +1490,8 @@ impl ToolExecutor {
                                 project: project.clone(),
                                 path: path.clone(),
                                 content: String::new(),
+                                diagnostics: Vec::new(),
+                                syntax_errors: Vec::new(),
                                 error: Some(format!(
                                     "Failed to get explorer for project {}: {}",
                                     project, e
@@ -363,18 +1506,49 @@ impl ToolExecutor {
                 }
                 let full_path = explorer.root_dir().join(path);
 
-                match explorer.write_file(&full_path, content, *append) {
-                    Ok(_) => ToolResult::WriteFile {
-                        project: project.clone(),
-                        path: path.clone(),
-                        content: content.clone(),
-                        error: None,
-                    },
+                // Check the syntax of the resulting file before anything is written
+                let original = explorer.read_file(&full_path).ok();
+                let edited = match (&original, *append) {
+                    (Some(original), true) => format!("{}{}", original, content),
+                    _ => content.clone(),
+                };
+                let syntax_check = check_edit_syntax(
+                    project_manager.as_ref(),
+                    project,
+                    path,
+                    original.as_deref(),
+                    &edited,
+                );
+
+                match syntax_check.and_then(|syntax_errors| {
+                    explorer
+                        .write_file(&full_path, content, *append)
+                        .map(|_| syntax_errors)
+                        .map_err(|e| e.to_string())
+                }) {
+                    Ok(syntax_errors) => {
+                        // Let a running language server check the new content
+                        let written = if *append {
+                            std::fs::read_to_string(&full_path).unwrap_or_default()
+                        } else {
+                            content.clone()
+                        };
+                        ToolResult::WriteFile {
+                            project: project.clone(),
+                            path: path.clone(),
+                            content: content.clone(),
+                            diagnostics: lsp::sync_file(project, path, &written).await,
+                            syntax_errors,
+                            error: None,
+                        }
+                    }
                     Err(e) => ToolResult::WriteFile {
                         project: project.clone(),
                         path: path.clone(),
                         content: String::new(), // Empty content on error
-                        error: Some(e.to_string()),
+                        diagnostics: Vec::new(),
+                        syntax_errors: Vec::new(),
+                        error: Some(e),
                     },
                 }
             }
@@ -394,6 +1568,8 @@ impl ToolExecutor {
                                 project: project.clone(),
                                 path: path.clone(),
                                 content: String::new(),
+                                diagnostics: Vec::new(),
+                                syntax_errors: Vec::new(),
                                 error: Some(crate::utils::FileUpdaterError::Other(format!(
                                     "Failed to get explorer for project {}: {}",
                                     project, e
@@ -409,21 +1585,42 @@ impl ToolExecutor {
                 let full_path = explorer.root_dir().join(path);
 
                 // Read the current content in order to return it in error case
-                let current_content = match std::fs::read_to_string(&full_path) {
+                let current_content = match explorer.read_file(&full_path) {
                     Ok(content) => content,
                     Err(_) => String::new(),
                 };
 
-                match explorer.apply_replacements(&full_path, replacements) {
-                    Ok(new_content) => ToolResult::ReplaceInFile {
+                // Check the syntax of the result before anything is written,
+                // failing replacements are reported by apply_replacements below
+                let syntax_check =
+                    match apply_replacements_normalized(&current_content, replacements) {
+                        Ok(updated) => check_edit_syntax(
+                            project_manager.as_ref(),
+                            project,
+                            path,
+                            Some(&current_content),
+                            &updated,
+                        ),
+                        Err(_) => Ok(Vec::new()),
+                    };
+
+                match syntax_check
+                    .map_err(|message| anyhow::Error::new(FileUpdaterError::Other(message)))
+                    .and_then(|syntax_errors| {
+                        explorer
+                            .apply_replacements(&full_path, replacements)
+                            .map(|new_content| (new_content, syntax_errors))
+                    }) {
+                    Ok((new_content, syntax_errors)) => ToolResult::ReplaceInFile {
                         project: project.clone(),
                         path: path.clone(),
+                        diagnostics: lsp::sync_file(project, path, &new_content).await,
+                        syntax_errors,
                         content: new_content,
                         error: None,
                     },
                     Err(e) => {
                         // Extract FileUpdaterError if present or create Other variant
-                        use crate::utils::FileUpdaterError;
                         let error = if let Some(file_err) = e.downcast_ref::<FileUpdaterError>() {
                             file_err.clone()
                         } else {
@@ -434,12 +1631,150 @@ impl ToolExecutor {
                             project: project.clone(),
                             path: path.clone(),
                             content: current_content,
+                            diagnostics: Vec::new(),
+                            syntax_errors: Vec::new(),
                             error: Some(error),
                         }
                     }
                 }
             }
 
+            Tool::EditLines {
+                project,
+                path,
+                edit,
+            } => {
+                if let Some(error) = check_absolute_path(path) {
+                    return Ok((String::new(), error));
+                }
+                let result: Result<_> = async {
+                    let explorer = project_manager.get_explorer_for_project(project)?;
+                    let full_path = explorer.root_dir().join(path);
+                    let original = explorer.read_file(&full_path)?;
+                    let updated = apply_line_edit(&original, edit)?;
+                    let syntax_errors = check_edit_syntax(
+                        project_manager.as_ref(),
+                        project,
+                        path,
+                        Some(&original),
+                        &updated,
+                    )
+                    .map_err(|message| anyhow::anyhow!(message))?;
+                    explorer.write_file(&full_path, &updated, false)?;
+                    Ok((updated, syntax_errors))
+                }
+                .await;
+                match result {
+                    Ok((content, syntax_errors)) => ToolResult::EditLines {
+                        project: project.clone(),
+                        path: path.clone(),
+                        diagnostics: lsp::sync_file(project, path, &content).await,
+                        syntax_errors,
+                        content,
+                        error: None,
+                    },
+                    Err(e) => ToolResult::EditLines {
+                        project: project.clone(),
+                        path: path.clone(),
+                        content: String::new(),
+                        diagnostics: Vec::new(),
+                        syntax_errors: Vec::new(),
+                        error: Some(e.to_string()),
+                    },
+                }
+            }
+
+            Tool::EditFiles { project, edits } => {
+                if let Some(error) = edits.iter().find_map(|e| check_absolute_path(&e.path)) {
+                    return Ok((String::new(), error));
+                }
+                match apply_edit_transaction(project_manager.as_ref(), project, edits) {
+                    Ok((files, diff, syntax_errors)) => {
+                        // Let a running language server check the new contents
+                        let mut diagnostics = Vec::new();
+                        for (path, content) in &files {
+                            diagnostics.extend(lsp::sync_file(project, path, content).await);
+                        }
+                        ToolResult::EditFiles {
+                            project: project.clone(),
+                            files,
+                            diff,
+                            diagnostics,
+                            syntax_errors,
+                            error: None,
+                        }
+                    }
+                    Err(e) => ToolResult::EditFiles {
+                        project: project.clone(),
+                        files: Vec::new(),
+                        diff: String::new(),
+                        diagnostics: Vec::new(),
+                        syntax_errors: Vec::new(),
+                        error: Some(e),
+                    },
+                }
+            }
+
+            Tool::ReplaceAcrossFiles {
+                project,
+                pattern,
+                replacement,
+                regex,
+                whole_word,
+                ignore_case,
+                include,
+                exclude,
+                apply,
+            } => {
+                let options = SearchOptions {
+                    query: pattern.clone(),
+                    case_sensitive: !ignore_case,
+                    whole_words: *whole_word,
+                    mode: if *regex {
+                        SearchMode::Regex
+                    } else {
+                        SearchMode::Exact
+                    },
+                    max_results: None,
+                };
+                match replace_across_files(
+                    project_manager.as_ref(),
+                    project,
+                    options,
+                    replacement,
+                    include,
+                    exclude,
+                    *apply,
+                ) {
+                    Ok((sites, files, syntax_errors)) => {
+                        let mut diagnostics = Vec::new();
+                        for (path, content) in &files {
+                            diagnostics.extend(lsp::sync_file(project, path, content).await);
+                        }
+                        ToolResult::ReplaceAcrossFiles {
+                            project: project.clone(),
+                            pattern: pattern.clone(),
+                            sites,
+                            applied: *apply,
+                            files,
+                            diagnostics,
+                            syntax_errors,
+                            error: None,
+                        }
+                    }
+                    Err(e) => ToolResult::ReplaceAcrossFiles {
+                        project: project.clone(),
+                        pattern: pattern.clone(),
+                        sites: Vec::new(),
+                        applied: false,
+                        files: Vec::new(),
+                        diagnostics: Vec::new(),
+                        syntax_errors: Vec::new(),
+                        error: Some(e),
+                    },
+                }
+            }
+
             Tool::DeleteFiles { project, paths } => {
                 // Get explorer for the specified project
                 let explorer = match project_manager.get_explorer_for_project(project) {
@@ -1,5 +1,7 @@
-use crate::tools::ToolResultHandler;
+use crate::tools::{fence_untrusted, ToolResultHandler};
 use crate::types::{FileTreeEntry, LoadedResource, ToolResult, WorkingMemory};
+use crate::utils::number_lines;
+use crate::web::format_outline;
 use crate::PathBuf;
 use anyhow::Result;
 use async_trait::async_trait;
@@ -22,6 +24,7 @@ fn format_output_for_result(result: &ToolResult) -> Result<String> {
             project,
             loaded_files,
             failed_files,
+            first_lines,
         } => {
             // Format detailed output with file contents
             let mut output = String::new();
@@ -39,11 +42,16 @@ fn format_output_for_result(result: &ToolResult) -> Result<String> {
                 output.push_str(&format!("Successfully loaded the following file(s):\n"));
                 for (path, content) in loaded_files {
                     //output.push_str(&format!("-----[ {} ]-----\n{}\n", path.display(), content));
-                    output.push_str(&format!(
-                        ">>>>> FILE: {}\n{}\n<<<<< END FILE\n",
-                        path.display(),
-                        content
+                    let content = match first_lines.get(path) {
+                        Some(first_line) => number_lines(content, *first_line),
+                        None => content.clone(),
+                    };
+                    output.push_str(&fence_untrusted(
+                        "FILE",
+                        &path.display().to_string(),
+                        &content,
                     ));
+                    output.push('\n');
                 }
             }
             Ok(output)
@@ -69,13 +77,41 @@ fn format_output_for_result(result: &ToolResult) -> Result<String> {
             if let Some(e) = error {
                 output.push_str(&format!("Failed to fetch page: {}", e));
             } else {
-                output.push_str(&format!(
-                    "Page fetched successfully:\n>>>>> CONTENT:\n{}\n<<<<< END CONTENT",
-                    page.content
-                ));
+                output.push_str("Page fetched successfully");
+                if let Some(position) = page.position() {
+                    output.push_str(&format!(", showing {}", position));
+                }
+                output.push_str(":\n");
+                if !page.outline.is_empty() {
+                    output.push_str(&format!(
+                        "Outline (use the section parameter to read a section):\n{}\n",
+                        format_outline(&page.outline)
+                    ));
+                }
+                output.push_str(&fence_untrusted("CONTENT", &page.url, &page.content));
+                if page.page < page.total_pages {
+                    output.push_str(&format!(
+                        "\nThere is more content, continue with page {}.",
+                        page.page + 1
+                    ));
+                }
             }
             Ok(output)
         }
+        ToolResult::CrawlDocs {
+            url,
+            crawl: Some(crawl),
+            error: None,
+        } => Ok(fence_untrusted(
+            "CONTENT",
+            url,
+            crawl.to_string().trim_end(),
+        )),
+        ToolResult::RepoMap {
+            map: Some(map),
+            error: None,
+            ..
+        } => Ok(format!("{}\n{}", result.format_message(), map.content)),
         // All other tools use standard message
         _ => Ok(result.format_message()),
     }
@@ -130,6 +166,7 @@ fn update_working_memory(working_memory: &mut WorkingMemory, result: &ToolResult
             ToolResult::ReadFiles {
                 project,
                 loaded_files,
+                first_lines,
                 ..
             } => {
                 for (path, content) in loaded_files {
@@ -138,8 +175,61 @@ fn update_working_memory(working_memory: &mut WorkingMemory, result: &ToolResult
                         path.clone(),
                         LoadedResource::File(content.clone()),
                     );
+                    // Reading a file again decides whether it is shown with line numbers
+                    let key = (project.clone(), path.clone());
+                    match first_lines.get(path) {
+                        Some(first_line) => {
+                            working_memory.numbered_resources.insert(key, *first_line);
+                        }
+                        None => {
+                            working_memory.numbered_resources.remove(&key);
+                        }
+                    }
                 }
             }
+            ToolResult::ViewImage {
+                project,
+                path,
+                image: Some(image),
+                ..
+            } => {
+                working_memory.add_resource(
+                    project.clone(),
+                    path.clone(),
+                    LoadedResource::Image(image.clone()),
+                );
+            }
+            ToolResult::Browser {
+                state: Some(state), ..
+            } => {
+                // Only the latest screenshot is kept
+                if let Some(screenshot) = &state.screenshot {
+                    working_memory.add_resource(
+                        "browser".to_string(),
+                        PathBuf::from("screenshot"),
+                        LoadedResource::Image(screenshot.clone()),
+                    );
+                }
+            }
+            ToolResult::EditLines {
+                project,
+                path,
+                content,
+                diagnostics,
+                syntax_errors,
+                error: None,
+            } => {
+                working_memory.update_resource(
+                    &project,
+                    path,
+                    LoadedResource::File(content.clone()),
+                );
+                working_memory.set_file_diagnostics(
+                    project,
+                    path,
+                    &[syntax_errors.as_slice(), diagnostics.as_slice()].concat(),
+                );
+            }
             ToolResult::WebSearch {
                 results,
                 query,
@@ -174,6 +264,19 @@ fn update_working_memory(working_memory: &mut WorkingMemory, result: &ToolResult
                     .loaded_resources
                     .insert((project, path), LoadedResource::WebPage(page.clone()));
             }
+            ToolResult::CrawlDocs {
+                url,
+                crawl: Some(crawl),
+                error: None,
+            } => {
+                // A crawl replaces earlier crawls from the same URL
+                let path =
+                    PathBuf::from(format!("crawl-{}", url.replace([':', '/', '?', '#'], "_")));
+                working_memory.loaded_resources.insert(
+                    ("web".to_string(), path),
+                    LoadedResource::DocsCrawl(crawl.clone()),
+                );
+            }
             ToolResult::Summarize { project, path, summary } => {
                 // Remove from loaded resources
                 working_memory
@@ -189,6 +292,8 @@ fn update_working_memory(working_memory: &mut WorkingMemory, result: &ToolResult
                 project,
                 path,
                 content,
+                diagnostics,
+                syntax_errors,
                 ..
             } => {
                 // Update working memory if file was loaded
@@ -197,13 +302,51 @@ fn update_working_memory(working_memory: &mut WorkingMemory, result: &ToolResult
                     path,
                     LoadedResource::File(content.clone()),
                 );
+                working_memory.set_file_diagnostics(
+                    project,
+                    path,
+                    &[syntax_errors.as_slice(), diagnostics.as_slice()].concat(),
+                );
+            }
+            ToolResult::EditFiles {
+                project,
+                files,
+                diagnostics,
+                syntax_errors,
+                error: None,
+                ..
+            }
+            | ToolResult::ReplaceAcrossFiles {
+                project,
+                files,
+                diagnostics,
+                syntax_errors,
+                error: None,
+                ..
+            } => {
+                for (path, content) in files {
+                    working_memory.update_resource(
+                        &project,
+                        path,
+                        LoadedResource::File(content.clone()),
+                    );
+                    // Diagnostics of all files are reported together
+                    let file_diagnostics: Vec<_> = syntax_errors
+                        .iter()
+                        .chain(diagnostics.iter())
+                        .filter(|d| d.path == *path)
+                        .cloned()
+                        .collect();
+                    working_memory.set_file_diagnostics(project, path, &file_diagnostics);
+                }
             }
             ToolResult::WriteFile {
                 project,
                 path,
                 content,
+                diagnostics,
+                syntax_errors,
                 error: None,
-                ..
             } => {
                 // Remove any existing summary since file is new/overwritten
                 working_memory
@@ -216,6 +359,11 @@ fn update_working_memory(working_memory: &mut WorkingMemory, result: &ToolResult
                     path.clone(),
                     LoadedResource::File(content.clone()),
                 );
+                working_memory.set_file_diagnostics(
+                    project,
+                    path,
+                    &[syntax_errors.as_slice(), diagnostics.as_slice()].concat(),
+                );
             }
             ToolResult::DeleteFiles {
                 project, deleted, ..
@@ -229,6 +377,49 @@ fn update_working_memory(working_memory: &mut WorkingMemory, result: &ToolResult
                         .remove(&(project.clone(), path.clone()));
                 }
             }
+            ToolResult::StartProcess {
+                process: Some(process),
+                ..
+            } => {
+                working_memory
+                    .background_processes
+                    .insert(process.id.clone(), process.clone());
+            }
+            ToolResult::ReadProcessOutput {
+                process: Some(process),
+                ..
+            } => {
+                working_memory
+                    .background_processes
+                    .insert(process.id.clone(), process.clone());
+            }
+            ToolResult::StopProcess { process_id, .. } => {
+                working_memory.background_processes.remove(process_id);
+            }
+            ToolResult::CheckProject {
+                project,
+                diagnostics,
+                error: None,
+                ..
+            } => {
+                // Replace the results of the previous check
+                if diagnostics.is_empty() {
+                    working_memory.diagnostics.remove(project);
+                } else {
+                    working_memory
+                        .diagnostics
+                        .insert(project.clone(), diagnostics.clone());
+                }
+            }
+            ToolResult::RepoMap {
+                project,
+                map: Some(map),
+                ..
+            } => {
+                working_memory
+                    .repo_maps
+                    .insert(project.clone(), map.content.clone());
+            }
             _ => {}
         }
     }
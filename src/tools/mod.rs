@@ -6,10 +6,13 @@ mod executor;
 mod handlers;
 mod parse;
 mod result;
+mod untrusted;
 
+pub use definitions::WEB_TOOLS;
 pub use executor::ToolExecutor;
 pub use handlers::{AgentChatToolHandler, AgentToolHandler, MCPToolHandler};
 pub use parse::{parse_tool_json, parse_tool_xml, TOOL_TAG_PREFIX};
+pub use untrusted::{fence_untrusted, untrusted_marker};
 
 #[async_trait::async_trait]
 pub trait ToolResultHandler: Send + Sync {
@@ -1,4 +1,6 @@
-use crate::types::{FileReplacement, Tool, ToolError};
+use crate::types::{FileEdit, FileReplacement, LineEdit, LineOperation, Tool, ToolError};
+use crate::ui::AnswerType;
+use crate::web::{BrowserAction, RequestBody};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use tracing::trace;
@@ -6,17 +8,42 @@ use tracing::trace;
 pub const TOOL_TAG_PREFIX: &str = "tool:";
 const PARAM_TAG_PREFIX: &str = "param:";
 
-/// Represents a parsed path with optional line ranges
+/// Represents a parsed path with optional line ranges or a symbol
 #[derive(Debug, Clone)]
 pub struct PathWithLineRange {
     pub path: PathBuf,
     pub start_line: Option<usize>,
     pub end_line: Option<usize>,
+    /// Symbol like `Type::method` whose definition should be read
+    pub symbol: Option<String>,
 }
 
 impl PathWithLineRange {
     /// Parse a path string that may contain line ranges like "file.txt:10-20"
+    /// or a symbol like "file.rs#Type::method"
     pub fn parse(path_str: &str) -> Result<Self, ToolError> {
+        // Only a '#' in the file name followed by an identifier path starts a
+        // symbol, other file names like "notes#1.md" may contain one too
+        let file_name_start = path_str.rfind(['/', '\\']).map_or(0, |i| i + 1);
+        if let Some(hash_pos) = path_str[file_name_start..].rfind('#') {
+            let (file_path, symbol) = path_str.split_at(file_name_start + hash_pos);
+            let symbol = symbol[1..].trim();
+            if symbol.is_empty() {
+                return Err(ToolError::ParseError(format!(
+                    "Missing symbol name after '#' in {}",
+                    path_str
+                )));
+            }
+            if is_symbol_path(symbol) && file_path.len() > file_name_start {
+                return Ok(Self {
+                    path: PathBuf::from(file_path),
+                    start_line: None,
+                    end_line: None,
+                    symbol: Some(symbol.to_string()),
+                });
+            }
+        }
+
         // Check if the path contains a colon (not part of Windows drive letter)
         if let Some(colon_pos) = path_str.rfind(':') {
             // Skip Windows drive letter (e.g., C:)
@@ -33,6 +60,7 @@ impl PathWithLineRange {
                         path: PathBuf::from(path_str),
                         start_line: None,
                         end_line: None,
+                        symbol: None,
                     });
                 }
 
@@ -61,6 +89,7 @@ impl PathWithLineRange {
                         path: PathBuf::from(file_path),
                         start_line,
                         end_line,
+                        symbol: None,
                     });
                 } else {
                     // Single line: file.txt:15
@@ -72,6 +101,7 @@ impl PathWithLineRange {
                         path: PathBuf::from(file_path),
                         start_line: Some(line_num),
                         end_line: Some(line_num),
+                        symbol: None,
                     });
                 }
             }
@@ -82,10 +112,19 @@ impl PathWithLineRange {
             path: PathBuf::from(path_str),
             start_line: None,
             end_line: None,
+            symbol: None,
         })
     }
 }
 
+/// Whether `text` looks like a symbol such as `Type::method`
+fn is_symbol_path(text: &str) -> bool {
+    text.split("::").all(|part| {
+        part.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && part.chars().all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
 // Helper function to parse JSON arrays containing paths with optional line ranges
 fn parse_path_array(arr: &serde_json::Value, param_name: &str) -> Result<Vec<PathBuf>, ToolError> {
     arr.as_array()
@@ -245,6 +284,163 @@ fn get_optional_param<'a>(
     params.get(key).and_then(|v| v.first())
 }
 
+fn parse_line_operation(value: &str) -> Result<LineOperation, ToolError> {
+    match value.trim() {
+        "insert_after" => Ok(LineOperation::InsertAfter),
+        "insert_before" => Ok(LineOperation::InsertBefore),
+        "delete_range" => Ok(LineOperation::DeleteRange),
+        "replace_range" => Ok(LineOperation::ReplaceRange),
+        other => Err(ToolError::ParseError(format!(
+            "Invalid operation '{}', expected insert_after, insert_before, delete_range or replace_range",
+            other
+        ))),
+    }
+}
+
+// Choices without an explicit answer type mean exactly one should be picked
+fn parse_ask_user(
+    question: String,
+    answer_type: Option<&str>,
+    choices: Vec<String>,
+) -> Result<Tool, ToolError> {
+    let answer_type = match answer_type.map(str::trim) {
+        None | Some("") if choices.is_empty() => AnswerType::Text,
+        None | Some("") => AnswerType::PickOne,
+        Some("text") => AnswerType::Text,
+        Some("yes_no") => AnswerType::YesNo,
+        Some("pick_one") => AnswerType::PickOne,
+        Some("pick_many") => AnswerType::PickMany,
+        Some(other) => {
+            return Err(ToolError::ParseError(format!(
+                "Invalid answer_type '{}', expected text, yes_no, pick_one or pick_many",
+                other
+            )))
+        }
+    };
+    if matches!(answer_type, AnswerType::PickOne | AnswerType::PickMany) && choices.is_empty() {
+        return Err(ToolError::ParseError(
+            "At least one choice is required for pick_one and pick_many".into(),
+        ));
+    }
+    Ok(Tool::AskUser {
+        question,
+        answer_type,
+        choices,
+    })
+}
+
+// Only the parameters of the action are required
+fn parse_browser(
+    project: Option<&str>,
+    action: &str,
+    url: Option<&str>,
+    selector: Option<&str>,
+    text: Option<&str>,
+    timeout_secs: Option<u64>,
+) -> Result<Tool, ToolError> {
+    let action = action.trim();
+    let required = |value: Option<&str>, name: &str| {
+        value.map(str::to_string).ok_or_else(|| {
+            ToolError::ParseError(format!(
+                "Missing required parameter for the {} action: {}",
+                action, name
+            ))
+        })
+    };
+    let action = match action {
+        "open" => BrowserAction::Open {
+            url: required(url, "url")?.trim().to_string(),
+        },
+        "click" => BrowserAction::Click {
+            selector: required(selector, "selector")?.trim().to_string(),
+        },
+        "type" => BrowserAction::Type {
+            selector: required(selector, "selector")?.trim().to_string(),
+            text: required(text, "text")?,
+        },
+        "wait_for" => BrowserAction::WaitFor {
+            selector: required(selector, "selector")?.trim().to_string(),
+            timeout_secs,
+        },
+        "console" => BrowserAction::Console,
+        "screenshot" => BrowserAction::Screenshot,
+        other => {
+            return Err(ToolError::ParseError(format!(
+                "Invalid action '{}', expected open, click, type, wait_for, console or screenshot",
+                other
+            )))
+        }
+    };
+    Ok(Tool::Browser {
+        project: project.map(|project| project.trim().to_string()),
+        action,
+    })
+}
+
+// Repeated parameters like headers in the form "name: value"
+fn parse_pairs(
+    values: Option<&Vec<String>>,
+    separator: char,
+    param: &str,
+) -> Result<Vec<(String, String)>, ToolError> {
+    values
+        .into_iter()
+        .flatten()
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| {
+            let (name, value) = value.split_once(separator).ok_or_else(|| {
+                ToolError::ParseError(format!(
+                    "Invalid {} parameter '{}', expected name{}value",
+                    param,
+                    value.trim(),
+                    separator
+                ))
+            })?;
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+// Object values as pairs, e.g. {"page": 2} as ("page", "2")
+fn json_pairs(value: &serde_json::Value) -> Vec<(String, String)> {
+    value
+        .as_object()
+        .map(|object| {
+            object
+                .iter()
+                .map(|(name, value)| {
+                    let value = match value {
+                        serde_json::Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    (name.clone(), value)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn parse_request_body(
+    json: Option<serde_json::Value>,
+    text: Option<String>,
+) -> Result<Option<RequestBody>, ToolError> {
+    match (json, text) {
+        (Some(_), Some(_)) => Err(ToolError::ParseError(
+            "Use either the json or the body parameter, not both".into(),
+        )),
+        (Some(json), None) => Ok(Some(RequestBody::Json(json))),
+        (None, Some(text)) => Ok(Some(RequestBody::Text(text))),
+        (None, None) => Ok(None),
+    }
+}
+
+fn parse_line_param(params: &HashMap<String, Vec<String>>) -> Result<usize, ToolError> {
+    get_required_param(params, "line")?
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| ToolError::ParseError("Invalid line parameter".into()))
+}
+
 pub fn parse_tool_from_params(
     tool_name: &str,
     params: &HashMap<String, Vec<String>>,
@@ -259,6 +455,15 @@ pub fn parse_tool_from_params(
             regex: get_required_param(params, "regex")?.clone(),
         }),
 
+        "find_files" => Ok(Tool::FindFiles {
+            project: get_required_param(params, "project")?.clone(),
+            pattern: get_required_param(params, "pattern")?.trim().to_string(),
+            max_results: get_optional_param(params, "max_results")
+                .map(|v| v.trim().parse::<usize>())
+                .transpose()
+                .map_err(|_| ToolError::ParseError("Invalid max_results parameter".into()))?,
+        }),
+
         "list_files" => Ok(Tool::ListFiles {
             project: get_required_param(params, "project")?.clone(),
             paths: params
@@ -284,6 +489,13 @@ pub fn parse_tool_from_params(
                     Ok(PathBuf::from(s.trim()))
                 })
                 .collect::<Result<Vec<PathBuf>, ToolError>>()?,
+            line_numbers: get_optional_param(params, "line_numbers")
+                .is_some_and(|s| s.trim() == "true"),
+        }),
+
+        "view_image" => Ok(Tool::ViewImage {
+            project: get_required_param(params, "project")?.clone(),
+            path: PathBuf::from(get_required_param(params, "path")?.trim()),
         }),
 
         "summarize" => Ok(Tool::Summarize {
@@ -298,6 +510,83 @@ pub fn parse_tool_from_params(
             replacements: parse_search_replace_blocks(get_required_param(params, "diff")?)?,
         }),
 
+        "edit_files" => {
+            let paths = params
+                .get("path")
+                .ok_or_else(|| ToolError::ParseError("Missing required parameter: path".into()))?;
+            let diffs = params
+                .get("diff")
+                .ok_or_else(|| ToolError::ParseError("Missing required parameter: diff".into()))?;
+            // Each path is paired with the diff following it
+            if paths.len() != diffs.len() {
+                return Err(ToolError::ParseError(
+                    "Each path parameter needs exactly one diff parameter".into(),
+                ));
+            }
+            Ok(Tool::EditFiles {
+                project: get_required_param(params, "project")?.clone(),
+                edits: paths
+                    .iter()
+                    .zip(diffs)
+                    .map(|(path, diff)| {
+                        Ok(FileEdit {
+                            path: PathBuf::from(path.trim()),
+                            replacements: parse_search_replace_blocks(diff)?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, ToolError>>()?,
+            })
+        }
+
+        "edit_lines" => {
+            let line_param = |key: &str| {
+                get_optional_param(params, key)
+                    .map(|v| v.trim().parse::<usize>())
+                    .transpose()
+                    .map_err(|_| ToolError::ParseError(format!("Invalid {} parameter", key)))
+            };
+            // Content usually starts on the line after the opening tag
+            let text_param = |key: &str| {
+                get_optional_param(params, key)
+                    .map(|s| s.strip_prefix('\n').unwrap_or(s).to_string())
+            };
+            Ok(Tool::EditLines {
+                project: get_required_param(params, "project")?.clone(),
+                path: PathBuf::from(get_required_param(params, "path")?.trim()),
+                edit: LineEdit {
+                    operation: parse_line_operation(get_required_param(params, "operation")?)?,
+                    start_line: line_param("start_line")?.ok_or_else(|| {
+                        ToolError::ParseError("Missing required parameter: start_line".into())
+                    })?,
+                    end_line: line_param("end_line")?,
+                    content: text_param("content").unwrap_or_default(),
+                    expected: text_param("expected"),
+                },
+            })
+        }
+
+        "replace_across_files" => {
+            let flag =
+                |key: &str| get_optional_param(params, key).is_some_and(|s| s.trim() == "true");
+            let globs = |key: &str| {
+                params
+                    .get(key)
+                    .map(|v| v.iter().map(|s| s.trim().to_string()).collect())
+                    .unwrap_or_default()
+            };
+            Ok(Tool::ReplaceAcrossFiles {
+                project: get_required_param(params, "project")?.clone(),
+                pattern: get_required_param(params, "pattern")?.clone(),
+                replacement: get_required_param(params, "replacement")?.clone(),
+                regex: flag("regex"),
+                whole_word: flag("whole_word"),
+                ignore_case: flag("ignore_case"),
+                include: globs("include"),
+                exclude: globs("exclude"),
+                apply: flag("apply"),
+            })
+        }
+
         "write_file" => Ok(Tool::WriteFile {
             project: get_required_param(params, "project")?.clone(),
             path: PathBuf::from(get_required_param(params, "path")?),
@@ -319,13 +608,135 @@ pub fn parse_tool_from_params(
             message: get_required_param(params, "message")?.clone(),
         }),
 
+        "ask_user" => parse_ask_user(
+            get_required_param(params, "question")?.trim().to_string(),
+            get_optional_param(params, "answer_type").map(String::as_str),
+            params
+                .get("choice")
+                .map(|choices| {
+                    choices
+                        .iter()
+                        .map(|choice| choice.trim().to_string())
+                        .filter(|choice| !choice.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        ),
+
         "execute_command" => Ok(Tool::ExecuteCommand {
             command_line: get_required_param(params, "command_line")?.clone(),
             working_dir: get_optional_param(params, "working_dir").map(PathBuf::from),
             project: get_required_param(params, "project")?.clone(),
+            timeout_secs: get_optional_param(params, "timeout_secs")
+                .map(|v| v.trim().parse::<u64>())
+                .transpose()
+                .map_err(|_| ToolError::ParseError("Invalid timeout_secs parameter".into()))?,
+            persistent_session: get_optional_param(params, "persistent_session")
+                .map(|s| s.trim() == "true"),
+        }),
+
+        "reset_shell_session" => Ok(Tool::ResetShellSession {
+            project: get_required_param(params, "project")?.clone(),
+        }),
+
+        "check_project" => Ok(Tool::CheckProject {
+            project: get_required_param(params, "project")?.clone(),
+            build_system: get_optional_param(params, "build_system").map(|s| s.trim().to_string()),
+        }),
+
+        "run_tests" => Ok(Tool::RunTests {
+            project: get_required_param(params, "project")?.clone(),
+            filter: get_optional_param(params, "filter").map(|s| s.trim().to_string()),
+            runner: get_optional_param(params, "runner").map(|s| s.trim().to_string()),
+        }),
+
+        "goto_definition" => Ok(Tool::GotoDefinition {
+            project: get_required_param(params, "project")?.clone(),
+            path: PathBuf::from(get_required_param(params, "path")?.trim()),
+            line: parse_line_param(params)?,
+            symbol: get_optional_param(params, "symbol").map(|s| s.trim().to_string()),
+            column: get_optional_param(params, "column")
+                .map(|v| v.trim().parse::<usize>())
+                .transpose()
+                .map_err(|_| ToolError::ParseError("Invalid column parameter".into()))?,
+        }),
+
+        "find_references" => Ok(Tool::FindReferences {
+            project: get_required_param(params, "project")?.clone(),
+            path: PathBuf::from(get_required_param(params, "path")?.trim()),
+            line: parse_line_param(params)?,
+            symbol: get_optional_param(params, "symbol").map(|s| s.trim().to_string()),
+            column: get_optional_param(params, "column")
+                .map(|v| v.trim().parse::<usize>())
+                .transpose()
+                .map_err(|_| ToolError::ParseError("Invalid column parameter".into()))?,
+        }),
+
+        "hover" => Ok(Tool::Hover {
+            project: get_required_param(params, "project")?.clone(),
+            path: PathBuf::from(get_required_param(params, "path")?.trim()),
+            line: parse_line_param(params)?,
+            symbol: get_optional_param(params, "symbol").map(|s| s.trim().to_string()),
+            column: get_optional_param(params, "column")
+                .map(|v| v.trim().parse::<usize>())
+                .transpose()
+                .map_err(|_| ToolError::ParseError("Invalid column parameter".into()))?,
+        }),
+
+        "document_symbols" => Ok(Tool::DocumentSymbols {
+            project: get_required_param(params, "project")?.clone(),
+            path: PathBuf::from(get_required_param(params, "path")?.trim()),
+        }),
+
+        "workspace_symbols" => Ok(Tool::WorkspaceSymbols {
+            project: get_required_param(params, "project")?.clone(),
+            query: get_required_param(params, "query")?.trim().to_string(),
+        }),
+
+        "repo_map" => Ok(Tool::RepoMap {
+            project: get_required_param(params, "project")?.clone(),
+            paths: params
+                .get("path")
+                .map(|paths| paths.iter().map(|s| PathBuf::from(s.trim())).collect())
+                .unwrap_or_default(),
+            max_tokens: get_optional_param(params, "max_tokens")
+                .map(|v| v.trim().parse::<usize>())
+                .transpose()
+                .map_err(|_| ToolError::ParseError("Invalid max_tokens parameter".into()))?,
+        }),
+
+        "start_process" => Ok(Tool::StartProcess {
+            project: get_required_param(params, "project")?.clone(),
+            command_line: get_required_param(params, "command_line")?.clone(),
+            working_dir: get_optional_param(params, "working_dir").map(PathBuf::from),
+        }),
+
+        "read_process_output" => Ok(Tool::ReadProcessOutput {
+            project: get_required_param(params, "project")?.clone(),
+            process_id: get_required_param(params, "process_id")?.trim().to_string(),
+            since_offset: get_optional_param(params, "since_offset")
+                .map(|v| v.trim().parse::<usize>())
+                .transpose()
+                .map_err(|_| ToolError::ParseError("Invalid since_offset parameter".into()))?,
+            tail_lines: get_optional_param(params, "tail_lines")
+                .map(|v| v.trim().parse::<usize>())
+                .transpose()
+                .map_err(|_| ToolError::ParseError("Invalid tail_lines parameter".into()))?,
+        }),
+
+        "send_process_input" => Ok(Tool::SendProcessInput {
+            project: get_required_param(params, "project")?.clone(),
+            process_id: get_required_param(params, "process_id")?.trim().to_string(),
+            input: get_required_param(params, "input")?.clone(),
+        }),
+
+        "stop_process" => Ok(Tool::StopProcess {
+            project: get_required_param(params, "project")?.clone(),
+            process_id: get_required_param(params, "process_id")?.trim().to_string(),
         }),
 
         "web_search" => Ok(Tool::WebSearch {
+            project: get_required_param(params, "project")?.clone(),
             query: get_required_param(params, "query")?.clone(),
             hits_page_number: get_required_param(params, "hits_page_number")?
                 .trim()
@@ -334,10 +745,69 @@ pub fn parse_tool_from_params(
         }),
 
         "web_fetch" => Ok(Tool::WebFetch {
+            project: get_required_param(params, "project")?.clone(),
             url: get_required_param(params, "url")?.clone(),
             selectors: params
                 .get("selector")
                 .map(|selectors| selectors.iter().map(|s| s.to_string()).collect()),
+            page: get_optional_param(params, "page")
+                .map(|v| v.trim().parse::<usize>())
+                .transpose()
+                .map_err(|_| ToolError::ParseError("Invalid page parameter".into()))?,
+            section: get_optional_param(params, "section").map(|s| s.trim().to_string()),
+        }),
+
+        "http_request" => Ok(Tool::HttpRequest {
+            method: get_optional_param(params, "method")
+                .map(|method| method.trim().to_uppercase())
+                .unwrap_or_else(|| "GET".to_string()),
+            url: get_required_param(params, "url")?.trim().to_string(),
+            headers: parse_pairs(params.get("header"), ':', "header")?,
+            query: parse_pairs(params.get("query"), '=', "query")?,
+            body: parse_request_body(
+                get_optional_param(params, "json")
+                    .map(|json| serde_json::from_str(json))
+                    .transpose()
+                    .map_err(|e| ToolError::ParseError(format!("Invalid json parameter: {}", e)))?,
+                get_optional_param(params, "body").cloned(),
+            )?,
+            timeout_secs: get_optional_param(params, "timeout_secs")
+                .map(|v| v.trim().parse::<u64>())
+                .transpose()
+                .map_err(|_| ToolError::ParseError("Invalid timeout_secs parameter".into()))?,
+        }),
+
+        "browser" => parse_browser(
+            get_optional_param(params, "project").map(String::as_str),
+            get_required_param(params, "action")?,
+            get_optional_param(params, "url").map(String::as_str),
+            get_optional_param(params, "selector").map(String::as_str),
+            get_optional_param(params, "text").map(String::as_str),
+            get_optional_param(params, "timeout_secs")
+                .map(|v| v.trim().parse::<u64>())
+                .transpose()
+                .map_err(|_| ToolError::ParseError("Invalid timeout_secs parameter".into()))?,
+        ),
+
+        "crawl_docs" => Ok(Tool::CrawlDocs {
+            project: get_required_param(params, "project")?.clone(),
+            url: get_required_param(params, "url")?.trim().to_string(),
+            max_depth: get_optional_param(params, "max_depth")
+                .map(|v| v.trim().parse::<usize>())
+                .transpose()
+                .map_err(|_| ToolError::ParseError("Invalid max_depth parameter".into()))?,
+            max_pages: get_optional_param(params, "max_pages")
+                .map(|v| v.trim().parse::<usize>())
+                .transpose()
+                .map_err(|_| ToolError::ParseError("Invalid max_pages parameter".into()))?,
+            prefix: get_optional_param(params, "prefix").map(|s| s.trim().to_string()),
+        }),
+
+        "dependency_docs" => Ok(Tool::DependencyDocs {
+            project: get_required_param(params, "project")?.clone(),
+            name: get_required_param(params, "name")?.trim().to_string(),
+            version: get_optional_param(params, "version").map(|s| s.trim().to_string()),
+            item: get_optional_param(params, "item").map(|s| s.trim().to_string()),
         }),
 
         "list_projects" => Ok(Tool::ListProjects),
@@ -355,6 +825,19 @@ pub fn parse_tool_json(name: &str, params: &serde_json::Value) -> Result<Tool, T
             .to_string())
     };
 
+    let get_process_id = |p: &serde_json::Value| -> Result<String, ToolError> {
+        Ok(p["process_id"]
+            .as_str()
+            .ok_or_else(|| ToolError::ParseError("Missing required parameter: process_id".into()))?
+            .to_string())
+    };
+
+    let json_path_param = |p: &serde_json::Value| -> Result<PathBuf, ToolError> {
+        Ok(PathBuf::from(p["path"].as_str().ok_or_else(|| {
+            ToolError::ParseError("Missing required parameter: path".into())
+        })?))
+    };
+
     match name {
         "list_projects" => Ok(Tool::ListProjects),
         "update_plan" => Ok(Tool::UpdatePlan {
@@ -375,6 +858,100 @@ pub fn parse_tool_json(name: &str, params: &serde_json::Value) -> Result<Tool, T
                 .get("working_dir")
                 .and_then(|d| d.as_str())
                 .map(PathBuf::from),
+            timeout_secs: params["timeout_secs"].as_u64(),
+            persistent_session: params.get("persistent_session").and_then(|b| b.as_bool()),
+        }),
+        "reset_shell_session" => Ok(Tool::ResetShellSession {
+            project: get_project(params)?,
+        }),
+        "check_project" => Ok(Tool::CheckProject {
+            project: get_project(params)?,
+            build_system: params["build_system"].as_str().map(|s| s.to_string()),
+        }),
+        "run_tests" => Ok(Tool::RunTests {
+            project: get_project(params)?,
+            filter: params["filter"].as_str().map(|s| s.to_string()),
+            runner: params["runner"].as_str().map(|s| s.to_string()),
+        }),
+        "goto_definition" => Ok(Tool::GotoDefinition {
+            project: get_project(params)?,
+            path: json_path_param(params)?,
+            line: params["line"]
+                .as_u64()
+                .ok_or_else(|| ToolError::ParseError("Missing required parameter: line".into()))?
+                as usize,
+            symbol: params["symbol"].as_str().map(|s| s.to_string()),
+            column: params["column"].as_u64().map(|c| c as usize),
+        }),
+        "find_references" => Ok(Tool::FindReferences {
+            project: get_project(params)?,
+            path: json_path_param(params)?,
+            line: params["line"]
+                .as_u64()
+                .ok_or_else(|| ToolError::ParseError("Missing required parameter: line".into()))?
+                as usize,
+            symbol: params["symbol"].as_str().map(|s| s.to_string()),
+            column: params["column"].as_u64().map(|c| c as usize),
+        }),
+        "hover" => Ok(Tool::Hover {
+            project: get_project(params)?,
+            path: json_path_param(params)?,
+            line: params["line"]
+                .as_u64()
+                .ok_or_else(|| ToolError::ParseError("Missing required parameter: line".into()))?
+                as usize,
+            symbol: params["symbol"].as_str().map(|s| s.to_string()),
+            column: params["column"].as_u64().map(|c| c as usize),
+        }),
+        "document_symbols" => Ok(Tool::DocumentSymbols {
+            project: get_project(params)?,
+            path: json_path_param(params)?,
+        }),
+        "workspace_symbols" => Ok(Tool::WorkspaceSymbols {
+            project: get_project(params)?,
+            query: params["query"]
+                .as_str()
+                .ok_or_else(|| ToolError::ParseError("Missing required parameter: query".into()))?
+                .to_string(),
+        }),
+        "repo_map" => Ok(Tool::RepoMap {
+            project: get_project(params)?,
+            paths: match params.get("paths") {
+                Some(paths) => parse_path_array(paths, "paths")?,
+                None => Vec::new(),
+            },
+            max_tokens: params["max_tokens"].as_u64().map(|t| t as usize),
+        }),
+        "start_process" => Ok(Tool::StartProcess {
+            project: get_project(params)?,
+            command_line: params["command_line"]
+                .as_str()
+                .ok_or_else(|| {
+                    ToolError::ParseError("Missing required parameter: command_line".into())
+                })?
+                .to_string(),
+            working_dir: params
+                .get("working_dir")
+                .and_then(|d| d.as_str())
+                .map(PathBuf::from),
+        }),
+        "read_process_output" => Ok(Tool::ReadProcessOutput {
+            project: get_project(params)?,
+            process_id: get_process_id(params)?,
+            since_offset: params["since_offset"].as_u64().map(|n| n as usize),
+            tail_lines: params["tail_lines"].as_u64().map(|n| n as usize),
+        }),
+        "send_process_input" => Ok(Tool::SendProcessInput {
+            project: get_project(params)?,
+            process_id: get_process_id(params)?,
+            input: params["input"]
+                .as_str()
+                .ok_or_else(|| ToolError::ParseError("Missing required parameter: input".into()))?
+                .to_string(),
+        }),
+        "stop_process" => Ok(Tool::StopProcess {
+            project: get_project(params)?,
+            process_id: get_process_id(params)?,
         }),
         "search_files" => Ok(Tool::SearchFiles {
             project: get_project(params)?,
@@ -383,6 +960,14 @@ pub fn parse_tool_json(name: &str, params: &serde_json::Value) -> Result<Tool, T
                 .ok_or_else(|| ToolError::ParseError("Missing required parameter: regex".into()))?
                 .to_string(),
         }),
+        "find_files" => Ok(Tool::FindFiles {
+            project: get_project(params)?,
+            pattern: params["pattern"]
+                .as_str()
+                .ok_or_else(|| ToolError::ParseError("Missing required parameter: pattern".into()))?
+                .to_string(),
+            max_results: params["max_results"].as_u64().map(|n| n as usize),
+        }),
         "list_files" => Ok(Tool::ListFiles {
             project: get_project(params)?,
             paths: parse_path_array(&params["paths"], "paths")?,
@@ -406,6 +991,11 @@ pub fn parse_tool_json(name: &str, params: &serde_json::Value) -> Result<Tool, T
                     Ok(PathBuf::from(path_str))
                 })
                 .collect::<Result<Vec<PathBuf>, ToolError>>()?,
+            line_numbers: params["line_numbers"].as_bool().unwrap_or(false),
+        }),
+        "view_image" => Ok(Tool::ViewImage {
+            project: get_project(params)?,
+            path: json_path_param(params)?,
         }),
         "summarize" => Ok(Tool::Summarize {
             project: get_project(params)?,
@@ -427,6 +1017,75 @@ pub fn parse_tool_json(name: &str, params: &serde_json::Value) -> Result<Tool, T
                 )?)?,
             })
         }
+        "edit_files" => Ok(Tool::EditFiles {
+            project: get_project(params)?,
+            edits: params["edits"]
+                .as_array()
+                .ok_or_else(|| ToolError::ParseError("Missing required parameter: edits".into()))?
+                .iter()
+                .map(|edit| {
+                    let path = edit["path"].as_str().ok_or_else(|| {
+                        ToolError::ParseError("Missing path in edits array".into())
+                    })?;
+                    let diff = edit["diff"].as_str().ok_or_else(|| {
+                        ToolError::ParseError("Missing diff in edits array".into())
+                    })?;
+                    Ok(FileEdit {
+                        path: PathBuf::from(path),
+                        replacements: parse_search_replace_blocks(diff)?,
+                    })
+                })
+                .collect::<Result<Vec<_>, ToolError>>()?,
+        }),
+        "edit_lines" => Ok(Tool::EditLines {
+            project: get_project(params)?,
+            path: json_path_param(params)?,
+            edit: LineEdit {
+                operation: parse_line_operation(params["operation"].as_str().ok_or_else(
+                    || ToolError::ParseError("Missing required parameter: operation".into()),
+                )?)?,
+                start_line: params["start_line"].as_u64().ok_or_else(|| {
+                    ToolError::ParseError("Missing required parameter: start_line".into())
+                })? as usize,
+                end_line: params["end_line"].as_u64().map(|n| n as usize),
+                content: params["content"].as_str().unwrap_or_default().to_string(),
+                expected: params["expected"].as_str().map(|s| s.to_string()),
+            },
+        }),
+        "replace_across_files" => {
+            let flag = |key: &str| params[key].as_bool().unwrap_or(false);
+            let globs = |key: &str| {
+                params[key]
+                    .as_array()
+                    .map(|a| {
+                        a.iter()
+                            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            };
+            Ok(Tool::ReplaceAcrossFiles {
+                project: get_project(params)?,
+                pattern: params["pattern"]
+                    .as_str()
+                    .ok_or_else(|| {
+                        ToolError::ParseError("Missing required parameter: pattern".into())
+                    })?
+                    .to_string(),
+                replacement: params["replacement"]
+                    .as_str()
+                    .ok_or_else(|| {
+                        ToolError::ParseError("Missing required parameter: replacement".into())
+                    })?
+                    .to_string(),
+                regex: flag("regex"),
+                whole_word: flag("whole_word"),
+                ignore_case: flag("ignore_case"),
+                include: globs("include"),
+                exclude: globs("exclude"),
+                apply: flag("apply"),
+            })
+        }
         "write_file" => Ok(Tool::WriteFile {
             project: get_project(params)?,
             path: PathBuf::from(
@@ -453,7 +1112,27 @@ pub fn parse_tool_json(name: &str, params: &serde_json::Value) -> Result<Tool, T
                 .ok_or_else(|| ToolError::ParseError("Missing required parameter: message".into()))?
                 .to_string(),
         }),
+        "ask_user" => parse_ask_user(
+            params["question"]
+                .as_str()
+                .ok_or_else(|| {
+                    ToolError::ParseError("Missing required parameter: question".into())
+                })?
+                .to_string(),
+            params["answer_type"].as_str(),
+            params["choices"]
+                .as_array()
+                .map(|choices| {
+                    choices
+                        .iter()
+                        .filter_map(|choice| choice.as_str())
+                        .map(|choice| choice.to_string())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        ),
         "web_search" => Ok(Tool::WebSearch {
+            project: get_project(params)?,
             query: params["query"]
                 .as_str()
                 .ok_or_else(|| ToolError::ParseError("Missing required parameter: query".into()))?
@@ -463,6 +1142,7 @@ pub fn parse_tool_json(name: &str, params: &serde_json::Value) -> Result<Tool, T
             })? as u32,
         }),
         "web_fetch" => Ok(Tool::WebFetch {
+            project: get_project(params)?,
             url: params["url"]
                 .as_str()
                 .ok_or_else(|| ToolError::ParseError("Missing required parameter: url".into()))?
@@ -472,6 +1152,51 @@ pub fn parse_tool_json(name: &str, params: &serde_json::Value) -> Result<Tool, T
                     .map(|v| v.as_str().unwrap_or_default().to_string())
                     .collect()
             }),
+            page: params["page"].as_u64().map(|n| n as usize),
+            section: params["section"].as_str().map(|s| s.to_string()),
+        }),
+        "http_request" => Ok(Tool::HttpRequest {
+            method: params["method"].as_str().unwrap_or("GET").to_uppercase(),
+            url: params["url"]
+                .as_str()
+                .ok_or_else(|| ToolError::ParseError("Missing required parameter: url".into()))?
+                .to_string(),
+            headers: json_pairs(&params["headers"]),
+            query: json_pairs(&params["query"]),
+            body: parse_request_body(
+                Some(params["json"].clone()).filter(|json| !json.is_null()),
+                params["body"].as_str().map(|s| s.to_string()),
+            )?,
+            timeout_secs: params["timeout_secs"].as_u64(),
+        }),
+        "browser" => parse_browser(
+            params["project"].as_str(),
+            params["action"].as_str().ok_or_else(|| {
+                ToolError::ParseError("Missing required parameter: action".into())
+            })?,
+            params["url"].as_str(),
+            params["selector"].as_str(),
+            params["text"].as_str(),
+            params["timeout_secs"].as_u64(),
+        ),
+        "crawl_docs" => Ok(Tool::CrawlDocs {
+            project: get_project(params)?,
+            url: params["url"]
+                .as_str()
+                .ok_or_else(|| ToolError::ParseError("Missing required parameter: url".into()))?
+                .to_string(),
+            max_depth: params["max_depth"].as_u64().map(|n| n as usize),
+            max_pages: params["max_pages"].as_u64().map(|n| n as usize),
+            prefix: params["prefix"].as_str().map(|s| s.to_string()),
+        }),
+        "dependency_docs" => Ok(Tool::DependencyDocs {
+            project: get_project(params)?,
+            name: params["name"]
+                .as_str()
+                .ok_or_else(|| ToolError::ParseError("Missing required parameter: name".into()))?
+                .to_string(),
+            version: params["version"].as_str().map(|s| s.to_string()),
+            item: params["item"].as_str().map(|s| s.to_string()),
         }),
         _ => Err(ToolError::UnknownTool(name.to_string())),
     }
@@ -479,7 +1204,190 @@ pub fn parse_tool_json(name: &str, params: &serde_json::Value) -> Result<Tool, T
 
 #[cfg(test)]
 mod tests {
-    use super::super::parse::parse_search_replace_blocks;
+    use super::super::parse::{parse_search_replace_blocks, parse_tool_json, PathWithLineRange};
+    use crate::types::Tool;
+    use crate::ui::AnswerType;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_path_with_symbol() {
+        let parsed = PathWithLineRange::parse("src/config.rs#Config::load").unwrap();
+        assert_eq!(parsed.path, PathBuf::from("src/config.rs"));
+        assert_eq!(parsed.symbol.as_deref(), Some("Config::load"));
+        assert_eq!((parsed.start_line, parsed.end_line), (None, None));
+
+        let parsed = PathWithLineRange::parse("src/main.rs:10-20").unwrap();
+        assert_eq!(parsed.path, PathBuf::from("src/main.rs"));
+        assert_eq!((parsed.start_line, parsed.end_line), (Some(10), Some(20)));
+        assert_eq!(parsed.symbol, None);
+
+        // A '#' in a directory name is part of the path
+        let parsed = PathWithLineRange::parse("docs#old/main.rs").unwrap();
+        assert_eq!(parsed.path, PathBuf::from("docs#old/main.rs"));
+        assert_eq!(parsed.symbol, None);
+
+        // File names containing '#' without a symbol after it
+        for path in ["notes#1.md", "docs/C#.md", "C#.md:3"] {
+            let parsed = PathWithLineRange::parse(path).unwrap();
+            assert_eq!(parsed.symbol, None, "{}", path);
+        }
+        assert_eq!(
+            PathWithLineRange::parse("docs/C#.md").unwrap().path,
+            PathBuf::from("docs/C#.md")
+        );
+        let parsed = PathWithLineRange::parse("C#.md:3").unwrap();
+        assert_eq!(parsed.path, PathBuf::from("C#.md"));
+        assert_eq!(parsed.start_line, Some(3));
+        let parsed = PathWithLineRange::parse("Program#1.cs#Main").unwrap();
+        assert_eq!(parsed.path, PathBuf::from("Program#1.cs"));
+        assert_eq!(parsed.symbol.as_deref(), Some("Main"));
+
+        assert!(PathWithLineRange::parse("src/main.rs#").is_err());
+    }
+
+    #[test]
+    fn test_parse_ask_user() {
+        let tool = parse_tool_json(
+            "ask_user",
+            &serde_json::json!({
+                "question": "Which database?",
+                "choices": ["SQLite", "Postgres"]
+            }),
+        )
+        .unwrap();
+        assert!(matches!(
+            tool,
+            Tool::AskUser {
+                answer_type: AnswerType::PickOne,
+                ..
+            }
+        ));
+
+        let tool = parse_tool_json("ask_user", &serde_json::json!({"question": "Why?"})).unwrap();
+        assert!(matches!(
+            tool,
+            Tool::AskUser {
+                answer_type: AnswerType::Text,
+                ..
+            }
+        ));
+
+        // Picking needs something to pick from
+        assert!(parse_tool_json(
+            "ask_user",
+            &serde_json::json!({"question": "Which?", "answer_type": "pick_many"}),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_browser() {
+        let params = HashMap::from([
+            ("action".to_string(), vec!["type".to_string()]),
+            ("selector".to_string(), vec![" #name ".to_string()]),
+            ("text".to_string(), vec!["Ada ".to_string()]),
+        ]);
+        let Tool::Browser { project, action } = parse_tool_from_params("browser", &params).unwrap()
+        else {
+            panic!("Expected a browser action");
+        };
+        assert_eq!(project, None);
+        assert_eq!(
+            action,
+            BrowserAction::Type {
+                selector: "#name".to_string(),
+                text: "Ada ".to_string(),
+            }
+        );
+
+        let tool = parse_tool_json(
+            "browser",
+            &serde_json::json!({
+                "project": "shop",
+                "action": "wait_for",
+                "selector": ".done",
+                "timeout_secs": 5
+            }),
+        )
+        .unwrap();
+        assert!(matches!(
+            tool,
+            Tool::Browser {
+                project: Some(ref project),
+                action: BrowserAction::WaitFor {
+                    timeout_secs: Some(5),
+                    ..
+                }
+            } if project == "shop"
+        ));
+
+        let error =
+            parse_tool_json("browser", &serde_json::json!({"action": "click"})).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to parse tool parameters: Missing required parameter for the click action: selector"
+        );
+        assert!(parse_tool_json("browser", &serde_json::json!({"action": "scroll"})).is_err());
+    }
+
+    #[test]
+    fn test_parse_http_request() {
+        let params = HashMap::from([
+            ("method".to_string(), vec!["post".to_string()]),
+            (
+                "url".to_string(),
+                vec!["http://localhost:3000/items".to_string()],
+            ),
+            (
+                "header".to_string(),
+                vec!["Authorization: Bearer a:b".to_string()],
+            ),
+            ("query".to_string(), vec!["dry_run=true".to_string()]),
+            ("json".to_string(), vec![r#"{"name": "x"}"#.to_string()]),
+        ]);
+        let Tool::HttpRequest {
+            method,
+            headers,
+            query,
+            body,
+            ..
+        } = parse_tool_from_params("http_request", &params).unwrap()
+        else {
+            panic!("Expected an http_request");
+        };
+        assert_eq!(method, "POST");
+        assert_eq!(
+            headers,
+            [("Authorization".to_string(), "Bearer a:b".to_string())]
+        );
+        assert_eq!(query, [("dry_run".to_string(), "true".to_string())]);
+        assert_eq!(
+            body,
+            Some(RequestBody::Json(serde_json::json!({"name": "x"})))
+        );
+
+        let tool = parse_tool_json(
+            "http_request",
+            &serde_json::json!({
+                "url": "http://localhost:3000/items",
+                "query": {"page": 2},
+                "body": "plain"
+            }),
+        )
+        .unwrap();
+        assert!(matches!(
+            tool,
+            Tool::HttpRequest { method, query, body: Some(RequestBody::Text(_)), .. }
+                if method == "GET" && query == [("page".to_string(), "2".to_string())]
+        ));
+
+        // A body is either JSON or text
+        assert!(parse_tool_json(
+            "http_request",
+            &serde_json::json!({"url": "http://localhost", "json": {}, "body": "x"}),
+        )
+        .is_err());
+    }
 
     #[test]
     fn test_parse_search_replace_blocks_normal() {
@@ -1,4 +1,17 @@
-use crate::types::ToolResult;
+use super::fence_untrusted;
+use crate::types::{CodeSymbol, Diagnostic, DiagnosticSeverity, ImageData, TestReport, ToolResult};
+
+// Diagnostics beyond this are only counted
+const MAX_LISTED_DIAGNOSTICS: usize = 50;
+// Locations and symbols beyond this are only counted
+const MAX_LISTED_LOCATIONS: usize = 100;
+// Change sites of replace_across_files listed in the result
+const MAX_LISTED_REPLACEMENTS: usize = 100;
+// Failed tests beyond this are only counted
+const MAX_LISTED_FAILURES: usize = 20;
+// Limits for the details of each failed test
+const MAX_FAILURE_MESSAGE_CHARS: usize = 200;
+const MAX_FAILURE_OUTPUT_LINES: usize = 20;
 
 impl ToolResult {
     // Format a user-facing message describing the result
@@ -21,6 +34,9 @@ impl ToolResult {
             ToolResult::AbsolutePathError { path } => {
                 format!("Path must be relative to project root: {}", path.display())
             }
+            ToolResult::NotApproved { tool, reason } => {
+                format!("The user didn't approve {}: {}", tool, reason)
+            }
             ToolResult::ReadFiles {
                 loaded_files,
                 failed_files,
@@ -52,6 +68,18 @@ impl ToolResult {
                 }
                 msg
             }
+            ToolResult::ViewImage {
+                path, image, error, ..
+            } => match (image, error) {
+                (Some(image), None) => {
+                    format!("Successfully loaded image {} ({})", path.display(), image)
+                }
+                (_, error) => format!(
+                    "Failed to load image {}: {}",
+                    path.display(),
+                    error.as_deref().unwrap_or("unknown error")
+                ),
+            },
             ToolResult::ListFiles {
                 expanded_paths,
                 failed_paths,
@@ -83,6 +111,286 @@ impl ToolResult {
                 }
                 msg
             }
+            ToolResult::StartProcess {
+                command_line,
+                process,
+                error,
+                ..
+            } => match (process, error) {
+                (_, Some(e)) => format!("Failed to start '{}': {}", command_line, e),
+                (Some(process), None) => format!("Started background process {}", process),
+                (None, None) => format!("Started '{}'", command_line),
+            },
+            ToolResult::ReadProcessOutput {
+                process_id,
+                output,
+                next_offset,
+                truncated,
+                process,
+                error,
+                ..
+            } => {
+                if let Some(e) = error {
+                    format!("Failed to read output of process {}: {}", process_id, e)
+                } else {
+                    let mut msg = match process {
+                        Some(process) => format!("Output of {}", process),
+                        None => format!("Output of process {}", process_id),
+                    };
+                    msg.push_str(&format!(", next offset {}:\n", next_offset));
+                    if *truncated {
+                        msg.push_str("[earlier output omitted]\n");
+                    }
+                    if output.is_empty() {
+                        msg.push_str("(no new output)\n");
+                    } else {
+                        msg.push_str(output);
+                        if !output.ends_with('\n') {
+                            msg.push('\n');
+                        }
+                    }
+                    msg
+                }
+            }
+            ToolResult::SendProcessInput {
+                process_id, error, ..
+            } => match error {
+                Some(e) => format!("Failed to send input to process {}: {}", process_id, e),
+                None => format!("Input sent to process {}", process_id),
+            },
+            ToolResult::StopProcess {
+                process_id,
+                process,
+                error,
+                ..
+            } => match (process, error) {
+                (_, Some(e)) => format!("Failed to stop process {}: {}", process_id, e),
+                (Some(process), None) => format!("Stopped process {}", process),
+                (None, None) => format!("Stopped process {}", process_id),
+            },
+            ToolResult::CheckProject {
+                build_system,
+                success,
+                diagnostics,
+                output,
+                error,
+                ..
+            } => {
+                if let Some(e) = error {
+                    return format!("Failed to check project: {}", e);
+                }
+                let count = |severity| {
+                    diagnostics
+                        .iter()
+                        .filter(|d| d.severity == severity)
+                        .count()
+                };
+                let (errors, warnings) = (
+                    count(DiagnosticSeverity::Error),
+                    count(DiagnosticSeverity::Warning),
+                );
+                let mut msg = format!(
+                    "{} check {}: {} error(s), {} warning(s)\n",
+                    build_system,
+                    if *success { "passed" } else { "failed" },
+                    errors,
+                    warnings
+                );
+                for diagnostic in diagnostics.iter().take(MAX_LISTED_DIAGNOSTICS) {
+                    msg.push_str(&format!("{}\n", diagnostic));
+                }
+                if diagnostics.len() > MAX_LISTED_DIAGNOSTICS {
+                    msg.push_str(&format!(
+                        "... and {} more\n",
+                        diagnostics.len() - MAX_LISTED_DIAGNOSTICS
+                    ));
+                }
+                if let Some(output) = output {
+                    msg.push_str("No diagnostics found, output:\n");
+                    msg.push_str(output);
+                }
+                msg
+            }
+            ToolResult::RunTests {
+                runner,
+                success,
+                report,
+                output,
+                error,
+                ..
+            } => {
+                if let Some(e) = error {
+                    return format!("Failed to run tests: {}", e);
+                }
+                let mut msg = format!(
+                    "{} tests {}: {} passed, {} failed, {} ignored\n",
+                    runner,
+                    if *success { "passed" } else { "failed" },
+                    report.passed,
+                    report.failed,
+                    report.ignored
+                );
+                msg.push_str(&format_test_failures(report));
+                if let Some(output) = output {
+                    msg.push_str("No test failures found, output:\n");
+                    msg.push_str(output);
+                }
+                msg
+            }
+            ToolResult::GotoDefinition {
+                path,
+                line,
+                locations,
+                error,
+                ..
+            } => {
+                if let Some(e) = error {
+                    return format!("Failed to find definition: {}", e);
+                }
+                if locations.is_empty() {
+                    return format!("No definition found for {}:{}", path.display(), line);
+                }
+                let mut msg = String::from("Definition:\n");
+                for location in locations.iter().take(MAX_LISTED_LOCATIONS) {
+                    msg.push_str(&format!("{}\n", location));
+                }
+                msg
+            }
+            ToolResult::FindReferences {
+                path,
+                line,
+                locations,
+                error,
+                ..
+            } => {
+                if let Some(e) = error {
+                    return format!("Failed to find references: {}", e);
+                }
+                if locations.is_empty() {
+                    return format!("No references found for {}:{}", path.display(), line);
+                }
+                let mut msg = format!("Found {} reference(s):\n", locations.len());
+                for location in locations.iter().take(MAX_LISTED_LOCATIONS) {
+                    msg.push_str(&format!("{}\n", location));
+                }
+                if locations.len() > MAX_LISTED_LOCATIONS {
+                    msg.push_str(&format!(
+                        "... and {} more\n",
+                        locations.len() - MAX_LISTED_LOCATIONS
+                    ));
+                }
+                msg
+            }
+            ToolResult::Hover {
+                path,
+                line,
+                contents,
+                error,
+                ..
+            } => match error {
+                Some(e) => format!("Failed to get hover information: {}", e),
+                None if contents.is_empty() => {
+                    format!("No information available for {}:{}", path.display(), line)
+                }
+                None => contents.clone(),
+            },
+            ToolResult::DocumentSymbols {
+                path,
+                symbols,
+                error,
+                ..
+            } => {
+                if let Some(e) = error {
+                    return format!("Failed to list symbols: {}", e);
+                }
+                if symbols.is_empty() {
+                    return format!("No symbols found in {}", path.display());
+                }
+                let mut msg = format!("Symbols in {}:\n", path.display());
+                for symbol in symbols {
+                    msg.push_str(&format!(
+                        "{}{}\n",
+                        "  ".repeat(symbol.depth),
+                        format_symbol(symbol)
+                    ));
+                }
+                msg
+            }
+            ToolResult::WorkspaceSymbols {
+                query,
+                symbols,
+                error,
+                ..
+            } => {
+                if let Some(e) = error {
+                    return format!("Failed to search symbols: {}", e);
+                }
+                if symbols.is_empty() {
+                    return format!("No symbols found matching '{}'", query);
+                }
+                let mut msg = format!("Symbols matching '{}':\n", query);
+                for symbol in symbols.iter().take(MAX_LISTED_LOCATIONS) {
+                    msg.push_str(&format!("{}\n", format_symbol(symbol)));
+                }
+                if symbols.len() > MAX_LISTED_LOCATIONS {
+                    msg.push_str(&format!(
+                        "... and {} more\n",
+                        symbols.len() - MAX_LISTED_LOCATIONS
+                    ));
+                }
+                msg
+            }
+            ToolResult::RepoMap {
+                project,
+                map,
+                error,
+            } => match (map, error) {
+                (_, Some(e)) => format!("Failed to create repository map: {}", e),
+                (Some(map), None) if map.files_shown > 0 => format!(
+                    "Created repository map of project '{}' with {} of {} source files",
+                    project, map.files_shown, map.files_total
+                ),
+                _ => format!(
+                    "No source files with symbols found in project '{}'",
+                    project
+                ),
+            },
+            ToolResult::ResetShellSession {
+                project,
+                was_running,
+                error,
+            } => match (error, was_running) {
+                (Some(e), _) => format!("Failed to reset shell session: {}", e),
+                (None, true) => format!("Shell session of project {} was reset", project),
+                (None, false) => format!("No shell session was running for project {}", project),
+            },
+            ToolResult::FindFiles {
+                pattern,
+                results,
+                error,
+                ..
+            } => {
+                if let Some(e) = error {
+                    format!("Failed to find files: {}", e)
+                } else if results.is_empty() {
+                    format!("No files found matching '{}'", pattern)
+                } else {
+                    let mut msg = format!("Files matching '{}':\n", pattern);
+                    for result in results {
+                        let modified = result
+                            .modified
+                            .map(|m| format!(", modified {}", m.format("%Y-%m-%d %H:%M")))
+                            .unwrap_or_default();
+                        msg.push_str(&format!(
+                            "- {} ({}{})\n",
+                            result.path.display(),
+                            format_size(result.size),
+                            modified
+                        ));
+                    }
+                    msg
+                }
+            }
             ToolResult::SearchFiles { results, regex, .. } => {
                 if results.is_empty() {
                     format!("No matches found for '{}'", regex)
@@ -109,9 +417,18 @@ impl ToolResult {
                 }
             }
             ToolResult::ExecuteCommand {
-                output, success, ..
+                output,
+                success,
+                timed_out,
+                output_file,
+                ..
             } => {
-                if !success {
+                let mut msg = if *timed_out {
+                    format!(
+                        "Command timed out and was terminated. Output so far:\n{}",
+                        output
+                    )
+                } else if !success {
                     format!("Command failed:\n{}", output)
                 } else {
                     if output.is_empty() {
@@ -119,9 +436,22 @@ impl ToolResult {
                     } else {
                         output.to_string()
                     }
+                };
+                if let Some(path) = output_file {
+                    msg.push_str(&format!(
+                        "\n\nOutput was truncated. The complete output is in {}",
+                        path.display()
+                    ));
                 }
+                msg
             }
-            ToolResult::WriteFile { path, error, .. } => {
+            ToolResult::WriteFile {
+                path,
+                diagnostics,
+                syntax_errors,
+                error,
+                ..
+            } => {
                 if error.is_some() {
                     format!(
                         "Failed to write file {}: {}",
@@ -129,15 +459,132 @@ impl ToolResult {
                         error.as_ref().unwrap()
                     )
                 } else {
-                    format!("Successfully wrote file: {}", path.display())
+                    format!(
+                        "Successfully wrote file: {}{}{}",
+                        path.display(),
+                        format_syntax_errors(syntax_errors),
+                        format_edit_diagnostics(diagnostics)
+                    )
                 }
             }
-            ToolResult::ReplaceInFile { path, error, .. } => {
+            ToolResult::ReplaceInFile {
+                path,
+                diagnostics,
+                syntax_errors,
+                error,
+                ..
+            } => {
                 if let Some(err) = error {
                     format!("Failed to replace in file {}: {}", path.display(), err)
                 } else {
-                    format!("Successfully replaced in file: {}", path.display())
+                    format!(
+                        "Successfully replaced in file: {}{}{}",
+                        path.display(),
+                        format_syntax_errors(syntax_errors),
+                        format_edit_diagnostics(diagnostics)
+                    )
+                }
+            }
+            ToolResult::EditLines {
+                path,
+                diagnostics,
+                syntax_errors,
+                error,
+                ..
+            } => {
+                if let Some(err) = error {
+                    format!("Failed to edit lines of file {}: {}", path.display(), err)
+                } else {
+                    format!(
+                        "Successfully edited lines of file: {}{}{}",
+                        path.display(),
+                        format_syntax_errors(syntax_errors),
+                        format_edit_diagnostics(diagnostics)
+                    )
+                }
+            }
+            ToolResult::EditFiles {
+                files,
+                diff,
+                diagnostics,
+                syntax_errors,
+                error,
+                ..
+            } => {
+                if let Some(err) = error {
+                    format!("Failed to edit files: {}", err)
+                } else {
+                    format!(
+                        "Successfully edited {} files: {}{}{}\n\n```diff\n{}```",
+                        files.len(),
+                        files
+                            .iter()
+                            .map(|(p, _)| p.display().to_string())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        format_syntax_errors(syntax_errors),
+                        format_edit_diagnostics(diagnostics),
+                        diff
+                    )
+                }
+            }
+            ToolResult::ReplaceAcrossFiles {
+                pattern,
+                sites,
+                applied,
+                diagnostics,
+                syntax_errors,
+                error,
+                ..
+            } => {
+                if let Some(err) = error {
+                    return format!("Failed to replace '{}': {}", pattern, err);
+                }
+                if sites.is_empty() {
+                    return format!("No matches found for '{}'", pattern);
+                }
+                let matches: usize = sites.iter().map(|s| s.matches).sum();
+                // Sites are ordered by file
+                let mut changed_files: Vec<_> = sites.iter().map(|s| &s.path).collect();
+                changed_files.dedup();
+                let mut msg = if *applied {
+                    format!(
+                        "Replaced {} matches of '{}' in {} files:\n",
+                        matches,
+                        pattern,
+                        changed_files.len()
+                    )
+                } else {
+                    format!(
+                        "Preview of {} matches of '{}' in {} files, no files were changed:\n",
+                        matches,
+                        pattern,
+                        changed_files.len()
+                    )
+                };
+                for site in sites.iter().take(MAX_LISTED_REPLACEMENTS) {
+                    msg.push_str(&format!(
+                        ">>>>> CHANGE: {}:{}\n",
+                        site.path.display(),
+                        site.line
+                    ));
+                    for line in site.before.lines() {
+                        msg.push_str(&format!("- {}\n", line));
+                    }
+                    for line in site.after.lines() {
+                        msg.push_str(&format!("+ {}\n", line));
+                    }
+                    msg.push_str("<<<<< END CHANGE\n");
+                }
+                if sites.len() > MAX_LISTED_REPLACEMENTS {
+                    msg.push_str(&format!(
+                        "... and {} more changes\n",
+                        sites.len() - MAX_LISTED_REPLACEMENTS
+                    ));
                 }
+                msg.push_str(&format_syntax_errors(syntax_errors));
+                msg.push_str(&format_edit_diagnostics(diagnostics));
+                msg
             }
             ToolResult::DeleteFiles {
                 deleted, failed, ..
@@ -173,35 +620,114 @@ impl ToolResult {
             }
             ToolResult::UserInput { message } => message.clone(),
             ToolResult::CompleteTask { result } => result.clone(),
-            ToolResult::WebSearch { results, error, .. } => {
+            ToolResult::AskUser { answers, error, .. } => match error {
+                Some(e) => format!("Failed to ask the user: {}", e),
+                None => format!("The user answered: {}", answers.join(", ")),
+            },
+            ToolResult::WebSearch {
+                query,
+                results,
+                error,
+            } => {
                 if let Some(e) = error {
                     format!("Search failed: {}", e)
                 } else if results.is_empty() {
                     "No search results found.".to_string()
                 } else {
-                    let mut msg = String::from("Search results:\n");
+                    let mut msg = String::new();
                     for result in results {
                         msg.push_str(&format!(
                             "- Title: {}\n  URL: {}\n  Snippet: {}\n\n",
                             result.title, result.url, result.snippet
                         ));
                     }
-                    msg
+                    format!(
+                        "Search results:\n{}",
+                        fence_untrusted("SEARCH RESULTS", query, msg.trim_end())
+                    )
                 }
             }
             ToolResult::WebFetch { page, error } => {
                 if let Some(e) = error {
                     format!("Failed to fetch page: {}", e)
                 } else {
-                    format!("Page fetched successfully: {}", page.url)
+                    match page.position() {
+                        Some(position) => {
+                            format!("Page fetched successfully: {} ({})", page.url, position)
+                        }
+                        None => format!("Page fetched successfully: {}", page.url),
+                    }
                 }
             }
+            ToolResult::HttpRequest {
+                method,
+                url,
+                response,
+                error,
+            } => match (response, error) {
+                (_, Some(e)) => format!("{} {} failed: {}", method, url, e),
+                (Some(response), None) => fence_untrusted(
+                    "RESPONSE",
+                    &format!("{} {}", method, url),
+                    &response.to_string(),
+                ),
+                (None, None) => format!("{} {} returned no response", method, url),
+            },
+            ToolResult::Browser {
+                action,
+                state,
+                error,
+            } => match (state, error) {
+                (_, Some(e)) => format!("Browser action failed ({}): {}", action, e),
+                (Some(state), None) => {
+                    let mut page = format!("Title: {}", state.title);
+                    if !state.console.is_empty() {
+                        page.push_str("\nConsole:");
+                        for message in &state.console {
+                            page.push_str(&format!("\n- {}", message));
+                        }
+                    }
+                    let mut msg = format!("Browser action done: {}\n", action);
+                    if let Some(screenshot) = &state.screenshot {
+                        msg.push_str(&format!("Screenshot attached ({})\n", screenshot));
+                    }
+                    msg.push_str(&fence_untrusted("PAGE", &state.url, &page));
+                    msg
+                }
+                (None, None) => format!("Browser action done: {}", action),
+            },
+            ToolResult::CrawlDocs { url, crawl, error } => match (crawl, error) {
+                (_, Some(e)) => format!("Failed to crawl {}: {}", url, e),
+                (Some(crawl), None) => {
+                    format!("Crawled {} page(s) from {}", crawl.pages.len(), url)
+                }
+                (None, None) => format!("Crawled {}", url),
+            },
+            ToolResult::DependencyDocs {
+                name, docs, error, ..
+            } => match (docs, error) {
+                (_, Some(e)) => format!("Failed to look up {}: {}", name, e),
+                (Some(docs), None) => docs.to_string(),
+                (None, None) => format!("No documentation found for {}", name),
+            },
+        }
+    }
+
+    /// Image to send to the model along with the formatted message
+    pub fn image(&self) -> Option<&ImageData> {
+        match self {
+            ToolResult::ViewImage { image, .. } => image.as_ref(),
+            ToolResult::Browser {
+                state: Some(state), ..
+            } => state.screenshot.as_ref(),
+            _ => None,
         }
     }
 
     pub fn is_success(&self) -> bool {
         match self {
             ToolResult::AbsolutePathError { .. } => false,
+            ToolResult::NotApproved { .. } => false,
             ToolResult::ReadFiles {
                 loaded_files,
                 failed_files,
@@ -215,11 +741,146 @@ impl ToolResult {
             ToolResult::ExecuteCommand { success, .. } => *success,
             ToolResult::WriteFile { error, .. } => error.is_none(),
             ToolResult::ReplaceInFile { error, .. } => error.is_none(),
+            ToolResult::EditLines { error, .. } => error.is_none(),
+            ToolResult::ViewImage { error, .. } => error.is_none(),
+            ToolResult::AskUser { error, .. } => error.is_none(),
+            ToolResult::EditFiles { error, .. } => error.is_none(),
+            ToolResult::ReplaceAcrossFiles { error, .. } => error.is_none(),
             ToolResult::DeleteFiles {
                 deleted, failed, ..
             } => !deleted.is_empty() && failed.is_empty(),
+            ToolResult::FindFiles { error, .. } => error.is_none(),
+            ToolResult::StartProcess { error, .. } => error.is_none(),
+            ToolResult::ReadProcessOutput { error, .. } => error.is_none(),
+            ToolResult::SendProcessInput { error, .. } => error.is_none(),
+            ToolResult::StopProcess { error, .. } => error.is_none(),
+            ToolResult::ResetShellSession { error, .. } => error.is_none(),
+            ToolResult::CheckProject { error, .. } => error.is_none(),
+            ToolResult::GotoDefinition { error, .. } => error.is_none(),
+            ToolResult::FindReferences { error, .. } => error.is_none(),
+            ToolResult::Hover { error, .. } => error.is_none(),
+            ToolResult::DocumentSymbols { error, .. } => error.is_none(),
+            ToolResult::WorkspaceSymbols { error, .. } => error.is_none(),
+            ToolResult::RepoMap { error, .. } => error.is_none(),
+            // Error statuses are a valid response, e.g. when testing a service
+            ToolResult::HttpRequest { error, .. } => error.is_none(),
+            ToolResult::Browser { error, .. } => error.is_none(),
+            ToolResult::CrawlDocs { error, .. } => error.is_none(),
+            ToolResult::DependencyDocs { error, .. } => error.is_none(),
+            // Failing tests are reported like a failed command
+            ToolResult::RunTests { success, error, .. } => *success && error.is_none(),
             ToolResult::Summarize { .. } => true,
             _ => true,
         }
     }
 }
+
+// Syntax errors of an edit that was applied anyway
+fn format_syntax_errors(syntax_errors: &[Diagnostic]) -> String {
+    if syntax_errors.is_empty() {
+        return String::new();
+    }
+    let mut msg = String::from("\n\nWarning: the edit introduces syntax errors:\n");
+    for error in syntax_errors {
+        msg.push_str(&format!("{}\n", error));
+    }
+    msg
+}
+
+// Language server findings appended to the result of an edit
+fn format_edit_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    if diagnostics.is_empty() {
+        return String::new();
+    }
+    let mut msg = String::from("\n\nThe language server reports:\n");
+    for diagnostic in diagnostics.iter().take(MAX_LISTED_DIAGNOSTICS) {
+        msg.push_str(&format!("{}\n", diagnostic));
+    }
+    if diagnostics.len() > MAX_LISTED_DIAGNOSTICS {
+        msg.push_str(&format!(
+            "... and {} more\n",
+            diagnostics.len() - MAX_LISTED_DIAGNOSTICS
+        ));
+    }
+    msg
+}
+
+// One line per symbol: kind, qualified name, detail and location
+fn format_symbol(symbol: &CodeSymbol) -> String {
+    let mut line = format!("{} ", symbol.kind);
+    // Document symbols show nesting by indentation instead
+    if let (Some(container), Some(_)) = (&symbol.container, &symbol.path) {
+        line.push_str(&format!("{}::", container));
+    }
+    line.push_str(&symbol.name);
+    if let Some(detail) = &symbol.detail {
+        line.push_str(&format!(": {}", detail));
+    }
+    match &symbol.path {
+        Some(path) => line.push_str(&format!(" ({}:{})", path.display(), symbol.start_line)),
+        None => line.push_str(&format!(
+            " (lines {}-{})",
+            symbol.start_line, symbol.end_line
+        )),
+    }
+    line
+}
+
+// Render failed tests as a table, followed by their captured output
+fn format_test_failures(report: &TestReport) -> String {
+    if report.failures.is_empty() {
+        return String::new();
+    }
+
+    let mut msg = String::from("\n| Test | Location | Message |\n|------|----------|---------|\n");
+    for failure in report.failures.iter().take(MAX_LISTED_FAILURES) {
+        let mut message = failure
+            .message
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .replace('|', "\\|");
+        if let Some((cut, _)) = message.char_indices().nth(MAX_FAILURE_MESSAGE_CHARS) {
+            message.truncate(cut);
+            message.push_str("...");
+        }
+        msg.push_str(&format!(
+            "| {} | {} | {} |\n",
+            failure.name,
+            failure.location.as_deref().unwrap_or("-"),
+            message
+        ));
+    }
+    if report.failures.len() > MAX_LISTED_FAILURES {
+        msg.push_str(&format!(
+            "\n... and {} more failed tests\n",
+            report.failures.len() - MAX_LISTED_FAILURES
+        ));
+    }
+
+    for failure in report.failures.iter().take(MAX_LISTED_FAILURES) {
+        if let Some(output) = &failure.output {
+            let lines: Vec<&str> = output.lines().collect();
+            msg.push_str(&format!("\nOutput of {}:\n", failure.name));
+            if lines.len() > MAX_FAILURE_OUTPUT_LINES {
+                msg.push_str("[...]\n");
+            }
+            msg.push_str(&lines[lines.len().saturating_sub(MAX_FAILURE_OUTPUT_LINES)..].join("\n"));
+            msg.push('\n');
+        }
+    }
+    msg
+}
+
+// Format a file size in human readable units
+fn format_size(size: u64) -> String {
+    const KB: u64 = 1024;
+    const MB: u64 = KB * 1024;
+    if size >= MB {
+        format!("{:.1} MB", size as f64 / MB as f64)
+    } else if size >= KB {
+        format!("{:.1} KB", size as f64 / KB as f64)
+    } else {
+        format!("{} B", size)
+    }
+}
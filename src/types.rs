@@ -1,6 +1,11 @@
+use crate::dependencies::DependencyDocs;
 use crate::llm::Message;
+use crate::ui::AnswerType;
 
-use crate::web::{WebPage, WebSearchResult};
+use crate::web::{
+    format_outline, BrowserAction, BrowserPageState, DocsCrawl, HttpResponse, RequestBody, WebPage,
+    WebSearchResult,
+};
 use anyhow::Result;
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
@@ -10,6 +15,148 @@ use std::path::{Path, PathBuf};
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Project {
     pub path: PathBuf,
+    /// Default timeout for execute_command in seconds, 0 disables the timeout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_timeout_secs: Option<u64>,
+    /// Run execute_command in a persistent shell session by default
+    #[serde(default)]
+    pub persistent_shell: bool,
+    /// Language server used for code navigation and diagnostics after edits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language_server: Option<LanguageServerConfig>,
+    /// Token budget of the repository map shown in working memory, unset or 0 disables it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo_map_tokens: Option<usize>,
+    /// How edits that introduce syntax errors are handled
+    #[serde(default)]
+    pub syntax_check: SyntaxCheck,
+    /// Which untrusted content makes the agent ask before destructive tools
+    #[serde(default)]
+    pub untrusted_content_approval: UntrustedContentApproval,
+    /// Domains web_fetch and crawl_docs can reach and web_search results come
+    /// from, instead of those in web.json
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetch: Option<FetchConfig>,
+}
+
+/// Handling of edits that break the syntax of a source file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyntaxCheck {
+    /// Don't write the file and report the errors, unless the file had
+    /// syntax errors before the edit
+    #[default]
+    Reject,
+    /// Write the file and report the errors
+    Warn,
+    Off,
+}
+
+/// Content after which commands and edits need the user's approval, as it
+/// could contain instructions that are not the user's
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UntrustedContentApproval {
+    Off,
+    /// Web search results, fetched pages and HTTP responses, except failed
+    /// requests and pages on this machine
+    #[default]
+    Web,
+    /// All web content, including local pages, and the files read by the agent
+    All,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LanguageServerConfig {
+    /// Executable like rust-analyzer or typescript-language-server
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Settings of the web tools, from `web.json` in the config directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebConfig {
+    /// Disables web_search, web_fetch and crawl_docs, e.g. without outbound traffic
+    #[serde(default)]
+    pub offline: bool,
+    #[serde(default)]
+    pub search: SearchBackendConfig,
+    #[serde(default)]
+    pub fetch: FetchConfig,
+    #[serde(default)]
+    pub http_request: HttpRequestConfig,
+}
+
+/// Domains web_fetch, crawl_docs and the browser can reach, `*.example.com`
+/// includes subdomains
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FetchConfig {
+    /// Only these domains are fetched, unless it is empty
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+    /// Domains that are never fetched
+    #[serde(default)]
+    pub blocked_domains: Vec<String>,
+}
+
+/// Settings of the http_request tool
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HttpRequestConfig {
+    /// Hosts that can be reached besides localhost, `*.example.com` includes
+    /// subdomains and `*` allows all hosts
+    #[serde(default)]
+    pub allowed_hosts: Vec<String>,
+}
+
+/// Service answering web searches
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "snake_case")]
+pub enum SearchBackendConfig {
+    #[default]
+    #[serde(rename = "duckduckgo")]
+    DuckDuckGo,
+    /// A SearXNG instance with the JSON format enabled
+    Searxng {
+        url: String,
+    },
+    JsonApi(JsonSearchConfig),
+}
+
+/// Search API returning JSON, mapped to results by field paths like `web.results`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonSearchConfig {
+    /// Request URL with `{query}` and `{page}` placeholders
+    pub url: String,
+    /// Request headers, e.g. for an API key
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Path to the array of results, empty if the response is the array
+    #[serde(default)]
+    pub results_path: String,
+    #[serde(default = "default_title_field")]
+    pub title_field: String,
+    #[serde(default = "default_url_field")]
+    pub url_field: String,
+    #[serde(default = "default_snippet_field")]
+    pub snippet_field: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_field: Option<String>,
+    /// Relevance of a result, higher is better
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score_field: Option<String>,
+}
+
+fn default_title_field() -> String {
+    "title".to_string()
+}
+
+fn default_url_field() -> String {
+    "url".to_string()
+}
+
+fn default_snippet_field() -> String {
+    "snippet".to_string()
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -28,6 +175,34 @@ pub enum LoadedResource {
         results: Vec<WebSearchResult>,
     },
     WebPage(WebPage),
+    /// Index and condensed pages of a crawled documentation site
+    DocsCrawl(DocsCrawl),
+    Image(ImageData),
+}
+
+/// An image prepared for the model, base64 encoded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageData {
+    pub media_type: String,
+    pub data: String,
+    pub width: u32,
+    pub height: u32,
+    /// Dimensions of the file if the image was downscaled
+    pub original_size: Option<(u32, u32)>,
+}
+
+impl std::fmt::Display for ImageData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}, {}x{} pixels",
+            self.media_type, self.width, self.height
+        )?;
+        if let Some((width, height)) = self.original_size {
+            write!(f, " (downscaled from {}x{})", width, height)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -95,6 +270,47 @@ pub struct WorkingMemory {
     pub expanded_directories: HashMap<String, Vec<PathBuf>>,
     /// Available project names
     pub available_projects: Vec<String>,
+    /// Background processes started in this session, key is the process id
+    #[serde(default)]
+    pub background_processes: HashMap<String, ProcessInfo>,
+    /// Diagnostics from check_project and the language server, key is the project name
+    #[serde(default)]
+    pub diagnostics: HashMap<String, Vec<Diagnostic>>,
+    /// Repository maps created by repo_map or refreshed each turn, key is the project name
+    #[serde(default)]
+    pub repo_maps: HashMap<String, String>,
+    /// First line number of resources shown with line numbers
+    /// Key is (project_name, path)
+    #[serde(default)]
+    pub numbered_resources: HashMap<(String, PathBuf), usize>,
+}
+
+/// A long-running process started via start_process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessInfo {
+    pub id: String,
+    pub project: String,
+    pub command_line: String,
+    pub pid: Option<u32>,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub running: bool,
+    pub exit_code: Option<i32>,
+}
+
+impl std::fmt::Display for ProcessInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "`{}` [{}] `{}`",
+            self.id, self.project, self.command_line
+        )?;
+        match (self.running, self.exit_code, self.pid) {
+            (true, _, Some(pid)) => write!(f, " (running, pid {})", pid),
+            (true, _, None) => write!(f, " (running)"),
+            (false, Some(code), _) => write!(f, " (exited with code {})", code),
+            (false, None, _) => write!(f, " (terminated)"),
+        }
+    }
 }
 
 impl std::fmt::Display for LoadedResource {
@@ -110,14 +326,38 @@ impl std::fmt::Display for LoadedResource {
                 Ok(())
             }
             LoadedResource::WebPage(page) => {
-                writeln!(f, "Content from: {}", page.url)?;
+                match page.position() {
+                    Some(position) => writeln!(f, "Content from: {} ({})", page.url, position)?,
+                    None => writeln!(f, "Content from: {}", page.url)?,
+                }
+                if !page.outline.is_empty() {
+                    writeln!(f, "Outline:\n{}", format_outline(&page.outline))?;
+                }
                 write!(f, "{}", page.content)
             }
+            LoadedResource::DocsCrawl(crawl) => write!(f, "{}", crawl),
+            LoadedResource::Image(image) => {
+                write!(f, "Image ({}), attached after the working memory", image)
+            }
         }
     }
 }
 
 impl WorkingMemory {
+    /// Loaded images, which are sent as image content along with the markdown
+    pub fn images(&self) -> Vec<(&str, &Path, &ImageData)> {
+        let mut images: Vec<_> = self
+            .loaded_resources
+            .iter()
+            .filter_map(|((project, path), resource)| match resource {
+                LoadedResource::Image(image) => Some((project.as_str(), path.as_path(), image)),
+                _ => None,
+            })
+            .collect();
+        images.sort_by_key(|(project, path, _)| (*project, *path));
+        images
+    }
+
     /// Convert working memory to markdown format
     pub fn to_markdown(&self) -> String {
         let mut result = String::new();
@@ -147,6 +387,17 @@ impl WorkingMemory {
             result.push_str("\n");
         }
 
+        // Background processes
+        if !self.background_processes.is_empty() {
+            result.push_str("## Background Processes\n\n");
+            let mut processes: Vec<_> = self.background_processes.values().collect();
+            processes.sort_by_key(|p| p.started_at);
+            for process in processes {
+                result.push_str(&format!("- {}\n", process));
+            }
+            result.push_str("\n");
+        }
+
         // Action history
         result.push_str("## Previous Tools\n\n");
         if self.action_history.is_empty() {
@@ -184,12 +435,53 @@ impl WorkingMemory {
         } else {
             result.push_str("All resources are shown in their latest version. They already reflect the tools you may have used.\n\n");
             for ((project, path), resource) in &self.loaded_resources {
-                result.push_str(&format!(">>>>> RESOURCE: [{}] {}\n", project, path.display()));
-                result.push_str(&resource.to_string());
-                result.push_str("\n<<<<< END RESOURCE\n\n");
+                let mut content = match (
+                    resource,
+                    self.numbered_resources.get(&(project.clone(), path.clone())),
+                ) {
+                    (LoadedResource::File(content), Some(first_line)) => {
+                        crate::utils::number_lines(content, *first_line)
+                    }
+                    _ => resource.to_string(),
+                };
+                let diagnostics = self.file_diagnostics(project, path);
+                if !diagnostics.is_empty() {
+                    content.push_str("\n----- DIAGNOSTICS\n");
+                    for diagnostic in diagnostics {
+                        content.push_str(&format!("{}\n", diagnostic));
+                    }
+                }
+                // Files and web content may contain instructions that are not the user's
+                result.push_str(&crate::tools::fence_untrusted(
+                    "RESOURCE",
+                    &format!("[{}] {}", project, path.display()),
+                    &content,
+                ));
+                result.push_str("\n\n");
             }
         }
 
+        // Diagnostics of files that are not loaded
+        let mut other_diagnostics: Vec<_> = self
+            .diagnostics
+            .iter()
+            .flat_map(|(project, diagnostics)| diagnostics.iter().map(move |d| (project, d)))
+            .filter(|(project, d)| {
+                !self
+                    .loaded_resources
+                    .contains_key(&(project.to_string(), d.path.clone()))
+            })
+            .collect();
+        if !other_diagnostics.is_empty() {
+            other_diagnostics.sort_by_key(|(project, d)| (*project, d.severity));
+            result.push_str("## Diagnostics\n\n");
+            result.push_str("Reported by check_project for files that are not loaded:\n\n");
+            for (project, diagnostic) in other_diagnostics {
+                result.push_str(&format!("- [{}] {}\n", project, diagnostic));
+            }
+            result.push_str("\n");
+        }
+
         // File trees
         result.push_str("## File Trees\n\n");
         if self.file_trees.is_empty() {
@@ -203,6 +495,17 @@ impl WorkingMemory {
             }
         }
 
+        // Repository maps
+        if !self.repo_maps.is_empty() {
+            result.push_str("## Repository Map\n\n");
+            for (project, map) in &self.repo_maps {
+                result.push_str(&format!("### Project: {}\n\n", project));
+                result.push_str("Top-level symbols of source files related to the loaded files, with line numbers:\n\n");
+                result.push_str(map);
+                result.push_str("\n");
+            }
+        }
+
         // Summaries
         result.push_str("## Summaries\n\n");
         if self.summaries.is_empty() {
@@ -216,6 +519,32 @@ impl WorkingMemory {
 
         result
     }
+    /// Diagnostics of the last check that belong to the given file
+    pub fn file_diagnostics(&self, project: &str, path: &Path) -> Vec<&Diagnostic> {
+        self.diagnostics
+            .get(project)
+            .map(|diagnostics| diagnostics.iter().filter(|d| d.path == path).collect())
+            .unwrap_or_default()
+    }
+
+    /// Drop diagnostics of a file, their locations are outdated after edits
+    pub fn clear_file_diagnostics(&mut self, project: &str, path: &Path) {
+        if let Some(diagnostics) = self.diagnostics.get_mut(project) {
+            diagnostics.retain(|d| d.path != path);
+        }
+    }
+
+    /// Replace the diagnostics of a file, e.g. with those reported after an edit
+    pub fn set_file_diagnostics(&mut self, project: &str, path: &Path, diagnostics: &[Diagnostic]) {
+        self.clear_file_diagnostics(project, path);
+        if !diagnostics.is_empty() {
+            self.diagnostics
+                .entry(project.to_string())
+                .or_default()
+                .extend_from_slice(diagnostics);
+        }
+    }
+
     /// Add a new resource to working memory
     pub fn add_resource(&mut self, project: String, path: PathBuf, resource: LoadedResource) {
         self.loaded_resources.insert((project, path), resource);
@@ -233,6 +562,48 @@ impl WorkingMemory {
     }
 }
 
+/// Operations of the edit_lines tool
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum LineOperation {
+    InsertAfter,
+    InsertBefore,
+    DeleteRange,
+    ReplaceRange,
+}
+
+/// A line-based edit, line numbers are 1-based
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LineEdit {
+    pub operation: LineOperation,
+    pub start_line: usize,
+    /// Last line of a range, defaults to start_line
+    pub end_line: Option<usize>,
+    /// Lines to insert or to replace the range with
+    #[serde(default)]
+    pub content: String,
+    /// Current content of the addressed lines, guards against stale line numbers
+    pub expected: Option<String>,
+}
+
+/// Replacements in one file of an edit_files batch
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileEdit {
+    pub path: PathBuf,
+    pub replacements: Vec<FileReplacement>,
+}
+
+/// Lines changed by replace_across_files, neighboring matches are combined
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReplacementSite {
+    pub path: PathBuf,
+    /// First changed line, 1-based
+    pub line: usize,
+    pub before: String,
+    pub after: String,
+    pub matches: usize,
+}
+
 /// Details for a text replacement operation
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FileReplacement {
@@ -273,7 +644,12 @@ pub enum Tool {
     ReadFiles {
         project: String,
         paths: Vec<PathBuf>,
+        /// Show the files with line numbers
+        #[serde(default)]
+        line_numbers: bool,
     },
+    /// Load an image file to look at it, needs a model with vision support
+    ViewImage { project: String, path: PathBuf },
     /// Write content to a file
     WriteFile {
         project: String,
@@ -288,10 +664,51 @@ pub enum Tool {
         path: PathBuf,
         replacements: Vec<FileReplacement>,
     },
+    /// Insert, delete or replace lines of a file by line number
+    EditLines {
+        project: String,
+        path: PathBuf,
+        edit: LineEdit,
+    },
+    /// Replace parts within several files at once. Either all edits are
+    /// applied or none.
+    EditFiles {
+        project: String,
+        edits: Vec<FileEdit>,
+    },
+    /// Replace a pattern in all matching files of a project
+    ReplaceAcrossFiles {
+        project: String,
+        pattern: String,
+        replacement: String,
+        /// Treat the pattern as a regex, the replacement may then use capture groups
+        #[serde(default)]
+        regex: bool,
+        #[serde(default)]
+        whole_word: bool,
+        #[serde(default)]
+        ignore_case: bool,
+        /// Globs of files to include, all files if empty
+        #[serde(default)]
+        include: Vec<String>,
+        #[serde(default)]
+        exclude: Vec<String>,
+        /// Only preview the changes unless set
+        #[serde(default)]
+        apply: bool,
+    },
     /// Replace contents of resources with summaries in working memory
     Summarize { project: String, path: PathBuf, summary: String },
     /// Complete the current task
     CompleteTask { message: String },
+    /// Ask the user a question without ending the turn
+    AskUser {
+        question: String,
+        #[serde(default)]
+        answer_type: AnswerType,
+        #[serde(default)]
+        choices: Vec<String>,
+    },
     /// Execute a CLI command
     ExecuteCommand {
         project: String,
@@ -299,22 +716,156 @@ pub enum Tool {
         command_line: String,
         /// Optional working directory for the command
         working_dir: Option<PathBuf>,
+        /// Optional timeout in seconds, overrides the project default
+        timeout_secs: Option<u64>,
+        /// Run in the persistent shell session, overrides the project default
+        persistent_session: Option<bool>,
+    },
+    /// Terminate the persistent shell session of a project
+    ResetShellSession { project: String },
+    /// Run the compiler or linter of a project and collect diagnostics
+    CheckProject {
+        project: String,
+        /// Build system to use instead of detecting it, e.g. "cargo"
+        build_system: Option<String>,
+    },
+    /// Run the tests of a project and report failures
+    RunTests {
+        project: String,
+        /// Only run tests matching this name filter
+        filter: Option<String>,
+        /// Test runner to use instead of detecting it, e.g. "pytest"
+        runner: Option<String>,
     },
+    /// Find the definition of a symbol using the language server
+    GotoDefinition {
+        project: String,
+        path: PathBuf,
+        /// 1-based line of the symbol
+        line: usize,
+        /// Name of the symbol on the line, takes precedence over column
+        symbol: Option<String>,
+        /// 1-based column of the symbol
+        column: Option<usize>,
+    },
+    /// Find all references to a symbol using the language server
+    FindReferences {
+        project: String,
+        path: PathBuf,
+        line: usize,
+        symbol: Option<String>,
+        column: Option<usize>,
+    },
+    /// Show type information and documentation of a symbol
+    Hover {
+        project: String,
+        path: PathBuf,
+        line: usize,
+        symbol: Option<String>,
+        column: Option<usize>,
+    },
+    /// List the symbols defined in a file
+    DocumentSymbols { project: String, path: PathBuf },
+    /// Search symbols by name in the whole project
+    WorkspaceSymbols { project: String, query: String },
+    /// Show the top-level symbols of the project's source files
+    RepoMap {
+        project: String,
+        /// Files to rank the map by, in addition to the loaded files
+        paths: Vec<PathBuf>,
+        max_tokens: Option<usize>,
+    },
+    /// Start a long-running process in the background
+    StartProcess {
+        project: String,
+        /// The complete command line to execute
+        command_line: String,
+        /// Optional working directory for the process
+        working_dir: Option<PathBuf>,
+    },
+    /// Read output of a background process
+    ReadProcessOutput {
+        project: String,
+        process_id: String,
+        /// Read from this offset instead of continuing after the last read
+        since_offset: Option<usize>,
+        /// Only return the last lines of the output
+        tail_lines: Option<usize>,
+    },
+    /// Send a line to the stdin of a background process
+    SendProcessInput {
+        project: String,
+        process_id: String,
+        input: String,
+    },
+    /// Stop a background process
+    StopProcess { project: String, process_id: String },
     /// Search for text in files
     SearchFiles {
         project: String,
         /// The text to search for in regex syntax
         regex: String,
     },
-    /// Web search using DuckDuckGo
+    /// Find files by glob pattern or fuzzy name
+    FindFiles {
+        project: String,
+        /// Glob pattern like 'src/**/*.rs' or a (partial) file name
+        pattern: String,
+        /// Optional limit for the number of results
+        max_results: Option<usize>,
+    },
+    /// Web search using the configured search backend
     WebSearch {
+        /// Project whose fetch settings filter the results
+        project: String,
         query: String,
         hits_page_number: u32,
     },
     /// Fetch and extract content from a web page
     WebFetch {
+        /// Project whose fetch settings apply
+        project: String,
         url: String,
         selectors: Option<Vec<String>>,
+        /// 1-based page of content that doesn't fit on one page
+        page: Option<usize>,
+        /// Limit the content to a section, by number or title
+        section: Option<String>,
+    },
+    /// Send an HTTP request, e.g. to test a local service
+    HttpRequest {
+        method: String,
+        url: String,
+        headers: Vec<(String, String)>,
+        query: Vec<(String, String)>,
+        body: Option<RequestBody>,
+        timeout_secs: Option<u64>,
+    },
+    /// Interact with a page in the browser, e.g. of a frontend in development
+    Browser {
+        /// Project whose fetch settings apply, else those of web.json
+        project: Option<String>,
+        action: BrowserAction,
+    },
+    /// Fetch linked pages of a documentation site
+    CrawlDocs {
+        /// Project whose fetch settings apply
+        project: String,
+        url: String,
+        max_depth: Option<usize>,
+        max_pages: Option<usize>,
+        /// Only crawl URLs starting with this
+        prefix: Option<String>,
+    },
+    /// Look up the public items of a dependency in its locked version
+    DependencyDocs {
+        project: String,
+        /// Crate or npm package name
+        name: String,
+        /// Only needed when Cargo.lock has several versions
+        version: Option<String>,
+        /// Module to list or item to show the source of
+        item: Option<String>,
     },
 }
 
@@ -333,10 +884,24 @@ pub enum ToolResult {
     AbsolutePathError {
         path: PathBuf,
     },
+    /// The user didn't approve running the tool
+    NotApproved {
+        tool: String,
+        reason: String,
+    },
     ReadFiles {
         project: String,
         loaded_files: HashMap<PathBuf, String>,
         failed_files: Vec<(PathBuf, String)>,
+        /// First line number of files loaded with line numbers
+        #[serde(default)]
+        first_lines: HashMap<PathBuf, usize>,
+    },
+    ViewImage {
+        project: String,
+        path: PathBuf,
+        image: Option<ImageData>,
+        error: Option<String>,
     },
     ListFiles {
         project: String,
@@ -348,23 +913,170 @@ pub enum ToolResult {
         results: Vec<SearchResult>,
         regex: String,
     },
+    FindFiles {
+        project: String,
+        pattern: String,
+        results: Vec<FileMatch>,
+        error: Option<String>,
+    },
     ExecuteCommand {
         project: String,
         output: String,
         success: bool,
+        /// The command was killed after exceeding its timeout
+        #[serde(default)]
+        timed_out: bool,
+        /// Project-relative file with the complete output if it was truncated
+        #[serde(default)]
+        output_file: Option<PathBuf>,
+    },
+    StartProcess {
+        project: String,
+        command_line: String,
+        process: Option<ProcessInfo>,
+        error: Option<String>,
+    },
+    ReadProcessOutput {
+        project: String,
+        process_id: String,
+        output: String,
+        next_offset: usize,
+        truncated: bool,
+        process: Option<ProcessInfo>,
+        error: Option<String>,
+    },
+    SendProcessInput {
+        project: String,
+        process_id: String,
+        error: Option<String>,
+    },
+    StopProcess {
+        project: String,
+        process_id: String,
+        process: Option<ProcessInfo>,
+        error: Option<String>,
+    },
+    ResetShellSession {
+        project: String,
+        /// Whether a session was running
+        was_running: bool,
+        error: Option<String>,
+    },
+    CheckProject {
+        project: String,
+        build_system: String,
+        success: bool,
+        diagnostics: Vec<Diagnostic>,
+        /// Tail of the raw output if the check failed without diagnostics
+        output: Option<String>,
+        error: Option<String>,
+    },
+    RunTests {
+        project: String,
+        runner: String,
+        success: bool,
+        report: TestReport,
+        /// Tail of the raw output if the run failed without test failures
+        output: Option<String>,
+        error: Option<String>,
+    },
+    GotoDefinition {
+        project: String,
+        path: PathBuf,
+        line: usize,
+        locations: Vec<CodeLocation>,
+        error: Option<String>,
+    },
+    FindReferences {
+        project: String,
+        path: PathBuf,
+        line: usize,
+        locations: Vec<CodeLocation>,
+        error: Option<String>,
+    },
+    Hover {
+        project: String,
+        path: PathBuf,
+        line: usize,
+        contents: String,
+        error: Option<String>,
+    },
+    DocumentSymbols {
+        project: String,
+        path: PathBuf,
+        symbols: Vec<CodeSymbol>,
+        error: Option<String>,
+    },
+    WorkspaceSymbols {
+        project: String,
+        query: String,
+        symbols: Vec<CodeSymbol>,
+        error: Option<String>,
+    },
+    RepoMap {
+        project: String,
+        map: Option<RepoMap>,
+        error: Option<String>,
     },
     WriteFile {
         project: String,
         path: PathBuf,
         content: String,
+        /// Errors and warnings the language server reports after the write
+        #[serde(default)]
+        diagnostics: Vec<Diagnostic>,
+        /// Syntax errors the edit introduced, if the project allows such edits
+        #[serde(default)]
+        syntax_errors: Vec<Diagnostic>,
         error: Option<String>,
     },
     ReplaceInFile {
         project: String,
         path: PathBuf,
         content: String,
+        #[serde(default)]
+        diagnostics: Vec<Diagnostic>,
+        #[serde(default)]
+        syntax_errors: Vec<Diagnostic>,
         error: Option<crate::utils::FileUpdaterError>,
     },
+    EditLines {
+        project: String,
+        path: PathBuf,
+        content: String,
+        #[serde(default)]
+        diagnostics: Vec<Diagnostic>,
+        #[serde(default)]
+        syntax_errors: Vec<Diagnostic>,
+        error: Option<String>,
+    },
+    EditFiles {
+        project: String,
+        /// New contents of the edited files
+        files: Vec<(PathBuf, String)>,
+        /// Unified diff of all edits
+        diff: String,
+        #[serde(default)]
+        diagnostics: Vec<Diagnostic>,
+        #[serde(default)]
+        syntax_errors: Vec<Diagnostic>,
+        error: Option<String>,
+    },
+    ReplaceAcrossFiles {
+        project: String,
+        pattern: String,
+        sites: Vec<ReplacementSite>,
+        /// Whether the changes were written or only previewed
+        applied: bool,
+        /// New contents of the changed files, if applied
+        #[serde(default)]
+        files: Vec<(PathBuf, String)>,
+        #[serde(default)]
+        diagnostics: Vec<Diagnostic>,
+        #[serde(default)]
+        syntax_errors: Vec<Diagnostic>,
+        error: Option<String>,
+    },
     DeleteFiles {
         project: String,
         deleted: Vec<PathBuf>,
@@ -378,6 +1090,11 @@ pub enum ToolResult {
     CompleteTask {
         result: String,
     },
+    AskUser {
+        question: String,
+        answers: Vec<String>,
+        error: Option<String>,
+    },
     WebSearch {
         query: String,
         results: Vec<WebSearchResult>,
@@ -387,6 +1104,28 @@ pub enum ToolResult {
         page: WebPage,
         error: Option<String>,
     },
+    HttpRequest {
+        method: String,
+        url: String,
+        response: Option<HttpResponse>,
+        error: Option<String>,
+    },
+    Browser {
+        action: BrowserAction,
+        state: Option<BrowserPageState>,
+        error: Option<String>,
+    },
+    CrawlDocs {
+        url: String,
+        crawl: Option<DocsCrawl>,
+        error: Option<String>,
+    },
+    DependencyDocs {
+        project: String,
+        name: String,
+        docs: Option<DependencyDocs>,
+        error: Option<String>,
+    },
 }
 
 /// Collection of all available tool definitions
@@ -483,6 +1222,138 @@ pub struct SearchResult {
     pub match_ranges: Vec<Vec<(usize, usize)>>, // Match positions for each line, aligned with match_lines
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct FindOptions {
+    /// Glob pattern (e.g. "src/**/*.rs") or a fuzzy file name
+    pub pattern: String,
+    pub max_results: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileMatch {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl std::fmt::Display for DiagnosticSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiagnosticSeverity::Error => write!(f, "error"),
+            DiagnosticSeverity::Warning => write!(f, "warning"),
+            DiagnosticSeverity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A compiler or linter message for a file in a project
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Diagnostic {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub column: Option<usize>,
+    pub severity: DiagnosticSeverity,
+    /// Error code like E0308 or TS2322
+    pub code: Option<String>,
+    pub message: String,
+    /// Suggested fix as reported by the tool
+    pub suggestion: Option<String>,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.path.display(), self.start_line)?;
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        write!(f, " {}", self.severity)?;
+        if let Some(code) = &self.code {
+            write!(f, "[{}]", code)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n  fix: {}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of a test run
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TestReport {
+    pub passed: usize,
+    pub failed: usize,
+    pub ignored: usize,
+    pub failures: Vec<TestFailure>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TestFailure {
+    pub name: String,
+    /// Assertion or panic message
+    pub message: String,
+    /// Where the test failed, as file:line[:column]
+    pub location: Option<String>,
+    /// Output captured while the test ran
+    pub output: Option<String>,
+}
+
+/// A position in a file as reported by the language server
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CodeLocation {
+    /// Relative to the project root, absolute for files outside the project
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+    /// The source line at the location
+    pub preview: String,
+}
+
+impl std::fmt::Display for CodeLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.path.display(), self.line, self.column)?;
+        if !self.preview.is_empty() {
+            write!(f, ": {}", self.preview)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compact map of the symbols in a project
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RepoMap {
+    /// Files with their symbol signatures, one symbol per line
+    pub content: String,
+    pub files_shown: usize,
+    /// Source files with symbols, excluding the focus files
+    pub files_total: usize,
+}
+
+/// A symbol from a document or workspace symbol query
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CodeSymbol {
+    pub name: String,
+    /// Symbol kind like function, struct or method
+    pub kind: String,
+    pub detail: Option<String>,
+    /// Only set for workspace symbols
+    pub path: Option<PathBuf>,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub container: Option<String>,
+    /// Nesting level within the document
+    pub depth: usize,
+}
+
 /// Specifies the tool integration mode
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ToolMode {
@@ -550,4 +1421,9 @@ pub trait CodeExplorer: Send + Sync {
     fn apply_replacements(&self, path: &Path, replacements: &[FileReplacement]) -> Result<String>;
     /// Search for text in files with advanced options
     fn search(&self, path: &Path, options: SearchOptions) -> Result<Vec<SearchResult>>;
+    /// Find files by glob pattern or fuzzy name, ranked by relevance
+    fn find_files(&self, path: &Path, options: FindOptions) -> Result<Vec<FileMatch>>;
+    /// Map of the top-level symbols of source files, ranked by relevance to
+    /// the focus files (relative to the root) and capped to a token budget
+    fn repo_map(&self, focus: &[PathBuf], max_tokens: usize) -> Result<RepoMap>;
 }
@@ -2,12 +2,15 @@ use crate::ui::gpui::parameter_renderers::ParameterRenderer;
 use gpui::{div, px, rgb, rgba, Element, FontWeight, IntoElement, ParentElement, Styled};
 use similar::{ChangeTag, TextDiff};
 
-/// Renderer for the "diff" parameter of the replace_in_file tool
+/// Renderer for the "diff" parameter of the replace_in_file and edit_files tools
 pub struct DiffParameterRenderer;
 
 impl ParameterRenderer for DiffParameterRenderer {
     fn supported_parameters(&self) -> Vec<(String, String)> {
-        vec![("replace_in_file".to_string(), "diff".to_string())]
+        vec![
+            ("replace_in_file".to_string(), "diff".to_string()),
+            ("edit_files".to_string(), "diff".to_string()),
+        ]
     }
 
     fn render(&self, _tool_name: &str, _param_name: &str, param_value: &str) -> gpui::AnyElement {
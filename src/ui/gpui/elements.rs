@@ -9,6 +9,9 @@ use gpui::{prelude::*, FontWeight};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
+// Number of output lines shown in a running tool block
+const MAX_LIVE_OUTPUT_LINES: usize = 12;
+
 /// Role of a message in the conversation
 #[derive(Debug, Clone, PartialEq)]
 pub enum MessageRole {
@@ -75,6 +78,7 @@ impl MessageContainer {
             parameters: Vec::new(),
             status: ToolStatus::Pending,
             status_message: None,
+            output: None,
         }));
     }
 
@@ -100,6 +104,22 @@ impl MessageContainer {
         false // No matching tool found
     }
 
+    // Append live output to a running tool block
+    pub fn append_tool_output(&self, tool_id: &str, chunk: &str) -> bool {
+        let mut elements = self.elements.lock().unwrap();
+
+        for element in elements.iter_mut() {
+            if let MessageElement::ToolUse(tool) = element {
+                if tool.id == tool_id {
+                    tool.output.get_or_insert_with(String::new).push_str(chunk);
+                    return true;
+                }
+            }
+        }
+
+        false // No matching tool found
+    }
+
     // Add or append to text block
     pub fn add_or_append_to_text_block(&self, content: impl Into<String>) {
         self.finish_any_thinking_blocks();
@@ -182,6 +202,7 @@ impl MessageContainer {
             parameters: Vec::new(),
             status: ToolStatus::Pending,
             status_message: None,
+            output: None,
         };
 
         tool.parameters.push(ParameterBlock { name, value });
@@ -296,6 +317,8 @@ pub struct ToolUseBlock {
     pub parameters: Vec<ParameterBlock>,
     pub status: ToolStatus,
     pub status_message: Option<String>,
+    /// Output streamed while the tool is running
+    pub output: Option<String>,
 }
 
 /// Parameter for a tool
@@ -591,6 +614,27 @@ impl IntoElement for MessageElement {
                                     );
                                 }
 
+                                // Live output (only shown while running)
+                                if block.status == crate::ui::ToolStatus::Running {
+                                    if let Some(output) = &block.output {
+                                        let lines: Vec<&str> = output.lines().collect();
+                                        let visible = lines
+                                            [lines.len().saturating_sub(MAX_LIVE_OUTPUT_LINES)..]
+                                            .join("\n");
+                                        elements.push(
+                                            div()
+                                                .mt_1()
+                                                .p_2()
+                                                .rounded_md()
+                                                .bg(hsla(0., 0., 0.1, 0.4))
+                                                .text_color(hsla(0., 0., 0.8, 1.0))
+                                                .text_size(px(12.))
+                                                .child(visible)
+                                                .into_any(),
+                                        );
+                                    }
+                                }
+
                                 // Error message (only shown for error status)
                                 if block.status == crate::ui::ToolStatus::Error {
                                     if let Some(msg) = &block.status_message {
@@ -612,6 +656,25 @@ impl IntoElement for MessageElement {
                                     }
                                 }
 
+                                // Test results table (failures are shown as error above)
+                                if block.name == "run_tests"
+                                    && block.status == crate::ui::ToolStatus::Success
+                                {
+                                    if let Some(msg) = &block.status_message {
+                                        elements.push(
+                                            div()
+                                                .mt_1()
+                                                .p_2()
+                                                .rounded_md()
+                                                .bg(hsla(0., 0., 0.1, 0.4))
+                                                .text_color(hsla(0., 0., 0.8, 1.0))
+                                                .text_size(px(12.))
+                                                .child(msg.clone())
+                                                .into_any(),
+                                        );
+                                    }
+                                }
+
                                 elements
                             }),
                         ),
@@ -276,16 +276,35 @@ impl FileIcons {
     pub fn get_tool_icon(&self, tool_name: &str) -> Option<SharedString> {
         let icon_type = match tool_name {
             "read_files" => TOOL_READ_FILES,
+            "view_image" => TOOL_READ_FILES,
             "list_files" => TOOL_LIST_FILES,
             "execute_command" => TOOL_EXECUTE_COMMAND,
+            "reset_shell_session" => TOOL_EXECUTE_COMMAND,
+            "start_process" => TOOL_EXECUTE_COMMAND,
+            "read_process_output" => TOOL_EXECUTE_COMMAND,
+            "send_process_input" => TOOL_EXECUTE_COMMAND,
+            "stop_process" => TOOL_EXECUTE_COMMAND,
+            "check_project" => TOOL_EXECUTE_COMMAND,
+            "run_tests" => TOOL_EXECUTE_COMMAND,
             "write_file" => TOOL_WRITE_FILE,
             "replace_in_file" => TOOL_REPLACE_IN_FILE,
+            "edit_lines" => TOOL_REPLACE_IN_FILE,
+            "edit_files" => TOOL_REPLACE_IN_FILE,
+            "replace_across_files" => TOOL_REPLACE_IN_FILE,
             "search_files" => TOOL_SEARCH_FILES,
+            "find_files" => TOOL_SEARCH_FILES,
+            "goto_definition" => TOOL_SEARCH_FILES,
+            "find_references" => TOOL_SEARCH_FILES,
+            "workspace_symbols" => TOOL_SEARCH_FILES,
+            "hover" => TOOL_READ_FILES,
+            "document_symbols" => TOOL_READ_FILES,
+            "repo_map" => TOOL_LIST_FILES,
+            "dependency_docs" => TOOL_READ_FILES,
             "web_search" => TOOL_WEB_SEARCH,
-            "web_fetch" => TOOL_WEB_FETCH,
+            "web_fetch" | "crawl_docs" | "http_request" | "browser" => TOOL_WEB_FETCH,
             "delete_files" => TOOL_DELETE_FILES,
             "open_project" => TOOL_OPEN_PROJECT,
-            "user_input" => TOOL_USER_INPUT,
+            "user_input" | "ask_user" => TOOL_USER_INPUT,
             "complete_task" => TOOL_COMPLETE_TASK,
             "update_plan" => TOOL_UPDATE_PLAN,
             _ => TOOL_GENERIC,
@@ -177,11 +177,16 @@ impl MemoryView {
             .children(memory.loaded_resources.iter().map(|((project, path), resource)| {
                 // Get appropriate icon for resource type
                 let icon = match resource {
-                    LoadedResource::File(_) => file_icons::get().get_icon(path),
+                    LoadedResource::File(_) | LoadedResource::Image(_) => {
+                        file_icons::get().get_icon(path)
+                    }
                     LoadedResource::WebSearch { .. } => {
                         file_icons::get().get_type_icon(file_icons::MAGNIFYING_GLASS)
                     }
                     LoadedResource::WebPage(_) => file_icons::get().get_type_icon(file_icons::HTML),
+                    LoadedResource::DocsCrawl(_) => {
+                        file_icons::get().get_type_icon(file_icons::LIBRARY)
+                    }
                 };
 
                 div()
@@ -218,6 +223,8 @@ impl MemoryView {
                                 LoadedResource::File(_) => "File",
                                 LoadedResource::WebSearch { .. } => "Web Search",
                                 LoadedResource::WebPage(_) => "Web Page",
+                                LoadedResource::DocsCrawl(_) => "Documentation",
+                                LoadedResource::Image(_) => "Image",
                             }),
                     )
             }));
@@ -4,6 +4,7 @@ use super::input::TextInput;
 use super::memory_view::MemoryView;
 use super::scrollbar::{Scrollbar, ScrollbarState};
 use super::CloseWindow;
+use crate::ui::{AnswerType, UserQuestion};
 use gpui::{
     div, prelude::*, px, rgb, white, App, Context, CursorStyle, Entity, FocusHandle, Focusable,
     MouseButton, MouseUpEvent, ScrollHandle,
@@ -19,6 +20,9 @@ pub struct MessageView {
     input_value: Arc<Mutex<Option<String>>>,
     message_queue: Arc<Mutex<Vec<MessageContainer>>>,
     input_requested: Arc<Mutex<bool>>,
+    pending_question: Arc<Mutex<Option<UserQuestion>>>,
+    // Choices picked so far when several can be picked
+    picked_choices: Vec<usize>,
     // Scroll handle for messages
     messages_scroll_handle: ScrollHandle,
     // Track the number of thinking blocks for click handling
@@ -33,6 +37,7 @@ impl MessageView {
         input_value: Arc<Mutex<Option<String>>>,
         message_queue: Arc<Mutex<Vec<MessageContainer>>>,
         input_requested: Arc<Mutex<bool>>,
+        pending_question: Arc<Mutex<Option<UserQuestion>>>,
     ) -> Self {
         Self {
             text_input,
@@ -42,6 +47,8 @@ impl MessageView {
             input_value,
             message_queue,
             input_requested,
+            pending_question,
+            picked_choices: Vec::new(),
             // Initialize scroll handle
             messages_scroll_handle: ScrollHandle::new(),
             thinking_block_count: 0,
@@ -80,6 +87,125 @@ impl MessageView {
         cx.notify();
     }
 
+    fn on_choice_click(&mut self, answer: String, cx: &mut Context<Self>) {
+        *self.input_value.lock().unwrap() = Some(answer);
+        self.picked_choices.clear();
+        cx.notify();
+    }
+
+    fn on_choice_toggle(&mut self, index: usize, cx: &mut Context<Self>) {
+        if let Some(position) = self.picked_choices.iter().position(|i| *i == index) {
+            self.picked_choices.remove(position);
+        } else {
+            self.picked_choices.push(index);
+        }
+        cx.notify();
+    }
+
+    // Buttons to answer a question of the ask_user tool, typing an answer works as well
+    fn render_choices(&self, question: &UserQuestion, cx: &mut Context<Self>) -> impl IntoElement {
+        let button = |label: String, selected: bool| {
+            div()
+                .border_1()
+                .border_color(rgb(0x505050))
+                .rounded_md()
+                .px_3()
+                .py_1()
+                .bg(if selected {
+                    rgb(0x3355bb)
+                } else {
+                    rgb(0x404040)
+                })
+                .text_color(white())
+                .cursor_pointer()
+                .hover(|style| style.bg(rgb(0x4466cc)))
+                .child(label)
+        };
+
+        let buttons: Vec<gpui::AnyElement> = match question.answer_type {
+            AnswerType::Text => Vec::new(),
+            AnswerType::YesNo => ["yes", "no"]
+                .into_iter()
+                .map(|answer| {
+                    button(answer.to_string(), false)
+                        .on_mouse_up(
+                            MouseButton::Left,
+                            cx.listener(move |view, _: &MouseUpEvent, _window, cx| {
+                                view.on_choice_click(answer.to_string(), cx)
+                            }),
+                        )
+                        .into_any_element()
+                })
+                .collect(),
+            AnswerType::PickOne => question
+                .choices
+                .iter()
+                .map(|choice| {
+                    let answer = choice.clone();
+                    button(choice.clone(), false)
+                        .on_mouse_up(
+                            MouseButton::Left,
+                            cx.listener(move |view, _: &MouseUpEvent, _window, cx| {
+                                view.on_choice_click(answer.clone(), cx)
+                            }),
+                        )
+                        .into_any_element()
+                })
+                .collect(),
+            AnswerType::PickMany => {
+                let mut buttons: Vec<gpui::AnyElement> = question
+                    .choices
+                    .iter()
+                    .enumerate()
+                    .map(|(index, choice)| {
+                        button(choice.clone(), self.picked_choices.contains(&index))
+                            .on_mouse_up(
+                                MouseButton::Left,
+                                cx.listener(move |view, _: &MouseUpEvent, _window, cx| {
+                                    view.on_choice_toggle(index, cx)
+                                }),
+                            )
+                            .into_any_element()
+                    })
+                    .collect();
+                // The answer lists the picked choices line by line
+                let answer = self
+                    .picked_choices
+                    .iter()
+                    .filter_map(|index| question.choices.get(*index).cloned())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                buttons.push(
+                    button("Confirm".to_string(), false)
+                        .font_weight(gpui::FontWeight(600.0))
+                        .when(!answer.is_empty(), |style| {
+                            style.on_mouse_up(
+                                MouseButton::Left,
+                                cx.listener(move |view, _: &MouseUpEvent, _window, cx| {
+                                    view.on_choice_click(answer.clone(), cx)
+                                }),
+                            )
+                        })
+                        .into_any_element(),
+                );
+                buttons
+            }
+        };
+
+        div()
+            .id("choices")
+            .flex_none()
+            .bg(rgb(0x303030))
+            .border_t_1()
+            .border_color(rgb(0x404040))
+            .flex()
+            .flex_row()
+            .flex_wrap()
+            .p_2()
+            .gap_2()
+            .children(buttons)
+    }
+
     fn on_thinking_toggle(
         &mut self,
         index: usize,
@@ -124,6 +250,14 @@ impl Render for MessageView {
         // Check if input is requested
         let is_input_requested = *self.input_requested.lock().unwrap();
 
+        // Choices are only offered while the answer is awaited
+        let question = self
+            .pending_question
+            .lock()
+            .unwrap()
+            .clone()
+            .filter(|question| is_input_requested && question.answer_type != AnswerType::Text);
+
         // Create scrollbar state for messages
         let messages_scrollbar_state =
             ScrollbarState::new(self.messages_scroll_handle.clone()).parent_entity(&cx.entity());
@@ -255,6 +389,9 @@ impl Render for MessageView {
                                 None => div().w(px(0.)).h(px(0.)).into_any_element(),
                             }),
                     )
+                    .when_some(question, |style, question| {
+                        style.child(self.render_choices(&question, cx))
+                    })
                     .child(
                         // Input area - ensure this doesn't get pushed out
                         div()
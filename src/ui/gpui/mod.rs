@@ -16,7 +16,9 @@ use crate::ui::gpui::{
     parameter_renderers::{DefaultParameterRenderer, ParameterRendererRegistry},
     simple_renderers::SimpleParameterRenderer,
 };
-use crate::ui::{async_trait, DisplayFragment, ToolStatus, UIError, UIMessage, UserInterface};
+use crate::ui::{
+    async_trait, DisplayFragment, ToolStatus, UIError, UIMessage, UserInterface, UserQuestion,
+};
 use gpui::{actions, AppContext, Focusable};
 use input::TextInput;
 pub use memory_view::MemoryView;
@@ -33,6 +35,8 @@ pub struct GPUI {
     message_queue: Arc<Mutex<Vec<MessageContainer>>>,
     input_value: Arc<Mutex<Option<String>>>,
     input_requested: Arc<Mutex<bool>>,
+    // Question of the ask_user tool, answered with the choice buttons
+    pending_question: Arc<Mutex<Option<UserQuestion>>>,
     ui_update_needed: Arc<Mutex<bool>>,
     working_memory: Arc<Mutex<Option<WorkingMemory>>>,
     current_request_id: Arc<Mutex<u64>>,
@@ -46,6 +50,7 @@ impl GPUI {
         let message_queue = Arc::new(Mutex::new(Vec::new()));
         let input_value = Arc::new(Mutex::new(None));
         let input_requested = Arc::new(Mutex::new(false));
+        let pending_question = Arc::new(Mutex::new(None));
         let ui_update_needed = Arc::new(Mutex::new(false));
         let working_memory = Arc::new(Mutex::new(None));
         let current_request_id = Arc::new(Mutex::new(0));
@@ -62,9 +67,34 @@ impl GPUI {
         registry.register_renderer(Box::new(SimpleParameterRenderer::new(
             vec![
                 ("execute_command".to_string(), "command_line".to_string()),
+                ("start_process".to_string(), "command_line".to_string()),
+                ("read_process_output".to_string(), "process_id".to_string()),
+                ("send_process_input".to_string(), "process_id".to_string()),
+                ("stop_process".to_string(), "process_id".to_string()),
                 ("read_files".to_string(), "paths".to_string()),
+                ("view_image".to_string(), "path".to_string()),
                 ("replace_in_file".to_string(), "path".to_string()),
+                ("edit_lines".to_string(), "path".to_string()),
+                ("edit_files".to_string(), "path".to_string()),
+                ("replace_across_files".to_string(), "pattern".to_string()),
                 ("search_files".to_string(), "regex".to_string()),
+                ("find_files".to_string(), "pattern".to_string()),
+                ("run_tests".to_string(), "filter".to_string()),
+                ("goto_definition".to_string(), "symbol".to_string()),
+                ("find_references".to_string(), "symbol".to_string()),
+                ("hover".to_string(), "symbol".to_string()),
+                ("document_symbols".to_string(), "path".to_string()),
+                ("workspace_symbols".to_string(), "query".to_string()),
+                ("repo_map".to_string(), "paths".to_string()),
+                ("ask_user".to_string(), "question".to_string()),
+                ("http_request".to_string(), "method".to_string()),
+                ("http_request".to_string(), "url".to_string()),
+                ("browser".to_string(), "action".to_string()),
+                ("browser".to_string(), "url".to_string()),
+                ("browser".to_string(), "selector".to_string()),
+                ("crawl_docs".to_string(), "url".to_string()),
+                ("dependency_docs".to_string(), "name".to_string()),
+                ("dependency_docs".to_string(), "item".to_string()),
             ],
             false, // These are not full-width
         )));
@@ -79,6 +109,7 @@ impl GPUI {
             message_queue,
             input_value,
             input_requested,
+            pending_question,
             ui_update_needed,
             working_memory,
             current_request_id,
@@ -93,6 +124,7 @@ impl GPUI {
         let message_queue = self.message_queue.clone();
         let input_value = self.input_value.clone();
         let input_requested = self.input_requested.clone();
+        let pending_question = self.pending_question.clone();
         let ui_update_needed = self.ui_update_needed.clone();
         let working_memory = self.working_memory.clone();
 
@@ -146,6 +178,7 @@ impl GPUI {
                             input_value.clone(),
                             message_queue.clone(),
                             input_requested.clone(),
+                            pending_question.clone(),
                         )
                     })
                 },
@@ -265,6 +298,27 @@ impl GPUI {
             *flag = true;
         }
     }
+
+    // Requests input and waits until the user submitted it
+    async fn wait_for_input(&self) -> String {
+        *self.input_requested.lock().unwrap() = true;
+        if let Ok(mut flag) = self.ui_update_needed.lock() {
+            *flag = true;
+        }
+
+        loop {
+            {
+                let mut input = self.input_value.lock().unwrap();
+                if let Some(value) = input.take() {
+                    // Reset input request
+                    let mut requested = self.input_requested.lock().unwrap();
+                    *requested = false;
+                    return value;
+                }
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
 }
 
 #[async_trait]
@@ -319,25 +373,27 @@ impl UserInterface for GPUI {
         self.display(UIMessage::Question(prompt.to_string()))
             .await?;
 
-        // Request input
-        {
-            let mut requested = self.input_requested.lock().unwrap();
-            *requested = true;
-        }
+        Ok(self.wait_for_input().await)
+    }
 
-        // Wait for input
-        loop {
-            {
-                let mut input = self.input_value.lock().unwrap();
-                if let Some(value) = input.take() {
-                    // Reset input request
-                    let mut requested = self.input_requested.lock().unwrap();
-                    *requested = false;
-                    return Ok(value);
-                }
+    async fn ask_user(&self, question: &UserQuestion) -> Result<Vec<String>, UIError> {
+        self.display(UIMessage::Question(question.to_string()))
+            .await?;
+
+        // Show the choice buttons while waiting for the answer
+        *self.pending_question.lock().unwrap() = Some(question.clone());
+        let answers = loop {
+            let input = self.wait_for_input().await;
+            match question.parse_answer(&input) {
+                Ok(answers) => break answers,
+                Err(hint) => self.display(UIMessage::Action(hint)).await?,
             }
-            tokio::time::sleep(Duration::from_millis(100)).await;
-        }
+        };
+        *self.pending_question.lock().unwrap() = None;
+
+        self.display(UIMessage::UserInput(answers.join(", ")))
+            .await?;
+        Ok(answers)
     }
 
     fn display_fragment(&self, fragment: &DisplayFragment) -> Result<(), UIError> {
@@ -428,6 +484,26 @@ impl UserInterface for GPUI {
         Ok(())
     }
 
+    async fn append_tool_output(&self, tool_id: &str, chunk: &str) -> Result<(), UIError> {
+        let queue = self.message_queue.lock().unwrap();
+        let mut updated = false;
+
+        for msg_container in queue.iter() {
+            if msg_container.append_tool_output(tool_id, chunk) {
+                updated = true;
+            }
+        }
+
+        if updated {
+            // Request UI refresh
+            if let Ok(mut flag) = self.ui_update_needed.lock() {
+                *flag = true;
+            }
+        }
+
+        Ok(())
+    }
+
     async fn update_memory(&self, memory: &WorkingMemory) -> Result<(), UIError> {
         // Update the shared working memory directly
         if let Ok(mut memory_guard) = self.working_memory.lock() {
@@ -466,6 +542,7 @@ impl Clone for GPUI {
             message_queue: self.message_queue.clone(),
             input_value: self.input_value.clone(),
             input_requested: self.input_requested.clone(),
+            pending_question: self.pending_question.clone(),
             ui_update_needed: self.ui_update_needed.clone(),
             working_memory: self.working_memory.clone(),
             current_request_id: self.current_request_id.clone(),
@@ -1,8 +1,10 @@
 pub mod gpui;
+mod question;
 pub mod streaming;
 pub mod terminal;
 use crate::types::WorkingMemory;
 use async_trait::async_trait;
+pub use question::{AnswerType, UserQuestion};
 pub use streaming::DisplayFragment;
 use thiserror::Error;
 
@@ -42,6 +44,19 @@ pub trait UserInterface: Send + Sync {
     /// Get input from the user
     async fn get_input(&self, prompt: &str) -> Result<String, UIError>;
 
+    /// Ask the user a question, asking again until the answer fits the expected type
+    async fn ask_user(&self, question: &UserQuestion) -> Result<Vec<String>, UIError> {
+        self.display(UIMessage::Question(question.to_string()))
+            .await?;
+        loop {
+            let input = self.get_input(&question.prompt()).await?;
+            match question.parse_answer(&input) {
+                Ok(answers) => return Ok(answers),
+                Err(hint) => self.display(UIMessage::Action(hint)).await?,
+            }
+        }
+    }
+
     /// Display a streaming fragment with specific type information
     fn display_fragment(&self, fragment: &DisplayFragment) -> Result<(), UIError>;
 
@@ -53,6 +68,9 @@ pub trait UserInterface: Send + Sync {
         message: Option<String>,
     ) -> Result<(), UIError>;
 
+    /// Append output of a running tool, e.g. a build in progress
+    async fn append_tool_output(&self, tool_id: &str, chunk: &str) -> Result<(), UIError>;
+
     /// Update memory view with current working memory
     async fn update_memory(&self, memory: &WorkingMemory) -> Result<(), UIError>;
 
@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+
+/// Kind of answer the ask_user tool expects
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AnswerType {
+    /// Free text
+    #[default]
+    Text,
+    YesNo,
+    /// Exactly one of the choices
+    PickOne,
+    /// One or more of the choices
+    PickMany,
+}
+
+/// A question the agent asks the user while working on a task
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UserQuestion {
+    pub question: String,
+    pub answer_type: AnswerType,
+    pub choices: Vec<String>,
+}
+
+impl UserQuestion {
+    /// Short hint on how to answer, shown as the input prompt
+    pub fn prompt(&self) -> String {
+        match self.answer_type {
+            AnswerType::Text => ">".to_string(),
+            AnswerType::YesNo => "[y/n]>".to_string(),
+            AnswerType::PickOne => format!("[1-{}]>", self.choices.len()),
+            AnswerType::PickMany => format!("[1-{}, separated by commas]>", self.choices.len()),
+        }
+    }
+
+    /// Validates the user's input, returning the answers or what to correct
+    pub fn parse_answer(&self, input: &str) -> Result<Vec<String>, String> {
+        let input = input.trim();
+        match self.answer_type {
+            AnswerType::Text if input.is_empty() => Err("Please enter an answer.".to_string()),
+            AnswerType::Text => Ok(vec![input.to_string()]),
+            AnswerType::YesNo => match input.to_lowercase().as_str() {
+                "y" | "yes" => Ok(vec!["yes".to_string()]),
+                "n" | "no" => Ok(vec!["no".to_string()]),
+                _ => Err("Please answer yes or no.".to_string()),
+            },
+            AnswerType::PickOne => self
+                .find_choice(input)
+                .map(|choice| vec![choice])
+                .ok_or_else(|| {
+                    format!(
+                        "Please enter a number between 1 and {}.",
+                        self.choices.len()
+                    )
+                }),
+            AnswerType::PickMany => {
+                let mut answers = Vec::new();
+                // The GUI separates picked choices by line breaks
+                for item in input.split([',', '\n']).map(str::trim) {
+                    if item.is_empty() {
+                        continue;
+                    }
+                    let choice = self.find_choice(item).ok_or_else(|| {
+                        format!(
+                            "'{}' is not one of the choices, please enter numbers between 1 and {}.",
+                            item,
+                            self.choices.len()
+                        )
+                    })?;
+                    if !answers.contains(&choice) {
+                        answers.push(choice);
+                    }
+                }
+                if answers.is_empty() {
+                    return Err("Please pick at least one choice.".to_string());
+                }
+                Ok(answers)
+            }
+        }
+    }
+
+    // A choice is picked by its number or its text
+    fn find_choice(&self, input: &str) -> Option<String> {
+        if let Ok(number) = input.parse::<usize>() {
+            return number
+                .checked_sub(1)
+                .and_then(|index| self.choices.get(index))
+                .cloned();
+        }
+        self.choices
+            .iter()
+            .find(|choice| choice.eq_ignore_ascii_case(input))
+            .cloned()
+    }
+}
+
+impl std::fmt::Display for UserQuestion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.question)?;
+        if matches!(self.answer_type, AnswerType::PickOne | AnswerType::PickMany) {
+            for (index, choice) in self.choices.iter().enumerate() {
+                write!(f, "\n  {}. {}", index + 1, choice)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn question(answer_type: AnswerType) -> UserQuestion {
+        UserQuestion {
+            question: "Which database?".to_string(),
+            answer_type,
+            choices: vec!["SQLite".to_string(), "Postgres".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_parse_answer() {
+        let text = question(AnswerType::Text);
+        assert_eq!(text.parse_answer(" any \n"), Ok(vec!["any".to_string()]));
+        assert!(text.parse_answer("  ").is_err());
+
+        let yes_no = question(AnswerType::YesNo);
+        assert_eq!(yes_no.parse_answer("Y"), Ok(vec!["yes".to_string()]));
+        assert_eq!(yes_no.parse_answer("no"), Ok(vec!["no".to_string()]));
+        assert!(yes_no.parse_answer("maybe").is_err());
+
+        let pick_one = question(AnswerType::PickOne);
+        assert_eq!(pick_one.parse_answer("2"), Ok(vec!["Postgres".to_string()]));
+        assert_eq!(
+            pick_one.parse_answer("sqlite"),
+            Ok(vec!["SQLite".to_string()])
+        );
+        assert_eq!(
+            pick_one.parse_answer("3"),
+            Err("Please enter a number between 1 and 2.".to_string())
+        );
+        assert!(pick_one.parse_answer("0").is_err());
+
+        let pick_many = question(AnswerType::PickMany);
+        assert_eq!(
+            pick_many.parse_answer("2, 1, 2"),
+            Ok(vec!["Postgres".to_string(), "SQLite".to_string()])
+        );
+        assert_eq!(
+            pick_many.parse_answer("SQLite\nPostgres"),
+            Ok(vec!["SQLite".to_string(), "Postgres".to_string()])
+        );
+        assert!(pick_many.parse_answer("1, MySQL").is_err());
+        assert!(pick_many.parse_answer("").is_err());
+    }
+
+    #[test]
+    fn test_question_display() {
+        assert_eq!(
+            question(AnswerType::PickOne).to_string(),
+            "Which database?\n  1. SQLite\n  2. Postgres"
+        );
+        assert_eq!(question(AnswerType::YesNo).to_string(), "Which database?");
+        assert_eq!(
+            question(AnswerType::PickMany).prompt(),
+            "[1-2, separated by commas]>"
+        );
+    }
+}
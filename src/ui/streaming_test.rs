@@ -92,6 +92,11 @@ impl UserInterface for TestUI {
         Ok(())
     }
 
+    async fn append_tool_output(&self, _tool_id: &str, _chunk: &str) -> Result<(), UIError> {
+        // Test implementation does nothing with tool output
+        Ok(())
+    }
+
     async fn update_memory(&self, _memory: &crate::types::WorkingMemory) -> Result<(), UIError> {
         // Test implementation does nothing with memory updates
         Ok(())
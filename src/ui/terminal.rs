@@ -270,6 +270,14 @@ impl UserInterface for TerminalUI {
         Ok(())
     }
 
+    async fn append_tool_output(&self, _tool_id: &str, chunk: &str) -> Result<(), UIError> {
+        // Stream output dimmed, so it stands apart from the agent's text
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "{}", chunk.to_string().dark_grey())?;
+        stdout.flush()?;
+        Ok(())
+    }
+
     async fn update_memory(&self, _memory: &WorkingMemory) -> Result<(), UIError> {
         // Terminal UI doesn't display memory visually, so this is a no-op
         Ok(())
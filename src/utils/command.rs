@@ -1,21 +1,126 @@
+use super::process::{
+    register_process_group, unregister_process_group, ProcessManager, ProcessOutput,
+};
+use super::shell_session::ShellSession;
+use crate::types::ProcessInfo;
 use anyhow::Result;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+
+/// Receives chunks of output while a command is running
+pub type OutputSender = tokio::sync::mpsc::UnboundedSender<String>;
 
 pub struct CommandOutput {
     pub success: bool,
     pub output: String,
+    /// The command was killed because it exceeded its timeout
+    pub timed_out: bool,
 }
 
 #[async_trait::async_trait]
 pub trait CommandExecutor: Send + Sync {
+    /// Run a command to completion. Output is forwarded to `output_sender` as it
+    /// arrives. Without a timeout the command may run forever.
     async fn execute(
         &self,
         command_line: &str,
         working_dir: Option<&PathBuf>,
+        timeout: Option<Duration>,
+        output_sender: Option<OutputSender>,
     ) -> Result<CommandOutput>;
+
+    /// Run a command in the persistent shell session of a project. The session
+    /// is started in `project_root` on first use and keeps its working directory
+    /// and environment between calls.
+    async fn execute_in_session(
+        &self,
+        _project: &str,
+        _project_root: &PathBuf,
+        _command_line: &str,
+        _working_dir: Option<&PathBuf>,
+        _timeout: Option<Duration>,
+        _output_sender: Option<OutputSender>,
+    ) -> Result<CommandOutput> {
+        Err(anyhow::anyhow!(
+            "Persistent shell sessions are not supported"
+        ))
+    }
+
+    /// Terminate the shell session of a project. Returns false if there was none.
+    async fn reset_session(&self, _project: &str) -> Result<bool> {
+        Err(anyhow::anyhow!(
+            "Persistent shell sessions are not supported"
+        ))
+    }
+
+    /// Start a long-running process in the background
+    async fn start_process(
+        &self,
+        _project: &str,
+        _command_line: &str,
+        _working_dir: &PathBuf,
+    ) -> Result<ProcessInfo> {
+        Err(anyhow::anyhow!("Background processes are not supported"))
+    }
+
+    /// Read new output of a background process, or output since the given offset
+    async fn read_process_output(
+        &self,
+        _project: &str,
+        _process_id: &str,
+        _since_offset: Option<usize>,
+        _tail_lines: Option<usize>,
+    ) -> Result<ProcessOutput> {
+        Err(anyhow::anyhow!("Background processes are not supported"))
+    }
+
+    /// Write a line to the stdin of a background process
+    async fn send_process_input(
+        &self,
+        _project: &str,
+        _process_id: &str,
+        _input: &str,
+    ) -> Result<()> {
+        Err(anyhow::anyhow!("Background processes are not supported"))
+    }
+
+    /// Stop a background process including its child processes
+    async fn stop_process(&self, _project: &str, _process_id: &str) -> Result<ProcessInfo> {
+        Err(anyhow::anyhow!("Background processes are not supported"))
+    }
+
+    /// Current state of the background processes
+    async fn list_processes(&self) -> Vec<ProcessInfo> {
+        Vec::new()
+    }
+}
+
+/// Create shell command using login shell or fallback
+pub(crate) fn shell_command(command_line: &str) -> std::process::Command {
+    #[cfg(target_family = "unix")]
+    let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
+    #[cfg(target_family = "unix")]
+    let mut cmd = std::process::Command::new(shell);
+    #[cfg(target_family = "unix")]
+    cmd.args(["-c", command_line]);
+
+    #[cfg(target_family = "windows")]
+    let mut cmd = std::process::Command::new("cmd");
+    #[cfg(target_family = "windows")]
+    cmd.args(["/C", command_line]);
+
+    cmd
 }
 
-pub struct DefaultCommandExecutor;
+/// Runs commands through the shell and owns the background processes and
+/// persistent shell sessions of a session
+#[derive(Default)]
+pub struct DefaultCommandExecutor {
+    processes: ProcessManager,
+    shell_sessions: tokio::sync::Mutex<HashMap<String, ShellSession>>,
+}
 
 #[async_trait::async_trait]
 impl CommandExecutor for DefaultCommandExecutor {
@@ -23,6 +128,8 @@ impl CommandExecutor for DefaultCommandExecutor {
         &self,
         command_line: &str,
         working_dir: Option<&PathBuf>,
+        timeout: Option<Duration>,
+        output_sender: Option<OutputSender>,
     ) -> Result<CommandOutput> {
         // Validate working_dir first
         if let Some(dir) = working_dir {
@@ -40,27 +147,323 @@ impl CommandExecutor for DefaultCommandExecutor {
             }
         }
 
-        // Create shell command using login shell or fallback
-        #[cfg(target_family = "unix")]
-        let shell = std::env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
-        #[cfg(target_family = "unix")]
-        let mut cmd = std::process::Command::new(shell);
-        #[cfg(target_family = "unix")]
-        cmd.args(["-c", &format!("{} 2>&1", command_line)]);
-
-        #[cfg(target_family = "windows")]
-        let mut cmd = std::process::Command::new("cmd");
-        #[cfg(target_family = "windows")]
-        cmd.args(["/C", &format!("{} 2>&1", command_line)]);
-
+        let mut cmd = tokio::process::Command::from(shell_command(command_line));
         if let Some(dir) = working_dir {
             cmd.current_dir(dir);
         }
-        let output = cmd.output()?;
+        cmd.stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true);
+        // Own process group, so a timeout can kill everything the command spawned
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        let mut child = cmd.spawn()?;
+        let pid = child.id();
+        if let Some(pid) = pid {
+            register_process_group(pid);
+        }
+        let (mut stdout, mut stderr) = match (child.stdout.take(), child.stderr.take()) {
+            (Some(stdout), Some(stderr)) => (stdout, stderr),
+            _ => return Err(anyhow::anyhow!("Failed to capture command output")),
+        };
+
+        // Combined stdout and stderr, in the order they arrive
+        let mut output = Vec::new();
+        let run = async {
+            let mut stdout_buf = [0u8; 8192];
+            let mut stderr_buf = [0u8; 8192];
+            let (mut stdout_open, mut stderr_open) = (true, true);
+            while stdout_open || stderr_open {
+                let chunk = tokio::select! {
+                    n = stdout.read(&mut stdout_buf), if stdout_open => {
+                        let n = n?;
+                        stdout_open = n > 0;
+                        stdout_buf[..n].to_vec()
+                    }
+                    n = stderr.read(&mut stderr_buf), if stderr_open => {
+                        let n = n?;
+                        stderr_open = n > 0;
+                        stderr_buf[..n].to_vec()
+                    }
+                };
+                if chunk.is_empty() {
+                    continue;
+                }
+                if let Some(sender) = &output_sender {
+                    let _ = sender.send(String::from_utf8_lossy(&chunk).into_owned());
+                }
+                output.extend_from_slice(&chunk);
+            }
+            Ok::<_, anyhow::Error>(child.wait().await?)
+        };
+
+        let result = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, run).await.ok(),
+            None => Some(run.await),
+        };
+
+        let (success, timed_out) = match result {
+            Some(status) => (status?.success(), false),
+            None => {
+                #[cfg(unix)]
+                if let Some(pid) = pid {
+                    super::process::kill_process_tree(pid, libc::SIGKILL);
+                }
+                let _ = child.kill().await;
+                (false, true)
+            }
+        };
+        if let Some(pid) = pid {
+            unregister_process_group(pid);
+        }
 
         Ok(CommandOutput {
-            success: output.status.success(),
-            output: String::from_utf8_lossy(&output.stdout).into_owned(),
+            success,
+            output: String::from_utf8_lossy(&output).into_owned(),
+            timed_out,
         })
     }
+
+    async fn execute_in_session(
+        &self,
+        project: &str,
+        project_root: &PathBuf,
+        command_line: &str,
+        working_dir: Option<&PathBuf>,
+        timeout: Option<Duration>,
+        output_sender: Option<OutputSender>,
+    ) -> Result<CommandOutput> {
+        if let Some(dir) = working_dir {
+            if !dir.is_dir() {
+                return Err(anyhow::anyhow!(
+                    "Working directory does not exist: {}",
+                    dir.display()
+                ));
+            }
+        }
+
+        let mut sessions = self.shell_sessions.lock().await;
+        if !sessions.contains_key(project) {
+            let session = ShellSession::start(project_root).await?;
+            sessions.insert(project.to_string(), session);
+        }
+        let session = sessions.get_mut(project).unwrap();
+        let result = session
+            .run(
+                command_line,
+                working_dir.map(|d| d.as_path()),
+                timeout,
+                output_sender.as_ref(),
+            )
+            .await;
+
+        // Dropping a dead or stuck session kills it, the next call starts fresh
+        if result.is_err() || !session.is_alive() {
+            sessions.remove(project);
+        }
+        result
+    }
+
+    async fn reset_session(&self, project: &str) -> Result<bool> {
+        Ok(self.shell_sessions.lock().await.remove(project).is_some())
+    }
+
+    async fn start_process(
+        &self,
+        project: &str,
+        command_line: &str,
+        working_dir: &PathBuf,
+    ) -> Result<ProcessInfo> {
+        self.processes
+            .start(project, command_line, working_dir)
+            .await
+    }
+
+    async fn read_process_output(
+        &self,
+        project: &str,
+        process_id: &str,
+        since_offset: Option<usize>,
+        tail_lines: Option<usize>,
+    ) -> Result<ProcessOutput> {
+        self.processes
+            .read_output(project, process_id, since_offset, tail_lines)
+            .await
+    }
+
+    async fn send_process_input(&self, project: &str, process_id: &str, input: &str) -> Result<()> {
+        self.processes.send_input(project, process_id, input).await
+    }
+
+    async fn stop_process(&self, project: &str, process_id: &str) -> Result<ProcessInfo> {
+        self.processes.stop(project, process_id).await
+    }
+
+    async fn list_processes(&self) -> Vec<ProcessInfo> {
+        self.processes.list().await
+    }
+}
+
+/// Shorten long output to its first and last lines. Returns None if the output
+/// fits into the limits.
+pub fn truncate_output(
+    output: &str,
+    head_lines: usize,
+    tail_lines: usize,
+    max_bytes: usize,
+) -> Option<String> {
+    let lines: Vec<&str> = output.lines().collect();
+    if lines.len() <= head_lines + tail_lines && output.len() <= max_bytes {
+        return None;
+    }
+
+    let (mut head, mut tail) = if lines.len() > head_lines + tail_lines {
+        (
+            lines[..head_lines].join("\n"),
+            lines[lines.len() - tail_lines..].join("\n"),
+        )
+    } else {
+        (String::new(), output.to_string())
+    };
+
+    // Very long lines can still exceed the byte limit, the end matters most
+    let head_bytes = max_bytes / 4;
+    if head.len() > head_bytes {
+        let mut cut = head_bytes;
+        while !head.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        head.truncate(cut);
+    }
+    let tail_bytes = max_bytes - head.len();
+    if tail.len() > tail_bytes {
+        let mut cut = tail.len() - tail_bytes;
+        while !tail.is_char_boundary(cut) {
+            cut += 1;
+        }
+        tail = tail[cut..].to_string();
+    }
+
+    let omitted_bytes = output.len().saturating_sub(head.len() + tail.len());
+    let omitted_lines = lines
+        .len()
+        .saturating_sub(head.lines().count() + tail.lines().count());
+    let separator = if head.is_empty() { "" } else { "\n" };
+    Some(format!(
+        "{}{}[... {} lines ({} bytes) omitted ...]\n{}",
+        head, separator, omitted_lines, omitted_bytes, tail
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_output() {
+        let output = (1..=100)
+            .map(|i| format!("line {}", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        assert!(truncate_output(&output, 50, 50, 10_000).is_none());
+
+        let truncated = truncate_output(&output, 2, 3, 10_000).unwrap();
+        assert_eq!(
+            truncated,
+            "line 1\nline 2\n[... 95 lines (754 bytes) omitted ...]\nline 98\nline 99\nline 100"
+        );
+
+        // A single huge line is cut by bytes, keeping the end
+        let long_line = "x".repeat(1000) + "END";
+        let truncated = truncate_output(&long_line, 10, 10, 100).unwrap();
+        assert!(truncated.starts_with("[..."));
+        assert!(truncated.ends_with("END"));
+        assert!(truncated.len() < 200);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_execute_streams_output() -> Result<()> {
+        let executor = DefaultCommandExecutor::default();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+
+        let output = executor
+            .execute(
+                "echo one; sleep 0.1; echo two >&2",
+                None,
+                None,
+                Some(sender),
+            )
+            .await?;
+        assert!(output.success);
+        assert!(!output.timed_out);
+        assert_eq!(output.output, "one\ntwo\n");
+
+        let mut streamed = String::new();
+        while let Some(chunk) = receiver.recv().await {
+            streamed.push_str(&chunk);
+        }
+        assert_eq!(streamed, output.output);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_execute_in_session() -> Result<()> {
+        let executor = DefaultCommandExecutor::default();
+        let dir = tempfile::TempDir::new()?;
+        let root = dir.path().to_path_buf();
+
+        executor
+            .execute_in_session("test", &root, "export GREETING=hi", None, None, None)
+            .await?;
+        let output = executor
+            .execute_in_session("test", &root, "echo $GREETING", None, None, None)
+            .await?;
+        assert_eq!(output.output, "hi\n");
+
+        // Sessions are per project
+        let output = executor
+            .execute_in_session("other", &root, "echo \"[$GREETING]\"", None, None, None)
+            .await?;
+        assert_eq!(output.output, "[]\n");
+
+        assert!(executor.reset_session("test").await?);
+        assert!(!executor.reset_session("test").await?);
+        let output = executor
+            .execute_in_session("test", &root, "echo \"[$GREETING]\"", None, None, None)
+            .await?;
+        assert_eq!(output.output, "[]\n");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_execute_timeout_kills_process_tree() -> Result<()> {
+        let executor = DefaultCommandExecutor::default();
+        let dir = tempfile::TempDir::new()?;
+        let marker = dir.path().join("marker");
+
+        let start = std::time::Instant::now();
+        let output = executor
+            .execute(
+                &format!("echo started; (sleep 2; touch {}) & wait", marker.display()),
+                None,
+                Some(Duration::from_millis(300)),
+                None,
+            )
+            .await?;
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(output.timed_out);
+        assert!(!output.success);
+        assert_eq!(output.output, "started\n");
+
+        // The background child was killed along with the shell
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+        assert!(!marker.exists());
+        Ok(())
+    }
 }
@@ -1,5 +1,7 @@
-use crate::types::FileReplacement;
+use crate::types::{FileReplacement, LineEdit, LineOperation, ReplacementSite};
 use crate::utils::encoding;
+use regex::Regex;
+use std::path::Path;
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub enum FileUpdaterError {
@@ -79,6 +81,296 @@ pub fn apply_replacements_normalized(
     Ok(result)
 }
 
+/// Prefixes each line with its number, starting at `first_line`
+pub fn number_lines(content: &str, first_line: usize) -> String {
+    let line_count = content.lines().count();
+    let width = (first_line + line_count.saturating_sub(1))
+        .to_string()
+        .len();
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| format!("{:>width$} | {}", first_line + index, line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Applies a line-based edit to normalized content
+pub fn apply_line_edit(content: &str, edit: &LineEdit) -> Result<String, anyhow::Error> {
+    let normalized_content = encoding::normalize_content(content);
+    let mut lines: Vec<&str> = if normalized_content.is_empty() {
+        Vec::new()
+    } else {
+        normalized_content.split('\n').collect()
+    };
+
+    let is_insert = matches!(
+        edit.operation,
+        LineOperation::InsertAfter | LineOperation::InsertBefore
+    );
+    let start = edit.start_line;
+    let end = if is_insert {
+        start
+    } else {
+        edit.end_line.unwrap_or(start)
+    };
+    // Content can be inserted into an empty file
+    let inserts_into_empty = is_insert && lines.is_empty() && start == 1;
+    if start == 0 || end < start || (end > lines.len() && !inserts_into_empty) {
+        return Err(FileUpdaterError::Other(format!(
+            "Invalid line range {}-{}, the file has {} lines",
+            start,
+            end,
+            lines.len()
+        ))
+        .into());
+    }
+
+    if let Some(expected) = &edit.expected {
+        let current = lines[start - 1..end.min(lines.len())].join("\n");
+        if encoding::normalize_content(expected).trim_end_matches('\n') != current {
+            return Err(FileUpdaterError::Other(format!(
+                "Lines {}-{} don't contain the expected content, the file has changed. They currently are:\n{}\nPlease check the line numbers and try again.",
+                start,
+                end,
+                number_lines(&current, start)
+            ))
+            .into());
+        }
+    }
+
+    if edit.operation != LineOperation::DeleteRange && edit.content.is_empty() {
+        return Err(FileUpdaterError::Other(
+            "The content parameter is required for this operation".to_string(),
+        )
+        .into());
+    }
+    // A trailing line break doesn't add an empty line
+    let new_content = edit.content.replace("\r\n", "\n");
+    let new_content = new_content.strip_suffix('\n').unwrap_or(&new_content);
+    let new_lines: Vec<&str> = new_content.split('\n').map(|l| l.trim_end()).collect();
+
+    match edit.operation {
+        LineOperation::InsertAfter if !lines.is_empty() => {
+            lines.splice(start..start, new_lines);
+        }
+        LineOperation::InsertAfter | LineOperation::InsertBefore => {
+            lines.splice(start - 1..start - 1, new_lines);
+        }
+        LineOperation::DeleteRange => {
+            lines.drain(start - 1..end);
+        }
+        LineOperation::ReplaceRange => {
+            lines.splice(start - 1..end, new_lines);
+        }
+    }
+
+    Ok(lines.join("\n"))
+}
+
+#[test]
+fn test_apply_line_edit() -> Result<(), anyhow::Error> {
+    let content = "fn a() {}\nfn b() {}\nfn c() {}\n";
+    let edit = |operation, start_line, end_line, content: &str, expected: Option<&str>| LineEdit {
+        operation,
+        start_line,
+        end_line,
+        content: content.to_string(),
+        expected: expected.map(|e| e.to_string()),
+    };
+
+    let test_cases = vec![
+        (
+            edit(LineOperation::InsertAfter, 3, None, "fn d() {}\n", None),
+            "fn a() {}\nfn b() {}\nfn c() {}\nfn d() {}",
+        ),
+        (
+            edit(LineOperation::InsertBefore, 1, None, "use x;\n\n", None),
+            "use x;\n\nfn a() {}\nfn b() {}\nfn c() {}",
+        ),
+        (
+            edit(
+                LineOperation::DeleteRange,
+                1,
+                Some(2),
+                "",
+                Some("fn a() {}\nfn b() {}"),
+            ),
+            "fn c() {}",
+        ),
+        (
+            edit(
+                LineOperation::ReplaceRange,
+                2,
+                None,
+                "fn e() {}  ",
+                Some("fn b() {}\n"),
+            ),
+            "fn a() {}\nfn e() {}\nfn c() {}",
+        ),
+    ];
+    for (line_edit, expected) in test_cases {
+        assert_eq!(apply_line_edit(content, &line_edit)?, expected);
+    }
+
+    // Stale line numbers are detected
+    let error = apply_line_edit(
+        content,
+        &edit(LineOperation::DeleteRange, 2, None, "", Some("fn a() {}")),
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Lines 2-2 don't contain the expected content, the file has changed. They currently are:\n2 | fn b() {}\nPlease check the line numbers and try again."
+    );
+    assert!(apply_line_edit(
+        content,
+        &edit(LineOperation::DeleteRange, 3, Some(4), "", None)
+    )
+    .is_err());
+    assert_eq!(
+        apply_line_edit("", &edit(LineOperation::InsertAfter, 1, None, "x", None))?,
+        "x"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_number_lines() {
+    assert_eq!(number_lines("a\nb\nc", 9), " 9 | a\n10 | b\n11 | c");
+}
+
+/// Unified diff of a file edit with three lines of context
+pub fn unified_diff(path: &Path, old: &str, new: &str) -> String {
+    let path = path.display().to_string().replace('\\', "/");
+    similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string()
+}
+
+/// Replaces all matches of `regex` in `content`. Capture groups like `$1` in
+/// the replacement are expanded unless it is `literal`. Returns the new
+/// content and the changed lines.
+pub fn replace_pattern(
+    path: &Path,
+    content: &str,
+    regex: &Regex,
+    replacement: &str,
+    literal: bool,
+) -> (String, Vec<ReplacementSite>) {
+    // Matches as (start, end, replacement), grouped by the lines they touch
+    let mut groups: Vec<(usize, usize, Vec<(usize, usize, String)>)> = Vec::new();
+    for captures in regex.captures_iter(content) {
+        let m = captures.get(0).unwrap();
+        let mut expanded = String::new();
+        if literal {
+            expanded.push_str(replacement);
+        } else {
+            captures.expand(replacement, &mut expanded);
+        }
+
+        let line_start = content[..m.start()].rfind('\n').map_or(0, |i| i + 1);
+        // A match ending with a line break doesn't touch the next line
+        let last = if content[..m.end()].ends_with('\n') && m.end() > m.start() {
+            m.end() - 1
+        } else {
+            m.end()
+        };
+        let line_end = content[last..]
+            .find('\n')
+            .map_or(content.len(), |i| last + i);
+
+        match groups.last_mut() {
+            Some((_, end, matches)) if line_start <= *end => {
+                *end = (*end).max(line_end);
+                matches.push((m.start(), m.end(), expanded));
+            }
+            _ => groups.push((line_start, line_end, vec![(m.start(), m.end(), expanded)])),
+        }
+    }
+
+    let mut updated = String::with_capacity(content.len());
+    let mut copied = 0;
+    let mut sites = Vec::new();
+    for (start, end, matches) in groups {
+        let mut after = String::new();
+        let mut position = start;
+        for (match_start, match_end, expanded) in &matches {
+            after.push_str(&content[position..*match_start]);
+            after.push_str(expanded);
+            position = *match_end;
+        }
+        after.push_str(&content[position..end]);
+
+        updated.push_str(&content[copied..start]);
+        updated.push_str(&after);
+        copied = end;
+
+        let before = &content[start..end];
+        if before != after {
+            sites.push(ReplacementSite {
+                path: path.to_path_buf(),
+                line: content[..start].matches('\n').count() + 1,
+                before: before.to_string(),
+                after,
+                matches: matches.len(),
+            });
+        }
+    }
+    updated.push_str(&content[copied..]);
+
+    (updated, sites)
+}
+
+#[test]
+fn test_replace_pattern() {
+    let path = Path::new("src/lib.rs");
+    let content = "fn load_config() {}\n\nfn main() {\n    load_config(); load_config();\n}\n";
+
+    let regex = Regex::new(r"\bload_(\w+)\(").unwrap();
+    let (updated, sites) = replace_pattern(path, content, &regex, "read_$1(", false);
+    assert_eq!(
+        updated,
+        "fn read_config() {}\n\nfn main() {\n    read_config(); read_config();\n}\n"
+    );
+    assert_eq!(sites.len(), 2);
+    assert_eq!(sites[1].line, 4);
+    assert_eq!(sites[1].before, "    load_config(); load_config();");
+    assert_eq!(sites[1].after, "    read_config(); read_config();");
+    assert_eq!(sites[1].matches, 2);
+
+    // Literal replacements don't expand capture groups
+    let regex = Regex::new(&regex::escape("main")).unwrap();
+    let (updated, sites) = replace_pattern(path, content, &regex, "$1", true);
+    assert!(updated.contains("fn $1() {"));
+    assert_eq!(sites[0].line, 3);
+
+    // Matches over several lines form one site
+    let regex = Regex::new(r"\{\n\s+").unwrap();
+    let (updated, sites) = replace_pattern(path, content, &regex, "{ ", false);
+    assert!(updated.contains("fn main() { load_config();"));
+    assert_eq!(sites.len(), 1);
+    assert_eq!(
+        sites[0].before,
+        "fn main() {\n    load_config(); load_config();"
+    );
+}
+
+#[test]
+fn test_unified_diff() {
+    let diff = unified_diff(
+        Path::new("src/lib.rs"),
+        "fn a() {}\nfn b() {}\n",
+        "fn a() {}\nfn c() {}\n",
+    );
+    assert_eq!(
+        diff,
+        "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -1,2 +1,2 @@\n fn a() {}\n-fn b() {}\n+fn c() {}\n"
+    );
+}
+
 #[test]
 fn test_apply_replacements_normalized() -> Result<(), anyhow::Error> {
     let test_cases: Vec<(&str, Vec<FileReplacement>, Result<&str, &str>)> = vec![
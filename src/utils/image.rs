@@ -0,0 +1,183 @@
+use crate::types::ImageData;
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use image::{DynamicImage, ImageFormat, ImageReader};
+use std::io::Cursor;
+use std::path::Path;
+
+/// Larger image files are not loaded at all
+pub const MAX_IMAGE_FILE_BYTES: usize = 20 * 1024 * 1024;
+/// Images with a longer edge are downscaled, larger ones don't improve what models see
+pub const MAX_IMAGE_DIMENSION: u32 = 1568;
+/// Images that are larger after encoding are downscaled, providers reject them otherwise
+pub const MAX_IMAGE_BYTES: usize = 3 * 1024 * 1024;
+
+/// Media type for image files that can be sent to models, by extension
+pub fn image_media_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Reads an image file with `load_image`. Files that are too large are
+/// rejected before they are read.
+pub fn load_image_file(path: &Path) -> Result<ImageData> {
+    check_image_size(std::fs::metadata(path)?.len())?;
+    load_image(&std::fs::read(path)?)
+}
+
+/// Prepares an image file for the model: the format is detected from the
+/// content, and images that are too large are downscaled
+pub fn load_image(bytes: &[u8]) -> Result<ImageData> {
+    check_image_size(bytes.len() as u64)?;
+
+    let format = image::guess_format(bytes)
+        .map_err(|_| anyhow!("Unknown image format, supported are PNG, JPEG, GIF and WebP"))?;
+    let media_type = match format {
+        ImageFormat::Png => "image/png",
+        ImageFormat::Jpeg => "image/jpeg",
+        ImageFormat::Gif => "image/gif",
+        ImageFormat::WebP => "image/webp",
+        other => {
+            return Err(anyhow!(
+                "Unsupported image format {:?}, supported are PNG, JPEG, GIF and WebP",
+                other
+            ))
+        }
+    };
+
+    let (width, height) = ImageReader::with_format(Cursor::new(bytes), format)
+        .into_dimensions()
+        .map_err(|e| anyhow!("Failed to read image: {}", e))?;
+
+    // Small images are sent unchanged, which keeps e.g. GIF animations
+    if width.max(height) <= MAX_IMAGE_DIMENSION && bytes.len() <= MAX_IMAGE_BYTES {
+        return Ok(ImageData {
+            media_type: media_type.to_string(),
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+            width,
+            height,
+            original_size: None,
+        });
+    }
+
+    let image = image::load_from_memory_with_format(bytes, format)
+        .map_err(|e| anyhow!("Failed to decode image: {}", e))?;
+    let mut max_dimension = MAX_IMAGE_DIMENSION.min(width.max(height));
+    loop {
+        let resized = image.resize(
+            max_dimension,
+            max_dimension,
+            image::imageops::FilterType::Triangle,
+        );
+        // Photos stay JPEG, everything else becomes a lossless PNG
+        let (encoded, media_type) = if format == ImageFormat::Jpeg {
+            (
+                encode(
+                    &DynamicImage::ImageRgb8(resized.to_rgb8()),
+                    ImageFormat::Jpeg,
+                )?,
+                "image/jpeg",
+            )
+        } else {
+            (encode(&resized, ImageFormat::Png)?, "image/png")
+        };
+
+        if encoded.len() <= MAX_IMAGE_BYTES || max_dimension <= 256 {
+            return Ok(ImageData {
+                media_type: media_type.to_string(),
+                data: base64::engine::general_purpose::STANDARD.encode(&encoded),
+                width: resized.width(),
+                height: resized.height(),
+                original_size: Some((width, height)),
+            });
+        }
+        max_dimension = max_dimension * 3 / 4;
+    }
+}
+
+fn check_image_size(size: u64) -> Result<()> {
+    if size > MAX_IMAGE_FILE_BYTES as u64 {
+        return Err(anyhow!(
+            "Image is too large ({} bytes, at most {} bytes are supported)",
+            size,
+            MAX_IMAGE_FILE_BYTES
+        ));
+    }
+    Ok(())
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>> {
+    let mut encoded = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut encoded), format)
+        .map_err(|e| anyhow!("Failed to encode image: {}", e))?;
+    Ok(encoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbImage::from_fn(width, height, |x, y| Rgb([x as u8, y as u8, 128]));
+        encode(&DynamicImage::ImageRgb8(image), ImageFormat::Png).unwrap()
+    }
+
+    #[test]
+    fn test_load_image() -> Result<()> {
+        // Small images are passed through unchanged
+        let bytes = png(40, 20);
+        let image = load_image(&bytes)?;
+        assert_eq!(image.media_type, "image/png");
+        assert_eq!((image.width, image.height), (40, 20));
+        assert_eq!(image.original_size, None);
+        assert_eq!(
+            base64::engine::general_purpose::STANDARD.decode(&image.data)?,
+            bytes
+        );
+
+        // Large images are downscaled, keeping the aspect ratio
+        let image = load_image(&png(3136, 1000))?;
+        assert_eq!((image.width, image.height), (1568, 500));
+        assert_eq!(image.original_size, Some((3136, 1000)));
+
+        assert!(load_image(b"not an image").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_image_file() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let path = dir.path().join("small.png");
+        std::fs::write(&path, png(40, 20))?;
+        assert_eq!(load_image_file(&path)?.width, 40);
+
+        // Too large files are rejected by their size, without reading them
+        let path = dir.path().join("huge.png");
+        std::fs::File::create(&path)?.set_len(MAX_IMAGE_FILE_BYTES as u64 + 1)?;
+        let error = load_image_file(&path).unwrap_err();
+        assert!(error.to_string().contains("Image is too large"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_media_type() {
+        assert_eq!(
+            image_media_type(Path::new("docs/ui.PNG")),
+            Some("image/png")
+        );
+        assert_eq!(
+            image_media_type(Path::new("photo.jpeg")),
+            Some("image/jpeg")
+        );
+        assert_eq!(image_media_type(Path::new("logo.svg")), None);
+        assert_eq!(image_media_type(Path::new("Makefile")), None);
+    }
+}
@@ -1,8 +1,19 @@
 mod command;
 mod file_updater;
+mod process;
+mod shell_session;
 
 pub mod encoding;
+pub mod image;
 
 #[allow(unused_imports)]
-pub use command::{CommandExecutor, CommandOutput, DefaultCommandExecutor};
-pub use file_updater::{apply_replacements_normalized, FileUpdaterError};
+pub use command::{
+    truncate_output, CommandExecutor, CommandOutput, DefaultCommandExecutor, OutputSender,
+};
+pub use file_updater::{
+    apply_line_edit, apply_replacements_normalized, number_lines, replace_pattern, unified_diff,
+    FileUpdaterError,
+};
+pub use process::kill_all_processes;
+pub(crate) use process::{kill_process_group, register_process_group};
+pub(crate) use shell_session::shell_quote;
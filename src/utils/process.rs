@@ -0,0 +1,474 @@
+use crate::types::ProcessInfo;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStdin};
+
+// Maximum number of bytes of output kept per process
+const MAX_BUFFER_SIZE: usize = 1024 * 1024;
+// Maximum number of bytes returned by a single read
+const MAX_READ_SIZE: usize = 32 * 1024;
+// How long to wait for a process to exit after SIGTERM before killing it
+const STOP_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Process groups of all live child processes, so they can be cleaned up
+// on exit paths that skip destructors (GUI shutdown, Ctrl-C)
+#[cfg(unix)]
+static PROCESS_GROUPS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Output read from a background process
+#[derive(Debug, Clone)]
+pub struct ProcessOutput {
+    pub output: String,
+    /// Offset to pass as `since_offset` to continue reading after this output
+    pub next_offset: usize,
+    /// Whether older output was skipped because of buffer or read limits
+    pub truncated: bool,
+    pub process: ProcessInfo,
+}
+
+/// Combined stdout/stderr of a process. Offsets are absolute byte positions
+/// in the stream, so they stay valid when old output is dropped.
+#[derive(Default)]
+struct OutputBuffer {
+    data: String,
+    /// Number of bytes dropped from the front of `data`
+    dropped: usize,
+    /// Offset up to which output was already returned
+    read_offset: usize,
+}
+
+impl OutputBuffer {
+    fn push(&mut self, text: &str) {
+        self.data.push_str(text);
+        if self.data.len() > MAX_BUFFER_SIZE {
+            let mut cut = self.data.len() - MAX_BUFFER_SIZE;
+            while !self.data.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.data.drain(..cut);
+            self.dropped += cut;
+        }
+    }
+
+    fn end_offset(&self) -> usize {
+        self.dropped + self.data.len()
+    }
+
+    fn slice_from(&self, offset: usize) -> (&str, bool) {
+        let truncated = offset < self.dropped;
+        let mut start = offset.saturating_sub(self.dropped).min(self.data.len());
+        while !self.data.is_char_boundary(start) {
+            start += 1;
+        }
+        (&self.data[start..], truncated)
+    }
+}
+
+struct ManagedProcess {
+    info: ProcessInfo,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    output: Arc<Mutex<OutputBuffer>>,
+}
+
+impl ManagedProcess {
+    /// Refresh running state and exit code
+    fn refresh(&mut self) -> &ProcessInfo {
+        if self.info.running {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                self.info.running = false;
+                self.info.exit_code = status.code();
+            }
+        }
+        &self.info
+    }
+}
+
+/// Keeps track of long-running child processes started by the agent.
+/// All processes are killed when the manager is dropped.
+pub struct ProcessManager {
+    processes: tokio::sync::Mutex<HashMap<String, ManagedProcess>>,
+    next_id: AtomicUsize,
+}
+
+impl Default for ProcessManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProcessManager {
+    pub fn new() -> Self {
+        Self {
+            processes: tokio::sync::Mutex::new(HashMap::new()),
+            next_id: AtomicUsize::new(1),
+        }
+    }
+
+    pub async fn start(
+        &self,
+        project: &str,
+        command_line: &str,
+        working_dir: &Path,
+    ) -> Result<ProcessInfo> {
+        if !working_dir.is_dir() {
+            return Err(anyhow::anyhow!(
+                "Working directory does not exist: {}",
+                working_dir.display()
+            ));
+        }
+
+        let mut cmd = tokio::process::Command::from(super::command::shell_command(command_line));
+        cmd.current_dir(working_dir)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true);
+        // Own process group, so we can stop everything the command spawns
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        let mut child = cmd.spawn()?;
+        if let Some(pid) = child.id() {
+            register_process_group(pid);
+        }
+        let output = Arc::new(Mutex::new(OutputBuffer::default()));
+        if let Some(stdout) = child.stdout.take() {
+            tokio::spawn(collect_output(stdout, output.clone()));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(collect_output(stderr, output.clone()));
+        }
+
+        let id = format!("proc-{}", self.next_id.fetch_add(1, Ordering::Relaxed));
+        let info = ProcessInfo {
+            id: id.clone(),
+            project: project.to_string(),
+            command_line: command_line.to_string(),
+            pid: child.id(),
+            started_at: chrono::Utc::now(),
+            running: true,
+            exit_code: None,
+        };
+
+        self.processes.lock().await.insert(
+            id,
+            ManagedProcess {
+                info: info.clone(),
+                stdin: child.stdin.take(),
+                child,
+                output,
+            },
+        );
+
+        Ok(info)
+    }
+
+    /// Current state of all processes
+    pub async fn list(&self) -> Vec<ProcessInfo> {
+        let mut processes = self.processes.lock().await;
+        processes
+            .values_mut()
+            .map(|process| process.refresh().clone())
+            .collect()
+    }
+
+    /// Read output of a process. Without `since_offset`, returns everything not
+    /// returned by a previous read. `tail_lines` limits the result to the last lines.
+    pub async fn read_output(
+        &self,
+        project: &str,
+        id: &str,
+        since_offset: Option<usize>,
+        tail_lines: Option<usize>,
+    ) -> Result<ProcessOutput> {
+        let mut processes = self.processes.lock().await;
+        let process = get_process(&mut processes, project, id)?;
+        let info = process.refresh().clone();
+
+        let mut buffer = process.output.lock().unwrap();
+        let offset = since_offset.unwrap_or(buffer.read_offset);
+        let (text, mut truncated) = buffer.slice_from(offset);
+
+        let mut start = 0;
+        if let Some(n) = tail_lines {
+            let trimmed = text.trim_end_matches('\n');
+            start = if n == 0 {
+                text.len()
+            } else {
+                trimmed
+                    .match_indices('\n')
+                    .rev()
+                    .nth(n - 1)
+                    .map(|(pos, _)| pos + 1)
+                    .unwrap_or(0)
+            };
+            truncated |= start > 0;
+        }
+        if text.len() - start > MAX_READ_SIZE {
+            start = text.len() - MAX_READ_SIZE;
+            while !text.is_char_boundary(start) {
+                start += 1;
+            }
+            truncated = true;
+        }
+
+        let output = text[start..].to_string();
+        let next_offset = buffer.end_offset();
+        // Re-reading older output must not move the default cursor
+        if since_offset.is_none() {
+            buffer.read_offset = next_offset;
+        }
+
+        Ok(ProcessOutput {
+            output,
+            next_offset,
+            truncated,
+            process: info,
+        })
+    }
+
+    pub async fn send_input(&self, project: &str, id: &str, input: &str) -> Result<()> {
+        let mut processes = self.processes.lock().await;
+        let process = get_process(&mut processes, project, id)?;
+        if !process.refresh().running {
+            return Err(anyhow::anyhow!("Process {} is no longer running", id));
+        }
+        let stdin = process
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("Input of process {} is closed", id))?;
+
+        let mut input = input.to_string();
+        if !input.ends_with('\n') {
+            input.push('\n');
+        }
+        stdin.write_all(input.as_bytes()).await?;
+        stdin.flush().await?;
+        Ok(())
+    }
+
+    /// Stop a process and everything it spawned, and forget about it
+    pub async fn stop(&self, project: &str, id: &str) -> Result<ProcessInfo> {
+        let mut processes = self.processes.lock().await;
+        get_process(&mut processes, project, id)?;
+        let mut process = processes.remove(id).unwrap();
+
+        if process.refresh().running {
+            // Ask nicely first, then force
+            #[cfg(unix)]
+            if let Some(pid) = process.child.id() {
+                kill_process_tree(pid, libc::SIGTERM);
+            }
+            #[cfg(not(unix))]
+            let _ = process.child.start_kill();
+
+            let status = match tokio::time::timeout(STOP_TIMEOUT, process.child.wait()).await {
+                Ok(status) => status?,
+                Err(_) => {
+                    #[cfg(unix)]
+                    if let Some(pid) = process.child.id() {
+                        kill_process_tree(pid, libc::SIGKILL);
+                    }
+                    process.child.kill().await?;
+                    process.child.wait().await?
+                }
+            };
+            process.info.running = false;
+            process.info.exit_code = status.code();
+        }
+        if let Some(pid) = process.info.pid {
+            unregister_process_group(pid);
+        }
+
+        Ok(process.info)
+    }
+}
+
+impl Drop for ProcessManager {
+    fn drop(&mut self) {
+        // kill_on_drop only reaches the shell, so take down the whole group
+        for process in self.processes.get_mut().values_mut() {
+            if let Some(pid) = process.info.pid {
+                unregister_process_group(pid);
+            }
+            if process.refresh().running {
+                #[cfg(unix)]
+                if let Some(pid) = process.child.id() {
+                    kill_process_tree(pid, libc::SIGKILL);
+                }
+                let _ = process.child.start_kill();
+            }
+        }
+    }
+}
+
+/// Remember a process group for cleanup by `kill_all_processes`
+pub(crate) fn register_process_group(pid: u32) {
+    #[cfg(unix)]
+    PROCESS_GROUPS.lock().unwrap().push(pid);
+    #[cfg(not(unix))]
+    let _ = pid;
+}
+
+pub(crate) fn unregister_process_group(pid: u32) {
+    #[cfg(unix)]
+    PROCESS_GROUPS.lock().unwrap().retain(|p| *p != pid);
+    #[cfg(not(unix))]
+    let _ = pid;
+}
+
+/// Kill the process group led by `pid` and forget about it
+pub(crate) fn kill_process_group(pid: u32) {
+    unregister_process_group(pid);
+    #[cfg(unix)]
+    kill_process_tree(pid, libc::SIGKILL);
+}
+
+/// Terminate all processes started by the agent that are still running
+pub fn kill_all_processes() {
+    #[cfg(unix)]
+    for pid in PROCESS_GROUPS.lock().unwrap().drain(..) {
+        kill_process_tree(pid, libc::SIGTERM);
+    }
+}
+
+fn get_process<'a>(
+    processes: &'a mut HashMap<String, ManagedProcess>,
+    project: &str,
+    id: &str,
+) -> Result<&'a mut ManagedProcess> {
+    match processes.get_mut(id) {
+        Some(process) if process.info.project == project => Ok(process),
+        _ => Err(anyhow::anyhow!(
+            "Process {} not found in project {}",
+            id,
+            project
+        )),
+    }
+}
+
+/// Send a signal to the process group led by `pid`
+#[cfg(unix)]
+pub(crate) fn kill_process_tree(pid: u32, signal: i32) {
+    unsafe {
+        libc::kill(-(pid as i32), signal);
+    }
+}
+
+async fn collect_output<R: AsyncRead + Unpin>(mut reader: R, output: Arc<Mutex<OutputBuffer>>) {
+    let mut chunk = [0u8; 8192];
+    // Bytes of an incomplete UTF-8 sequence at the end of the last chunk
+    let mut pending = Vec::new();
+    loop {
+        match reader.read(&mut chunk).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                pending.extend_from_slice(&chunk[..n]);
+                let valid = match std::str::from_utf8(&pending) {
+                    Ok(_) => pending.len(),
+                    Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                    Err(_) => pending.len(),
+                };
+                let text = String::from_utf8_lossy(&pending[..valid]).into_owned();
+                pending.drain(..valid);
+                output.lock().unwrap().push(&text);
+            }
+        }
+    }
+    if !pending.is_empty() {
+        output
+            .lock()
+            .unwrap()
+            .push(&String::from_utf8_lossy(&pending));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_background_process_lifecycle() -> Result<()> {
+        let manager = ProcessManager::new();
+        let dir = tempfile::TempDir::new()?;
+
+        let info = manager
+            .start(
+                "test",
+                "echo ready; while read line; do echo got $line; done",
+                dir.path(),
+            )
+            .await?;
+        assert_eq!(info.id, "proc-1");
+        assert!(info.running);
+
+        manager.send_input("test", &info.id, "hello").await?;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let output = manager.read_output("test", &info.id, None, None).await?;
+        assert_eq!(output.output, "ready\ngot hello\n");
+        assert!(output.process.running);
+
+        // Incremental read returns nothing new
+        let again = manager.read_output("test", &info.id, None, None).await?;
+        assert_eq!(again.output, "");
+        assert_eq!(again.next_offset, output.next_offset);
+
+        // Offsets and tails work on the full buffer
+        let tail = manager
+            .read_output("test", &info.id, Some(0), Some(1))
+            .await?;
+        assert_eq!(tail.output, "got hello\n");
+
+        // Reading from an explicit offset keeps the default cursor
+        manager.send_input("test", &info.id, "again").await?;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        manager.read_output("test", &info.id, Some(0), None).await?;
+        let new = manager.read_output("test", &info.id, None, None).await?;
+        assert_eq!(new.output, "got again\n");
+
+        // Process belongs to its project only
+        assert!(manager
+            .read_output("other", &info.id, None, None)
+            .await
+            .is_err());
+
+        let stopped = manager.stop("test", &info.id).await?;
+        assert!(!stopped.running);
+        assert!(manager
+            .read_output("test", &info.id, None, None)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_exited_process_keeps_output() -> Result<()> {
+        let manager = ProcessManager::new();
+        let dir = tempfile::TempDir::new()?;
+
+        let info = manager
+            .start("test", "echo done; exit 3", dir.path())
+            .await?;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let output = manager.read_output("test", &info.id, None, None).await?;
+        assert_eq!(output.output, "done\n");
+        assert!(!output.process.running);
+        assert_eq!(output.process.exit_code, Some(3));
+        assert!(manager.send_input("test", &info.id, "x").await.is_err());
+
+        let listed = manager.list().await;
+        assert_eq!(listed.len(), 1);
+        assert!(!listed[0].running);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,309 @@
+use super::command::{CommandOutput, OutputSender};
+use super::process::{register_process_group, unregister_process_group};
+use anyhow::Result;
+use rand::Rng;
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStdin, ChildStdout};
+
+// Appended to the output when the shell is gone after a command
+const SESSION_ENDED_NOTE: &str = "[Shell session ended, the next command starts a new session]";
+
+/// A long-lived shell that keeps working directory and environment between
+/// commands. Each command is followed by a unique marker line carrying its
+/// exit status, which separates the output of consecutive commands.
+pub struct ShellSession {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+    /// Output that arrived after the marker of the previous command
+    pending: Vec<u8>,
+    alive: bool,
+}
+
+impl ShellSession {
+    pub async fn start(working_dir: &Path) -> Result<Self> {
+        if !cfg!(unix) {
+            return Err(anyhow::anyhow!(
+                "Persistent shell sessions are only supported on Unix"
+            ));
+        }
+
+        let mut cmd = tokio::process::Command::new(session_shell());
+        cmd.current_dir(working_dir)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        let mut child = cmd.spawn()?;
+        if let Some(pid) = child.id() {
+            register_process_group(pid);
+        }
+        let (stdin, stdout) = match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => (stdin, stdout),
+            _ => return Err(anyhow::anyhow!("Failed to connect to shell session")),
+        };
+
+        let mut session = Self {
+            child,
+            stdin,
+            stdout,
+            pending: Vec::new(),
+            alive: true,
+        };
+        // Merge stderr into stdout for the rest of the session
+        session.stdin.write_all(b"exec 2>&1\n").await?;
+        Ok(session)
+    }
+
+    /// Whether the session can run further commands
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+
+    pub async fn run(
+        &mut self,
+        command_line: &str,
+        working_dir: Option<&Path>,
+        timeout: Option<Duration>,
+        output_sender: Option<&OutputSender>,
+    ) -> Result<CommandOutput> {
+        let marker = format!(
+            "__CODE_ASSISTANT_DONE_{:016x}__",
+            rand::thread_rng().gen::<u64>()
+        );
+
+        // Run in the current shell (not a subshell) so cd and exports persist.
+        // Stdin is detached so commands can't swallow the marker.
+        let mut script = String::new();
+        if let Some(dir) = working_dir {
+            script.push_str(&format!("cd {} && ", shell_quote(&dir.to_string_lossy())));
+        }
+        script.push_str(&format!("{{\n{}\n}} < /dev/null\n", command_line));
+        script.push_str(&format!("printf '\\n%s %d\\n' '{}' \"$?\"\n", marker));
+
+        if let Err(e) = self.write(script.as_bytes()).await {
+            self.alive = false;
+            return Err(e);
+        }
+
+        let needle = format!("\n{} ", marker).into_bytes();
+        let deadline = timeout.map(|t| tokio::time::Instant::now() + t);
+        let mut output = std::mem::take(&mut self.pending);
+        let mut streamed = 0;
+        let mut chunk = [0u8; 8192];
+
+        let exit_code = loop {
+            if let Some(pos) = find_bytes(&output, &needle) {
+                let status_start = pos + needle.len();
+                if let Some(len) = output[status_start..].iter().position(|b| *b == b'\n') {
+                    let status = String::from_utf8_lossy(&output[status_start..status_start + len])
+                        .trim()
+                        .parse::<i32>()
+                        .unwrap_or(-1);
+                    self.pending = output[status_start + len + 1..].to_vec();
+                    output.truncate(pos);
+                    send_output(output_sender, &output[streamed..]);
+                    break Some(status);
+                }
+            }
+
+            // Forward everything that can't be the beginning of the marker
+            let safe = output.len().saturating_sub(needle.len() + 16);
+            if safe > streamed {
+                send_output(output_sender, &output[streamed..safe]);
+                streamed = safe;
+            }
+
+            let read = match deadline {
+                Some(deadline) => {
+                    match tokio::time::timeout_at(deadline, self.stdout.read(&mut chunk)).await {
+                        Ok(read) => read?,
+                        Err(_) => {
+                            // The command is still running, the session is unusable
+                            self.alive = false;
+                            send_output(output_sender, &output[streamed..]);
+                            let mut output = String::from_utf8_lossy(&output).into_owned();
+                            output.push_str(&format!("\n{}\n", SESSION_ENDED_NOTE));
+                            return Ok(CommandOutput {
+                                success: false,
+                                output,
+                                timed_out: true,
+                            });
+                        }
+                    }
+                }
+                None => self.stdout.read(&mut chunk).await?,
+            };
+
+            if read == 0 {
+                // The shell exited, e.g. because the command called `exit`
+                self.alive = false;
+                send_output(output_sender, &output[streamed..]);
+                break self.child.wait().await.ok().and_then(|s| s.code());
+            }
+            output.extend_from_slice(&chunk[..read]);
+        };
+
+        let mut text = String::from_utf8_lossy(&output).into_owned();
+        if !self.alive {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text.push_str(SESSION_ENDED_NOTE);
+            text.push('\n');
+        }
+
+        Ok(CommandOutput {
+            success: exit_code == Some(0),
+            output: text,
+            timed_out: false,
+        })
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.stdin.write_all(data).await?;
+        self.stdin.flush().await?;
+        Ok(())
+    }
+}
+
+impl Drop for ShellSession {
+    fn drop(&mut self) {
+        if let Some(pid) = self.child.id() {
+            unregister_process_group(pid);
+            // Also stop anything the session left running in the background
+            #[cfg(unix)]
+            super::process::kill_process_tree(pid, libc::SIGKILL);
+        }
+        let _ = self.child.start_kill();
+    }
+}
+
+/// The user's shell if it understands POSIX syntax, otherwise /bin/sh
+fn session_shell() -> String {
+    match std::env::var("SHELL") {
+        Ok(shell)
+            if ["bash", "zsh", "sh", "dash", "ksh"]
+                .iter()
+                .any(|s| shell.ends_with(s)) =>
+        {
+            shell
+        }
+        _ => "/bin/sh".to_string(),
+    }
+}
+
+/// Quote a string for POSIX shells
+pub(crate) fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn send_output(sender: Option<&OutputSender>, data: &[u8]) {
+    if let Some(sender) = sender {
+        if !data.is_empty() {
+            let _ = sender.send(String::from_utf8_lossy(data).into_owned());
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_session_keeps_state() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        std::fs::create_dir(dir.path().join("sub"))?;
+        let mut session = ShellSession::start(dir.path()).await?;
+
+        let output = session
+            .run("cd sub && export FOO=bar", None, None, None)
+            .await?;
+        assert!(output.success);
+        assert_eq!(output.output, "");
+
+        let output = session
+            .run("basename $(pwd); echo $FOO", None, None, None)
+            .await?;
+        assert_eq!(output.output, "sub\nbar\n");
+
+        // Exit status and stderr of each command are separated
+        let output = session
+            .run("echo oops >&2; false", None, None, None)
+            .await?;
+        assert!(!output.success);
+        assert_eq!(output.output, "oops\n");
+
+        // Output without trailing newline is kept as is
+        let output = session.run("printf abc", None, None, None).await?;
+        assert_eq!(output.output, "abc");
+
+        // Explicit working directory changes the session directory
+        let output = session
+            .run("basename $(pwd)", Some(dir.path()), None, None)
+            .await?;
+        let name = dir.path().file_name().unwrap().to_string_lossy();
+        assert_eq!(output.output, format!("{}\n", name));
+        assert!(session.is_alive());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_session_streams_output() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let mut session = ShellSession::start(dir.path()).await?;
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+
+        let output = session
+            .run("echo one; sleep 0.2; echo two", None, None, Some(&sender))
+            .await?;
+        drop(sender);
+
+        let mut streamed = String::new();
+        while let Some(chunk) = receiver.recv().await {
+            streamed.push_str(&chunk);
+        }
+        assert_eq!(streamed, output.output);
+        assert_eq!(output.output, "one\ntwo\n");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_session_ends_on_exit_and_timeout() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+
+        let mut session = ShellSession::start(dir.path()).await?;
+        let output = session.run("echo bye; exit 3", None, None, None).await?;
+        assert!(!output.success);
+        assert!(output.output.starts_with("bye\n"));
+        assert!(!session.is_alive());
+
+        let mut session = ShellSession::start(dir.path()).await?;
+        let output = session
+            .run(
+                "echo waiting; sleep 5",
+                None,
+                Some(Duration::from_millis(300)),
+                None,
+            )
+            .await?;
+        assert!(output.timed_out);
+        assert!(output.output.starts_with("waiting\n"));
+        assert!(!session.is_alive());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+use super::client::{block_requests, WebClient, PAGE_LOAD_TIMEOUT};
+use super::http::{check_fetch_domain, is_local_url};
+use crate::types::{FetchConfig, ImageData};
+use crate::utils::image::load_image;
+use anyhow::{anyhow, Result};
+use chromiumoxide::cdp::browser_protocol::log::{EventEntryAdded, LogEntryLevel};
+use chromiumoxide::cdp::browser_protocol::page::CaptureScreenshotFormat;
+use chromiumoxide::cdp::js_protocol::runtime::{
+    ConsoleApiCalledType, EventConsoleApiCalled, EventExceptionThrown, RemoteObject,
+};
+use chromiumoxide::page::ScreenshotParams;
+use chromiumoxide::Page;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use url::Url;
+
+const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// Pages logging in a loop shouldn't fill the memory
+const MAX_CONSOLE_MESSAGES: usize = 200;
+
+/// Step of the browser tool, on the page kept open for the session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum BrowserAction {
+    /// Load a URL, e.g. of a dev server on localhost
+    Open {
+        url: String,
+    },
+    Click {
+        selector: String,
+    },
+    /// Click an input and type the text into it
+    Type {
+        selector: String,
+        text: String,
+    },
+    /// Wait until an element matches the selector
+    WaitFor {
+        selector: String,
+        timeout_secs: Option<u64>,
+    },
+    /// All console errors since the page was opened
+    Console,
+    Screenshot,
+}
+
+impl std::fmt::Display for BrowserAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BrowserAction::Open { url } => write!(f, "open {}", url),
+            BrowserAction::Click { selector } => write!(f, "click `{}`", selector),
+            BrowserAction::Type { selector, .. } => write!(f, "type into `{}`", selector),
+            BrowserAction::WaitFor { selector, .. } => write!(f, "wait for `{}`", selector),
+            BrowserAction::Console => write!(f, "read the console"),
+            BrowserAction::Screenshot => write!(f, "take a screenshot"),
+        }
+    }
+}
+
+/// The browser page after an action
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrowserPageState {
+    pub url: String,
+    pub title: String,
+    /// Console errors, warnings and uncaught exceptions since the previous
+    /// action, or since the page was opened for the console action
+    pub console: Vec<String>,
+    pub screenshot: Option<ImageData>,
+}
+
+/// Whose page a browser action runs on. Each agent and MCP server has its own
+/// page, so they don't navigate away from each other's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BrowserTabId(u64);
+
+impl BrowserTabId {
+    pub fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Default for BrowserTabId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// A tab's page, locked while an action runs on it
+pub(super) type SharedTab = Arc<tokio::sync::Mutex<Option<BrowserTab>>>;
+
+/// The page of the browser tool, it stays open between actions
+pub(super) struct BrowserTab {
+    page: Page,
+    // Pages of a previous, crashed browser can't be used anymore
+    generation: u64,
+    console: Arc<Mutex<Vec<String>>>,
+    // Messages that were already returned
+    reported: usize,
+    listener: JoinHandle<()>,
+    // Settings of the latest action, its requests are checked against them
+    fetch: Arc<Mutex<FetchConfig>>,
+    blocker: JoinHandle<()>,
+}
+
+impl BrowserTab {
+    async fn open(page: Page, generation: u64, fetch: &FetchConfig) -> Result<Self> {
+        let fetch = Arc::new(Mutex::new(fetch.clone()));
+        let allowed = fetch.clone();
+        let blocker = block_requests(&page, move |url| {
+            is_local_url(url) || check_fetch_domain(url, &allowed.lock().unwrap()).is_ok()
+        })
+        .await?;
+        let mut console_calls = page.event_listener::<EventConsoleApiCalled>().await?;
+        let mut exceptions = page.event_listener::<EventExceptionThrown>().await?;
+        let mut log_entries = page.event_listener::<EventEntryAdded>().await?;
+
+        let console = Arc::new(Mutex::new(Vec::new()));
+        let messages = console.clone();
+        let listener = tokio::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    Some(call) = console_calls.next() => format_console_call(&call),
+                    Some(exception) = exceptions.next() => {
+                        let details = &exception.exception_details;
+                        let description = details
+                            .exception
+                            .as_ref()
+                            .and_then(|exception| exception.description.clone())
+                            .unwrap_or_else(|| details.text.clone());
+                        Some(format!("Uncaught {}", description))
+                    }
+                    // Failed requests of the page, e.g. scripts that are not found
+                    Some(entry) = log_entries.next() => {
+                        (entry.entry.level == LogEntryLevel::Error).then(|| {
+                            match &entry.entry.url {
+                                Some(url) => format!("{} ({})", entry.entry.text, url),
+                                None => entry.entry.text.clone(),
+                            }
+                        })
+                    }
+                    else => break,
+                };
+                if let Some(message) = message {
+                    let mut messages = messages.lock().unwrap();
+                    if messages.len() < MAX_CONSOLE_MESSAGES {
+                        messages.push(message);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            page,
+            generation,
+            console,
+            reported: 0,
+            listener,
+            fetch,
+            blocker,
+        })
+    }
+
+    pub(super) async fn close(self) {
+        self.listener.abort();
+        self.blocker.abort();
+        let _ = self.page.close().await;
+    }
+
+    async fn run(&mut self, action: &BrowserAction) -> Result<BrowserPageState> {
+        let mut screenshot = None;
+        match action {
+            BrowserAction::Open { url } => {
+                // Messages of the previous page don't matter anymore, those
+                // logged while the new one loads do
+                self.console.lock().unwrap().clear();
+                self.reported = 0;
+                tokio::time::timeout(PAGE_LOAD_TIMEOUT, self.page.goto(url.as_str()))
+                    .await
+                    .map_err(|_| anyhow!("Timeout loading {} in the browser", url))??;
+            }
+            BrowserAction::Click { selector } => {
+                self.find_element(selector).await?.click().await?;
+            }
+            BrowserAction::Type { selector, text } => {
+                self.find_element(selector)
+                    .await?
+                    .click()
+                    .await?
+                    .type_str(text)
+                    .await?;
+            }
+            BrowserAction::WaitFor {
+                selector,
+                timeout_secs,
+            } => {
+                let timeout = timeout_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_WAIT_TIMEOUT)
+                    .min(MAX_WAIT_TIMEOUT);
+                let found = tokio::time::timeout(timeout, async {
+                    while self.page.find_element(selector.as_str()).await.is_err() {
+                        tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+                    }
+                })
+                .await;
+                if found.is_err() {
+                    return Err(anyhow!(
+                        "No element matches `{}` after {} seconds",
+                        selector,
+                        timeout.as_secs()
+                    ));
+                }
+            }
+            BrowserAction::Console => {
+                self.reported = 0;
+            }
+            BrowserAction::Screenshot => {
+                let bytes = self
+                    .page
+                    .screenshot(
+                        ScreenshotParams::builder()
+                            .format(CaptureScreenshotFormat::Png)
+                            .build(),
+                    )
+                    .await?;
+                screenshot = Some(load_image(&bytes)?);
+            }
+        }
+
+        let url = self.page.url().await?.unwrap_or_default();
+        let title = self.page.get_title().await?.unwrap_or_default();
+        // Read last, so the listener got to the messages of the action
+        let console = {
+            let messages = self.console.lock().unwrap();
+            let new_messages = messages[self.reported.min(messages.len())..].to_vec();
+            self.reported = messages.len();
+            new_messages
+        };
+        Ok(BrowserPageState {
+            url,
+            title,
+            console,
+            screenshot,
+        })
+    }
+
+    async fn find_element(&self, selector: &str) -> Result<chromiumoxide::Element> {
+        self.page
+            .find_element(selector)
+            .await
+            .map_err(|_| anyhow!("No element on the page matches `{}`", selector))
+    }
+}
+
+// Errors, warnings and failed assertions, other calls are left out
+fn format_console_call(call: &EventConsoleApiCalled) -> Option<String> {
+    let kind = match call.r#type {
+        ConsoleApiCalledType::Error => "error",
+        ConsoleApiCalledType::Warning => "warning",
+        ConsoleApiCalledType::Assert => "assertion failed",
+        _ => return None,
+    };
+    let args: Vec<String> = call.args.iter().map(format_remote_object).collect();
+    Some(format!("console {}: {}", kind, args.join(" ")))
+}
+
+fn format_remote_object(object: &RemoteObject) -> String {
+    match &object.value {
+        Some(serde_json::Value::String(text)) => text.clone(),
+        Some(value) => value.to_string(),
+        None => object.description.clone().unwrap_or_default(),
+    }
+}
+
+// Pages on this machine can always be opened
+fn check_browser_url(url: &str, fetch: &FetchConfig) -> Result<()> {
+    let url = Url::parse(url).map_err(|e| anyhow!("Invalid URL '{}': {}", url, e))?;
+    if is_local_url(&url) {
+        return Ok(());
+    }
+    check_fetch_domain(&url, fetch)
+}
+
+impl WebClient {
+    /// Runs an action of the browser tool on the page of the tab. The page is
+    /// opened on first use and reused by later actions, until the tab or the
+    /// browser is closed. Pages on this machine can always be loaded, others
+    /// only as `fetch` allows. The page is closed if it ends up elsewhere.
+    pub async fn browser(
+        &self,
+        tab_id: BrowserTabId,
+        action: &BrowserAction,
+        fetch: &FetchConfig,
+    ) -> Result<BrowserPageState> {
+        if let BrowserAction::Open { url } = action {
+            check_browser_url(url, fetch)?;
+        }
+        let shared = self
+            .browser_tabs
+            .lock()
+            .await
+            .entry(tab_id)
+            .or_default()
+            .clone();
+        let mut tab = shared.lock().await;
+        let stale = match tab.as_ref() {
+            Some(current) => !self.is_current_browser(current.generation).await,
+            None => true,
+        };
+        if stale {
+            if let Some(previous) = tab.take() {
+                previous.close().await;
+            }
+            if !matches!(action, BrowserAction::Open { .. }) {
+                return Err(anyhow!("No page is open in the browser, open a URL first"));
+            }
+            let (page, generation) = self.acquire_page().await?;
+            *tab = Some(BrowserTab::open(page, generation, fetch).await?);
+        }
+        let current = tab.as_mut().unwrap();
+        *current.fetch.lock().unwrap() = fetch.clone();
+        let state = current.run(action).await?;
+        // Redirects, clicks and scripts may have navigated to another domain
+        if let Err(e) = check_browser_url(&state.url, fetch) {
+            if let Some(current) = tab.take() {
+                current.close().await;
+            }
+            return Err(e);
+        }
+        Ok(state)
+    }
+
+    /// Closes the page of the tab, e.g. when its session ends
+    pub async fn close_browser_tab(&self, tab_id: BrowserTabId) {
+        let shared = self.browser_tabs.lock().await.remove(&tab_id);
+        if let Some(tab) = shared {
+            if let Some(tab) = tab.lock().await.take() {
+                tab.close().await;
+            }
+        }
+    }
+}
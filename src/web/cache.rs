@@ -0,0 +1,133 @@
+use super::client::WebPage;
+use super::http::is_local_url;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tracing::debug;
+use url::Url;
+
+/// How long fetched pages are read from the cache
+pub const PAGE_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Fetched pages on disk, so reading further pages doesn't fetch them again.
+/// Pages on this machine change while the agent works on them and are not cached.
+pub struct PageCache {
+    dir: Option<PathBuf>,
+    ttl: Duration,
+    local_urls: bool,
+    // Expired entries are removed once, before the first write
+    pruned: AtomicBool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    selectors: Vec<String>,
+    fetched_at: chrono::DateTime<chrono::Utc>,
+    page: WebPage,
+}
+
+impl PageCache {
+    pub fn new(dir: Option<PathBuf>, ttl: Duration) -> Self {
+        Self {
+            dir,
+            ttl,
+            local_urls: false,
+            pruned: AtomicBool::new(false),
+        }
+    }
+
+    /// Also cache pages on this machine, for tests against local servers
+    #[cfg(test)]
+    pub fn with_local_urls(mut self) -> Self {
+        self.local_urls = true;
+        self
+    }
+
+    /// Cache in the user's cache directory, disabled if there is none
+    pub fn user_cache() -> Self {
+        let dir = dirs::cache_dir().map(|dir| dir.join("code-assistant").join("web"));
+        Self::new(dir, PAGE_CACHE_TTL)
+    }
+
+    fn entry_path(&self, url: &str, selectors: &[String]) -> Option<PathBuf> {
+        if !self.local_urls && Url::parse(url).is_ok_and(|url| is_local_url(&url)) {
+            return None;
+        }
+        let hash = stable_hash(std::iter::once(url).chain(selectors.iter().map(String::as_str)));
+        Some(self.dir.as_ref()?.join(format!("{:016x}.json", hash)))
+    }
+
+    /// Remove entries older than the TTL
+    fn prune(&self) {
+        let Some(Ok(entries)) = self.dir.as_ref().map(std::fs::read_dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let expired = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| modified.elapsed().unwrap_or_default() > self.ttl);
+            let is_entry = entry.path().extension().is_some_and(|ext| ext == "json");
+            if expired && is_entry {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    /// The page if it was fetched within the TTL. Expired entries are removed.
+    pub fn get(&self, url: &str, selectors: &[String]) -> Option<WebPage> {
+        let path = self.entry_path(url, selectors)?;
+        let entry: CacheEntry = serde_json::from_slice(&std::fs::read(&path).ok()?).ok()?;
+        // Hashes of different URLs could collide
+        if entry.url != url || entry.selectors != selectors {
+            return None;
+        }
+        let age = chrono::Utc::now()
+            .signed_duration_since(entry.fetched_at)
+            .to_std()
+            .unwrap_or_default();
+        if age > self.ttl {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+        Some(entry.page)
+    }
+
+    /// Failing to write the cache only means the page is fetched again
+    pub fn put(&self, url: &str, selectors: &[String], page: &WebPage) {
+        let Some(path) = self.entry_path(url, selectors) else {
+            return;
+        };
+        if !self.pruned.swap(true, Ordering::Relaxed) {
+            self.prune();
+        }
+        let entry = CacheEntry {
+            url: url.to_string(),
+            selectors: selectors.to_vec(),
+            fetched_at: chrono::Utc::now(),
+            page: page.clone(),
+        };
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, serde_json::to_vec(&entry)?));
+        if let Err(e) = result {
+            debug!("Failed to cache {}: {}", url, e);
+        }
+    }
+}
+
+/// FNV-1a hash of the parts, file names have to stay the same across builds
+fn stable_hash<'a>(parts: impl Iterator<Item = &'a str>) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for part in parts {
+        // Separator, so ["ab", "c"] and ["a", "bc"] differ
+        for byte in part.bytes().chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
@@ -1,23 +1,75 @@
-use anyhow::Result;
-use chromiumoxide::{Browser, BrowserConfig};
+use super::browser::{BrowserTabId, SharedTab};
+use super::cache::PageCache;
+use super::http::{
+    check_allowed_host, check_fetch_domain, send_request, HttpRequest, HttpResponse,
+};
+use super::search::SearchBackend;
+use super::sections::{select_page, OutlineEntry};
+use crate::types::FetchConfig;
+use anyhow::{anyhow, Result};
+use chromiumoxide::cdp::browser_protocol::fetch::{
+    ContinueRequestParams, DisableParams, EnableParams, EventRequestPaused, FailRequestParams,
+};
+use chromiumoxide::cdp::browser_protocol::network::ErrorReason;
+use chromiumoxide::{Browser, BrowserConfig, Page};
 use futures::StreamExt;
 use htmd::{Element, HtmlToMarkdown};
-use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
 use regex::Regex;
 use reqwest::Client;
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
 use tempfile::TempDir;
+use tokio::task::JoinHandle;
+use tracing::debug;
 use url::Url;
 
+// Pages kept open for reuse, more are closed after use
+const MAX_IDLE_PAGES: usize = 4;
+pub(super) const PAGE_LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+// Redirects followed when fetching a page
+const MAX_REDIRECTS: usize = 10;
+const BROWSER_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+// Pages with less main content are likely rendered by scripts
+const MIN_STATIC_CONTENT_CHARS: usize = 200;
+const USER_AGENT: &str = concat!("code-assistant/", env!("CARGO_PKG_VERSION"));
+
+/// Searches and fetches web pages. Pages are requested over plain HTTP first,
+/// the browser for pages that need JavaScript is started on first use.
 pub struct WebClient {
     http_client: Client,
+    // Returns redirects instead of following them, for http_request and to
+    // check where fetched pages redirect to
+    request_client: Client,
+    browser: tokio::sync::Mutex<Option<BrowserSession>>,
+    // Pages of the browser tool, one per session
+    pub(super) browser_tabs: tokio::sync::Mutex<HashMap<BrowserTabId, SharedTab>>,
+    next_generation: AtomicU64,
+    cache: PageCache,
+}
+
+/// The client shared by all tools of the session
+pub fn web_client() -> &'static WebClient {
+    static CLIENT: OnceLock<WebClient> = OnceLock::new();
+    CLIENT.get_or_init(WebClient::new)
+}
+
+/// A running headless Chromium with its pool of idle pages
+struct BrowserSession {
     browser: Browser,
+    handler: JoinHandle<()>,
+    idle_pages: Vec<Page>,
+    // Pages of a previous, crashed browser are not returned to the pool
+    generation: u64,
     _user_data_dir: TempDir,
 }
 
-impl WebClient {
-    pub async fn new() -> Result<Self> {
+impl BrowserSession {
+    async fn launch(generation: u64) -> Result<Self> {
         // Create temporary user data directory
         let user_data_dir = tempfile::tempdir()?;
 
@@ -26,135 +78,506 @@ impl WebClient {
                 //.with_head()
                 .user_data_dir(user_data_dir.path().to_path_buf())
                 .build()
-                .map_err(|e| anyhow::anyhow!("{}", e))?,
+                .map_err(|e| anyhow!("{}", e))?,
         )
         .await?;
 
-        // Run browser handler in background
-        tokio::spawn(async move {
+        // Run browser handler in background, it ends when the browser is gone
+        let handler = tokio::spawn(async move {
             while let Some(event) = handler.next().await {
                 if let Err(e) = event {
-                    eprintln!("Browser handler error: {}", e);
+                    debug!("Browser handler error: {}", e);
                 }
             }
         });
 
         Ok(Self {
-            http_client: Client::new(),
             browser,
+            handler,
+            idle_pages: Vec::new(),
+            generation,
             _user_data_dir: user_data_dir,
         })
     }
 
-    pub async fn search(&self, query: &str, page: u32) -> Result<Vec<WebSearchResult>> {
-        let search_url = format!(
-            "https://html.duckduckgo.com/html/?q={}&s={}",
-            utf8_percent_encode(query, NON_ALPHANUMERIC),
-            (page - 1) * 20
-        );
-
-        let resp = self.http_client.get(&search_url).send().await?;
-        let html = resp.text().await?;
-        let document = Html::parse_document(&html);
-
-        let result_selector = Selector::parse(".result").unwrap();
-        let link_selector = Selector::parse(".result__a").unwrap();
-        let snippet_selector = Selector::parse(".result__snippet").unwrap();
-
-        let mut results = Vec::new();
-        for result in document.select(&result_selector) {
-            if let Some(link) = result.select(&link_selector).next() {
-                let encoded_url = link.value().attr("href").unwrap_or_default();
-
-                // Parse the redirect URL
-                let redirect_url = Url::parse(&format!("https:{}", encoded_url))?;
-
-                // Get the actual URL from the 'uddg' parameter
-                let url = redirect_url
-                    .query_pairs()
-                    .find(|(key, _)| key == "uddg")
-                    .map(|(_, value)| value.to_string())
-                    .unwrap_or_default();
-
-                let title = link.text().collect::<String>();
-                let snippet = result
-                    .select(&snippet_selector)
-                    .next()
-                    .map(|s| s.text().collect::<String>())
-                    .unwrap_or_default();
-
-                results.push(WebSearchResult {
-                    url: url.to_string(),
-                    title,
-                    snippet,
-                    metadata: PageMetadata::default(),
-                });
+    fn is_alive(&mut self) -> bool {
+        !self.handler.is_finished() && matches!(self.browser.try_wait(), Ok(None))
+    }
+
+    async fn close(mut self) {
+        for page in self.idle_pages.drain(..) {
+            let _ = page.close().await;
+        }
+        if tokio::time::timeout(BROWSER_CLOSE_TIMEOUT, self.browser.close())
+            .await
+            .is_err()
+        {
+            debug!("Browser did not close in time, killing it");
+        }
+        if tokio::time::timeout(BROWSER_CLOSE_TIMEOUT, self.browser.wait())
+            .await
+            .is_err()
+        {
+            let _ = self.browser.kill().await;
+        }
+        self.handler.abort();
+    }
+}
+
+impl Default for WebClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WebClient {
+    pub fn new() -> Self {
+        Self {
+            http_client: Client::builder()
+                .user_agent(USER_AGENT)
+                .timeout(HTTP_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            request_client: Client::builder()
+                .user_agent(USER_AGENT)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap_or_default(),
+            browser: tokio::sync::Mutex::new(None),
+            browser_tabs: tokio::sync::Mutex::new(HashMap::new()),
+            next_generation: AtomicU64::new(0),
+            cache: PageCache::user_cache(),
+        }
+    }
+
+    /// Use another cache for fetched pages
+    pub fn with_cache(mut self, cache: PageCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Close the browser if it was started. It is started again when needed.
+    pub async fn shutdown(&self) {
+        let tabs: Vec<_> = self.browser_tabs.lock().await.drain().collect();
+        for (_, tab) in tabs {
+            if let Some(tab) = tab.lock().await.take() {
+                tab.close().await;
             }
         }
+        if let Some(session) = self.browser.lock().await.take() {
+            session.close().await;
+        }
+    }
 
-        Ok(results)
+    #[cfg(test)]
+    pub async fn is_browser_running(&self) -> bool {
+        self.browser.lock().await.is_some()
     }
 
-    pub async fn fetch(&self, url: &str) -> Result<WebPage> {
-        let url = Url::parse(url)?;
-        let page = self.browser.new_page(url.as_str()).await?;
+    // Whether pages of this generation belong to the running browser
+    pub(super) async fn is_current_browser(&self, generation: u64) -> bool {
+        let mut session = self.browser.lock().await;
+        session
+            .as_mut()
+            .is_some_and(|current| current.is_alive() && current.generation == generation)
+    }
 
-        // Wait for page to load
-        let page = page.wait_for_navigation().await?;
+    // A page of the running browser, which is (re)started if needed
+    pub(super) async fn acquire_page(&self) -> Result<(Page, u64)> {
+        let mut session = self.browser.lock().await;
+        if !session.as_mut().is_some_and(BrowserSession::is_alive) {
+            if let Some(crashed) = session.take() {
+                debug!("Browser is not running anymore, restarting it");
+                crashed.close().await;
+            }
+            let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+            *session = Some(BrowserSession::launch(generation).await?);
+        }
 
-        // Get content either from main content or body
-        let html = if let Ok(main) = page.find_element("main, article, #content, .content").await {
-            main.inner_html().await?.unwrap_or_default()
-        } else {
-            let body = page.find_element("body").await?;
-            body.inner_html().await?.unwrap_or_default()
-        };
+        let current = session.as_mut().unwrap();
+        let generation = current.generation;
+        if let Some(page) = current.idle_pages.pop() {
+            return Ok((page, generation));
+        }
+        match current.browser.new_page("about:blank").await {
+            Ok(page) => Ok((page, generation)),
+            Err(e) => {
+                // The browser is unusable, start a new one next time
+                if let Some(broken) = session.take() {
+                    broken.close().await;
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    // Pages that loaded fine go back to the pool
+    async fn release_page(&self, page: Page, generation: u64, reusable: bool) {
+        let reusable = reusable && page.goto("about:blank").await.is_ok();
+        let mut session = self.browser.lock().await;
+        if let Some(current) = session.as_mut() {
+            if reusable
+                && current.generation == generation
+                && current.idle_pages.len() < MAX_IDLE_PAGES
+            {
+                current.idle_pages.push(page);
+                return;
+            }
+        }
+        drop(session);
+        let _ = page.close().await;
+    }
+
+    /// Search with the given backend, sharing the HTTP client
+    pub async fn search(
+        &self,
+        backend: &dyn SearchBackend,
+        query: &str,
+        page: u32,
+    ) -> Result<Vec<WebSearchResult>> {
+        backend.search(&self.http_client, query, page).await
+    }
 
-        // Convert HTML to Markdown with improved handlers
-        let converter = HtmlToMarkdown::builder()
-            .skip_tags(vec!["script", "style", "noscript"])
-            .add_handler(vec!["svg"], |_: Element| Some("".to_string()))
-            // .add_handler(vec!["img"], |elem: Element| {
-            //     elem.attrs.
-            //     let alt = elem.attr("alt").unwrap_or("Image");
-            //     if !alt.is_empty() {
-            //         Some(format!("[{}]", alt))
-            //     } else {
-            //         Some("".to_string())
-            //     }
-            // })
-            .build();
-        let content = converter.convert(&html).unwrap();
-
-        // Clean up the markdown
-        let image_pattern = Regex::new(r"!\[.*?\]\([^)]*\)\n?").unwrap();
-        let empty_heading_pattern = Regex::new(r"\n*#+ *\n+").unwrap();
-        let relative_link_pattern = Regex::new(r"\[([^\]]+)\]\(/[^)]+\)").unwrap();
-        let multiple_newlines = Regex::new(r"\n{3,}").unwrap();
-        let empty_brackets = Regex::new(r"\[\]").unwrap();
-
-        // Apply cleanup
-        let mut content = image_pattern.replace_all(&content, "").to_string();
-        content = empty_heading_pattern.replace_all(&content, "").to_string();
-        content = multiple_newlines.replace_all(&content, "\n\n").to_string();
-        content = empty_brackets.replace_all(&content, "").to_string();
-
-        // Handle relative links (preserve links as in original code)
-        let base_url = url.origin().ascii_serialization();
-        content = relative_link_pattern
-            .replace_all(&content, |caps: &regex::Captures| {
-                let link_text = &caps[1];
-                let link_url = &caps[0][caps[1].len() + 3..].trim_end_matches(')');
-                format!("[{}]({}{})", link_text, base_url, link_url)
-            })
-            .into_owned();
+    /// Sends a request of the http_request tool, if it goes to localhost or
+    /// one of the allowed hosts
+    pub async fn http_request(
+        &self,
+        request: &HttpRequest,
+        allowed_hosts: &[String],
+    ) -> Result<HttpResponse> {
+        let url = Url::parse(&request.url)
+            .map_err(|e| anyhow!("Invalid URL '{}': {}", request.url, e))?;
+        check_allowed_host(&url, allowed_hosts)?;
+        send_request(&self.request_client, request).await
+    }
+
+    /// Fetches a page and converts it to markdown. With selectors, only the
+    /// matching elements are extracted, in sections labeled by the selector.
+    /// The page and every redirect must be on a domain the settings allow.
+    pub async fn fetch(
+        &self,
+        url: &str,
+        selectors: Option<&[String]>,
+        fetch: &FetchConfig,
+    ) -> Result<WebPage> {
+        let url = Url::parse(url)?;
+        let selectors = selectors.filter(|selectors| !selectors.is_empty());
+        for selector in selectors.unwrap_or_default() {
+            Selector::parse(selector)
+                .map_err(|e| anyhow!("Invalid CSS selector '{}': {}", selector, e))?;
+        }
 
+        let document = self.fetch_document(&url, selectors, fetch).await?;
         Ok(WebPage {
             url: url.to_string(),
+            content: document.content,
+            ..Default::default()
+        })
+    }
+
+    // Plain HTTP request first, the browser for pages that need scripts
+    pub(super) async fn fetch_document(
+        &self,
+        url: &Url,
+        selectors: Option<&[String]>,
+        fetch: &FetchConfig,
+    ) -> Result<FetchedDocument> {
+        let response = self.get_following_redirects(url, fetch).await?;
+        // The browser starts where the checked redirects ended
+        let checked_url = response.url().clone();
+        if let Some(document) = read_static(response, url, selectors).await {
+            return Ok(document);
+        }
+        let (final_url, html) = self.fetch_rendered(&checked_url, fetch).await?;
+        // Scripts may have navigated on, to an allowed domain or an error page
+        check_fetch_domain(&final_url, fetch)?;
+        Ok(FetchedDocument {
+            content: extract_content(&html, &final_url, selectors)?,
+            url: final_url,
+            html: Some(html),
+        })
+    }
+
+    // Follows redirects one by one, so each is checked before it is requested
+    async fn get_following_redirects(
+        &self,
+        url: &Url,
+        fetch: &FetchConfig,
+    ) -> Result<reqwest::Response> {
+        let mut url = url.clone();
+        for _ in 0..=MAX_REDIRECTS {
+            check_fetch_domain(&url, fetch)?;
+            let response = self
+                .request_client
+                .get(url.clone())
+                .timeout(HTTP_TIMEOUT)
+                .send()
+                .await
+                .map_err(|e| anyhow!("Failed to fetch {}: {}", url, e))?;
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|location| location.to_str().ok());
+            match location {
+                Some(location) if response.status().is_redirection() => {
+                    url = url
+                        .join(location)
+                        .map_err(|e| anyhow!("Invalid redirect from {}: {}", url, e))?;
+                }
+                _ => return Ok(response),
+            }
+        }
+        Err(anyhow!("Too many redirects fetching {}", url))
+    }
+
+    // Pages fetched by other tools, so web_fetch can read them from the cache
+    pub(super) fn cache_page(&self, page: &WebPage) {
+        self.cache.put(&page.url, &[], page);
+    }
+
+    /// Fetches a page like `fetch` and returns one page of its content, see
+    /// `select_page`. The fetched content is cached, so further pages and
+    /// sections don't fetch again. The first page is always fetched fresh.
+    pub async fn fetch_page(
+        &self,
+        url: &str,
+        selectors: Option<&[String]>,
+        section: Option<&str>,
+        page: usize,
+        fetch: &FetchConfig,
+    ) -> Result<WebPage> {
+        let parsed = Url::parse(url)?;
+        // Cached pages may have been fetched with other settings
+        check_fetch_domain(&parsed, fetch)?;
+        let cache_url = parsed.to_string();
+        let cache_selectors = selectors.unwrap_or_default();
+        let follow_up = page > 1 || section.is_some();
+        let cached = follow_up
+            .then(|| self.cache.get(&cache_url, cache_selectors))
+            .flatten();
+        let full = match cached {
+            Some(full) => full,
+            None => {
+                let full = self.fetch(url, selectors, fetch).await?;
+                self.cache.put(&cache_url, cache_selectors, &full);
+                full
+            }
+        };
+        select_page(&full, section, page)
+    }
+
+    // The URL after redirects and the HTML of the rendered page. Requests to
+    // domains that can't be fetched fail, the page's own scripts included.
+    async fn fetch_rendered(&self, url: &Url, fetch: &FetchConfig) -> Result<(Url, String)> {
+        let (page, generation) = self.acquire_page().await?;
+        let allowed = fetch.clone();
+        let blocking = block_requests(&page, move |url| check_fetch_domain(url, &allowed).is_ok());
+        let blocker = match blocking.await {
+            Ok(blocker) => blocker,
+            Err(e) => {
+                self.release_page(page, generation, false).await;
+                return Err(e);
+            }
+        };
+        let result = tokio::time::timeout(PAGE_LOAD_TIMEOUT, async {
+            page.goto(url.as_str()).await?;
+            // Wait for page to load
+            page.wait_for_navigation().await?;
+            let final_url = match page.url().await? {
+                Some(final_url) => Url::parse(&final_url)?,
+                None => url.clone(),
+            };
+            Ok::<_, anyhow::Error>((final_url, page.content().await?))
+        })
+        .await
+        .unwrap_or_else(|_| Err(anyhow!("Timeout loading {} in the browser", url)));
+
+        // Pages in the pool load without restrictions
+        let reusable = result.is_ok() && page.execute(DisableParams::default()).await.is_ok();
+        blocker.abort();
+        self.release_page(page, generation, reusable).await;
+        result
+    }
+}
+
+/// Fails the requests of the page to URLs that are not allowed, redirects
+/// and navigations by scripts included, until the returned task is aborted
+/// or interception is disabled again
+pub(super) async fn block_requests(
+    page: &Page,
+    allowed: impl Fn(&Url) -> bool + Send + 'static,
+) -> Result<JoinHandle<()>> {
+    let mut paused = page.event_listener::<EventRequestPaused>().await?;
+    page.execute(EnableParams::default()).await?;
+    let page = page.clone();
+    Ok(tokio::spawn(async move {
+        while let Some(event) = paused.next().await {
+            let request_id = event.request_id.clone();
+            // Data and blob URLs don't leave the browser
+            let permitted = Url::parse(&event.request.url)
+                .is_ok_and(|url| !matches!(url.scheme(), "http" | "https") || allowed(&url));
+            // Requests the page cancelled meanwhile can't be continued
+            if permitted {
+                let _ = page.execute(ContinueRequestParams::new(request_id)).await;
+            } else {
+                debug!("Blocked request to {}", event.request.url);
+                let _ = page
+                    .execute(FailRequestParams::new(
+                        request_id,
+                        ErrorReason::BlockedByClient,
+                    ))
+                    .await;
+            }
+        }
+    }))
+}
+
+/// A page converted to markdown
+pub(super) struct FetchedDocument {
+    /// URL after redirects
+    pub url: Url,
+    pub content: String,
+    /// None for plain text
+    pub html: Option<String>,
+}
+
+// Page from a plain HTTP request, None if it should be rendered by the browser
+async fn read_static(
+    response: reqwest::Response,
+    url: &Url,
+    selectors: Option<&[String]>,
+) -> Option<FetchedDocument> {
+    if !response.status().is_success() {
+        return None;
+    }
+    // Links are relative to the URL after redirects
+    let final_url = response.url().clone();
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_lowercase();
+    let body = response.text().await.ok()?;
+
+    if content_type.starts_with("text/html") || content_type.contains("xhtml") {
+        let content = extract_content(&body, url, selectors).ok()?;
+        // Selectors that match don't need scripts, the main content might
+        let has_content = selectors.is_some() || content.trim().len() >= MIN_STATIC_CONTENT_CHARS;
+        has_content.then_some(FetchedDocument {
+            url: final_url,
             content,
-            metadata: PageMetadata::default(),
+            html: Some(body),
         })
+    } else if selectors.is_none()
+        && (content_type.starts_with("text/") || content_type.contains("json"))
+    {
+        // Plain text, markdown or JSON is returned as is
+        Some(FetchedDocument {
+            url: final_url,
+            content: body,
+            html: None,
+        })
+    } else {
+        None
+    }
+}
+
+// Content extracted when no selectors are given, the body if none of these exist
+const MAIN_CONTENT_SELECTOR: &str = "main, article, #content, .content";
+
+fn extract_content(html: &str, url: &Url, selectors: Option<&[String]>) -> Result<String> {
+    let document = Html::parse_document(html);
+
+    let selectors = match selectors {
+        Some(selectors) if !selectors.is_empty() => selectors,
+        _ => {
+            let main = Selector::parse(MAIN_CONTENT_SELECTOR).unwrap();
+            let body = Selector::parse("body").unwrap();
+            let html = document
+                .select(&main)
+                .next()
+                .or_else(|| document.select(&body).next())
+                .map(|element| element.inner_html())
+                .unwrap_or_default();
+            return html_to_markdown(&html, url);
+        }
+    };
+
+    let mut sections = Vec::new();
+    let mut matched_any = false;
+    for selector_text in selectors {
+        let selector = Selector::parse(selector_text)
+            .map_err(|e| anyhow!("Invalid CSS selector '{}': {}", selector_text, e))?;
+        let mut elements = Vec::new();
+        for element in document.select(&selector) {
+            let markdown = html_to_markdown(&element.html(), url)?;
+            if !markdown.trim().is_empty() {
+                elements.push(markdown.trim().to_string());
+            }
+        }
+
+        if elements.is_empty() {
+            sections.push(format!(
+                "## Selector `{}`\n\nNo elements on the page match this selector.",
+                selector_text
+            ));
+        } else {
+            matched_any = true;
+            sections.push(format!(
+                "## Selector `{}`\n\n{}",
+                selector_text,
+                elements.join("\n\n---\n\n")
+            ));
+        }
+    }
+
+    if !matched_any {
+        return Err(anyhow!(
+            "No elements on {} match the selectors {}",
+            url,
+            selectors
+                .iter()
+                .map(|selector| format!("`{}`", selector))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
     }
+    Ok(sections.join("\n\n"))
+}
+
+fn html_to_markdown(html: &str, url: &Url) -> Result<String> {
+    // Convert HTML to Markdown with improved handlers
+    let converter = HtmlToMarkdown::builder()
+        .skip_tags(vec!["script", "style", "noscript"])
+        .add_handler(vec!["svg"], |_: Element| Some("".to_string()))
+        .build();
+    let content = converter.convert(html)?;
+
+    // Clean up the markdown
+    let image_pattern = Regex::new(r"!\[.*?\]\([^)]*\)\n?").unwrap();
+    let empty_heading_pattern = Regex::new(r"\n*#+ *\n+").unwrap();
+    let relative_link_pattern = Regex::new(r"\[([^\]]+)\]\(/[^)]+\)").unwrap();
+    let multiple_newlines = Regex::new(r"\n{3,}").unwrap();
+    let empty_brackets = Regex::new(r"\[\]").unwrap();
+
+    // Apply cleanup
+    let mut content = image_pattern.replace_all(&content, "").to_string();
+    content = empty_heading_pattern.replace_all(&content, "").to_string();
+    content = multiple_newlines.replace_all(&content, "\n\n").to_string();
+    content = empty_brackets.replace_all(&content, "").to_string();
+
+    // Handle relative links (preserve links as in original code)
+    let base_url = url.origin().ascii_serialization();
+    content = relative_link_pattern
+        .replace_all(&content, |caps: &regex::Captures| {
+            let link_text = &caps[1];
+            let link_url = &caps[0][caps[1].len() + 3..].trim_end_matches(')');
+            format!("[{}]({}{})", link_text, base_url, link_url)
+        })
+        .into_owned();
+
+    Ok(content)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -170,6 +593,20 @@ pub struct WebPage {
     pub url: String,
     pub content: String,
     pub metadata: PageMetadata,
+    /// Sections of the whole page, given with the first of several pages
+    #[serde(default)]
+    pub outline: Vec<OutlineEntry>,
+    /// Title of the section the content is limited to
+    #[serde(default)]
+    pub section: Option<String>,
+    #[serde(default = "first_page")]
+    pub page: usize,
+    #[serde(default = "first_page")]
+    pub total_pages: usize,
+}
+
+fn first_page() -> usize {
+    1
 }
 
 impl Default for WebPage {
@@ -178,6 +615,22 @@ impl Default for WebPage {
             url: String::default(),
             content: String::default(),
             metadata: PageMetadata::default(),
+            outline: Vec::new(),
+            section: None,
+            page: 1,
+            total_pages: 1,
+        }
+    }
+}
+
+impl WebPage {
+    /// Which part of the page this is, e.g. "page 2 of 5 of section 'Install'"
+    pub fn position(&self) -> Option<String> {
+        let pages = format!("page {} of {}", self.page, self.total_pages);
+        match &self.section {
+            Some(section) => Some(format!("{} of section '{}'", pages, section)),
+            None if self.total_pages > 1 => Some(pages),
+            None => None,
         }
     }
 }
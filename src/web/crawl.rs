@@ -0,0 +1,243 @@
+use super::client::{WebClient, WebPage};
+use crate::types::FetchConfig;
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::sync::OnceLock;
+use url::Url;
+
+pub const DEFAULT_CRAWL_DEPTH: usize = 2;
+pub const DEFAULT_CRAWL_PAGES: usize = 10;
+pub const MAX_CRAWL_DEPTH: usize = 5;
+pub const MAX_CRAWL_PAGES: usize = 30;
+// Content of each page beyond this is left out, web_fetch reads it in full
+const MAX_CONDENSED_CHARS: usize = 6_000;
+// Links to files that are not documentation pages
+const SKIPPED_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "svg", "webp", "ico", "pdf", "zip", "gz", "tgz", "css", "js",
+    "woff", "woff2", "mp4",
+];
+
+/// Limits of a documentation crawl
+#[derive(Debug, Clone)]
+pub struct CrawlOptions {
+    /// Links are followed this many times from the start page
+    pub max_depth: usize,
+    pub max_pages: usize,
+    /// Only URLs starting with this are crawled, a path like `/docs/` is
+    /// relative to the start page's site
+    pub prefix: Option<String>,
+}
+
+impl Default for CrawlOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_CRAWL_DEPTH,
+            max_pages: DEFAULT_CRAWL_PAGES,
+            prefix: None,
+        }
+    }
+}
+
+/// Pages of a documentation site, with condensed content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocsCrawl {
+    pub start_url: String,
+    pub pages: Vec<CrawledPage>,
+    /// Pages that could not be fetched, with the error
+    pub failed: Vec<(String, String)>,
+    /// More pages were linked than the page limit allowed
+    pub limit_reached: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrawledPage {
+    pub url: String,
+    pub title: String,
+    /// Links followed from the start page
+    pub depth: usize,
+    /// Markdown without link targets, shortened if long
+    pub content: String,
+    pub truncated: bool,
+}
+
+impl WebClient {
+    /// Fetches the start page and the pages it links on the same site,
+    /// breadth first. Fetched pages are cached for web_fetch. Pages that
+    /// redirect to a domain the settings don't allow are failed.
+    pub async fn crawl_docs(
+        &self,
+        start_url: &str,
+        options: &CrawlOptions,
+        fetch: &FetchConfig,
+    ) -> Result<DocsCrawl> {
+        let start =
+            Url::parse(start_url).map_err(|e| anyhow!("Invalid URL '{}': {}", start_url, e))?;
+        let prefix = options
+            .prefix
+            .as_deref()
+            .map(|prefix| start.join(prefix.trim()))
+            .transpose()
+            .map_err(|e| anyhow!("Invalid prefix: {}", e))?;
+        let max_pages = options.max_pages.clamp(1, MAX_CRAWL_PAGES);
+        let max_depth = options.max_depth.min(MAX_CRAWL_DEPTH);
+
+        let mut crawl = DocsCrawl {
+            start_url: start.to_string(),
+            pages: Vec::new(),
+            failed: Vec::new(),
+            limit_reached: false,
+        };
+        let mut queue = VecDeque::from([(start.clone(), 0)]);
+        let mut seen = HashSet::from([normalize(&start)]);
+
+        while let Some((url, depth)) = queue.pop_front() {
+            if crawl.pages.len() >= max_pages {
+                crawl.limit_reached = true;
+                break;
+            }
+            let document = match self.fetch_document(&url, None, fetch).await {
+                Ok(document) => document,
+                // Without the start page there is nothing to crawl
+                Err(e) if crawl.pages.is_empty() && crawl.failed.is_empty() => return Err(e),
+                Err(e) => {
+                    crawl.failed.push((url.to_string(), e.to_string()));
+                    continue;
+                }
+            };
+            self.cache_page(&WebPage {
+                url: url.to_string(),
+                content: document.content.clone(),
+                ..Default::default()
+            });
+
+            let html = document.html.as_deref().map(Html::parse_document);
+            if depth < max_depth {
+                for link in html.iter().flat_map(|html| links(html, &document.url)) {
+                    let in_scope = link.origin() == start.origin()
+                        && match &prefix {
+                            Some(prefix) => link.as_str().starts_with(prefix.as_str()),
+                            None => true,
+                        };
+                    if in_scope && seen.insert(normalize(&link)) {
+                        queue.push_back((link, depth + 1));
+                    }
+                }
+            }
+
+            let (content, truncated) = condense(&document.content);
+            crawl.pages.push(CrawledPage {
+                title: html
+                    .as_ref()
+                    .and_then(page_title)
+                    .unwrap_or_else(|| url.path().to_string()),
+                url: url.to_string(),
+                depth,
+                content,
+                truncated,
+            });
+        }
+        Ok(crawl)
+    }
+}
+
+// Links of the page in document order, without fragments
+fn links(html: &Html, base: &Url) -> Vec<Url> {
+    let selector = Selector::parse("a[href]").unwrap();
+    html.select(&selector)
+        .filter_map(|link| base.join(link.value().attr("href")?).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .filter(|url| {
+            let extension = url
+                .path()
+                .rsplit_once('.')
+                .map(|(_, extension)| extension.to_lowercase());
+            !extension.is_some_and(|extension| SKIPPED_EXTENSIONS.contains(&extension.as_str()))
+        })
+        .map(|mut url| {
+            url.set_fragment(None);
+            url
+        })
+        .collect()
+}
+
+// The same page with or without a trailing slash is crawled once
+fn normalize(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    url.to_string().trim_end_matches('/').to_string()
+}
+
+fn page_title(html: &Html) -> Option<String> {
+    ["title", "h1"].iter().find_map(|tag| {
+        let selector = Selector::parse(tag).unwrap();
+        let title = html.select(&selector).next()?.text().collect::<String>();
+        let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+        (!title.is_empty()).then_some(title)
+    })
+}
+
+// Link targets are dropped, the index lists the crawled URLs
+fn condense(markdown: &str) -> (String, bool) {
+    static LINK: OnceLock<Regex> = OnceLock::new();
+    let link = LINK.get_or_init(|| Regex::new(r"\[([^\]]*)\]\([^)]*\)").unwrap());
+    let content = link.replace_all(markdown, "$1");
+    let content = content.trim();
+    if content.len() <= MAX_CONDENSED_CHARS {
+        return (content.to_string(), false);
+    }
+    let mut end = MAX_CONDENSED_CHARS;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    // Cut at a line break if there is one nearby
+    let end = content[..end]
+        .rfind('\n')
+        .filter(|&line_end| line_end > end / 2)
+        .unwrap_or(end);
+    (content[..end].trim_end().to_string(), true)
+}
+
+impl std::fmt::Display for DocsCrawl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Crawled {} page(s) from {}",
+            self.pages.len(),
+            self.start_url
+        )?;
+        for (index, page) in self.pages.iter().enumerate() {
+            writeln!(
+                f,
+                "{}{}. {} ({})",
+                "  ".repeat(page.depth),
+                index + 1,
+                page.title,
+                page.url
+            )?;
+        }
+        for (url, error) in &self.failed {
+            writeln!(f, "Failed to fetch {}: {}", url, error)?;
+        }
+        if self.limit_reached {
+            writeln!(f, "The page limit was reached, more pages are linked.")?;
+        }
+
+        for (index, page) in self.pages.iter().enumerate() {
+            write!(
+                f,
+                "\n## {}. {}\nURL: {}\n\n{}\n",
+                index + 1,
+                page.title,
+                page.url,
+                page.content
+            )?;
+            if page.truncated {
+                writeln!(f, "[Shortened, use web_fetch to read the whole page]")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use crate::types::FetchConfig;
+use crate::utils::truncate_output;
+use anyhow::{anyhow, Result};
+use reqwest::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Client, Method};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use url::{Host, Url};
+
+pub const DEFAULT_HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+// Longer bodies are shortened to their first and last lines
+const MAX_BODY_BYTES: usize = 20_000;
+const MAX_BODY_HEAD_LINES: usize = 300;
+const MAX_BODY_TAIL_LINES: usize = 100;
+
+/// Body of a request made with the http_request tool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestBody {
+    Json(Value),
+    Text(String),
+}
+
+/// Request made with the http_request tool
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub query: Vec<(String, String)>,
+    pub body: Option<RequestBody>,
+    pub timeout: Duration,
+}
+
+/// Response to an http_request, with the body prepared for the model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub reason: String,
+    pub headers: Vec<(String, String)>,
+    /// Pretty-printed if it is JSON, shortened if it is long
+    pub body: String,
+    pub body_bytes: usize,
+    pub elapsed_ms: u64,
+}
+
+/// Requests may go to localhost and the allowed hosts, where `*.example.com`
+/// includes subdomains and `*` allows every host
+pub fn check_allowed_host(url: &Url, allowed_hosts: &[String]) -> Result<()> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(anyhow!(
+            "Unsupported URL scheme '{}', only http and https are supported",
+            url.scheme()
+        ));
+    }
+    let host = url
+        .host()
+        .ok_or_else(|| anyhow!("The URL {} has no host", url))?;
+    if is_local_url(url) {
+        return Ok(());
+    }
+
+    let host = host.to_string().to_lowercase();
+    let allowed = allowed_hosts
+        .iter()
+        .any(|pattern| host_matches(&host, pattern));
+    if allowed {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Requests to {} are not allowed. Besides localhost, only the hosts listed in \
+             `http_request.allowed_hosts` of web.json in the config directory can be reached.",
+            host
+        ))
+    }
+}
+
+/// Pages may be fetched from hosts that are not blocked and, if there is an
+/// allowlist, are allowed
+pub fn check_fetch_domain(url: &Url, config: &FetchConfig) -> Result<()> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("The URL {} has no host", url))?
+        .to_lowercase();
+    if let Some(pattern) = config
+        .blocked_domains
+        .iter()
+        .find(|pattern| host_matches(&host, pattern))
+    {
+        return Err(anyhow!(
+            "Fetching from {} is blocked by the configured domain `{}`.",
+            host,
+            pattern.trim()
+        ));
+    }
+    if !config.allowed_domains.is_empty()
+        && !config
+            .allowed_domains
+            .iter()
+            .any(|pattern| host_matches(&host, pattern))
+    {
+        return Err(anyhow!(
+            "Fetching from {} is not allowed, only these domains can be fetched: {}",
+            host,
+            config.allowed_domains.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Whether the URL points to this machine
+pub fn is_local_url(url: &Url) -> bool {
+    match url.host() {
+        Some(Host::Domain(domain)) => {
+            let domain = domain.to_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        Some(Host::Ipv4(ip)) => ip.is_loopback() || ip.is_unspecified(),
+        Some(Host::Ipv6(ip)) => ip.is_loopback() || ip.is_unspecified(),
+        None => false,
+    }
+}
+
+// `*.example.com` includes the subdomains and `*` matches every host
+fn host_matches(host: &str, pattern: &str) -> bool {
+    let pattern = pattern.trim().to_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host == domain || host.ends_with(&format!(".{}", domain)),
+        None => pattern == "*" || pattern == host,
+    }
+}
+
+/// Sends the request without following redirects, so they show up in the response
+pub async fn send_request(client: &Client, request: &HttpRequest) -> Result<HttpResponse> {
+    let method = Method::from_bytes(request.method.trim().to_uppercase().as_bytes())
+        .map_err(|_| anyhow!("Invalid HTTP method '{}'", request.method))?;
+    let mut builder = client
+        .request(method, Url::parse(&request.url)?)
+        .timeout(request.timeout);
+    if !request.query.is_empty() {
+        builder = builder.query(&request.query);
+    }
+    for (name, value) in &request.headers {
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| anyhow!("Invalid header name '{}'", name))?;
+        let value = HeaderValue::from_str(value.trim())
+            .map_err(|_| anyhow!("Invalid value for header '{}'", name))?;
+        builder = builder.header(name, value);
+    }
+    builder = match &request.body {
+        Some(RequestBody::Json(json)) => builder.json(json),
+        Some(RequestBody::Text(text)) => builder.body(text.clone()),
+        None => builder,
+    };
+
+    let start = Instant::now();
+    let response = builder.send().await.map_err(|e| {
+        if e.is_timeout() {
+            anyhow!(
+                "Request timed out after {} seconds",
+                request.timeout.as_secs()
+            )
+        } else {
+            anyhow!("Request failed: {}", e)
+        }
+    })?;
+
+    let status = response.status();
+    let headers: Vec<_> = response
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).to_string(),
+            )
+        })
+        .collect();
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("json"));
+    let bytes = response.bytes().await?;
+    let elapsed_ms = start.elapsed().as_millis() as u64;
+
+    Ok(HttpResponse {
+        status: status.as_u16(),
+        reason: status.canonical_reason().unwrap_or_default().to_string(),
+        headers,
+        body: format_body(&bytes, is_json),
+        body_bytes: bytes.len(),
+        elapsed_ms,
+    })
+}
+
+fn format_body(bytes: &[u8], is_json: bool) -> String {
+    let Ok(text) = std::str::from_utf8(bytes) else {
+        return format!("[{} bytes of binary data]", bytes.len());
+    };
+    let looks_like_json = is_json || text.trim_start().starts_with(['{', '[']);
+    let body = match serde_json::from_str::<Value>(text) {
+        Ok(json) if looks_like_json => {
+            serde_json::to_string_pretty(&json).unwrap_or_else(|_| text.to_string())
+        }
+        _ => text.to_string(),
+    };
+    truncate_output(
+        &body,
+        MAX_BODY_HEAD_LINES,
+        MAX_BODY_TAIL_LINES,
+        MAX_BODY_BYTES,
+    )
+    .unwrap_or(body)
+}
+
+impl std::fmt::Display for HttpResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Status: {} {} ({} ms)",
+            self.status, self.reason, self.elapsed_ms
+        )?;
+        writeln!(f, "Headers:")?;
+        for (name, value) in &self.headers {
+            writeln!(f, "  {}: {}", name, value)?;
+        }
+        if self.body_bytes == 0 {
+            write!(f, "Body: (empty)")
+        } else {
+            write!(f, "Body ({} bytes):\n{}", self.body_bytes, self.body)
+        }
+    }
+}
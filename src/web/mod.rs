@@ -1,4 +1,18 @@
+mod browser;
+mod cache;
 mod client;
+mod crawl;
+mod http;
+mod search;
+mod sections;
 #[cfg(test)]
 mod tests;
-pub use client::{WebClient, WebPage, WebSearchResult};
+pub use browser::{BrowserAction, BrowserPageState, BrowserTabId};
+pub use client::{web_client, WebClient, WebPage, WebSearchResult};
+pub use crawl::{CrawlOptions, DocsCrawl};
+pub use http::{
+    check_fetch_domain, is_local_url, HttpRequest, HttpResponse, RequestBody,
+    DEFAULT_HTTP_REQUEST_TIMEOUT,
+};
+pub use search::create_search_backend;
+pub use sections::format_outline;
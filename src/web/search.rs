@@ -0,0 +1,292 @@
+use super::client::{PageMetadata, WebSearchResult};
+use crate::types::{JsonSearchConfig, SearchBackendConfig};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::Client;
+use scraper::{Html, Selector};
+use serde_json::Value;
+use url::Url;
+
+const DUCKDUCKGO_URL: &str = "https://html.duckduckgo.com";
+// DuckDuckGo's HTML endpoint pages by result offset
+const DUCKDUCKGO_PAGE_SIZE: u32 = 20;
+
+/// A service answering web searches
+#[async_trait]
+pub trait SearchBackend: Send + Sync {
+    /// Results of the given 1-based page
+    async fn search(&self, client: &Client, query: &str, page: u32)
+        -> Result<Vec<WebSearchResult>>;
+}
+
+pub fn create_search_backend(config: &SearchBackendConfig) -> Box<dyn SearchBackend> {
+    match config {
+        SearchBackendConfig::DuckDuckGo => Box::new(DuckDuckGo::new(DUCKDUCKGO_URL)),
+        SearchBackendConfig::Searxng { url } => Box::new(Searxng::new(url)),
+        SearchBackendConfig::JsonApi(config) => Box::new(JsonApi::new(config.clone())),
+    }
+}
+
+/// Scrapes the results from DuckDuckGo's HTML endpoint
+pub struct DuckDuckGo {
+    base_url: String,
+}
+
+impl DuckDuckGo {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl SearchBackend for DuckDuckGo {
+    async fn search(
+        &self,
+        client: &Client,
+        query: &str,
+        page: u32,
+    ) -> Result<Vec<WebSearchResult>> {
+        let search_url = format!(
+            "{}/html/?q={}&s={}",
+            self.base_url,
+            utf8_percent_encode(query, NON_ALPHANUMERIC),
+            page.saturating_sub(1) * DUCKDUCKGO_PAGE_SIZE
+        );
+
+        let resp = client.get(&search_url).send().await?.error_for_status()?;
+        let html = resp.text().await?;
+        parse_duckduckgo_results(&html, &Url::parse(&search_url)?)
+    }
+}
+
+fn parse_duckduckgo_results(html: &str, search_url: &Url) -> Result<Vec<WebSearchResult>> {
+    let document = Html::parse_document(html);
+
+    // Ads are marked as results as well
+    let result_selector = Selector::parse(".result:not(.result--ad)").unwrap();
+    let link_selector = Selector::parse(".result__a").unwrap();
+    let snippet_selector = Selector::parse(".result__snippet").unwrap();
+
+    let mut results = Vec::new();
+    for result in document.select(&result_selector) {
+        let Some(link) = result.select(&link_selector).next() else {
+            continue;
+        };
+        let href = link.value().attr("href").unwrap_or_default();
+
+        // Links usually go through a redirect with the target in the 'uddg' parameter
+        let link_url = search_url.join(href)?;
+        let url = link_url
+            .query_pairs()
+            .find(|(key, _)| key == "uddg")
+            .map(|(_, value)| value.to_string())
+            .unwrap_or_else(|| link_url.to_string());
+
+        results.push(WebSearchResult {
+            url,
+            title: link.text().collect::<String>().trim().to_string(),
+            snippet: result
+                .select(&snippet_selector)
+                .next()
+                .map(|s| s.text().collect::<String>().trim().to_string())
+                .unwrap_or_default(),
+            metadata: PageMetadata::default(),
+        });
+    }
+
+    // A page without any result markup likely means the markup changed
+    if results.is_empty() && !html.contains("no-results") && !html.contains("No results") {
+        return Err(anyhow!(
+            "Could not find search results in the DuckDuckGo response, consider configuring another search backend"
+        ));
+    }
+    Ok(results)
+}
+
+/// Uses the JSON API of a SearXNG instance
+pub struct Searxng {
+    base_url: String,
+}
+
+impl Searxng {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl SearchBackend for Searxng {
+    async fn search(
+        &self,
+        client: &Client,
+        query: &str,
+        page: u32,
+    ) -> Result<Vec<WebSearchResult>> {
+        let resp = client
+            .get(format!("{}/search", self.base_url))
+            .query(&[
+                ("q", query),
+                ("format", "json"),
+                ("pageno", &page.max(1).to_string()),
+            ])
+            .send()
+            .await?;
+        if resp.status() == reqwest::StatusCode::FORBIDDEN {
+            return Err(anyhow!(
+                "SearXNG at {} refused the request, make sure the json format is enabled in its settings",
+                self.base_url
+            ));
+        }
+        let response: Value = resp.error_for_status()?.json().await?;
+
+        let items = response["results"]
+            .as_array()
+            .ok_or_else(|| anyhow!("Unexpected SearXNG response without results"))?;
+        Ok(collect_results(items.iter().map(|item| ResultFields {
+            url: text(item.get("url")),
+            title: text(item.get("title")),
+            snippet: text(item.get("content")),
+            date: text(item.get("publishedDate")),
+            score: item.get("score").and_then(Value::as_f64),
+        })))
+    }
+}
+
+/// Any search API returning JSON, mapped by the configured field paths
+pub struct JsonApi {
+    config: JsonSearchConfig,
+}
+
+impl JsonApi {
+    pub fn new(config: JsonSearchConfig) -> Self {
+        Self { config }
+    }
+}
+
+#[async_trait]
+impl SearchBackend for JsonApi {
+    async fn search(
+        &self,
+        client: &Client,
+        query: &str,
+        page: u32,
+    ) -> Result<Vec<WebSearchResult>> {
+        let url = self
+            .config
+            .url
+            .replace(
+                "{query}",
+                &utf8_percent_encode(query, NON_ALPHANUMERIC).to_string(),
+            )
+            .replace("{page}", &page.max(1).to_string());
+
+        let mut request = client.get(&url);
+        for (name, value) in &self.config.headers {
+            request = request.header(name, value);
+        }
+        let response: Value = request.send().await?.error_for_status()?.json().await?;
+
+        let items = lookup(&response, &self.config.results_path)
+            .and_then(Value::as_array)
+            .ok_or_else(|| {
+                anyhow!(
+                    "No array of results at '{}' in the search response",
+                    self.config.results_path
+                )
+            })?;
+        Ok(collect_results(items.iter().map(|item| {
+            ResultFields {
+                url: text(lookup(item, &self.config.url_field)),
+                title: text(lookup(item, &self.config.title_field)),
+                snippet: text(lookup(item, &self.config.snippet_field)),
+                date: self
+                    .config
+                    .date_field
+                    .as_ref()
+                    .and_then(|path| text(lookup(item, path))),
+                score: self
+                    .config
+                    .score_field
+                    .as_ref()
+                    .and_then(|path| lookup(item, path))
+                    .and_then(Value::as_f64),
+            }
+        })))
+    }
+}
+
+/// Value at a dotted path like `web.results`, the value itself for an empty path
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .filter(|key| !key.is_empty())
+        .try_fold(value, |value, key| match value {
+            Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+            _ => value.get(key),
+        })
+}
+
+// Strings as they are, other values like numbers as JSON
+fn text(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::String(s) => Some(s.clone()),
+        Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+struct ResultFields {
+    url: Option<String>,
+    title: Option<String>,
+    snippet: Option<String>,
+    date: Option<String>,
+    score: Option<f64>,
+}
+
+// Results without a URL are skipped. Scores are relative to the best result.
+fn collect_results(items: impl Iterator<Item = ResultFields>) -> Vec<WebSearchResult> {
+    let items: Vec<_> = items.filter(|item| item.url.is_some()).collect();
+    let best_score = items
+        .iter()
+        .filter_map(|item| item.score)
+        .fold(0.0, f64::max);
+
+    items
+        .into_iter()
+        .map(|item| {
+            let mut metadata = PageMetadata {
+                date: item.date.as_deref().map(normalize_date),
+                ..Default::default()
+            };
+            if let Some(score) = item.score.filter(|_| best_score > 0.0) {
+                metadata.domain_score =
+                    (score / best_score * 100.0).clamp(0.0, 100.0).round() as u8;
+            }
+            WebSearchResult {
+                url: item.url.unwrap_or_default(),
+                title: item.title.unwrap_or_default().trim().to_string(),
+                snippet: item.snippet.unwrap_or_default().trim().to_string(),
+                metadata,
+            }
+        })
+        .collect()
+}
+
+// Dates are shown as YYYY-MM-DD when they can be parsed
+fn normalize_date(date: &str) -> String {
+    let date = date.trim();
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(date) {
+        return datetime.date_naive().to_string();
+    }
+    match date
+        .get(..10)
+        .and_then(|prefix| chrono::NaiveDate::parse_from_str(prefix, "%Y-%m-%d").ok())
+    {
+        Some(day) => day.to_string(),
+        None => date.to_string(),
+    }
+}
@@ -0,0 +1,300 @@
+use super::client::WebPage;
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Pages with more content are split into several pages at section boundaries
+pub const MAX_PAGE_CHARS: usize = 12_000;
+
+/// A heading of a fetched page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutlineEntry {
+    /// Number to request the section with
+    pub number: usize,
+    pub level: usize,
+    pub title: String,
+    /// Page on which the section starts
+    pub page: usize,
+}
+
+// Markdown of a page from one heading up to the next
+struct Section {
+    level: usize,
+    title: String,
+    content: String,
+}
+
+fn heading_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"^(#{1,6})\s+(.+?)[\s#]*$").unwrap())
+}
+
+// Splits at markdown headings outside of code blocks. Content before the
+// first heading becomes a section of its own.
+fn split_sections(markdown: &str) -> Vec<Section> {
+    let mut sections: Vec<Section> = Vec::new();
+    let mut in_code_block = false;
+
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+        }
+        let heading = (!in_code_block)
+            .then(|| heading_pattern().captures(line))
+            .flatten();
+
+        match (heading, sections.last_mut()) {
+            (Some(captures), _) => sections.push(Section {
+                level: captures[1].len(),
+                title: captures[2].to_string(),
+                content: String::new(),
+            }),
+            (None, None) if line.trim().is_empty() => continue,
+            (None, None) => sections.push(Section {
+                level: 1,
+                title: "Introduction".to_string(),
+                content: String::new(),
+            }),
+            (None, Some(_)) => {}
+        }
+        let section = sections.last_mut().unwrap();
+        section.content.push_str(line);
+        section.content.push('\n');
+    }
+    sections
+}
+
+// Packs the sections into pages, sections longer than a page are split at
+// paragraphs. Returns the pages and the page on which each section starts.
+fn paginate_sections(sections: &[Section]) -> (Vec<String>, Vec<usize>) {
+    let mut pages = vec![String::new()];
+    let mut start_pages = Vec::new();
+
+    for section in sections {
+        // Sections that fit on a page are not split
+        let current = pages.last().unwrap();
+        if !current.is_empty()
+            && section.content.len() <= MAX_PAGE_CHARS
+            && current.len() + section.content.len() > MAX_PAGE_CHARS
+        {
+            pages.push(String::new());
+        }
+
+        for (index, part) in split_long_text(&section.content).into_iter().enumerate() {
+            let current = pages.last_mut().unwrap();
+            if !current.is_empty() && current.len() + part.len() > MAX_PAGE_CHARS {
+                pages.push(part.to_string());
+            } else {
+                current.push_str(part);
+            }
+            if index == 0 {
+                start_pages.push(pages.len());
+            }
+        }
+    }
+
+    let pages = pages
+        .into_iter()
+        .map(|page| page.trim().to_string())
+        .collect();
+    (pages, start_pages)
+}
+
+// Paragraphs that fit on a page, single paragraphs that don't are cut
+fn split_long_text(text: &str) -> Vec<&str> {
+    if text.len() <= MAX_PAGE_CHARS {
+        return vec![text];
+    }
+    let mut parts = Vec::new();
+    for paragraph in text.split_inclusive("\n\n") {
+        let mut rest = paragraph;
+        while rest.len() > MAX_PAGE_CHARS {
+            let mut end = MAX_PAGE_CHARS;
+            while !rest.is_char_boundary(end) {
+                end -= 1;
+            }
+            parts.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        parts.push(rest);
+    }
+    parts
+}
+
+/// Page of the fetched content, optionally of a single section (including its
+/// subsections). The outline of all sections is included with the first page
+/// of content that doesn't fit on one page.
+pub fn select_page(full: &WebPage, section: Option<&str>, page: usize) -> Result<WebPage> {
+    let sections = split_sections(&full.content);
+    let (document_pages, start_pages) = paginate_sections(&sections);
+    let outline: Vec<OutlineEntry> = sections
+        .iter()
+        .zip(start_pages)
+        .enumerate()
+        .map(|(index, (section, page))| OutlineEntry {
+            number: index + 1,
+            level: section.level,
+            title: section.title.clone(),
+            page,
+        })
+        .collect();
+
+    let (selected_section, pages) = match section {
+        None => (None, document_pages),
+        Some(wanted) => {
+            let index = find_section(&outline, wanted).ok_or_else(|| {
+                anyhow!(
+                    "No section '{}' on {}, available sections are:\n{}",
+                    wanted,
+                    full.url,
+                    format_outline(&outline)
+                )
+            })?;
+            let level = sections[index].level;
+            let end = sections[index + 1..]
+                .iter()
+                .position(|section| section.level <= level)
+                .map_or(sections.len(), |offset| index + 1 + offset);
+            let (pages, _) = paginate_sections(&sections[index..end]);
+            (Some(sections[index].title.clone()), pages)
+        }
+    };
+
+    let total_pages = pages.len();
+    let Some(content) = pages.into_iter().nth(page.max(1) - 1) else {
+        let part = match &selected_section {
+            Some(title) => format!("section '{}' of {}", title, full.url),
+            None => full.url.clone(),
+        };
+        return Err(anyhow!(
+            "Page {} does not exist, {} has {} page(s)",
+            page,
+            part,
+            total_pages
+        ));
+    };
+
+    let show_outline = selected_section.is_none() && page <= 1 && total_pages > 1;
+    Ok(WebPage {
+        url: full.url.clone(),
+        content,
+        metadata: full.metadata.clone(),
+        outline: if show_outline { outline } else { Vec::new() },
+        section: selected_section,
+        page: page.max(1),
+        total_pages,
+    })
+}
+
+// By number, or by title ignoring case
+fn find_section(outline: &[OutlineEntry], wanted: &str) -> Option<usize> {
+    let wanted = wanted.trim().trim_start_matches('#').trim();
+    if let Ok(number) = wanted.parse::<usize>() {
+        return outline.iter().position(|entry| entry.number == number);
+    }
+    outline
+        .iter()
+        .position(|entry| entry.title.eq_ignore_ascii_case(wanted))
+        .or_else(|| {
+            let wanted = wanted.to_lowercase();
+            outline
+                .iter()
+                .position(|entry| entry.title.to_lowercase().contains(&wanted))
+        })
+}
+
+/// Numbered headings indented by level, with the page they start on
+pub fn format_outline(outline: &[OutlineEntry]) -> String {
+    let min_level = outline.iter().map(|entry| entry.level).min().unwrap_or(1);
+    outline
+        .iter()
+        .map(|entry| {
+            format!(
+                "{}{}. {} (page {})",
+                "  ".repeat(entry.level - min_level),
+                entry.number,
+                entry.title,
+                entry.page
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn web_page(content: String) -> WebPage {
+        WebPage {
+            url: "https://example.com/docs".to_string(),
+            content,
+            ..Default::default()
+        }
+    }
+
+    // A page of three sections, the second with a large subsection
+    fn long_document() -> String {
+        let paragraph = "Lorem ipsum dolor sit amet. ".repeat(20);
+        let mut content = "Welcome to the docs.\n\n# Install\n\nRun the installer.\n\n".to_string();
+        content.push_str("## Options\n\n```sh\n# not a heading\n```\n\n");
+        for _ in 0..50 {
+            content.push_str(&paragraph);
+            content.push_str("\n\n");
+        }
+        content.push_str("# Usage\n\nStart it.\n");
+        content
+    }
+
+    #[test]
+    fn test_select_page() -> Result<()> {
+        let full = web_page(long_document());
+
+        let first = select_page(&full, None, 1)?;
+        assert_eq!(first.total_pages, 3);
+        assert!(first.content.starts_with("Welcome to the docs."));
+        assert!(first.content.len() <= MAX_PAGE_CHARS);
+        assert_eq!(
+            format_outline(&first.outline),
+            "1. Introduction (page 1)\n2. Install (page 1)\n  3. Options (page 1)\n4. Usage (page 3)"
+        );
+
+        // Later pages come without the outline
+        let last = select_page(&full, None, 3)?;
+        assert!(last.outline.is_empty());
+        assert!(last.content.ends_with("# Usage\n\nStart it."));
+
+        // Sections include their subsections
+        let install = select_page(&full, Some("install"), 1)?;
+        assert_eq!(install.section.as_deref(), Some("Install"));
+        assert_eq!(install.total_pages, 3);
+        assert!(install.content.starts_with("# Install"));
+        assert!(!select_page(&full, Some("2"), 3)?
+            .content
+            .contains("# Usage"));
+        assert_eq!(
+            select_page(&full, Some("4"), 1)?.content,
+            "# Usage\n\nStart it."
+        );
+
+        let error = select_page(&full, None, 4).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Page 4 does not exist, https://example.com/docs has 3 page(s)"
+        );
+        assert!(select_page(&full, Some("Deploy"), 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_short_page_is_not_split() -> Result<()> {
+        let full = web_page("# Title\n\nShort page.".to_string());
+        let page = select_page(&full, None, 1)?;
+        assert_eq!(page.content, "# Title\n\nShort page.");
+        assert_eq!(page.total_pages, 1);
+        assert!(page.outline.is_empty());
+        Ok(())
+    }
+}
@@ -1,11 +1,114 @@
 #[cfg(test)]
 mod tests {
-    use super::super::WebClient;
+    use super::super::cache::{PageCache, PAGE_CACHE_TTL};
+    use super::super::crawl::CrawlOptions;
+    use super::super::http::{check_allowed_host, check_fetch_domain, HttpRequest, RequestBody};
+    use super::super::search::{create_search_backend, DuckDuckGo, JsonApi, Searxng};
+    use super::super::{BrowserAction, BrowserTabId, WebClient, WebPage};
+    use crate::types::{FetchConfig, JsonSearchConfig, SearchBackendConfig};
+    use axum::extract::Query;
+    use axum::http::{HeaderMap, Method, StatusCode};
+    use axum::response::Redirect;
+    use axum::{response::Html, routing::get, routing::post, Json, Router};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+    use url::Url;
+
+    // Fetch settings that allow all domains
+    const ANY_DOMAIN: FetchConfig = FetchConfig {
+        allowed_domains: Vec::new(),
+        blocked_domains: Vec::new(),
+    };
+
+    const FIXTURE_PAGE: &str = r#"<html>
+<head><title>Fixture</title><script>var tracking = 1;</script></head>
+<body>
+  <nav><a href="/">Home</a></nav>
+  <main>
+    <h1>Release notes</h1>
+    <div class="note"><h2>Version 2.0</h2><p>Adds <a href="/docs/async">async support</a>.</p></div>
+    <div class="note"><h2>Version 1.0</h2><p>First release.</p></div>
+  </main>
+  <footer>Copyright</footer>
+</body>
+</html>"#;
+
+    // Content is only there after the script ran
+    const SCRIPT_PAGE: &str = r#"<html>
+<body>
+  <div id="app"></div>
+  <script>document.getElementById("app").innerHTML = "<h1>Rendered by script</h1>";</script>
+</body>
+</html>"#;
+
+    // The greeting shows up a bit after the click
+    const INTERACTIVE_PAGE: &str = r#"<html>
+<head><title>Greeter</title></head>
+<body>
+  <input id="name">
+  <button id="greet">Greet</button>
+  <p id="greeting"></p>
+  <a id="away" href="https://blocked.example/">Elsewhere</a>
+  <script>
+    console.error("greeter loaded");
+    document.getElementById("greet").addEventListener("click", () => {
+      const name = document.getElementById("name").value;
+      console.error("greeting " + name);
+      setTimeout(() => {
+        const greeting = document.getElementById("greeting");
+        greeting.textContent = "Hello " + name;
+        greeting.className = "done";
+      }, 200);
+    });
+  </script>
+</body>
+</html>"#;
+
+    // Serves the fixture pages on a local port
+    async fn create_fixture_server() -> String {
+        let app = Router::new()
+            .route("/", get(|| async { Html(FIXTURE_PAGE) }))
+            .route("/app", get(|| async { Html(SCRIPT_PAGE) }))
+            .route("/greeter", get(|| async { Html(INTERACTIVE_PAGE) }))
+            .route(
+                "/guide",
+                get(|| async {
+                    Html(format!(
+                        "<html><body><article><h1>Guide</h1>{}</article></body></html>",
+                        "<p>Static content that needs no scripts.</p>".repeat(10)
+                    ))
+                }),
+            )
+            .route("/notes.txt", get(|| async { "Plain notes" }));
+        serve(app).await
+    }
+
+    // Serves the routes on a local port, returns the base URL ending with '/'
+    async fn serve(app: Router) -> String {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        format!("http://{}/", server_addr)
+    }
 
     #[tokio::test]
     async fn test_web_search() {
-        let client = WebClient::new().await.unwrap();
-        let results = client.search("rust programming", 1).await.unwrap();
+        let client = WebClient::new();
+        let backend = create_search_backend(&SearchBackendConfig::DuckDuckGo);
+        let results = client
+            .search(backend.as_ref(), "rust programming", 1)
+            .await
+            .unwrap();
 
         println!("\nSearch Results:");
         for (i, result) in results.iter().enumerate() {
@@ -22,12 +125,932 @@ mod tests {
 
     #[tokio::test]
     async fn test_web_fetch() {
-        let client = WebClient::new().await.unwrap();
-        let page = client.fetch("https://www.rust-lang.org").await.unwrap();
+        let client = WebClient::new();
+        let page = client
+            .fetch("https://www.rust-lang.org", None, &ANY_DOMAIN)
+            .await
+            .unwrap();
 
         println!("\nContent: {}", page.content);
 
         assert!(page.content.len() > 0);
         assert!(page.content.contains("Rust"));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_web_fetch_selectors() {
+        let url = create_fixture_server().await;
+        let client = WebClient::new();
+
+        // Without selectors, the main content is extracted
+        let page = client.fetch(&url, None, &ANY_DOMAIN).await.unwrap();
+        assert!(page.content.contains("Release notes"));
+        assert!(!page.content.contains("Copyright"));
+
+        let selectors = vec![".note h2".to_string(), "footer".to_string()];
+        let page = client
+            .fetch(&url, Some(&selectors), &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert!(page.content.starts_with("## Selector `.note h2`"));
+        assert!(page
+            .content
+            .contains("Version 2.0\n\n---\n\n## Version 1.0"));
+        assert!(page.content.contains("## Selector `footer`\n\nCopyright"));
+        assert!(!page.content.contains("First release"));
+
+        // Relative links point to the fetched site
+        let selectors = vec![".note p".to_string(), ".missing".to_string()];
+        let page = client
+            .fetch(&url, Some(&selectors), &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert!(page
+            .content
+            .contains(&format!("[async support]({}docs/async)", url)));
+        assert!(page
+            .content
+            .contains("## Selector `.missing`\n\nNo elements on the page match this selector."));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_web_fetch_selectors_without_match() {
+        let url = create_fixture_server().await;
+        let client = WebClient::new();
+
+        let selectors = vec!["#comments".to_string()];
+        let error = client
+            .fetch(&url, Some(&selectors), &ANY_DOMAIN)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("No elements on {} match the selectors `#comments`", url)
+        );
+
+        let selectors = vec!["div[".to_string()];
+        let error = client
+            .fetch(&url, Some(&selectors), &ANY_DOMAIN)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("Invalid CSS selector 'div['"));
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_web_fetch_static_pages_without_browser() {
+        let url = create_fixture_server().await;
+        let client = WebClient::new();
+
+        let page = client
+            .fetch(&format!("{}guide", url), None, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert!(page.content.starts_with("# Guide"));
+        let page = client
+            .fetch(&format!("{}notes.txt", url), None, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert_eq!(page.content, "Plain notes");
+
+        assert!(!client.is_browser_running().await);
+    }
+
+    #[tokio::test]
+    async fn test_web_fetch_restarts_browser() {
+        let url = create_fixture_server().await;
+        let client = WebClient::new();
+
+        // Pages rendered by scripts are loaded in the browser
+        let page = client
+            .fetch(&format!("{}app", url), None, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert!(page.content.contains("# Rendered by script"));
+        assert!(client.is_browser_running().await);
+
+        client.shutdown().await;
+        assert!(!client.is_browser_running().await);
+
+        // The browser is started again on demand, reusing its pages afterwards
+        for _ in 0..2 {
+            let page = client
+                .fetch(&format!("{}app", url), None, &ANY_DOMAIN)
+                .await
+                .unwrap();
+            assert!(page.content.contains("# Rendered by script"));
+        }
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_browser() {
+        let url = create_fixture_server().await;
+        let client = WebClient::new();
+        let tab = BrowserTabId::new();
+
+        let error = client
+            .browser(tab, &BrowserAction::Console, &ANY_DOMAIN)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("open a URL first"));
+
+        let state = client
+            .browser(
+                tab,
+                &BrowserAction::Open {
+                    url: format!("{}greeter", url),
+                },
+                &ANY_DOMAIN,
+            )
+            .await
+            .unwrap();
+        assert_eq!(state.title, "Greeter");
+        assert_eq!(state.console, vec!["console error: greeter loaded"]);
+
+        // Other sessions don't share the page
+        let other = BrowserTabId::new();
+        assert!(client
+            .browser(other, &BrowserAction::Console, &ANY_DOMAIN)
+            .await
+            .is_err());
+
+        client
+            .browser(
+                tab,
+                &BrowserAction::Type {
+                    selector: "#name".to_string(),
+                    text: "Ada".to_string(),
+                },
+                &ANY_DOMAIN,
+            )
+            .await
+            .unwrap();
+        let mut console = client
+            .browser(
+                tab,
+                &BrowserAction::Click {
+                    selector: "#greet".to_string(),
+                },
+                &ANY_DOMAIN,
+            )
+            .await
+            .unwrap()
+            .console;
+
+        // The page stays open between actions
+        let state = client
+            .browser(
+                tab,
+                &BrowserAction::WaitFor {
+                    selector: "#greeting.done".to_string(),
+                    timeout_secs: Some(5),
+                },
+                &ANY_DOMAIN,
+            )
+            .await
+            .unwrap();
+        console.extend(state.console);
+        assert_eq!(console, vec!["console error: greeting Ada"]);
+        let error = client
+            .browser(
+                tab,
+                &BrowserAction::WaitFor {
+                    selector: "#missing".to_string(),
+                    timeout_secs: Some(1),
+                },
+                &ANY_DOMAIN,
+            )
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("No element matches `#missing`"));
+        let error = client
+            .browser(
+                tab,
+                &BrowserAction::Click {
+                    selector: "#missing".to_string(),
+                },
+                &ANY_DOMAIN,
+            )
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("No element on the page matches"));
+
+        // Reported messages are only repeated by the console action
+        let state = client
+            .browser(tab, &BrowserAction::Screenshot, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert!(state.console.is_empty());
+        let screenshot = state.screenshot.unwrap();
+        assert_eq!(screenshot.media_type, "image/png");
+        assert!(screenshot.width > 0 && screenshot.height > 0);
+        let state = client
+            .browser(tab, &BrowserAction::Console, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert_eq!(
+            state.console,
+            vec![
+                "console error: greeter loaded",
+                "console error: greeting Ada"
+            ]
+        );
+        assert!(state.url.ends_with("/greeter"));
+
+        // Other domains can't be opened, and a page that navigates to one is closed
+        let docs_only = FetchConfig {
+            allowed_domains: vec!["docs.rs".to_string()],
+            ..Default::default()
+        };
+        let error = client
+            .browser(
+                tab,
+                &BrowserAction::Open {
+                    url: "https://blocked.example/".to_string(),
+                },
+                &docs_only,
+            )
+            .await
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("only these domains can be fetched: docs.rs"));
+        let away = BrowserAction::Click {
+            selector: "#away".to_string(),
+        };
+        match client.browser(tab, &away, &docs_only).await {
+            Ok(state) => assert!(state.url.ends_with("/greeter")),
+            Err(_) => assert!(client
+                .browser(tab, &BrowserAction::Console, &ANY_DOMAIN)
+                .await
+                .is_err()),
+        }
+
+        // The page is gone with the session, the browser keeps running
+        client.close_browser_tab(tab).await;
+        assert!(client
+            .browser(tab, &BrowserAction::Console, &ANY_DOMAIN)
+            .await
+            .is_err());
+        assert!(client.is_browser_running().await);
+        client.shutdown().await;
+        assert!(!client.is_browser_running().await);
+    }
+
+    // Documentation too long for a single page
+    fn long_docs_page() -> String {
+        format!(
+            "<html><body><main><h1>Reference</h1><h2>Functions</h2>{}<h2>Errors</h2><p>All errors.</p><h2>Notes</h2><p>Last words.</p></main></body></html>",
+            "<p>Each function of the library, described in detail.</p>".repeat(500)
+        )
+    }
+
+    #[tokio::test]
+    async fn test_web_fetch_pages_from_cache() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let app = Router::new().route(
+            "/docs",
+            get(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { Html(long_docs_page()) }
+            }),
+        );
+        let url = format!("{}docs", serve(app).await);
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = || {
+            PageCache::new(Some(cache_dir.path().to_path_buf()), PAGE_CACHE_TTL).with_local_urls()
+        };
+        let client = WebClient::new().with_cache(cache());
+
+        // The first page comes with the outline
+        let first = client
+            .fetch_page(&url, None, None, 1, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert!(first.content.starts_with("# Reference"));
+        assert!(first.total_pages > 1);
+        let titles: Vec<_> = first.outline.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(titles, ["Reference", "Functions", "Errors", "Notes"]);
+
+        let last = client
+            .fetch_page(&url, None, None, first.total_pages, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert!(last.outline.is_empty());
+        assert!(last.content.ends_with("Last words."));
+
+        let errors = client
+            .fetch_page(&url, None, Some("errors"), 1, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert_eq!(errors.content, "## Errors\n\nAll errors.");
+        assert_eq!(errors.section.as_deref(), Some("Errors"));
+
+        // A fresh client still reads the cache
+        let client = WebClient::new().with_cache(cache());
+        client
+            .fetch_page(&url, None, None, 2, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // The first page is always fetched, it may have changed
+        client
+            .fetch_page(&url, None, None, 1, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // Expired pages are fetched again
+        let client = WebClient::new().with_cache(
+            PageCache::new(Some(cache_dir.path().to_path_buf()), Duration::ZERO).with_local_urls(),
+        );
+        client
+            .fetch_page(&url, None, None, 2, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        let error = client
+            .fetch_page(&url, None, None, 99, &ANY_DOMAIN)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().starts_with("Page 99 does not exist"));
+
+        // Pages on this machine are not cached by default
+        let local_dir = tempfile::tempdir().unwrap();
+        let client = WebClient::new().with_cache(PageCache::new(
+            Some(local_dir.path().to_path_buf()),
+            PAGE_CACHE_TTL,
+        ));
+        client
+            .fetch_page(&url, None, None, 1, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        client
+            .fetch_page(&url, None, None, 2, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert_eq!(requests.load(Ordering::SeqCst), 6);
+        assert_eq!(std::fs::read_dir(local_dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_page_cache_prunes_expired_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let page = WebPage {
+            url: "https://docs.example.com/".to_string(),
+            content: "Cached".to_string(),
+            ..Default::default()
+        };
+        let entries = || std::fs::read_dir(dir.path()).unwrap().count();
+
+        let cache = PageCache::new(Some(dir.path().to_path_buf()), PAGE_CACHE_TTL);
+        let selectors = ["main".to_string()];
+        cache.put("https://docs.example.com/a", &[], &page);
+        cache.put("https://docs.example.com/b", &selectors, &page);
+        assert_eq!(entries(), 2);
+        assert!(cache.get("https://docs.example.com/a", &[]).is_some());
+        assert!(cache.get("https://docs.example.com/b", &[]).is_none());
+
+        // The next cache removes expired entries before its first write
+        std::thread::sleep(Duration::from_millis(20));
+        let cache = PageCache::new(Some(dir.path().to_path_buf()), Duration::from_millis(10));
+        cache.put("https://docs.example.com/c", &[], &page);
+        assert_eq!(entries(), 1);
+        assert!(cache.get("https://docs.example.com/c", &[]).is_some());
+    }
+
+    // Echoes what the service received
+    async fn create_api_server() -> String {
+        let app = Router::new()
+            .route(
+                "/items",
+                post(
+                    |method: Method,
+                     headers: HeaderMap,
+                     Query(query): Query<HashMap<String, String>>,
+                     Json(body): Json<serde_json::Value>| async move {
+                        (
+                            StatusCode::CREATED,
+                            Json(json!({
+                                "method": method.to_string(),
+                                "token": headers.get("x-token").and_then(|v| v.to_str().ok()),
+                                "query": query,
+                                "body": body,
+                            })),
+                        )
+                    },
+                ),
+            )
+            .route("/old", get(|| async { Redirect::permanent("/items") }))
+            .route("/log", get(|| async { "line\n".repeat(5000) }))
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    "done"
+                }),
+            );
+        serve(app).await
+    }
+
+    fn request(method: &str, url: String) -> HttpRequest {
+        HttpRequest {
+            method: method.to_string(),
+            url,
+            headers: Vec::new(),
+            query: Vec::new(),
+            body: None,
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_request() {
+        let url = create_api_server().await;
+        let client = WebClient::new();
+
+        let mut create = request("post", format!("{}items", url));
+        create.headers = vec![("X-Token".to_string(), "secret".to_string())];
+        create.query = vec![("dry_run".to_string(), "true".to_string())];
+        create.body = Some(RequestBody::Json(json!({"name": "x"})));
+        let response = client.http_request(&create, &[]).await.unwrap();
+        assert_eq!(
+            (response.status, response.reason.as_str()),
+            (201, "Created")
+        );
+        assert!(response
+            .headers
+            .contains(&("content-type".to_string(), "application/json".to_string())));
+        // JSON is pretty-printed
+        let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "method": "POST",
+                "token": "secret",
+                "query": {"dry_run": "true"},
+                "body": {"name": "x"}
+            })
+        );
+        assert!(response.body.contains("\n  \"body\": {"));
+
+        // Redirects are not followed
+        let response = client
+            .http_request(&request("GET", format!("{}old", url)), &[])
+            .await
+            .unwrap();
+        assert_eq!(response.status, 308);
+        assert!(response
+            .headers
+            .contains(&("location".to_string(), "/items".to_string())));
+
+        // Long bodies are shortened
+        let response = client
+            .http_request(&request("GET", format!("{}log", url)), &[])
+            .await
+            .unwrap();
+        assert_eq!(response.body_bytes, 25_000);
+        assert!(response.body.contains("lines (") && response.body.len() < 25_000);
+
+        let mut slow = request("GET", format!("{}slow", url));
+        slow.timeout = Duration::from_millis(200);
+        let error = client.http_request(&slow, &[]).await.unwrap_err();
+        assert!(error.to_string().starts_with("Request timed out"));
+
+        let error = client
+            .http_request(&request("FETCH IT", format!("{}items", url)), &[])
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Invalid HTTP method 'FETCH IT'");
+    }
+
+    #[test]
+    fn test_http_request_allowed_hosts() {
+        let allowed = vec!["api.example.com".to_string(), "*.internal.dev".to_string()];
+        let check = |url: &str| check_allowed_host(&Url::parse(url).unwrap(), &allowed);
+
+        assert!(check("http://localhost:8080/health").is_ok());
+        assert!(check("http://127.0.0.1:3000").is_ok());
+        assert!(check("http://[::1]:3000").is_ok());
+        assert!(check("http://app.localhost").is_ok());
+        assert!(check("https://API.example.com/v1").is_ok());
+        assert!(check("https://auth.internal.dev").is_ok());
+        assert!(check("https://internal.dev").is_ok());
+
+        let error = check("https://example.com").unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Requests to example.com are not allowed"));
+        assert!(check("https://evilinternal.dev").is_err());
+        assert!(check("ftp://localhost/file").is_err());
+
+        assert!(
+            check_allowed_host(&Url::parse("https://any.org").unwrap(), &["*".to_string()]).is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_domains() {
+        let check =
+            |url: &str, config: &FetchConfig| check_fetch_domain(&Url::parse(url).unwrap(), config);
+
+        let blocked = FetchConfig {
+            blocked_domains: vec!["*.tracker.io".to_string()],
+            ..Default::default()
+        };
+        assert!(check("https://docs.rs/serde", &blocked).is_ok());
+        assert_eq!(
+            check("https://cdn.Tracker.io/page", &blocked)
+                .unwrap_err()
+                .to_string(),
+            "Fetching from cdn.tracker.io is blocked by the configured domain `*.tracker.io`."
+        );
+
+        let allowed = FetchConfig {
+            allowed_domains: vec!["docs.rs".to_string(), "*.github.com".to_string()],
+            blocked_domains: vec!["gist.github.com".to_string()],
+        };
+        assert!(check("https://docs.rs/serde", &allowed).is_ok());
+        assert!(check("https://api.github.com/repos", &allowed).is_ok());
+        assert!(check("https://gist.github.com/user", &allowed).is_err());
+        assert_eq!(
+            check("http://localhost:8080/", &allowed)
+                .unwrap_err()
+                .to_string(),
+            "Fetching from localhost is not allowed, only these domains can be fetched: docs.rs, *.github.com"
+        );
+
+        // Redirects are checked before they are followed
+        let url = serve(Router::new().route(
+            "/moved",
+            get(|| async { Redirect::temporary("https://cdn.tracker.io/page") }),
+        ))
+        .await;
+        let error = WebClient::new()
+            .fetch(&format!("{}moved", url), None, &blocked)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Fetching from cdn.tracker.io is blocked by the configured domain `*.tracker.io`."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_web_fetch_blocks_script_redirects() {
+        let visits = Arc::new(AtomicUsize::new(0));
+        let counter = visits.clone();
+        let target = serve(Router::new().route(
+            "/page",
+            get(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { Html("<main><h1>Tracked</h1></main>") }
+            }),
+        ))
+        .await
+        .replace("127.0.0.1", "localhost");
+        // Too little content to be read without the browser
+        let page = format!(
+            "<html><head><script>location.replace(\"{}page\");</script></head></html>",
+            target
+        );
+        let url = serve(Router::new().route("/leave", get(move || async { Html(page) }))).await;
+        let client = WebClient::new();
+
+        let blocked = FetchConfig {
+            blocked_domains: vec!["localhost".to_string()],
+            ..Default::default()
+        };
+        let fetched = client.fetch(&format!("{}leave", url), None, &blocked).await;
+        assert!(fetched.map_or(true, |page| !page.content.contains("Tracked")));
+        assert_eq!(visits.load(Ordering::SeqCst), 0);
+
+        let page = client
+            .fetch(&format!("{}leave", url), None, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert!(page.content.contains("# Tracked"));
+        assert_eq!(visits.load(Ordering::SeqCst), 1);
+        client.shutdown().await;
+    }
+
+    // A documentation page with enough text to not need the browser
+    fn docs_page(title: &str, links: &[&str], paragraphs: usize) -> String {
+        let links: String = links
+            .iter()
+            .map(|href| format!("<li><a href=\"{}\">{}</a></li>", href, href))
+            .collect();
+        format!(
+            "<html><head><title>{}</title></head><body><nav><ul>{}</ul></nav><main><h1>{}</h1>{}</main></body></html>",
+            title,
+            links,
+            title,
+            format!("<p>{} explains a part of the library in detail.</p>", title).repeat(paragraphs)
+        )
+    }
+
+    // Documentation under /docs/ with a blog next to it
+    async fn create_docs_site() -> String {
+        let app = Router::new()
+            .route(
+                "/docs/",
+                get(|| async {
+                    Html(docs_page(
+                        "Overview",
+                        &[
+                            "install",
+                            "usage#options",
+                            "/docs/install",
+                            "/blog/post",
+                            "logo.png",
+                            "https://other.example.com/docs/",
+                        ],
+                        5,
+                    ))
+                }),
+            )
+            .route(
+                "/docs/install",
+                get(|| async { Html(docs_page("Install", &["/docs/", "advanced"], 5)) }),
+            )
+            .route(
+                "/docs/usage",
+                get(|| async { Html(docs_page("Usage", &["api"], 5)) }),
+            )
+            .route(
+                "/docs/advanced",
+                get(|| async { Html(docs_page("Advanced", &["deep"], 5)) }),
+            )
+            .route(
+                "/docs/api",
+                get(|| async { Html(docs_page("API", &[], 200)) }),
+            )
+            .route(
+                "/docs/deep",
+                get(|| async { Html(docs_page("Deep", &[], 5)) }),
+            )
+            .route(
+                "/blog/post",
+                get(|| async { Html(docs_page("Post", &[], 5)) }),
+            );
+        serve(app).await
+    }
+
+    #[tokio::test]
+    async fn test_crawl_docs() {
+        let url = create_docs_site().await;
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cache = || {
+            PageCache::new(Some(cache_dir.path().to_path_buf()), PAGE_CACHE_TTL).with_local_urls()
+        };
+        let client = WebClient::new().with_cache(cache());
+
+        let options = CrawlOptions {
+            prefix: Some("/docs/".to_string()),
+            ..Default::default()
+        };
+        let crawl = client
+            .crawl_docs(&format!("{}docs/", url), &options, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        let pages: Vec<_> = crawl
+            .pages
+            .iter()
+            .map(|page| (page.title.as_str(), page.depth))
+            .collect();
+        // Breadth first, each page once, only under the prefix and up to the depth
+        assert_eq!(
+            pages,
+            [
+                ("Overview", 0),
+                ("Install", 1),
+                ("Usage", 1),
+                ("Advanced", 2),
+                ("API", 2)
+            ]
+        );
+        assert!(!crawl.limit_reached);
+        assert!(crawl.failed.is_empty());
+
+        // Link targets are dropped and long pages shortened
+        assert!(!crawl.pages[0].content.contains("]("));
+        let api = &crawl.pages[4];
+        assert!(api.truncated);
+        assert!(api.content.len() <= 6_000);
+        let text = crawl.to_string();
+        assert!(text.contains(&format!("  2. Install ({}docs/install)", url)));
+        assert!(text.contains("[Shortened, use web_fetch to read the whole page]"));
+
+        // Crawled pages are read from the cache by web_fetch
+        let cached = cache().get(&format!("{}docs/api", url), &[]).unwrap();
+        assert!(cached.content.len() > api.content.len());
+
+        // Without a prefix, the whole site is crawled, up to the page limit
+        let options = CrawlOptions {
+            max_depth: 1,
+            max_pages: 3,
+            prefix: None,
+        };
+        let crawl = client
+            .crawl_docs(&format!("{}docs/", url), &options, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert_eq!(crawl.pages.len(), 3);
+        assert!(crawl.limit_reached);
+        let options = CrawlOptions {
+            max_depth: 1,
+            ..Default::default()
+        };
+        let crawl = client
+            .crawl_docs(&format!("{}docs/", url), &options, &ANY_DOMAIN)
+            .await
+            .unwrap();
+        assert_eq!(crawl.pages.last().unwrap().title, "Post");
+    }
+
+    const DUCKDUCKGO_PAGE: &str = r##"<html><body>
+<div class="result results_links result--ad">
+  <a class="result__a" href="https://ads.example.com/">Sponsored</a>
+</div>
+<div class="result results_links">
+  <h2><a class="result__a" href="//duckduckgo.com/l/?uddg=https%3A%2F%2Fdoc.rust-lang.org%2Fbook%2F&amp;rut=abc">The Rust <b>Programming</b> Language</a></h2>
+  <a class="result__snippet" href="#">The book about <b>Rust</b>.</a>
+</div>
+<div class="result results_links">
+  <h2><a class="result__a" href="https://www.rust-lang.org/">Rust</a></h2>
+</div>
+</body></html>"##;
+
+    #[tokio::test]
+    async fn test_duckduckgo_search() {
+        let app = Router::new()
+            .route(
+                "/html/",
+                get(|Query(params): Query<HashMap<String, String>>| async move {
+                    assert_eq!(params["q"], "rust book");
+                    assert_eq!(params["s"], "20");
+                    Html(DUCKDUCKGO_PAGE)
+                }),
+            )
+            .route(
+                "/changed/html/",
+                get(|| async { Html("<html><body><div class=\"hit\">Rust</div></body></html>") }),
+            );
+        let url = serve(app).await;
+        let client = WebClient::new();
+
+        let results = client
+            .search(&DuckDuckGo::new(&url), "rust book", 2)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].url, "https://doc.rust-lang.org/book/");
+        assert_eq!(results[0].title, "The Rust Programming Language");
+        assert_eq!(results[0].snippet, "The book about Rust.");
+        assert_eq!(results[1].url, "https://www.rust-lang.org/");
+        assert_eq!(results[1].snippet, "");
+
+        // Markup without results is reported instead of returning nothing
+        let error = client
+            .search(&DuckDuckGo::new(&format!("{}changed", url)), "rust", 1)
+            .await
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Could not find search results in the DuckDuckGo response"));
+    }
+
+    #[tokio::test]
+    async fn test_searxng_search() {
+        let app = Router::new()
+            .route(
+                "/search",
+                get(|Query(params): Query<HashMap<String, String>>| async move {
+                    assert_eq!(params["q"], "tokio runtime");
+                    assert_eq!(params["format"], "json");
+                    assert_eq!(params["pageno"], "1");
+                    Json(json!({
+                        "query": "tokio runtime",
+                        "results": [
+                            {
+                                "url": "https://docs.rs/tokio",
+                                "title": "tokio - Rust",
+                                "content": "A runtime for writing reliable network applications.",
+                                "publishedDate": "2024-03-01T12:30:00",
+                                "score": 4.0
+                            },
+                            {
+                                "url": "https://tokio.rs/",
+                                "title": "Tokio",
+                                "content": "Build reliable network applications.",
+                                "publishedDate": null,
+                                "score": 2.0
+                            }
+                        ]
+                    }))
+                }),
+            )
+            .route("/forbidden/search", get(|| async { StatusCode::FORBIDDEN }));
+        let url = serve(app).await;
+        let client = WebClient::new();
+
+        let results = client
+            .search(&Searxng::new(&url), "tokio runtime", 1)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].url, "https://docs.rs/tokio");
+        assert_eq!(results[0].metadata.date.as_deref(), Some("2024-03-01"));
+        assert_eq!(results[0].metadata.domain_score, 100);
+        assert_eq!(results[1].snippet, "Build reliable network applications.");
+        assert_eq!(results[1].metadata.date, None);
+        assert_eq!(results[1].metadata.domain_score, 50);
+
+        // Instances without the JSON format enabled answer with 403
+        let error = client
+            .search(&Searxng::new(&format!("{}forbidden", url)), "tokio", 1)
+            .await
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("make sure the json format is enabled"));
+    }
+
+    #[tokio::test]
+    async fn test_json_api_search() {
+        let app = Router::new().route(
+            "/api/search",
+            get(
+                |headers: HeaderMap, Query(params): Query<HashMap<String, String>>| async move {
+                    if headers.get("x-api-key").map(|key| key.as_bytes()) != Some(b"secret") {
+                        return Err(StatusCode::UNAUTHORIZED);
+                    }
+                    assert_eq!(params["query"], "serde json");
+                    assert_eq!(params["offset"], "3");
+                    Ok(Json(json!({
+                        "data": {
+                            "items": [
+                                {
+                                    "link": "https://serde.rs/",
+                                    "name": "Serde",
+                                    "summary": "Serialization framework",
+                                    "meta": { "published": "2023-11-05T08:00:00Z" },
+                                    "rank": 0.8
+                                },
+                                { "name": "Result without a link" },
+                                {
+                                    "link": "https://docs.rs/serde_json",
+                                    "name": "serde_json",
+                                    "rank": 0.2
+                                }
+                            ]
+                        }
+                    })))
+                },
+            ),
+        );
+        let url = serve(app).await;
+
+        // Configured like in web.json, the remaining fields have defaults
+        let config: JsonSearchConfig = serde_json::from_value(json!({
+            "url": format!("{}api/search?query={{query}}&offset={{page}}", url),
+            "headers": { "x-api-key": "secret" },
+            "results_path": "data.items",
+            "title_field": "name",
+            "url_field": "link",
+            "snippet_field": "summary",
+            "date_field": "meta.published",
+            "score_field": "rank"
+        }))
+        .unwrap();
+        let client = WebClient::new();
+
+        let results = client
+            .search(&JsonApi::new(config.clone()), "serde json", 3)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].title, "Serde");
+        assert_eq!(results[0].snippet, "Serialization framework");
+        assert_eq!(results[0].metadata.date.as_deref(), Some("2023-11-05"));
+        assert_eq!(results[0].metadata.domain_score, 100);
+        assert_eq!(results[1].url, "https://docs.rs/serde_json");
+        assert_eq!(results[1].snippet, "");
+        assert_eq!(results[1].metadata.domain_score, 25);
+
+        let config = JsonSearchConfig {
+            headers: HashMap::new(),
+            ..config
+        };
+        assert!(client
+            .search(&JsonApi::new(config), "serde json", 3)
+            .await
+            .is_err());
     }
 }